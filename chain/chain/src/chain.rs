@@ -1,3 +1,4 @@
+use std::cmp::min;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration as TimeDuration, Instant};
@@ -7,8 +8,12 @@ use chrono::Duration;
 use log::{debug, info};
 
 use near_primitives::hash::CryptoHash;
-use near_primitives::transaction::{ReceiptTransaction, TransactionResult};
-use near_primitives::types::{BlockIndex, MerkleHash, ShardId};
+use near_primitives::rpc::BlockEvents;
+use near_primitives::transaction::{
+    FinalTransactionResult, FinalTransactionStatus, ReceiptTransaction, TransactionLogs,
+    TransactionResult, TransactionStatus,
+};
+use near_primitives::types::{AccountId, BlockIndex, MerkleHash, ShardId, StatePart};
 use near_store::Store;
 
 use crate::error::{Error, ErrorKind};
@@ -24,6 +29,13 @@ const MAX_ORPHAN_AGE_SECS: u64 = 300;
 /// Refuse blocks more than this many block intervals in the future (as in bitcoin).
 const ACCEPTABLE_TIME_DIFFERENCE: i64 = 12 * 10;
 
+/// State root of the shard recorded in the header.
+fn shard_state_root(header: &BlockHeader, shard_id: ShardId) -> Result<MerkleHash, Error> {
+    header.prev_state_roots.get(shard_id as usize).cloned().ok_or_else(|| {
+        ErrorKind::InvalidStatePayload(format!("Unknown shard #{}", shard_id)).into()
+    })
+}
+
 pub struct Orphan {
     block: Block,
     provenance: Provenance,
@@ -110,7 +122,7 @@ impl Chain {
 
         // Get runtime initial state and create genesis block out of it.
         let (state_store_update, state_roots) = runtime_adapter.genesis_state();
-        let genesis = Block::genesis(state_roots[0], genesis_time);
+        let genesis = Block::genesis(state_roots.clone(), genesis_time);
 
         // Check if we have a head in the store, otherwise pick genesis block.
         let mut store_update = store.store_update();
@@ -154,8 +166,7 @@ impl Chain {
                             vec![],
                         )
                         .map_err(|err| ErrorKind::Other(err.to_string()))?;
-                    store_update
-                        .save_post_state_root(&genesis.hash(), &genesis.header.prev_state_root);
+                    store_update.save_post_state_roots(&genesis.hash(), state_roots.clone());
                     store_update.save_block_header(genesis.header.clone());
                     store_update.save_block(genesis.clone());
                     store_update.save_receipt(&genesis.header.hash(), vec![]);
@@ -302,7 +313,7 @@ impl Chain {
 
         match (has_head, is_next_block) {
             (true, true) => BlockStatus::Next,
-            (true, false) => BlockStatus::Reorg,
+            (true, false) => BlockStatus::Reorg(prev_head.last_block_hash),
             (false, _) => BlockStatus::Fork,
         }
     }
@@ -420,30 +431,150 @@ impl Chain {
         maybe_new_head
     }
 
+    /// Stores part of the state of the shard as of the given sync block.
+    pub fn set_state_part(
+        &mut self,
+        shard_id: ShardId,
+        hash: CryptoHash,
+        part: StatePart,
+    ) -> Result<(), Error> {
+        // TODO(1046): update this with any required changes for chunks support.
+        let header = self.get_block_header(&hash)?;
+        let state_root = shard_state_root(header, shard_id)?;
+
+        // Save state part in the runtime, will also check it's validity.
+        self.runtime_adapter
+            .set_state(shard_id, state_root, part)
+            .map_err(|err| ErrorKind::InvalidStatePayload(err.to_string()).into())
+    }
+
+    /// Finishes the state sync of the shard, once all parts of its state were set.
     pub fn set_shard_state(
         &mut self,
         shard_id: ShardId,
         hash: CryptoHash,
-        payload: Vec<u8>,
         receipts: Vec<ReceiptTransaction>,
     ) -> Result<(), Error> {
         // TODO(1046): update this with any required changes for chunks support.
         let header = self.get_block_header(&hash)?;
-        let (prev_hash, state_root) = (header.prev_hash, header.prev_state_root);
+        let (prev_hash, state_roots) = (header.prev_hash, header.prev_state_roots.clone());
+        let state_root = shard_state_root(header, shard_id)?;
 
-        // Save state in the runtime, will also check it's validity.
+        // Check that the whole state was received.
         self.runtime_adapter
-            .set_state(shard_id, state_root, payload)
+            .confirm_state(shard_id, state_root)
             .map_err(|err| ErrorKind::InvalidStatePayload(err.to_string()))?;
 
         // Update pointers to state root and receipts.
         let mut chain_store_update = self.store.store_update();
-        chain_store_update.save_post_state_root(&prev_hash, &state_root);
+        chain_store_update.save_post_state_roots(&prev_hash, state_roots);
         chain_store_update.save_receipt(&prev_hash, receipts);
         chain_store_update.commit()?;
 
         Ok(())
     }
+
+    /// Bootstraps a fresh chain from the state snapshot taken before block `header`.
+    /// The chain continues from the previous block, blocks after it are synced from peers.
+    /// State parts are taken from `state_parts` one at a time, so the snapshot can be streamed.
+    pub fn import_state_snapshot<I>(
+        &mut self,
+        prev_header: BlockHeader,
+        header: BlockHeader,
+        receipts: Vec<ReceiptTransaction>,
+        state_parts: I,
+    ) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = Result<(ShardId, StatePart), Error>>,
+    {
+        if self.head()?.height != 0 {
+            return Err(ErrorKind::Other(
+                "State snapshot can only be imported into a fresh chain".to_string(),
+            )
+            .into());
+        }
+        if header.prev_hash != prev_header.hash() {
+            return Err(
+                ErrorKind::Other("Snapshot headers are not consecutive".to_string()).into()
+            );
+        }
+        let hash = header.hash();
+        let num_shards = header.prev_state_roots.len() as ShardId;
+        let tip = Tip::from_header(&prev_header);
+
+        let mut chain_store_update = self.store.store_update();
+        chain_store_update.save_block_header(prev_header);
+        chain_store_update.save_block_header(header);
+        chain_store_update.commit()?;
+
+        // State is checked against the state root from the header.
+        for part in state_parts {
+            let (shard_id, part) = part?;
+            self.set_state_part(shard_id, hash, part)?;
+        }
+        // Fails unless all the parts of the shard were set.
+        for shard_id in 0..num_shards {
+            self.set_shard_state(shard_id, hash, receipts.clone())?;
+        }
+
+        let mut chain_store_update = self.store.store_update();
+        chain_store_update.reset_heads(&tip);
+        chain_store_update.commit()?;
+        info!(target: "chain", "Imported state snapshot at {} @ {}", tip.last_block_hash, tip.height);
+        Ok(())
+    }
+
+    /// Garbage collects blocks and states below `gc_height`, starting from the tail of the chain.
+    /// Clears at most `max_heights` heights per call, to not block the processing of new blocks.
+    pub fn clear_old_data(
+        &mut self,
+        gc_height: BlockIndex,
+        max_heights: BlockIndex,
+    ) -> Result<(), Error> {
+        let tail_height = match self.store.tail() {
+            Ok(tail) => tail.height,
+            Err(err) => match err.kind() {
+                ErrorKind::DBNotFoundErr(_) => self.genesis.height,
+                _ => return Err(err),
+            },
+        };
+        let gc_height = min(gc_height, self.head()?.height);
+        let trie = self.runtime_adapter.get_trie();
+        for height in (tail_height + 1)..min(gc_height, tail_height + 1 + max_heights) {
+            let canonical_hash = self.store.get_block_hash_by_height(height).ok();
+            let mut hashes = self.store.get_all_block_hashes_by_height(height)?;
+            // Abandoned forks must be discarded before the state they were built on.
+            hashes.sort_by_key(|hash| Some(*hash) == canonical_hash);
+            for hash in hashes.iter() {
+                let canonical = Some(*hash) == canonical_hash;
+                let prev_hash = self.store.get_block_header(hash)?.prev_hash;
+                let trie_changes = self.store.get_trie_changes(hash)?;
+                let mut chain_store_update = self.store.store_update();
+                for shard_trie_changes in trie_changes.unwrap_or_default() {
+                    let mut store_update = chain_store_update.store().store_update();
+                    if canonical {
+                        shard_trie_changes.deletions_into(trie.clone(), &mut store_update)
+                    } else {
+                        shard_trie_changes.revert_insertions_into(trie.clone(), &mut store_update)
+                    }
+                    .map_err(|err| ErrorKind::Other(err.to_string()))?;
+                    chain_store_update.merge(store_update);
+                }
+                // Trie changes are removed in the same update, so they are never applied twice.
+                let result_hashes = chain_store_update.clear_block_data(hash, canonical)?;
+                chain_store_update.commit()?;
+                self.store.clear_block_cache(hash, &prev_hash, &result_hashes);
+            }
+            if let Some(hash) = canonical_hash {
+                let tail = Tip::from_header(self.store.get_block_header(&hash)?);
+                let mut chain_store_update = self.store.store_update();
+                chain_store_update.save_body_tail(&tail);
+                chain_store_update.commit()?;
+                debug!(target: "chain", "Garbage collected blocks at {}, new tail {}", height, hash);
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Various chain getters.
@@ -498,6 +629,17 @@ impl Chain {
         self.store.get_block_header(&hash)
     }
 
+    /// Is the block with given hash and height on the chain ending with block `tip_hash`?
+    #[inline]
+    pub fn is_on_chain(
+        &mut self,
+        tip_hash: &CryptoHash,
+        hash: &CryptoHash,
+        height: BlockIndex,
+    ) -> Result<bool, Error> {
+        self.store.is_on_chain(tip_hash, hash, height)
+    }
+
     /// Get previous block header.
     #[inline]
     pub fn get_previous_header(&mut self, header: &BlockHeader) -> Result<&BlockHeader, Error> {
@@ -510,10 +652,22 @@ impl Chain {
         self.store.block_exists(hash)
     }
 
-    /// Get state root hash after applying header with given hash.
+    /// Get state root hashes of the shards after applying header with given hash.
     #[inline]
-    pub fn get_post_state_root(&mut self, hash: &CryptoHash) -> Result<&MerkleHash, Error> {
-        self.store.get_post_state_root(hash)
+    pub fn get_post_state_roots(&mut self, hash: &CryptoHash) -> Result<&Vec<MerkleHash>, Error> {
+        self.store.get_post_state_roots(hash)
+    }
+
+    /// Get state root hash of the shard after applying header with given hash.
+    pub fn get_post_state_root(
+        &mut self,
+        hash: &CryptoHash,
+        shard_id: ShardId,
+    ) -> Result<MerkleHash, Error> {
+        self.store.get_post_state_roots(hash)?.get(shard_id as usize).cloned().ok_or_else(|| {
+            ErrorKind::Other(format!("Missing state root of shard #{} at {}", shard_id, hash))
+                .into()
+        })
     }
 
     /// Get receipts stored for the given hash.
@@ -531,6 +685,96 @@ impl Chain {
         self.store.get_transaction_result(hash)
     }
 
+    /// Get transaction result for given hash or unknown result if it wasn't processed yet.
+    pub fn get_transaction_result_or_unknown(
+        &mut self,
+        hash: &CryptoHash,
+    ) -> Result<TransactionResult, Error> {
+        match self.store.get_transaction_result(hash) {
+            Ok(result) => Ok(result.clone()),
+            Err(err) => match err.kind() {
+                ErrorKind::DBNotFoundErr(_) => Ok(TransactionResult::default()),
+                _ => Err(err),
+            },
+        }
+    }
+
+    fn collect_transaction_final_result(
+        &mut self,
+        transaction_result: &TransactionResult,
+        logs: &mut Vec<TransactionLogs>,
+    ) -> Result<FinalTransactionStatus, Error> {
+        match transaction_result.status {
+            TransactionStatus::Unknown => Ok(FinalTransactionStatus::Unknown),
+            TransactionStatus::Failed => Ok(FinalTransactionStatus::Failed),
+            TransactionStatus::Completed => {
+                for r in transaction_result.receipts.iter() {
+                    let receipt_result = self.get_transaction_result_or_unknown(&r)?;
+                    logs.push(TransactionLogs {
+                        hash: *r,
+                        lines: receipt_result.logs.clone(),
+                        receipts: receipt_result.receipts.clone(),
+                        result: receipt_result.result.clone(),
+                    });
+                    match self.collect_transaction_final_result(&receipt_result, logs)? {
+                        FinalTransactionStatus::Failed => {
+                            return Ok(FinalTransactionStatus::Failed)
+                        }
+                        FinalTransactionStatus::Completed => {}
+                        _ => return Ok(FinalTransactionStatus::Started),
+                    };
+                }
+                Ok(FinalTransactionStatus::Completed)
+            }
+        }
+    }
+
+    /// Get events emitted by the account with the topic in the blocks of the main chain with
+    /// heights from `from_height` to `to_height` inclusive. Blocks without such events are skipped.
+    pub fn get_events(
+        &mut self,
+        account_id: &AccountId,
+        topic: &str,
+        from_height: BlockIndex,
+        to_height: BlockIndex,
+    ) -> Result<Vec<BlockEvents>, Error> {
+        let mut result = vec![];
+        for height in from_height..=to_height {
+            let hash = match self.store.get_block_hash_by_height(height) {
+                Ok(hash) => hash,
+                Err(err) => match err.kind() {
+                    ErrorKind::DBNotFoundErr(_) => continue,
+                    _ => return Err(err),
+                },
+            };
+            let events = self.store.get_events(account_id, topic, &hash)?;
+            if !events.is_empty() {
+                result.push(BlockEvents { block_index: height, block_hash: hash, events });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Get final result of the transaction with given hash, following all the receipts it produced.
+    pub fn get_final_transaction_result(
+        &mut self,
+        hash: &CryptoHash,
+    ) -> Result<FinalTransactionResult, Error> {
+        let transaction_result = self.get_transaction_result_or_unknown(hash)?;
+        let mut result = FinalTransactionResult {
+            status: FinalTransactionStatus::Unknown,
+            logs: vec![TransactionLogs {
+                hash: *hash,
+                lines: transaction_result.logs.clone(),
+                receipts: transaction_result.receipts.clone(),
+                result: transaction_result.result.clone(),
+            }],
+        };
+        result.status =
+            self.collect_transaction_final_result(&transaction_result, &mut result.logs)?;
+        Ok(result)
+    }
+
     /// Returns underlying ChainStore.
     #[inline]
     pub fn store(&self) -> &ChainStore {
@@ -632,6 +876,7 @@ impl<'a> ChainUpdate<'a> {
         // First real I/O expense.
         let prev = self.get_previous_header(&block.header)?;
         let prev_hash = prev.hash();
+        let prev_height = prev.height;
 
         // Block is an orphan if we do not know about the previous full block.
         if !is_next && !self.chain_store_update.block_exists(&prev_hash)? {
@@ -645,29 +890,83 @@ impl<'a> ChainUpdate<'a> {
         // Check the header is valid before we proceed with the full block.
         self.process_header_for_block(&block.header, provenance)?;
 
-        // Check that state root we computed from previous block matches recorded in this block.
-        let state_root = self.chain_store_update.get_post_state_root(&prev_hash)?;
-        if &block.header.prev_state_root != state_root {
+        // Check that state roots we computed from previous block match recorded in this block.
+        let state_roots = self.chain_store_update.get_post_state_roots(&prev_hash)?.clone();
+        if block.header.prev_state_roots != state_roots {
             return Err(ErrorKind::InvalidStateRoot.into());
         }
 
-        // Retrieve receipts from the previous block.
-        let receipts = self.chain_store_update.get_receipts(&prev_hash)?;
-        let receipt_hashes = receipts.iter().map(|r| r.get_hash()).collect::<Vec<_>>();
+        // Retrieve receipts from the previous block, they are routed to the shards of receivers.
+        let receipts = self.chain_store_update.get_receipts(&prev_hash)?.clone();
+
+        // Heights of the blocks referenced by the transactions, to check they are not expired.
+        // Only the ancestors of this block are known, blocks of other forks are not referenced.
+        let mut reference_indices = HashMap::new();
+        for transaction in block.transactions.iter() {
+            let height = match self.chain_store_update.get_block_header(&transaction.block_hash) {
+                Ok(header) if header.height <= prev_height => header.height,
+                _ => continue,
+            };
+            if self.chain_store_update.is_on_chain(&prev_hash, &transaction.block_hash, height)? {
+                reference_indices.insert(transaction.block_hash, height);
+            }
+        }
 
-        // Apply block to runtime.
-        let (trie_changes, state_root, mut tx_results, new_receipts, validator_proposals) = self
-            .runtime_adapter
-            .apply_transactions(
-                0,
-                &block.header.prev_state_root,
-                block.header.height,
-                &block.header.prev_hash,
-                &block.header.hash(),
-                &vec![receipts.clone()], // TODO: currently only taking into account one shard.
-                &block.transactions,
-            )
-            .map_err(|e| ErrorKind::Other(e.to_string()))?;
+        // Apply block to runtime, shard by shard.
+        let mut all_trie_changes = vec![];
+        let mut new_state_roots = vec![];
+        let mut all_validator_proposals = vec![];
+        let mut all_new_receipts: HashMap<ShardId, Vec<ReceiptTransaction>> = HashMap::new();
+        let mut events = vec![];
+        for (shard_id, state_root) in state_roots.iter().enumerate() {
+            let shard_id = shard_id as ShardId;
+            let shard_receipts: Vec<_> = receipts
+                .iter()
+                .filter(|receipt| {
+                    self.runtime_adapter.account_id_to_shard_id(&receipt.receiver) == shard_id
+                })
+                .cloned()
+                .collect();
+            // Transactions are applied in the shard of the originator.
+            let shard_transactions: Vec<_> = block
+                .transactions
+                .iter()
+                .filter(|transaction| {
+                    let originator = transaction.body.get_originator();
+                    self.runtime_adapter.account_id_to_shard_id(&originator) == shard_id
+                })
+                .cloned()
+                .collect();
+            let (trie_changes, new_state_root, tx_results, new_receipts, validator_proposals) =
+                self.runtime_adapter
+                    .apply_transactions(
+                        shard_id,
+                        state_root,
+                        block.header.height,
+                        &block.header.prev_hash,
+                        &block.header.hash(),
+                        &vec![shard_receipts.clone()],
+                        &shard_transactions,
+                        &reference_indices,
+                    )
+                    .map_err(|e| ErrorKind::Other(e.to_string()))?;
+            all_trie_changes.push(trie_changes);
+            new_state_roots.push(new_state_root);
+            all_validator_proposals.extend(validator_proposals);
+            for (receiver_shard_id, mut receipts) in new_receipts {
+                all_new_receipts.entry(receiver_shard_id).or_default().append(&mut receipts);
+            }
+            events.extend(tx_results.iter().flat_map(|result| result.events.iter().cloned()));
+
+            // Save receipt and transaction results.
+            let result_hashes = shard_receipts
+                .iter()
+                .map(|receipt| receipt.get_hash())
+                .chain(shard_transactions.iter().map(|transaction| transaction.get_hash()));
+            for (result_hash, tx_result) in result_hashes.zip(tx_results) {
+                self.chain_store_update.save_transaction_result(&result_hash, tx_result);
+            }
+        }
 
         // If block checks out, record validator proposals for given block.
         self.runtime_adapter
@@ -675,29 +974,23 @@ impl<'a> ChainUpdate<'a> {
                 block.header.prev_hash,
                 block.hash(),
                 block.header.height,
-                validator_proposals,
+                all_validator_proposals,
                 vec![],
             )
             .map_err(|err| ErrorKind::Other(err.to_string()))?;
 
-        self.chain_store_update.save_trie_changes(trie_changes);
-        // Save state root after applying transactions.
-        self.chain_store_update.save_post_state_root(&block.hash(), &state_root);
-        // Save resulting receipts.
-        // TODO: currently only taking into account one shard.
-        self.chain_store_update
-            .save_receipt(&block.hash(), new_receipts.get(&0).unwrap_or(&vec![]).to_vec());
-        // Save receipt and transaction results.
-        for (i, tx_result) in tx_results.drain(..).enumerate() {
-            if i < receipt_hashes.len() {
-                self.chain_store_update.save_transaction_result(&receipt_hashes[i], tx_result);
-            } else {
-                self.chain_store_update.save_transaction_result(
-                    &block.transactions[i - receipt_hashes.len()].get_hash(),
-                    tx_result,
-                );
-            }
+        self.chain_store_update.save_trie_changes(&block.hash(), all_trie_changes);
+        // Save state roots after applying transactions.
+        self.chain_store_update.save_post_state_roots(&block.hash(), new_state_roots);
+        // Save resulting receipts, ordered by the shard they are sent to, so every shard applies
+        // them in the same order.
+        let mut new_receipts = vec![];
+        for shard_id in 0..(state_roots.len() as ShardId) {
+            new_receipts.extend(all_new_receipts.remove(&shard_id).unwrap_or_default());
         }
+        self.chain_store_update.save_receipt(&block.hash(), new_receipts);
+        // Save events of the receipts and transactions, indexed by the account and the topic.
+        self.chain_store_update.save_events(&block.hash(), events);
 
         // Add validated block to the db, even if it's not the selected fork.
         self.chain_store_update.save_block(block.clone());
@@ -1,17 +1,19 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
 use std::io;
 use std::sync::Arc;
 
-use cached::SizedCache;
+use cached::{Cached, SizedCache};
 use log::debug;
 
 use near_primitives::hash::CryptoHash;
-use near_primitives::transaction::{ReceiptTransaction, TransactionResult};
-use near_primitives::types::{BlockIndex, MerkleHash};
+use near_primitives::transaction::{ContractEvent, ReceiptTransaction, TransactionResult};
+use near_primitives::types::{AccountId, BlockIndex, MerkleHash};
 use near_primitives::utils::index_to_bytes;
 use near_store::{
-    read_with_cache, Store, StoreUpdate, WrappedTrieChanges, COL_BLOCK, COL_BLOCK_HEADER,
-    COL_BLOCK_INDEX, COL_BLOCK_MISC, COL_RECEIPTS, COL_STATE_REF, COL_TRANSACTION_RESULT,
+    read_with_cache, Store, StoreUpdate, TrieChanges, WrappedTrieChanges, COL_BLOCK,
+    COL_BLOCK_HEADER, COL_BLOCK_INDEX, COL_BLOCK_MISC, COL_BLOCK_PER_HEIGHT, COL_EVENTS,
+    COL_RECEIPTS, COL_STATE_REF, COL_TRANSACTION_RESULT, COL_TRIE_CHANGES,
 };
 
 use crate::error::{Error, ErrorKind};
@@ -45,8 +47,8 @@ pub trait ChainStoreAccess {
     fn block_exists(&self, h: &CryptoHash) -> Result<bool, Error>;
     /// Get previous header.
     fn get_previous_header(&mut self, header: &BlockHeader) -> Result<&BlockHeader, Error>;
-    /// Get state root hash after applying header with given hash.
-    fn get_post_state_root(&mut self, h: &CryptoHash) -> Result<&MerkleHash, Error>;
+    /// Get state root hashes of the shards after applying header with given hash.
+    fn get_post_state_roots(&mut self, h: &CryptoHash) -> Result<&Vec<MerkleHash>, Error>;
     /// Get block header.
     fn get_block_header(&mut self, h: &CryptoHash) -> Result<&BlockHeader, Error>;
     /// Returns hash of the block on the main chain for given height.
@@ -55,6 +57,28 @@ pub trait ChainStoreAccess {
     fn get_receipts(&mut self, hash: &CryptoHash) -> Result<&Vec<ReceiptTransaction>, Error>;
     /// Returns transaction result for given tx hash.
     fn get_transaction_result(&mut self, hash: &CryptoHash) -> Result<&TransactionResult, Error>;
+
+    /// Is the block with given hash and height the block `tip_hash` or one of its ancestors?
+    /// Walks back from the tip until the branch joins the main chain, which is checked by index.
+    fn is_on_chain(
+        &mut self,
+        tip_hash: &CryptoHash,
+        hash: &CryptoHash,
+        height: BlockIndex,
+    ) -> Result<bool, Error> {
+        let mut header = self.get_block_header(tip_hash)?.clone();
+        loop {
+            if header.height <= height {
+                return Ok(header.height == height && header.hash() == *hash);
+            }
+            match self.get_block_hash_by_height(header.height) {
+                Ok(index_hash) if index_hash == header.hash() => {
+                    return Ok(self.get_block_hash_by_height(height).ok() == Some(*hash));
+                }
+                _ => header = self.get_previous_header(&header)?.clone(),
+            }
+        }
+    }
 }
 
 /// All chain-related database operations.
@@ -65,7 +89,7 @@ pub struct ChainStore {
     /// Cache with blocks.
     blocks: SizedCache<Vec<u8>, Block>,
     /// Cache with state roots.
-    post_state_roots: SizedCache<Vec<u8>, MerkleHash>,
+    post_state_roots: SizedCache<Vec<u8>, Vec<MerkleHash>>,
     // Cache with index to hash on the main chain.
     // block_index: SizedCache<Vec<u8>, CryptoHash>,
     /// Cache with receipts.
@@ -74,6 +98,22 @@ pub struct ChainStore {
     transaction_results: SizedCache<Vec<u8>, TransactionResult>,
 }
 
+fn block_per_height_key(height: BlockIndex, hash: &CryptoHash) -> Vec<u8> {
+    let mut key = index_to_bytes(height);
+    key.extend_from_slice(hash.as_ref());
+    key
+}
+
+/// Key of the events emitted by the account with the topic in the block with given hash.
+/// Account ids can't contain ',', so it separates them from the topic.
+fn events_key(account_id: &AccountId, topic: &str, hash: &CryptoHash) -> Vec<u8> {
+    let mut key = account_id.as_bytes().to_vec();
+    key.push(b',');
+    key.extend_from_slice(topic.as_bytes());
+    key.extend_from_slice(hash.as_ref());
+    key
+}
+
 pub fn option_to_not_found<T>(res: io::Result<Option<T>>, field_name: &str) -> Result<T, Error> {
     match res {
         Ok(Some(o)) => Ok(o),
@@ -98,6 +138,59 @@ impl ChainStore {
     pub fn store_update(&mut self) -> ChainStoreUpdate<Self> {
         ChainStoreUpdate::new(self)
     }
+
+    /// Evicts the cached data of the block removed by `ChainStoreUpdate::clear_block_data`,
+    /// given the hashes of the transaction results it cleared.
+    pub fn clear_block_cache(
+        &mut self,
+        hash: &CryptoHash,
+        prev_hash: &CryptoHash,
+        result_hashes: &[CryptoHash],
+    ) {
+        self.blocks.cache_remove(&hash.as_ref().to_vec());
+        self.post_state_roots.cache_remove(&hash.as_ref().to_vec());
+        self.post_state_roots.cache_remove(&prev_hash.as_ref().to_vec());
+        self.receipts.cache_remove(&hash.as_ref().to_vec());
+        self.receipts.cache_remove(&prev_hash.as_ref().to_vec());
+        for result_hash in result_hashes {
+            self.transaction_results.cache_remove(&result_hash.as_ref().to_vec());
+        }
+    }
+
+    /// Returns hashes of all processed blocks at given height, including forks.
+    pub fn get_all_block_hashes_by_height(
+        &self,
+        height: BlockIndex,
+    ) -> Result<Vec<CryptoHash>, Error> {
+        let prefix = index_to_bytes(height);
+        self.store
+            .iter_prefix(COL_BLOCK_PER_HEIGHT, &prefix)
+            .map(|(key, _)| {
+                CryptoHash::try_from(&key[prefix.len()..])
+                    .map_err(|err| ErrorKind::Other(err.to_string()).into())
+            })
+            .collect()
+    }
+
+    /// Returns changes to the states of the shards done by the block, if they were not garbage
+    /// collected yet.
+    pub fn get_trie_changes(&self, hash: &CryptoHash) -> Result<Option<Vec<TrieChanges>>, Error> {
+        self.store.get_ser(COL_TRIE_CHANGES, hash.as_ref()).map_err(|err| err.into())
+    }
+
+    /// Returns events emitted by the account with the topic in the block with given hash,
+    /// in the order they were emitted.
+    pub fn get_events(
+        &self,
+        account_id: &AccountId,
+        topic: &str,
+        hash: &CryptoHash,
+    ) -> Result<Vec<ContractEvent>, Error> {
+        self.store
+            .get_ser(COL_EVENTS, &events_key(account_id, topic, hash))
+            .map(Option::unwrap_or_default)
+            .map_err(|err| err.into())
+    }
 }
 
 impl ChainStoreAccess for ChainStore {
@@ -147,8 +240,8 @@ impl ChainStoreAccess for ChainStore {
         self.get_block_header(&header.prev_hash)
     }
 
-    /// Get state root hash after applying header with given hash.
-    fn get_post_state_root(&mut self, h: &CryptoHash) -> Result<&MerkleHash, Error> {
+    /// Get state root hashes of the shards after applying header with given hash.
+    fn get_post_state_roots(&mut self, h: &CryptoHash) -> Result<&Vec<MerkleHash>, Error> {
         option_to_not_found(
             read_with_cache(&*self.store, COL_STATE_REF, &mut self.post_state_roots, h.as_ref()),
             &format!("STATE ROOT: {}", h),
@@ -210,15 +303,17 @@ pub struct ChainStoreUpdate<'a, T> {
     blocks: HashMap<CryptoHash, Block>,
     deleted_blocks: HashSet<CryptoHash>,
     headers: HashMap<CryptoHash, BlockHeader>,
-    post_state_roots: HashMap<CryptoHash, MerkleHash>,
+    post_state_roots: HashMap<CryptoHash, Vec<MerkleHash>>,
     block_index: HashMap<BlockIndex, Option<CryptoHash>>,
     receipts: HashMap<CryptoHash, Vec<ReceiptTransaction>>,
     transaction_results: HashMap<CryptoHash, TransactionResult>,
+    /// Events emitted in the blocks with given hashes.
+    events: HashMap<CryptoHash, Vec<ContractEvent>>,
     head: Option<Tip>,
     tail: Option<Tip>,
     header_head: Option<Tip>,
     sync_head: Option<Tip>,
-    trie_changes: Option<WrappedTrieChanges>,
+    trie_changes: Option<(CryptoHash, Vec<WrappedTrieChanges>)>,
 }
 
 impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
@@ -233,6 +328,7 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
             post_state_roots: HashMap::default(),
             receipts: HashMap::default(),
             transaction_results: HashMap::default(),
+            events: HashMap::default(),
             head: None,
             tail: None,
             header_head: None,
@@ -306,12 +402,12 @@ impl<'a, T: ChainStoreAccess> ChainStoreAccess for ChainStoreUpdate<'a, T> {
         self.get_block_header(&header.prev_hash)
     }
 
-    /// Get state root hash after applying header with given hash.
-    fn get_post_state_root(&mut self, hash: &CryptoHash) -> Result<&MerkleHash, Error> {
-        if let Some(post_state_root) = self.post_state_roots.get(hash) {
-            Ok(post_state_root)
+    /// Get state root hashes of the shards after applying header with given hash.
+    fn get_post_state_roots(&mut self, hash: &CryptoHash) -> Result<&Vec<MerkleHash>, Error> {
+        if let Some(post_state_roots) = self.post_state_roots.get(hash) {
+            Ok(post_state_roots)
         } else {
-            self.chain_store.get_post_state_root(hash)
+            self.chain_store.get_post_state_roots(hash)
         }
     }
 
@@ -395,6 +491,15 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
         Ok(())
     }
 
+    /// Update all heads to the block, whose ancestors are not stored (e.g. imported from a snapshot).
+    pub fn reset_heads(&mut self, t: &Tip) {
+        self.block_index.insert(t.height, Some(t.last_block_hash));
+        self.head = Some(t.clone());
+        self.tail = Some(t.clone());
+        self.header_head = Some(t.clone());
+        self.sync_head = Some(t.clone());
+    }
+
     /// Save "sync" head.
     pub fn save_sync_head(&mut self, t: &Tip) {
         self.sync_head = Some(t.clone());
@@ -405,9 +510,9 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
         self.blocks.insert(block.hash(), block);
     }
 
-    /// Save post applying block state root.
-    pub fn save_post_state_root(&mut self, hash: &CryptoHash, state_root: &CryptoHash) {
-        self.post_state_roots.insert(*hash, *state_root);
+    /// Save post applying block state roots of the shards.
+    pub fn save_post_state_roots(&mut self, hash: &CryptoHash, state_roots: Vec<MerkleHash>) {
+        self.post_state_roots.insert(*hash, state_roots);
     }
 
     pub fn delete_block(&mut self, hash: &CryptoHash) {
@@ -426,6 +531,11 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
         self.transaction_results.insert(*hash, result);
     }
 
+    /// Saves all events emitted in the block, they are indexed by the account and the topic.
+    pub fn save_events(&mut self, hash: &CryptoHash, events: Vec<ContractEvent>) {
+        self.events.insert(*hash, events);
+    }
+
     /// Starts a sub-ChainUpdate with atomic commit/rollback of all operations done
     /// within this scope.
     /// If the closure returns and error, all changes are canceled.
@@ -452,8 +562,59 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
         }
     }
 
-    pub fn save_trie_changes(&mut self, trie_changes: WrappedTrieChanges) {
-        self.trie_changes = Some(trie_changes);
+    /// Saves the changes of the states of the shards done by the block, indexed by the shard id.
+    pub fn save_trie_changes(&mut self, hash: &CryptoHash, trie_changes: Vec<WrappedTrieChanges>) {
+        self.trie_changes = Some((*hash, trie_changes));
+    }
+
+    /// Removes the body of the block, its trie changes and its events.
+    ///
+    /// For the `canonical` block the receipts of the previous block and the results of everything
+    /// executed in this block are removed too, together with the reference to the state before
+    /// the block. An abandoned fork shares these with the canonical chain: its previous block is
+    /// often canonical and its transactions may be included into the canonical chain later. So
+    /// only the data keyed by the fork block itself is removed: its receipts and the reference to
+    /// the state after it. Headers are kept. Returns the hashes of the removed transaction results.
+    pub fn clear_block_data(
+        &mut self,
+        hash: &CryptoHash,
+        canonical: bool,
+    ) -> Result<Vec<CryptoHash>, Error> {
+        let block = self.get_block(hash)?.clone();
+        let mut store_update = self.store().store_update();
+        let mut result_hashes: Vec<CryptoHash> = self
+            .get_receipts(&block.header.prev_hash)
+            .map(|receipts| receipts.iter().map(|receipt| receipt.get_hash()).collect())
+            .unwrap_or_default();
+        result_hashes.extend(block.transactions.iter().map(|transaction| transaction.get_hash()));
+        // Events are keyed by the block, they are found through the results they were emitted by.
+        for result_hash in result_hashes.iter() {
+            if let Ok(result) = self.get_transaction_result(result_hash) {
+                for event in result.events.iter() {
+                    store_update
+                        .delete(COL_EVENTS, &events_key(&event.account_id, &event.topic, hash));
+                }
+            }
+        }
+        store_update.delete(COL_TRIE_CHANGES, hash.as_ref());
+        store_update.delete(
+            COL_BLOCK_PER_HEIGHT,
+            &block_per_height_key(block.header.height, hash),
+        );
+        if canonical {
+            for result_hash in result_hashes.iter() {
+                store_update.delete(COL_TRANSACTION_RESULT, result_hash.as_ref());
+            }
+            store_update.delete(COL_RECEIPTS, block.header.prev_hash.as_ref());
+            store_update.delete(COL_STATE_REF, block.header.prev_hash.as_ref());
+        } else {
+            result_hashes.clear();
+            store_update.delete(COL_RECEIPTS, hash.as_ref());
+            store_update.delete(COL_STATE_REF, hash.as_ref());
+        }
+        self.store_updates.push(store_update);
+        self.delete_block(hash);
+        Ok(result_hashes)
     }
 
     /// Merge another StoreUpdate into this one
@@ -483,6 +644,11 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
             store_update
                 .set_ser(COL_BLOCK, hash.as_ref(), &block)
                 .map_err::<Error, _>(|e| e.into())?;
+            store_update.set(
+                COL_BLOCK_PER_HEIGHT,
+                &block_per_height_key(block.header.height, &hash),
+                &[],
+            );
         }
         for hash in self.deleted_blocks.drain() {
             store_update.delete(COL_BLOCK, hash.as_ref());
@@ -492,9 +658,9 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
                 .set_ser(COL_BLOCK_HEADER, hash.as_ref(), &header)
                 .map_err::<Error, _>(|e| e.into())?;
         }
-        for (hash, state_root) in self.post_state_roots.drain() {
+        for (hash, state_roots) in self.post_state_roots.drain() {
             store_update
-                .set_ser(COL_STATE_REF, hash.as_ref(), &state_root)
+                .set_ser(COL_STATE_REF, hash.as_ref(), &state_roots)
                 .map_err::<Error, _>(|e| e.into())?;
         }
         for (height, hash) in self.block_index.drain() {
@@ -512,11 +678,25 @@ impl<'a, T: ChainStoreAccess> ChainStoreUpdate<'a, T> {
         for (hash, tx_result) in self.transaction_results.drain() {
             store_update.set_ser(COL_TRANSACTION_RESULT, hash.as_ref(), &tx_result)?;
         }
-        if let Some(trie_changes) = self.trie_changes {
-            trie_changes
-                .insertions_into(&mut store_update)
-                .map_err(|err| ErrorKind::Other(err.to_string()))?;
-            // TODO: save deletions separately for garbage collection.
+        for (hash, events) in self.events.drain() {
+            let mut indexed: BTreeMap<(AccountId, String), Vec<ContractEvent>> = BTreeMap::new();
+            for event in events {
+                let index = (event.account_id.clone(), event.topic.clone());
+                indexed.entry(index).or_insert_with(|| vec![]).push(event);
+            }
+            for ((account_id, topic), events) in indexed {
+                store_update.set_ser(COL_EVENTS, &events_key(&account_id, &topic, &hash), &events)?;
+            }
+        }
+        if let Some((hash, trie_changes)) = self.trie_changes {
+            for shard_trie_changes in trie_changes.iter() {
+                shard_trie_changes
+                    .insertions_into(&mut store_update)
+                    .map_err(|err| ErrorKind::Other(err.to_string()))?;
+            }
+            // Deletions are applied later by the garbage collection.
+            let trie_changes: Vec<_> = trie_changes.iter().map(|t| t.trie_changes()).collect();
+            store_update.set_ser(COL_TRIE_CHANGES, hash.as_ref(), &trie_changes)?;
         }
         for other in self.store_updates {
             store_update.merge(other);
@@ -1,17 +1,22 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
 
 use chrono::Utc;
 
 use near_primitives::crypto::signature::Signature;
 use near_primitives::crypto::signer::InMemorySigner;
-use near_primitives::hash::CryptoHash;
-use near_primitives::rpc::{AccountViewCallResult, QueryResponse};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::rpc::{
+    AccountViewCallResult, QueryResponse, SimulateTxResult, StatusContractCacheInfo,
+};
+use near_primitives::state_proof::StateProof;
 use near_primitives::test_utils::get_public_key_from_seed;
 use near_primitives::transaction::{
     ReceiptTransaction, SignedTransaction, TransactionResult, TransactionStatus,
 };
-use near_primitives::types::{AccountId, BlockIndex, MerkleHash, ShardId, ValidatorStake};
+use near_primitives::types::{
+    AccountId, BlockIndex, MerkleHash, ShardId, StatePart, ValidatorStake,
+};
 use near_store::test_utils::create_test_store;
 use near_store::{Store, StoreUpdate, Trie, TrieChanges, WrappedTrieChanges};
 
@@ -25,6 +30,10 @@ pub struct KeyValueRuntime {
     trie: Arc<Trie>,
     root: MerkleHash,
     validators: Vec<ValidatorStake>,
+    /// Number of parts the state is split into for the state sync.
+    num_state_parts: u64,
+    /// Parts of the states that were set, by the state root.
+    state_parts: RwLock<HashMap<MerkleHash, HashSet<u64>>>,
 }
 
 impl KeyValueRuntime {
@@ -46,12 +55,25 @@ impl KeyValueRuntime {
                     amount: 1_000_000,
                 })
                 .collect(),
+            num_state_parts: 1,
+            state_parts: Default::default(),
         }
     }
 
+    /// Same runtime, that splits the state into given number of parts for the state sync.
+    pub fn with_num_state_parts(self, num_state_parts: u64) -> Self {
+        KeyValueRuntime { num_state_parts, ..self }
+    }
+
     pub fn get_root(&self) -> MerkleHash {
         self.root
     }
+
+    /// Content of the state part, the parts are only checked to be the ones dumped.
+    pub fn state_part_data(state_root: &MerkleHash, part_id: u64) -> Vec<u8> {
+        let data = [state_root.as_ref(), &part_id.to_le_bytes()[..]].concat();
+        hash(&data).as_ref().to_vec()
+    }
 }
 
 impl RuntimeAdapter for KeyValueRuntime {
@@ -59,6 +81,10 @@ impl RuntimeAdapter for KeyValueRuntime {
         (self.store.store_update(), vec![MerkleHash::default()])
     }
 
+    fn get_trie(&self) -> Arc<Trie> {
+        self.trie.clone()
+    }
+
     fn compute_block_weight(
         &self,
         prev_header: &BlockHeader,
@@ -112,9 +138,11 @@ impl RuntimeAdapter for KeyValueRuntime {
         &self,
         _shard_id: ShardId,
         _state_root: MerkleHash,
+        _block_index: BlockIndex,
+        _reference_index: Option<BlockIndex>,
         transaction: SignedTransaction,
     ) -> Result<ValidTransaction, String> {
-        Ok(ValidTransaction { transaction })
+        Ok(ValidTransaction { transaction, fee: 0, valid_until: BlockIndex::max_value() })
     }
 
     fn add_validator_proposals(
@@ -137,6 +165,7 @@ impl RuntimeAdapter for KeyValueRuntime {
         _block_hash: &CryptoHash,
         _receipts: &Vec<Vec<ReceiptTransaction>>,
         transactions: &Vec<SignedTransaction>,
+        _reference_indices: &HashMap<CryptoHash, BlockIndex>,
     ) -> Result<
         (
             WrappedTrieChanges,
@@ -152,8 +181,13 @@ impl RuntimeAdapter for KeyValueRuntime {
             tx_results.push(TransactionResult {
                 status: TransactionStatus::Completed,
                 logs: vec![],
+                events: vec![],
                 receipts: vec![],
                 result: None,
+                gas_used: 0,
+                previous_code_hash: None,
+                prepare_error: None,
+                traces: vec![],
             });
         }
         Ok((
@@ -183,22 +217,89 @@ impl RuntimeAdapter for KeyValueRuntime {
         }))
     }
 
+    fn query_proof(
+        &self,
+        state_root: MerkleHash,
+        path: &str,
+        _data: &[u8],
+    ) -> Result<StateProof, Box<dyn std::error::Error>> {
+        Ok(self.trie.prove_value(&state_root, path.as_bytes()))
+    }
+
+    fn simulate_transaction(
+        &self,
+        _state_roots: &[MerkleHash],
+        _block_index: BlockIndex,
+        _prev_block_hash: &CryptoHash,
+        _transaction: &SignedTransaction,
+    ) -> Result<SimulateTxResult, Box<dyn std::error::Error>> {
+        Ok(SimulateTxResult {
+            result: TransactionResult {
+                status: TransactionStatus::Completed,
+                ..Default::default()
+            },
+            logs: vec![],
+            receipts: vec![],
+            cost: 0,
+            gas_used: 0,
+            traces: vec![],
+        })
+    }
+
     fn dump_state(
         &self,
         _shard_id: ShardId,
-        _state_root: MerkleHash,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        Ok(vec![])
+        state_root: MerkleHash,
+        part_id: u64,
+    ) -> Result<StatePart, Box<dyn std::error::Error>> {
+        if part_id >= self.num_state_parts {
+            return Err(format!("State part {} is out of {}", part_id, self.num_state_parts).into());
+        }
+        Ok(StatePart {
+            part_id,
+            num_parts: self.num_state_parts,
+            data: Self::state_part_data(&state_root, part_id),
+            proof: vec![],
+        })
     }
 
     fn set_state(
         &self,
         _shard_id: ShardId,
-        _state_root: MerkleHash,
-        _payload: Vec<u8>,
+        state_root: MerkleHash,
+        part: StatePart,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if part.num_parts != self.num_state_parts
+            || part.part_id >= part.num_parts
+            || part.data != Self::state_part_data(&state_root, part.part_id)
+        {
+            return Err(format!("Invalid state part {}", part.part_id).into());
+        }
+        self.state_parts
+            .write()
+            .unwrap()
+            .entry(state_root)
+            .or_insert_with(HashSet::default)
+            .insert(part.part_id);
+        Ok(())
+    }
+
+    fn confirm_state(
+        &self,
+        _shard_id: ShardId,
+        state_root: MerkleHash,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let num_set_parts =
+            self.state_parts.read().unwrap().get(&state_root).map_or(0, |parts| parts.len());
+        if num_set_parts as u64 != self.num_state_parts {
+            return Err(format!("State {} is not complete", state_root).into());
+        }
         Ok(())
     }
+
+    fn contract_cache_info(&self) -> StatusContractCacheInfo {
+        StatusContractCacheInfo::default()
+    }
 }
 
 pub fn setup() -> (Chain, Arc<KeyValueRuntime>, Arc<InMemorySigner>) {
@@ -1,13 +1,17 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub use near_primitives::block::{Block, BlockHeader, Weight};
 use near_primitives::crypto::signature::Signature;
 use near_primitives::crypto::signer::EDSigner;
 use near_primitives::hash::CryptoHash;
-use near_primitives::rpc::QueryResponse;
+use near_primitives::rpc::{QueryResponse, SimulateTxResult, StatusContractCacheInfo};
+use near_primitives::state_proof::StateProof;
 use near_primitives::transaction::{ReceiptTransaction, SignedTransaction, TransactionResult};
-use near_primitives::types::{AccountId, BlockIndex, MerkleHash, ShardId, ValidatorStake};
-use near_store::{StoreUpdate, WrappedTrieChanges};
+use near_primitives::types::{
+    AccountId, Balance, BlockIndex, MerkleHash, ShardId, StatePart, ValidatorStake,
+};
+use near_store::{StoreUpdate, Trie, WrappedTrieChanges};
 
 use crate::error::Error;
 
@@ -18,8 +22,8 @@ pub enum BlockStatus {
     /// Block does not update the chain head and is a fork.
     Fork,
     /// Block updates the chain head via a (potentially disruptive) "reorg".
-    /// Previous block was not our previous chain head.
-    Reorg,
+    /// Previous block was not our previous chain head, which is given.
+    Reorg(CryptoHash),
 }
 
 /// Options for block origin.
@@ -36,6 +40,10 @@ pub enum Provenance {
 /// Information about valid transaction that was processed by chain + runtime.
 pub struct ValidTransaction {
     pub transaction: SignedTransaction,
+    /// Most the transaction can pay for its execution, used to prioritize it.
+    pub fee: Balance,
+    /// Height of the last block transaction can be included into.
+    pub valid_until: BlockIndex,
 }
 
 /// Map of shard to list of receipts to send to it.
@@ -49,6 +57,9 @@ pub trait RuntimeAdapter: Send + Sync {
     /// StoreUpdate can be discarded if the chain past the genesis.
     fn genesis_state(&self) -> (StoreUpdate, Vec<MerkleHash>);
 
+    /// Trie that stores the state, used to garbage collect old states.
+    fn get_trie(&self) -> Arc<Trie>;
+
     /// Verify block producer validity and return weight of given block for fork choice rule.
     fn compute_block_weight(
         &self,
@@ -89,10 +100,14 @@ pub trait RuntimeAdapter: Send + Sync {
     fn account_id_to_shard_id(&self, account_id: &AccountId) -> ShardId;
 
     /// Validate transaction and return transaction information relevant to ordering it in the mempool.
+    /// `block_index` is the height of the block transaction is going to be included into and
+    /// `reference_index` is the height of the block it references, if this block is known.
     fn validate_tx(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
+        block_index: BlockIndex,
+        reference_index: Option<BlockIndex>,
         transaction: SignedTransaction,
     ) -> Result<ValidTransaction, String>;
 
@@ -108,6 +123,8 @@ pub trait RuntimeAdapter: Send + Sync {
 
     /// Apply transactions to given state root and return store update and new state root.
     /// Also returns transaction result for each transaction and new receipts.
+    /// `reference_indices` are the heights of the known blocks referenced by the transactions,
+    /// transactions that reference an unknown or too old block fail.
     fn apply_transactions(
         &self,
         shard_id: ShardId,
@@ -117,6 +134,7 @@ pub trait RuntimeAdapter: Send + Sync {
         block_hash: &CryptoHash,
         receipts: &Vec<Vec<ReceiptTransaction>>,
         transactions: &Vec<SignedTransaction>,
+        reference_indices: &HashMap<CryptoHash, BlockIndex>,
     ) -> Result<
         (
             WrappedTrieChanges,
@@ -137,21 +155,51 @@ pub trait RuntimeAdapter: Send + Sync {
         data: &[u8],
     ) -> Result<QueryResponse, Box<dyn std::error::Error>>;
 
-    /// Read state as byte array from given state root.
+    /// Reads the values the query for given `path` and `data` is answered from, with the nodes
+    /// proving them.
+    fn query_proof(
+        &self,
+        state_root: MerkleHash,
+        path: &str,
+        data: &[u8],
+    ) -> Result<StateProof, Box<dyn std::error::Error>>;
+
+    /// Executes transaction on top of given state roots of the shards as if it was included in
+    /// the block at given height, and returns its outcome. Nothing is persisted.
+    fn simulate_transaction(
+        &self,
+        state_roots: &[MerkleHash],
+        block_index: BlockIndex,
+        prev_block_hash: &CryptoHash,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulateTxResult, Box<dyn std::error::Error>>;
+
+    /// Read part of the state with given root, along with the proof of its nodes.
     fn dump_state(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+        part_id: u64,
+    ) -> Result<StatePart, Box<dyn std::error::Error>>;
 
-    /// Set state that expected to be given state root with provided payload.
-    /// Returns error if failed to parse or if the resulting tree doesn't match the expected root.
+    /// Store part of the state that is expected to be given state root.
+    /// Returns error if failed to parse or if the part doesn't match the expected root.
     fn set_state(
         &self,
-        _shard_id: ShardId,
+        shard_id: ShardId,
         state_root: MerkleHash,
-        payload: Vec<u8>,
+        part: StatePart,
     ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Checks that all parts of the state with given root were set.
+    fn confirm_state(
+        &self,
+        shard_id: ShardId,
+        state_root: MerkleHash,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Hits and misses of the cache of the compiled contracts, since the runtime was created.
+    fn contract_cache_info(&self) -> StatusContractCacheInfo;
 }
 
 /// The tip of a fork. A handle to the fork ancestry from its leaf in the
@@ -208,12 +256,12 @@ mod tests {
 
     #[test]
     fn test_block_produce() {
-        let genesis = Block::genesis(MerkleHash::default(), Utc::now());
+        let genesis = Block::genesis(vec![MerkleHash::default()], Utc::now());
         let signer = Arc::new(InMemorySigner::from_seed("other", "other"));
         let b1 = Block::produce(
             &genesis.header,
             1,
-            MerkleHash::default(),
+            vec![MerkleHash::default()],
             vec![],
             HashMap::default(),
             vec![],
@@ -227,7 +275,7 @@ mod tests {
         let b2 = Block::produce(
             &b1.header,
             2,
-            MerkleHash::default(),
+            vec![MerkleHash::default()],
             vec![],
             approvals,
             vec![],
@@ -1,8 +1,13 @@
 use std::collections::HashMap;
 
 use near_chain::test_utils::setup;
-use near_chain::{Block, ErrorKind, Provenance};
+use near_chain::{Block, ChainStoreAccess, ErrorKind, Provenance};
+use near_primitives::hash::hash;
+use near_primitives::rpc::BlockEvents;
 use near_primitives::test_utils::init_test_logger;
+use near_primitives::transaction::{
+    ContractEvent, ReceiptBody, ReceiptTransaction, TransactionResult,
+};
 use near_primitives::types::MerkleHash;
 
 #[test]
@@ -67,7 +72,7 @@ fn build_chain_with_skips_and_forks() {
     let b2 = Block::produce(
         chain.genesis(),
         2,
-        MerkleHash::default(),
+        vec![MerkleHash::default()],
         vec![],
         HashMap::default(),
         vec![],
@@ -77,7 +82,7 @@ fn build_chain_with_skips_and_forks() {
     let b4 = Block::produce(
         &b2.header,
         4,
-        MerkleHash::default(),
+        vec![MerkleHash::default()],
         vec![],
         HashMap::default(),
         vec![],
@@ -92,3 +97,123 @@ fn build_chain_with_skips_and_forks() {
     assert!(chain.get_header_by_height(1).is_err());
     assert_eq!(chain.get_header_by_height(5).unwrap().height, 5);
 }
+
+#[test]
+fn clear_old_data() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let mut blocks = vec![chain.get_block(&chain.genesis().hash()).unwrap().clone()];
+    for i in 1..10 {
+        let block = Block::empty(&blocks[i - 1].header, signer.clone());
+        blocks.push(block.clone());
+        chain.process_block(block, Provenance::PRODUCED, |_, _, _| {}).unwrap();
+    }
+    let fork = Block::empty(&blocks[1].header, signer.clone());
+    chain.process_block(fork.clone(), Provenance::NONE, |_, _, _| {}).unwrap();
+    assert!(chain.block_exists(&fork.hash()).unwrap());
+    // Cached data must not outlive the garbage collected rows.
+    assert!(chain.get_block(&blocks[2].hash()).is_ok());
+    assert!(chain.get_post_state_roots(&blocks[2].hash()).is_ok());
+    assert!(chain.get_post_state_roots(&fork.hash()).is_ok());
+
+    chain.clear_old_data(5, 100).unwrap();
+    assert_eq!(chain.store().tail().unwrap().height, 4);
+    assert!(!chain.block_exists(&fork.hash()).unwrap());
+    assert!(chain.get_post_state_roots(&fork.hash()).is_err());
+    for (i, block) in blocks.iter().enumerate() {
+        assert_eq!(chain.block_exists(&block.hash()).unwrap(), i == 0 || i >= 5);
+        assert_eq!(chain.get_block(&block.hash()).is_ok(), i == 0 || i >= 5);
+        assert_eq!(chain.get_post_state_roots(&block.hash()).is_ok(), i >= 4);
+        assert!(chain.get_block_header(&block.hash()).is_ok());
+    }
+}
+
+#[test]
+fn clear_old_data_fork_with_canonical_parent() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let b1 = Block::empty(chain.genesis(), signer.clone());
+    let b2 = Block::empty(&b1.header, signer.clone());
+    // The canonical chain skips heights 3 and 4, so the child of `b2` outlives the fork.
+    let b5 = Block::produce(
+        &b2.header,
+        5,
+        vec![MerkleHash::default()],
+        vec![],
+        HashMap::default(),
+        vec![],
+        signer.clone(),
+    );
+    let fork = Block::empty(&b2.header, signer.clone());
+    for block in vec![b1, b2.clone(), b5.clone()] {
+        chain.process_block(block, Provenance::PRODUCED, |_, _, _| {}).unwrap();
+    }
+    chain.process_block(fork.clone(), Provenance::NONE, |_, _, _| {}).unwrap();
+    for _ in 0..2 {
+        let block = Block::empty(chain.head_header().unwrap(), signer.clone());
+        chain.process_block(block, Provenance::PRODUCED, |_, _, _| {}).unwrap();
+    }
+
+    // Receipts of `b2` and their results are shared by `b5` and the fork.
+    let receipt = ReceiptTransaction::new(
+        "alice.near".to_string(),
+        "bob.near".to_string(),
+        hash(b"receipt"),
+        ReceiptBody::Refund(1),
+    );
+    let mut store_update = chain.mut_store().store_update();
+    store_update.save_receipt(&b2.hash(), vec![receipt.clone()]);
+    store_update.save_transaction_result(&receipt.get_hash(), TransactionResult::default());
+    store_update.commit().unwrap();
+
+    chain.clear_old_data(4, 100).unwrap();
+    assert!(!chain.block_exists(&fork.hash()).unwrap());
+    assert!(chain.get_post_state_roots(&fork.hash()).is_err());
+    // Data that `b5` is applied on outlives the collected `b2`.
+    assert!(!chain.block_exists(&b2.hash()).unwrap());
+    assert!(chain.block_exists(&b5.hash()).unwrap());
+    assert_eq!(chain.get_receipts(&b2.hash()).unwrap(), &vec![receipt.clone()]);
+    assert!(chain.get_transaction_result(&receipt.get_hash()).is_ok());
+    assert!(chain.get_post_state_roots(&b2.hash()).is_ok());
+}
+
+#[test]
+fn events_by_account_and_topic() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let block = Block::empty(chain.genesis(), signer);
+    let hash = block.hash();
+    chain.process_block(block, Provenance::PRODUCED, |_, _, _| {}).unwrap();
+
+    let event = |account_id: &str, topic: &str, data: &[u8]| ContractEvent {
+        account_id: account_id.to_string(),
+        topic: topic.to_string(),
+        data: data.to_vec(),
+    };
+    let mut store_update = chain.mut_store().store_update();
+    store_update.save_events(
+        &hash,
+        vec![
+            event("alice.near", "transfer", b"1"),
+            event("bob.near", "transfer", b"2"),
+            event("alice.near", "mint", b"3"),
+            event("alice.near", "transfer", b"4"),
+        ],
+    );
+    store_update.commit().unwrap();
+
+    let alice = "alice.near".to_string();
+    assert_eq!(
+        chain.get_events(&alice, "transfer", 0, 1).unwrap(),
+        vec![BlockEvents {
+            block_index: 1,
+            block_hash: hash,
+            events: vec![
+                event("alice.near", "transfer", b"1"),
+                event("alice.near", "transfer", b"4"),
+            ],
+        }]
+    );
+    assert!(chain.get_events(&alice, "transfer", 2, 5).unwrap().is_empty());
+    assert!(chain.get_events(&alice, "burn", 0, 1).unwrap().is_empty());
+}
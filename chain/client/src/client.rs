@@ -1,7 +1,7 @@
 //! Client is responsible for tracking the chain and related pieces of infrastructure.
 //! Block production is done in done in this actor as well (at the moment).
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Sub;
 use std::sync::{Arc, RwLock};
 use std::thread;
@@ -26,17 +26,20 @@ use near_pool::TransactionPool;
 use near_primitives::crypto::signature::Signature;
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::{ReceiptTransaction, SignedTransaction};
-use near_primitives::types::{AccountId, BlockIndex, ShardId};
+use near_primitives::types::{AccountId, BlockIndex, ShardId, StatePart};
 use near_primitives::unwrap_or_return;
 use near_store::Store;
 
 use crate::sync::{most_weight_peer, BlockSync, HeaderSync, StateSync};
 use crate::types::{
-    BlockProducer, ClientConfig, Error, NetworkInfo, ShardSyncStatus, Status, StatusSyncInfo,
-    SyncStatus,
+    BlockProducer, ClientConfig, Error, NetworkInfo, NewHead, ShardSyncStatus, Status,
+    StatusSyncInfo, SyncStatus,
 };
 use crate::{sync, StatusResponse};
 
+/// Maximum number of heights to garbage collect at once.
+const MAX_GC_HEIGHTS: BlockIndex = 100;
+
 pub struct ClientActor {
     config: ClientConfig,
     sync_status: SyncStatus,
@@ -62,6 +65,8 @@ pub struct ClientActor {
     num_blocks_processed: u64,
     /// Total number of transactions processed.
     num_tx_processed: u64,
+    /// View client, that pushes the new heads to the external subscribers of chain events.
+    view_client: Recipient<NewHead>,
 }
 
 fn wait_until_genesis(genesis_time: &DateTime<Utc>) {
@@ -84,10 +89,11 @@ impl ClientActor {
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         network_actor: Recipient<NetworkRequests>,
         block_producer: Option<BlockProducer>,
+        view_client: Recipient<NewHead>,
     ) -> Result<Self, Error> {
         wait_until_genesis(&genesis_time);
         let chain = Chain::new(store, runtime_adapter.clone(), genesis_time)?;
-        let tx_pool = TransactionPool::new();
+        let tx_pool = TransactionPool::new(config.pool_config.clone());
         let sync_status = SyncStatus::AwaitingPeers;
         let header_sync = HeaderSync::new(network_actor.clone());
         let block_sync = BlockSync::new(network_actor.clone(), config.block_fetch_horizon);
@@ -118,6 +124,7 @@ impl ClientActor {
             started: Instant::now(),
             num_blocks_processed: 0,
             num_tx_processed: 0,
+            view_client,
         })
     }
 }
@@ -134,6 +141,11 @@ impl Actor for ClientActor {
 
         // Start periodic logging of current state of the client.
         self.log_summary(ctx);
+
+        // Start garbage collection of old blocks and states, unless it's an archival node.
+        if !self.config.archive {
+            self.gc(ctx);
+        }
     }
 }
 
@@ -143,10 +155,10 @@ impl Handler<NetworkClientMessages> for ClientActor {
     fn handle(&mut self, msg: NetworkClientMessages, ctx: &mut Context<Self>) -> Self::Result {
         match msg {
             NetworkClientMessages::Transaction(tx) => match self.validate_tx(tx) {
-                Ok(valid_transaction) => {
-                    self.tx_pool.insert_transaction(valid_transaction);
-                    NetworkClientResponses::ValidTx
-                }
+                Ok(valid_transaction) => match self.tx_pool.insert_transaction(valid_transaction) {
+                    Ok(()) => NetworkClientResponses::ValidTx,
+                    Err(err) => NetworkClientResponses::InvalidTx(err.to_string()),
+                },
                 Err(err) => NetworkClientResponses::InvalidTx(err),
             },
             NetworkClientMessages::BlockHeader(header, peer_id) => {
@@ -196,44 +208,27 @@ impl Handler<NetworkClientMessages> for ClientActor {
                     NetworkClientResponses::Ban { ban_reason: ReasonForBan::BadBlockApproval }
                 }
             }
-            NetworkClientMessages::StateRequest(shard_id, hash) => {
-                if let Ok((payload, receipts)) = self.state_request(shard_id, hash) {
-                    return NetworkClientResponses::StateResponse {
-                        shard_id,
-                        hash,
-                        payload,
-                        receipts,
-                    };
-                }
-                NetworkClientResponses::NoResponse
-            }
-            NetworkClientMessages::StateResponse(shard_id, hash, payload, receipts) => {
-                if let SyncStatus::StateSync(sync_hash, sharded_statuses) = &mut self.sync_status {
-                    if hash != *sync_hash {
-                        sharded_statuses.insert(
-                            shard_id,
-                            ShardSyncStatus::Error(format!(
-                                "Incorrect hash of the state response, expected: {}, got: {}",
-                                sync_hash, hash
-                            )),
-                        );
-                    } else {
-                        match self.chain.set_shard_state(shard_id, hash, payload, receipts) {
-                            Ok(()) => {
-                                sharded_statuses.insert(shard_id, ShardSyncStatus::StateDone);
-                            }
-                            Err(err) => {
-                                sharded_statuses.insert(
-                                    shard_id,
-                                    ShardSyncStatus::Error(format!(
-                                        "Failed to set state for {} @ {}: {}",
-                                        shard_id, hash, err
-                                    )),
-                                );
-                            }
-                        }
+            NetworkClientMessages::StateRequest(shard_id, hash, part_id) => {
+                match self.state_request(shard_id, hash, part_id) {
+                    Ok((part, receipts)) => {
+                        NetworkClientResponses::StateResponse { shard_id, hash, part, receipts }
+                    }
+                    Err(err) => {
+                        debug!(target: "client", "Failed to get state part {} of shard {} @ {}: {}", part_id, shard_id, hash, err);
+                        NetworkClientResponses::NoResponse
                     }
                 }
+            }
+            NetworkClientMessages::StateResponse(shard_id, hash, part, receipts) => {
+                self.state_sync.on_state_response(
+                    &mut self.sync_status,
+                    &mut self.chain,
+                    &self.network_info.most_weight_peers,
+                    shard_id,
+                    hash,
+                    part,
+                    receipts,
+                );
                 NetworkClientResponses::NoResponse
             }
         }
@@ -248,8 +243,11 @@ impl Handler<Status> for ClientActor {
         let prev_header =
             self.chain.get_block_header(&head.last_block_hash).map_err(|err| err.to_string())?;
         let latest_block_time = prev_header.timestamp.clone();
-        let state_root =
-            self.chain.get_post_state_root(&head.last_block_hash).map_err(|err| err.to_string())?;
+        let state_roots = self
+            .chain
+            .get_post_state_roots(&head.last_block_hash)
+            .map_err(|err| err.to_string())?
+            .clone();
         let validators = self
             .runtime_adapter
             .get_epoch_block_proposers(head.last_block_hash, head.height)
@@ -262,10 +260,12 @@ impl Handler<Status> for ClientActor {
             sync_info: StatusSyncInfo {
                 latest_block_hash: head.last_block_hash,
                 latest_block_height: head.height,
-                latest_state_root: state_root.clone(),
+                latest_state_roots: state_roots,
                 latest_block_time,
                 syncing: self.sync_status.is_syncing(),
             },
+            tx_pool_info: self.tx_pool.info(),
+            contract_cache_info: self.runtime_adapter.contract_cache_info(),
         })
     }
 }
@@ -320,9 +320,69 @@ impl ClientActor {
         // Reconcile the txpool against the new block *after* we have broadcast it too our peers.
         // This may be slow and we do not want to delay block propagation.
         // We only want to reconcile the txpool against the new block *if* total weight has increased.
-        if status == BlockStatus::Next || status == BlockStatus::Reorg {
-            self.tx_pool.reconcile_block(&block);
+        match status {
+            BlockStatus::Next => self.tx_pool.reconcile_block(&block),
+            BlockStatus::Reorg(prev_head_hash) => {
+                if let Err(err) = self.reconcile_reorg(&block, prev_head_hash) {
+                    error!(target: "client", "Failed to reconcile transactions on reorg: {}", err);
+                    // At least the transactions of the new head must not stay in the pool.
+                    self.tx_pool.reconcile_block(&block);
+                }
+            }
+            BlockStatus::Fork => return,
         }
+        // Events about the new head are looked up and pushed to the subscribers by the view client,
+        // so that they don't hold up processing of the blocks.
+        let _ = self.view_client.do_send(NewHead { block });
+    }
+
+    /// Walks the old and the new branches back to their common ancestor. Transactions from the
+    /// blocks of the new branch are removed from the pool, and transactions that were only
+    /// included into the blocks of the old branch are returned to the pool if still valid.
+    fn reconcile_reorg(&mut self, block: &Block, prev_head_hash: CryptoHash) -> Result<(), Error> {
+        let mut old_hash = prev_head_hash;
+        let mut new_hash = block.hash();
+        let mut old_blocks = vec![];
+        let mut new_blocks = vec![];
+        while old_hash != new_hash {
+            let old_header = self.chain.get_block_header(&old_hash)?.clone();
+            let new_header = self.chain.get_block_header(&new_hash)?.clone();
+            if old_header.height >= new_header.height {
+                old_blocks.push(self.chain.get_block(&old_hash)?.clone());
+                old_hash = old_header.prev_hash;
+            }
+            if new_header.height >= old_header.height {
+                new_blocks.push(self.chain.get_block(&new_hash)?.clone());
+                new_hash = new_header.prev_hash;
+            }
+        }
+
+        let included: HashSet<CryptoHash> = new_blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| tx.get_hash()))
+            .collect();
+        for block in new_blocks.iter() {
+            self.tx_pool.reconcile_block(block);
+        }
+        let mut num_returned = 0;
+        for block in old_blocks.into_iter().rev() {
+            for transaction in block.transactions {
+                if included.contains(&transaction.get_hash()) {
+                    continue;
+                }
+                // Checks the transaction against the state of the new head.
+                match self.validate_tx(transaction) {
+                    Ok(valid_transaction) => {
+                        if self.tx_pool.insert_transaction(valid_transaction).is_ok() {
+                            num_returned += 1;
+                        }
+                    }
+                    Err(err) => debug!(target: "client", "Dropping transaction on reorg: {}", err),
+                }
+            }
+        }
+        debug!(target: "client", "Reorg from {} to {} at common ancestor {}, returned {} transactions to the pool", prev_head_hash, block.hash(), new_hash, num_returned);
+        Ok(())
     }
 
     fn get_block_proposer(
@@ -458,7 +518,7 @@ impl ClientActor {
             info!(target: "client", "Produce block: chain at {}, not block producer for next block.", next_height);
             return Ok(());
         }
-        let state_root = self.chain.get_post_state_root(&head.last_block_hash)?.clone();
+        let state_roots = self.chain.get_post_state_roots(&head.last_block_hash)?.clone();
         let has_receipts =
             self.chain.get_receipts(&head.last_block_hash).map(|r| r.len() > 0).unwrap_or(false);
 
@@ -512,7 +572,7 @@ impl ClientActor {
         let block = Block::produce(
             &prev_header,
             next_height,
-            state_root,
+            state_roots,
             transactions,
             self.approvals.drain().collect(),
             vec![],
@@ -674,12 +734,27 @@ impl ClientActor {
     /// Validate transaction and return transaction information relevant to ordering it in the mempool.
     fn validate_tx(&mut self, tx: SignedTransaction) -> Result<ValidTransaction, String> {
         let head = self.chain.head().map_err(|err| err.to_string())?;
+        // Transaction is validated against the state of the shard of the originator.
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(&tx.body.get_originator());
         let state_root = self
             .chain
-            .get_post_state_root(&head.last_block_hash)
-            .map_err(|err| err.to_string())?
-            .clone();
-        self.runtime_adapter.validate_tx(0, state_root, tx)
+            .get_post_state_root(&head.last_block_hash, shard_id)
+            .map_err(|err| err.to_string())?;
+        // Only the blocks of the canonical chain up to the head are known as reference.
+        let mut reference_index = match self.chain.get_block_header(&tx.block_hash) {
+            Ok(header) if header.height <= head.height => Some(header.height),
+            _ => None,
+        };
+        if let Some(height) = reference_index {
+            if !self
+                .chain
+                .is_on_chain(&head.last_block_hash, &tx.block_hash, height)
+                .map_err(|err| err.to_string())?
+            {
+                reference_index = None;
+            }
+        }
+        self.runtime_adapter.validate_tx(shard_id, state_root, head.height + 1, reference_index, tx)
     }
 
     /// Check whether need to (continue) sync.
@@ -800,8 +875,8 @@ impl ClientActor {
                         &mut self.chain,
                         highest_height,
                         &self.network_info.most_weight_peers,
-                        // TODO: add tracking shards here.
-                        vec![0],
+                        // Blocks are applied in all the shards, so all of them are synced.
+                        (0..self.runtime_adapter.num_shards()).collect(),
                     ));
                 }
             }
@@ -875,6 +950,26 @@ impl ClientActor {
         });
     }
 
+    /// Periodically garbage collects blocks and states that are behind the retention window.
+    fn gc(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(self.config.gc_period, move |act, ctx| {
+            let num_blocks_to_keep = act.config.gc_num_epochs_to_keep * act.config.epoch_length;
+            match act.chain.head() {
+                Ok(head) => {
+                    if head.height > num_blocks_to_keep {
+                        let gc_height = head.height - num_blocks_to_keep;
+                        if let Err(err) = act.chain.clear_old_data(gc_height, MAX_GC_HEIGHTS) {
+                            error!(target: "client", "Garbage collection failed: {}", err);
+                        }
+                    }
+                }
+                Err(err) => error!(target: "client", "Garbage collection failed: {}", err),
+            }
+
+            act.gc(ctx);
+        });
+    }
+
     /// Collects block approvals. Returns false if block approval is invalid.
     fn collect_block_approval(
         &mut self,
@@ -910,15 +1005,22 @@ impl ClientActor {
         &mut self,
         shard_id: ShardId,
         hash: CryptoHash,
-    ) -> Result<(Vec<u8>, Vec<ReceiptTransaction>), near_chain::Error> {
+        part_id: u64,
+    ) -> Result<(StatePart, Vec<ReceiptTransaction>), near_chain::Error> {
         let header = self.chain.get_block_header(&hash)?;
         let prev_hash = header.prev_hash;
-        let payload = self
+        let state_root = *header
+            .prev_state_roots
+            .get(shard_id as usize)
+            .ok_or_else(|| ErrorKind::Other(format!("Unknown shard #{}", shard_id)))?;
+        let part = self
             .runtime_adapter
-            .dump_state(shard_id, header.prev_state_root)
+            .dump_state(shard_id, state_root, part_id)
             .map_err(|err| ErrorKind::Other(err.to_string()))?;
-        let receipts = self.chain.get_receipts(&prev_hash)?.clone();
-        Ok((payload, receipts))
+        // Receipts are sent only once, along with the first part.
+        let receipts =
+            if part_id == 0 { self.chain.get_receipts(&prev_hash)?.clone() } else { vec![] };
+        Ok((part, receipts))
     }
 }
 
@@ -945,12 +1047,24 @@ fn display_sync_status(sync_status: &SyncStatus, head: &Tip) -> String {
                         shard_id,
                         match shard_status {
                             ShardSyncStatus::StateDownload {
-                                start_time: _,
-                                prev_update_time: _,
-                                prev_downloaded_size: _,
-                                downloaded_size: _,
-                                total_size: _,
-                            } => format!("download"),
+                                start_time,
+                                downloaded_parts,
+                                num_parts,
+                                downloaded_size,
+                            } => {
+                                let elapsed = (Utc::now() - *start_time).num_seconds();
+                                let speed = if elapsed > 0 {
+                                    downloaded_size / elapsed as u64
+                                } else {
+                                    0
+                                };
+                                format!(
+                                    "download {}/{} parts {}",
+                                    downloaded_parts,
+                                    num_parts,
+                                    pretty_bytes_per_sec(speed)
+                                )
+                            }
                             ShardSyncStatus::StateValidation => format!("validation"),
                             ShardSyncStatus::StateDone => format!("done"),
                             ShardSyncStatus::Error(error) => format!("error {}", error),
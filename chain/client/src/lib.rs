@@ -1,11 +1,13 @@
 pub use crate::client::ClientActor;
 pub use crate::types::{
-    BlockProducer, ClientConfig, Error, GetBlock, NetworkInfo, Query, Status, StatusResponse,
-    SyncStatus, TxStatus, TxDetails
+    BlockProducer, ClientConfig, Error, GetBlock, GetEvents, NetworkInfo, Query, SimulateTx,
+    Status, StatusResponse, Subscribe, Subscription, SubscriptionEvent, SubscriptionResult,
+    SyncStatus, TxStatus, TxDetails, Unsubscribe,
 };
 pub use crate::view_client::ViewClientActor;
 
 mod client;
+mod subscriptions;
 mod sync;
 pub mod test_utils;
 mod types;
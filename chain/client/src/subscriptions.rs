@@ -0,0 +1,124 @@
+//! Keeps track of external subscribers (e.g. RPC WebSocket sessions) and pushes chain events to them.
+
+use std::collections::HashMap;
+
+use actix::Recipient;
+use log::{debug, error};
+
+use near_chain::{Block, Chain, RuntimeAdapter};
+use near_primitives::rpc::{AccountViewCallResult, QueryResponse};
+use near_primitives::transaction::FinalTransactionStatus;
+
+use crate::types::{Subscribe, Subscription, SubscriptionEvent, SubscriptionResult};
+
+/// Maximum number of subscriptions that are active at the same time across all subscribers.
+const MAX_SUBSCRIPTIONS: usize = 1000;
+
+struct ActiveSubscription {
+    subscription: Subscription,
+    subscriber: Recipient<SubscriptionEvent>,
+    /// Last state of the account sent for account changes subscription.
+    last_account: Option<AccountViewCallResult>,
+}
+
+/// Active subscriptions indexed by subscription id.
+#[derive(Default)]
+pub struct Subscriptions {
+    active: HashMap<String, ActiveSubscription>,
+}
+
+impl Subscriptions {
+    pub fn subscribe(&mut self, msg: Subscribe) -> Result<(), String> {
+        if self.active.len() >= MAX_SUBSCRIPTIONS && !self.active.contains_key(&msg.id) {
+            return Err(format!(
+                "Node has reached the limit of {} subscriptions",
+                MAX_SUBSCRIPTIONS
+            ));
+        }
+        debug!(target: "client", "New subscription {}: {:?}", msg.id, msg.subscription);
+        self.active.insert(
+            msg.id,
+            ActiveSubscription {
+                subscription: msg.subscription,
+                subscriber: msg.subscriber,
+                last_account: None,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, id: &str) {
+        self.active.remove(id);
+    }
+
+    /// Pushes events for the block that became the new head of the chain.
+    /// Subscriptions that are fulfilled or whose subscriber is gone are removed.
+    pub fn on_new_head(
+        &mut self,
+        chain: &mut Chain,
+        runtime_adapter: &dyn RuntimeAdapter,
+        block: &Block,
+    ) {
+        if self.active.is_empty() {
+            return;
+        }
+        let state_roots = match chain.get_post_state_roots(&block.hash()) {
+            Ok(state_roots) => state_roots.clone(),
+            Err(err) => {
+                error!(target: "client", "Failed to get state roots for block {}: {}", block.hash(), err);
+                return;
+            }
+        };
+        let mut finished = vec![];
+        for (id, active) in self.active.iter_mut() {
+            let result = match &active.subscription {
+                Subscription::NewBlockHeaders => {
+                    Some(SubscriptionResult::NewBlockHeader(block.header.clone()))
+                }
+                Subscription::TxStatus(tx_hash) => {
+                    match chain.get_final_transaction_result(tx_hash) {
+                        Ok(result) => match result.status {
+                            FinalTransactionStatus::Unknown | FinalTransactionStatus::Started => {
+                                None
+                            }
+                            _ => {
+                                finished.push(id.clone());
+                                Some(SubscriptionResult::TxStatus(result))
+                            }
+                        },
+                        Err(err) => {
+                            error!(target: "client", "Failed to get status of transaction {}: {}", tx_hash, err);
+                            None
+                        }
+                    }
+                }
+                Subscription::AccountChanges(account_id) => match runtime_adapter.query(
+                    state_roots[runtime_adapter.account_id_to_shard_id(account_id) as usize],
+                    block.header.height,
+                    &format!("account/{}", account_id),
+                    &[],
+                ) {
+                    Ok(QueryResponse::ViewAccount(account)) => {
+                        if active.last_account.as_ref() != Some(&account) {
+                            active.last_account = Some(account.clone());
+                            Some(SubscriptionResult::AccountChange(account))
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                },
+            };
+            if let Some(result) = result {
+                // Subscriber is gone, if the message can not be delivered.
+                let event = SubscriptionEvent { id: id.clone(), result };
+                if active.subscriber.do_send(event).is_err() {
+                    finished.push(id.clone());
+                }
+            }
+        }
+        for id in finished {
+            self.active.remove(&id);
+        }
+    }
+}
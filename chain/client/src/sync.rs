@@ -3,15 +3,17 @@ use std::collections::{HashMap, HashSet};
 
 use actix::Recipient;
 use chrono::{DateTime, Duration, Utc};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use rand::{thread_rng, Rng};
 
 use near_chain::{Chain, Tip};
-use near_network::types::ReasonForBan;
+use near_network::types::{PeerId, ReasonForBan};
 use near_network::{FullPeerInfo, NetworkRequests};
 use near_primitives::hash::CryptoHash;
-use near_primitives::types::{BlockIndex, ShardId};
+use near_primitives::transaction::ReceiptTransaction;
+use near_primitives::types::{BlockIndex, ShardId, StatePart};
 use near_primitives::unwrap_or_return;
+use near_store::MAX_STATE_PARTS;
 
 use crate::types::{ShardSyncStatus, SyncStatus};
 
@@ -33,8 +35,11 @@ const BLOCK_REQUEST_TIMEOUT: i64 = 6;
 const BLOCK_SOME_RECEIVED_TIMEOUT: i64 = 1;
 const BLOCK_REQUEST_BROADCAST_OFFSET: u64 = 2;
 
-/// Sync state download timeout in minutes.
-const STATE_SYNC_TIMEOUT: i64 = 10;
+/// Maximum number of state parts to ask from single peer at once.
+const MAX_PEER_STATE_PART_REQUESTS: usize = 4;
+
+/// Timeout in seconds after which state part is requested again.
+const STATE_PART_REQUEST_TIMEOUT: i64 = 30;
 
 /// Get random peer from the most weighted peers.
 pub fn most_weight_peer(most_weight_peers: &Vec<FullPeerInfo>) -> Option<FullPeerInfo> {
@@ -391,13 +396,110 @@ impl BlockSync {
     }
 }
 
+/// Download of the state of a single shard.
+struct ShardStateDownload {
+    start_time: DateTime<Utc>,
+    /// Total number of parts, known once the first part is received.
+    num_parts: Option<u64>,
+    done_parts: HashSet<u64>,
+    /// Parts in flight with the peer they were requested from and the time of the request.
+    requested_parts: HashMap<u64, (PeerId, DateTime<Utc>)>,
+    /// Receipts come along with the first part.
+    receipts: Option<Vec<ReceiptTransaction>>,
+    downloaded_size: u64,
+}
+
+impl ShardStateDownload {
+    fn new(start_time: DateTime<Utc>) -> Self {
+        ShardStateDownload {
+            start_time,
+            num_parts: None,
+            done_parts: HashSet::default(),
+            requested_parts: HashMap::default(),
+            receipts: None,
+            downloaded_size: 0,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.num_parts == Some(self.done_parts.len() as u64)
+    }
+
+    fn status(&self) -> ShardSyncStatus {
+        ShardSyncStatus::StateDownload {
+            start_time: self.start_time,
+            downloaded_parts: self.done_parts.len() as u64,
+            num_parts: self.num_parts.unwrap_or(0),
+            downloaded_size: self.downloaded_size,
+        }
+    }
+
+    /// Requests missing parts, spreading them across peers. Requests that timed out or were sent
+    /// to disconnected peers are dropped, so these parts are requested again.
+    fn request_parts(
+        &mut self,
+        network_recipient: &Recipient<NetworkRequests>,
+        shard_id: ShardId,
+        hash: CryptoHash,
+        most_weight_peers: &Vec<FullPeerInfo>,
+    ) {
+        let now = Utc::now();
+        let mut in_flight: HashMap<PeerId, usize> =
+            most_weight_peers.iter().map(|peer| (peer.peer_info.id, 0)).collect();
+        self.requested_parts.retain(|part_id, (peer_id, requested_time)| {
+            if !in_flight.contains_key(peer_id) {
+                debug!(target: "sync", "State sync: peer {} is gone, request part {} of shard {} again", peer_id, part_id, shard_id);
+                false
+            } else if now - *requested_time > Duration::seconds(STATE_PART_REQUEST_TIMEOUT) {
+                debug!(target: "sync", "State sync: request of part {} of shard {} from {} timed out", part_id, shard_id, peer_id);
+                false
+            } else {
+                true
+            }
+        });
+        for (peer_id, _) in self.requested_parts.values() {
+            *in_flight.entry(*peer_id).or_insert(0) += 1;
+        }
+        // Number of parts is unknown until the first part is received.
+        let missing_parts: Vec<u64> = match self.num_parts {
+            Some(num_parts) => (0..num_parts)
+                .filter(|part_id| {
+                    !self.done_parts.contains(part_id) && !self.requested_parts.contains_key(part_id)
+                })
+                .collect(),
+            None if self.requested_parts.contains_key(&0) => vec![],
+            None => vec![0],
+        };
+        for part_id in missing_parts {
+            let peer_id = match in_flight
+                .iter()
+                .filter(|(_, count)| **count < MAX_PEER_STATE_PART_REQUESTS)
+                .min_by_key(|(_, count)| **count)
+            {
+                Some((peer_id, _)) => *peer_id,
+                None => break,
+            };
+            unwrap_or_return!(
+                network_recipient.do_send(NetworkRequests::StateRequest {
+                    shard_id,
+                    hash,
+                    part_id,
+                    peer_id,
+                }),
+                ()
+            );
+            self.requested_parts.insert(part_id, (peer_id, now));
+            *in_flight.entry(peer_id).or_insert(0) += 1;
+        }
+    }
+}
+
 /// Helper to track state sync.
 pub struct StateSync {
     network_recipient: Recipient<NetworkRequests>,
     state_fetch_horizon: BlockIndex,
 
-    syncing_peers: HashMap<ShardId, FullPeerInfo>,
-    prev_state_sync: HashMap<ShardId, DateTime<Utc>>,
+    downloads: HashMap<ShardId, ShardStateDownload>,
 }
 
 impl StateSync {
@@ -405,12 +507,7 @@ impl StateSync {
         network_recipient: Recipient<NetworkRequests>,
         state_fetch_horizon: BlockIndex,
     ) -> Self {
-        StateSync {
-            network_recipient,
-            state_fetch_horizon,
-            syncing_peers: Default::default(),
-            prev_state_sync: Default::default(),
-        }
+        StateSync { network_recipient, state_fetch_horizon, downloads: Default::default() }
     }
 
     fn find_sync_hash(&self, chain: &mut Chain) -> Result<CryptoHash, near_chain::Error> {
@@ -431,33 +528,18 @@ impl StateSync {
         tracking_shards: Vec<ShardId>,
     ) -> Result<(), near_chain::Error> {
         let header_head = chain.header_head()?;
-        let mut sync_need_restart = HashSet::new();
 
         let (sync_hash, mut new_shard_sync) = match &sync_status {
             SyncStatus::StateSync(sync_hash, shard_sync) => (sync_hash.clone(), shard_sync.clone()),
-            _ => (self.find_sync_hash(chain)?, HashMap::default()),
-        };
-
-        // Check syncing peer connection status.
-        let mut all_done = false;
-        if let SyncStatus::StateSync(_, shard_statuses) = sync_status {
-            all_done = true;
-            for (shard_id, shard_status) in shard_statuses.iter() {
-                all_done = all_done && ShardSyncStatus::StateDone == *shard_status;
-                if let ShardSyncStatus::Error(error) = shard_status {
-                    error!(target: "sync", "State sync: shard {} sync failed: {}", shard_id, error);
-                    sync_need_restart.insert(shard_id);
-                } else if let Some(ref peer) = self.syncing_peers.get(shard_id) {
-                    if let ShardSyncStatus::StateDownload { .. } = shard_status {
-                        if !most_weight_peers.contains(peer) {
-                            sync_need_restart.insert(shard_id);
-                            info!(target: "sync", "State sync: peer connection lost: {:?}, restart shard {}", peer.peer_info.id, shard_id);
-                        }
-                    }
-                }
+            _ => {
+                self.downloads.clear();
+                (self.find_sync_hash(chain)?, HashMap::default())
             }
-        }
+        };
 
+        let all_done = tracking_shards
+            .iter()
+            .all(|shard_id| new_shard_sync.get(shard_id) == Some(&ShardSyncStatus::StateDone));
         if all_done {
             info!(target: "sync", "State sync: all shards are done");
 
@@ -479,88 +561,273 @@ impl StateSync {
             chain.check_orphans(height + 1, |_, _, _| {});
 
             *sync_status = SyncStatus::BodySync { current_height: 0, highest_height: 0 };
-            self.prev_state_sync.clear();
-            self.syncing_peers.clear();
+            self.downloads.clear();
             return Ok(());
         }
 
         let now = Utc::now();
-        let mut update_sync_status = false;
         for shard_id in tracking_shards {
-            if sync_need_restart.contains(&shard_id) || header_head.height == highest_height {
-                let (go, download_timeout) = match self.prev_state_sync.get(&shard_id) {
-                    None => {
-                        self.prev_state_sync.insert(shard_id, now);
-                        (true, false)
-                    }
-                    Some(prev) => (false, now - *prev > Duration::minutes(STATE_SYNC_TIMEOUT)),
-                };
-
-                if download_timeout {
-                    error!(target: "sync", "State sync: state download for shard {} timed out in {} minutes", shard_id, STATE_SYNC_TIMEOUT);
-                }
-
-                if go || download_timeout {
-                    match self.request_state(shard_id, chain, sync_hash, most_weight_peers) {
-                        Some(peer) => {
-                            self.syncing_peers.insert(shard_id, peer);
-                            new_shard_sync.insert(
-                                shard_id,
-                                ShardSyncStatus::StateDownload {
-                                    start_time: now,
-                                    prev_update_time: now,
-                                    prev_downloaded_size: 0,
-                                    downloaded_size: 0,
-                                    total_size: 0,
-                                },
-                            );
-                        }
-                        None => {
-                            new_shard_sync.insert(
-                                shard_id,
-                                ShardSyncStatus::Error(format!(
-                                    "Failed to find peer with state for shard {}",
-                                    shard_id
-                                )),
-                            );
-                        }
-                    }
-                    update_sync_status = true;
+            match new_shard_sync.get(&shard_id) {
+                Some(ShardSyncStatus::StateDone) => continue,
+                Some(ShardSyncStatus::Error(error)) => {
+                    error!(target: "sync", "State sync: shard {} sync failed: {}, restarting", shard_id, error);
+                    self.downloads.remove(&shard_id);
                 }
+                Some(_) => {}
+                // Start downloading the state once all headers are received.
+                None if header_head.height != highest_height => continue,
+                None => {}
+            }
+            if most_weight_peers.is_empty() {
+                new_shard_sync.insert(
+                    shard_id,
+                    ShardSyncStatus::Error(format!(
+                        "Failed to find peer with state for shard {}",
+                        shard_id
+                    )),
+                );
+                continue;
             }
+            let download =
+                self.downloads.entry(shard_id).or_insert_with(|| ShardStateDownload::new(now));
+            download.request_parts(&self.network_recipient, shard_id, sync_hash, most_weight_peers);
+            new_shard_sync.insert(shard_id, download.status());
         }
-        if update_sync_status {
+        if !new_shard_sync.is_empty() {
             *sync_status = SyncStatus::StateSync(sync_hash, new_shard_sync);
         }
         Ok(())
     }
 
-    fn request_state(
+    /// Applies received state part and requests more parts. Once all parts of the shard are
+    /// received, finishes its sync.
+    pub fn on_state_response(
         &mut self,
+        sync_status: &mut SyncStatus,
+        chain: &mut Chain,
+        most_weight_peers: &Vec<FullPeerInfo>,
         shard_id: ShardId,
-        _chain: &Chain,
         hash: CryptoHash,
-        most_weight_peers: &Vec<FullPeerInfo>,
-    ) -> Option<FullPeerInfo> {
-        if let Some(peer) = most_weight_peer(most_weight_peers) {
-            unwrap_or_return!(
-                self.network_recipient.do_send(NetworkRequests::StateRequest {
-                    shard_id,
-                    hash,
-                    peer_id: peer.peer_info.id,
-                }),
-                None
-            );
-            return Some(peer);
+        part: StatePart,
+        receipts: Vec<ReceiptTransaction>,
+    ) {
+        let shard_statuses = match sync_status {
+            SyncStatus::StateSync(sync_hash, shard_statuses) => {
+                if *sync_hash != hash {
+                    debug!(target: "sync", "State sync: ignoring state part for {}, syncing to {}", hash, sync_hash);
+                    return;
+                }
+                shard_statuses
+            }
+            _ => return,
+        };
+        let download = match self.downloads.get_mut(&shard_id) {
+            Some(download) => download,
+            None => return,
+        };
+        let part_id = part.part_id;
+        download.requested_parts.remove(&part_id);
+        let expected_num_parts = download.num_parts.unwrap_or(part.num_parts);
+        if download.done_parts.contains(&part_id)
+            || (download.num_parts.is_none() && part_id != 0)
+            || part.num_parts != expected_num_parts
+            || part.num_parts > MAX_STATE_PARTS
+            || part_id >= part.num_parts
+        {
+            debug!(target: "sync", "State sync: ignoring part {}/{} of shard {}", part_id, part.num_parts, shard_id);
+            return;
+        }
+        let (num_parts, size) = (part.num_parts, part.data.len() as u64);
+        match chain.set_state_part(shard_id, hash, part) {
+            Ok(()) => {
+                download.num_parts = Some(num_parts);
+                download.done_parts.insert(part_id);
+                download.downloaded_size += size;
+                if part_id == 0 {
+                    download.receipts = Some(receipts);
+                }
+            }
+            Err(err) => {
+                // Part is requested again, likely from another peer.
+                warn!(target: "sync", "State sync: invalid part {} of shard {}: {}", part_id, shard_id, err);
+            }
+        }
+        if download.is_done() {
+            let receipts = download.receipts.take().unwrap_or_default();
+            let status = match chain.set_shard_state(shard_id, hash, receipts) {
+                Ok(()) => ShardSyncStatus::StateDone,
+                Err(err) => ShardSyncStatus::Error(format!(
+                    "Failed to set state for {} @ {}: {}",
+                    shard_id, hash, err
+                )),
+            };
+            shard_statuses.insert(shard_id, status);
+            self.downloads.remove(&shard_id);
+        } else {
+            download.request_parts(&self.network_recipient, shard_id, hash, most_weight_peers);
+            shard_statuses.insert(shard_id, download.status());
         }
-        None
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
+    use actix::{Actor, System};
+
+    use near_chain::test_utils::KeyValueRuntime;
+    use near_chain::RuntimeAdapter;
+    use near_network::types::PeerChainInfo;
+    use near_network::{NetworkResponses, PeerInfo};
+    use near_store::test_utils::create_test_store;
+
+    use crate::test_utils::NetworkMock;
+
     use super::*;
 
+    struct StateSyncTest {
+        chain: Chain,
+        runtime: Arc<KeyValueRuntime>,
+        state_sync: StateSync,
+        sync_status: SyncStatus,
+        peers: Vec<FullPeerInfo>,
+    }
+
+    impl StateSyncTest {
+        /// Syncs the state of the only shard at genesis, split into `num_parts` parts. Has to
+        /// be created within a running system.
+        fn new(num_parts: u64) -> Self {
+            let store = create_test_store();
+            let runtime =
+                Arc::new(KeyValueRuntime::new(store.clone()).with_num_state_parts(num_parts));
+            let chain = Chain::new(store, runtime.clone(), Utc::now()).unwrap();
+            let network = NetworkMock::mock(Box::new(|_, _| {
+                Box::new(Some(NetworkResponses::NoResponse))
+            }))
+            .start();
+            let peers = (0..2)
+                .map(|_| FullPeerInfo {
+                    peer_info: PeerInfo::random(),
+                    chain_info: PeerChainInfo {
+                        genesis: chain.genesis().hash(),
+                        height: 0,
+                        total_weight: 0.into(),
+                    },
+                })
+                .collect();
+            let sync_status = SyncStatus::StateSync(chain.genesis().hash(), HashMap::default());
+            StateSyncTest {
+                chain,
+                runtime,
+                state_sync: StateSync::new(network.recipient(), 0),
+                sync_status,
+                peers,
+            }
+        }
+
+        fn run(&mut self) {
+            self.state_sync
+                .run(&mut self.sync_status, &mut self.chain, 0, &self.peers, vec![0])
+                .unwrap();
+        }
+
+        fn part(&self, part_id: u64) -> StatePart {
+            let state_root = self.chain.genesis().prev_state_roots[0];
+            self.runtime.dump_state(0, state_root, part_id).unwrap()
+        }
+
+        fn receive(&mut self, part: StatePart) {
+            let hash = self.chain.genesis().hash();
+            self.state_sync.on_state_response(
+                &mut self.sync_status,
+                &mut self.chain,
+                &self.peers,
+                0,
+                hash,
+                part,
+                vec![],
+            );
+        }
+
+        fn requested_parts(&self) -> Vec<u64> {
+            let mut parts: Vec<_> =
+                self.state_sync.downloads[&0].requested_parts.keys().cloned().collect();
+            parts.sort();
+            parts
+        }
+
+        fn shard_status(&self) -> ShardSyncStatus {
+            match &self.sync_status {
+                SyncStatus::StateSync(_, shard_statuses) => shard_statuses[&0].clone(),
+                status => panic!("Unexpected sync status {:?}", status),
+            }
+        }
+    }
+
+    #[test]
+    fn test_state_sync_multiple_parts() {
+        System::run(|| {
+            let mut test = StateSyncTest::new(3);
+            test.run();
+            // Number of parts is unknown until the first part is received.
+            assert_eq!(test.requested_parts(), vec![0]);
+
+            test.receive(test.part(0));
+            assert_eq!(test.requested_parts(), vec![1, 2]);
+            let requested_parts = &test.state_sync.downloads[&0].requested_parts;
+            assert_ne!(requested_parts[&1].0, requested_parts[&2].0);
+            match test.shard_status() {
+                ShardSyncStatus::StateDownload { downloaded_parts, num_parts, .. } => {
+                    assert_eq!((downloaded_parts, num_parts), (1, 3));
+                }
+                status => panic!("Unexpected shard status {:?}", status),
+            }
+
+            test.receive(test.part(2));
+            test.receive(test.part(1));
+            assert_eq!(test.shard_status(), ShardSyncStatus::StateDone);
+            System::current().stop();
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_state_sync_rejects_bad_parts() {
+        System::run(|| {
+            let mut test = StateSyncTest::new(2);
+            test.run();
+
+            // First part that claims too many parts is ignored.
+            let mut part = test.part(0);
+            part.num_parts = MAX_STATE_PARTS + 1;
+            test.receive(part);
+            assert!(test.state_sync.downloads[&0].num_parts.is_none());
+
+            test.receive(test.part(0));
+            assert_eq!(test.requested_parts(), vec![1]);
+
+            // Part with the number of parts different from the first one is ignored.
+            let mut part = test.part(1);
+            part.num_parts = 3;
+            test.receive(part);
+            assert!(!test.state_sync.downloads[&0].done_parts.contains(&1));
+
+            // Invalid part is requested again.
+            test.run();
+            let mut part = test.part(1);
+            part.data[0] ^= 1;
+            test.receive(part);
+            assert!(!test.state_sync.downloads[&0].done_parts.contains(&1));
+            assert_eq!(test.requested_parts(), vec![1]);
+
+            test.receive(test.part(1));
+            assert_eq!(test.shard_status(), ShardSyncStatus::StateDone);
+            System::current().stop();
+        })
+        .unwrap();
+    }
+
     #[test]
     fn test_get_locator_heights() {
         assert_eq!(get_locator_heights(0), vec![0]);
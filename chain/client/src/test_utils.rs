@@ -13,13 +13,14 @@ use crate::{BlockProducer, ClientActor, ClientConfig, ViewClientActor};
 
 pub type NetworkMock = Mocker<PeerManagerActor>;
 
-/// Sets up ClientActor and ViewClientActor viewing the same store/runtime.
+/// Sets up ClientActor and ViewClientActor viewing the same store/runtime. ViewClientActor is
+/// started right away, since ClientActor pushes the new heads to it.
 pub fn setup(
     validators: Vec<&str>,
     account_id: &str,
     skip_sync_wait: bool,
     recipient: Recipient<NetworkRequests>,
-) -> (ClientActor, ViewClientActor) {
+) -> (ClientActor, Addr<ViewClientActor>) {
     let store = create_test_store();
     let runtime = Arc::new(KeyValueRuntime::new_with_validators(
         store.clone(),
@@ -28,7 +29,7 @@ pub fn setup(
     let signer = Arc::new(InMemorySigner::from_seed(account_id, account_id));
     let genesis_time = Utc::now();
     let view_client =
-        ViewClientActor::new(store.clone(), genesis_time.clone(), runtime.clone()).unwrap();
+        ViewClientActor::new(store.clone(), genesis_time.clone(), runtime.clone()).unwrap().start();
     let client = ClientActor::new(
         ClientConfig::test(skip_sync_wait),
         store,
@@ -36,6 +37,7 @@ pub fn setup(
         runtime,
         recipient,
         Some(signer.into()),
+        view_client.clone().recipient(),
     )
     .unwrap();
     (client, view_client)
@@ -65,7 +67,7 @@ pub fn setup_mock(
         }))
         .start();
         let (client, view_client) = setup(validators, account_id, skip_sync_wait, pm.recipient());
-        *view_client_addr1.write().unwrap() = Some(view_client.start());
+        *view_client_addr1.write().unwrap() = Some(view_client);
         client
     });
     (client_addr, view_client_addr.clone().read().unwrap().clone().unwrap())
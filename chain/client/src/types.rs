@@ -2,17 +2,20 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use actix::Message;
+use actix::{Message, Recipient};
 use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
 
-use near_chain::Block;
+use near_chain::{Block, BlockHeader};
 use near_network::types::FullPeerInfo;
+use near_pool::PoolConfig;
 use near_primitives::crypto::signer::{AccountSigner, EDSigner, InMemorySigner};
 use near_primitives::hash::CryptoHash;
-use near_primitives::rpc::QueryResponse;
-pub use near_primitives::rpc::{StatusResponse, StatusSyncInfo};
-use near_primitives::transaction::{FinalTransactionResult, TransactionResult};
-use near_primitives::types::{AccountId, BlockIndex, ShardId, Version};
+use near_primitives::rpc::{AccountViewCallResult, BlockEvents, QueryResponse, SimulateTxResult};
+pub use near_primitives::rpc::{StatusResponse, StatusSyncInfo, StatusTxPoolInfo};
+use near_primitives::serialize::base_format;
+use near_primitives::transaction::{FinalTransactionResult, SignedTransaction, TransactionResult};
+use near_primitives::types::{AccountId, BlockId, BlockIndex, ShardId, Version};
 
 /// Combines errors coming from chain, tx pool and block producer.
 #[derive(Debug)]
@@ -99,6 +102,14 @@ pub struct ClientConfig {
     pub block_fetch_horizon: BlockIndex,
     /// Horizon to step from the latest block when fetching state.
     pub state_fetch_horizon: BlockIndex,
+    /// Keep all the blocks and states, instead of garbage collecting them.
+    pub archive: bool,
+    /// Number of epochs of blocks and states to keep when not archival.
+    pub gc_num_epochs_to_keep: BlockIndex,
+    /// How often to garbage collect old blocks and states.
+    pub gc_period: Duration,
+    /// Limits of the transaction pool.
+    pub pool_config: PoolConfig,
 }
 
 impl ClientConfig {
@@ -122,6 +133,10 @@ impl ClientConfig {
             epoch_length: 10,
             block_fetch_horizon: 50,
             state_fetch_horizon: 5,
+            archive: true,
+            gc_num_epochs_to_keep: 3,
+            gc_period: Duration::from_secs(1),
+            pool_config: PoolConfig::default(),
         }
     }
 }
@@ -147,6 +162,10 @@ impl ClientConfig {
             epoch_length: 10,
             block_fetch_horizon: 50,
             state_fetch_horizon: 5,
+            archive: false,
+            gc_num_epochs_to_keep: 3,
+            gc_period: Duration::from_secs(1),
+            pool_config: PoolConfig::default(),
         }
     }
 }
@@ -173,13 +192,13 @@ impl From<Arc<InMemorySigner>> for BlockProducer {
 /// Various status of syncing a specific shard.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ShardSyncStatus {
-    /// Downloading state for fast sync.
+    /// Downloading state parts for fast sync.
     StateDownload {
         start_time: DateTime<Utc>,
-        prev_update_time: DateTime<Utc>,
-        prev_downloaded_size: u64,
+        downloaded_parts: u64,
+        /// Total number of parts, zero until the first part is received.
+        num_parts: u64,
         downloaded_size: u64,
-        total_size: u64,
     },
     /// Validating the full state.
     StateValidation,
@@ -232,10 +251,13 @@ impl Message for GetBlock {
     type Result = Result<Block, String>;
 }
 
-/// Queries client for given path / data.
+/// Queries client for given path / data at the state after given block.
+/// If `prove` is set, returns raw value with the proof instead.
 pub struct Query {
     pub path: String,
     pub data: Vec<u8>,
+    pub block_id: BlockId,
+    pub prove: bool,
 }
 
 impl Message for Query {
@@ -265,3 +287,85 @@ pub struct TxDetails {
 impl Message for TxDetails {
     type Result = Result<TransactionResult, String>;
 }
+
+/// Events emitted by the account with the topic in the blocks with heights from `from_height`
+/// to `to_height` inclusive.
+pub struct GetEvents {
+    pub account_id: AccountId,
+    pub topic: String,
+    pub from_height: BlockIndex,
+    pub to_height: BlockIndex,
+}
+
+impl Message for GetEvents {
+    type Result = Result<Vec<BlockEvents>, String>;
+}
+
+/// Executes transaction on top of the state of the head of the chain, without committing it.
+pub struct SimulateTx {
+    pub transaction: SignedTransaction,
+}
+
+impl Message for SimulateTx {
+    type Result = Result<SimulateTxResult, String>;
+}
+
+/// Chain events one can subscribe to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Subscription {
+    /// Headers of the blocks that become the new head of the chain.
+    NewBlockHeaders,
+    /// Final status of the transaction with given hash, sent once it is known.
+    TxStatus(#[serde(with = "base_format")] CryptoHash),
+    /// State of the given account, sent every time it changes.
+    AccountChanges(AccountId),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SubscriptionResult {
+    NewBlockHeader(BlockHeader),
+    TxStatus(FinalTransactionResult),
+    AccountChange(AccountViewCallResult),
+}
+
+/// Event pushed to the subscriber with the id of the subscription.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubscriptionEvent {
+    pub id: String,
+    pub result: SubscriptionResult,
+}
+
+impl Message for SubscriptionEvent {
+    type Result = ();
+}
+
+/// Subscribes recipient to the chain events under the given subscription id.
+pub struct Subscribe {
+    pub id: String,
+    pub subscription: Subscription,
+    pub subscriber: Recipient<SubscriptionEvent>,
+}
+
+impl Message for Subscribe {
+    type Result = Result<(), String>;
+}
+
+/// Cancels subscription with the given id.
+pub struct Unsubscribe {
+    pub id: String,
+}
+
+impl Message for Unsubscribe {
+    type Result = ();
+}
+
+/// Block that became the new head of the chain, sent by the client to push it to the subscribers.
+pub struct NewHead {
+    pub block: Block,
+}
+
+impl Message for NewHead {
+    type Result = ();
+}
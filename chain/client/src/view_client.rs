@@ -1,27 +1,33 @@
 //! Readonly view of the chain and state of the database.
 //! Useful for querying from RPC.
 
+use std::cmp::min;
 use std::sync::Arc;
 
 use actix::{Actor, Context, Handler};
 use chrono::{DateTime, Utc};
 
 use near_chain::{Block, Chain, ErrorKind, RuntimeAdapter};
-use near_primitives::hash::CryptoHash;
-use near_primitives::rpc::QueryResponse;
-use near_primitives::transaction::{
-    FinalTransactionResult, FinalTransactionStatus, TransactionLogs, TransactionResult,
-    TransactionStatus,
-};
+use near_primitives::rpc::{BlockEvents, ProofResult, QueryResponse, SimulateTxResult};
+use near_primitives::transaction::{FinalTransactionResult, TransactionResult};
+use near_primitives::types::{BlockId, BlockIndex};
 use near_store::Store;
 
-use crate::types::{Error, GetBlock, Query, TxStatus};
+use crate::subscriptions::Subscriptions;
+use crate::types::{
+    Error, GetBlock, GetEvents, NewHead, Query, SimulateTx, Subscribe, TxStatus, Unsubscribe,
+};
 use crate::TxDetails;
 
+/// Maximum number of blocks the events are looked up in by a single request.
+const MAX_EVENTS_BLOCK_RANGE: BlockIndex = 1000;
+
 /// View client provides currently committed (to the storage) view of the current chain and state.
 pub struct ViewClientActor {
     chain: Chain,
     runtime_adapter: Arc<dyn RuntimeAdapter>,
+    /// External subscribers to chain events.
+    subscriptions: Subscriptions,
 }
 
 impl ViewClientActor {
@@ -32,50 +38,7 @@ impl ViewClientActor {
     ) -> Result<Self, Error> {
         // TODO: should we create shared ChainStore that is passed to both Client and ViewClient?
         let chain = Chain::new(store, runtime_adapter.clone(), genesis_time)?;
-        Ok(ViewClientActor { chain, runtime_adapter })
-    }
-
-    pub fn get_transaction_result(
-        &mut self,
-        hash: &CryptoHash,
-    ) -> Result<TransactionResult, String> {
-        match self.chain.get_transaction_result(hash) {
-            Ok(result) => Ok(result.clone()),
-            Err(err) => match err.kind() {
-                ErrorKind::DBNotFoundErr(_) => Ok(TransactionResult::default()),
-                _ => Err(err.to_string()),
-            },
-        }
-    }
-
-    fn collect_transaction_final_result(
-        &mut self,
-        transaction_result: &TransactionResult,
-        logs: &mut Vec<TransactionLogs>,
-    ) -> Result<FinalTransactionStatus, String> {
-        match transaction_result.status {
-            TransactionStatus::Unknown => Ok(FinalTransactionStatus::Unknown),
-            TransactionStatus::Failed => Ok(FinalTransactionStatus::Failed),
-            TransactionStatus::Completed => {
-                for r in transaction_result.receipts.iter() {
-                    let receipt_result = self.get_transaction_result(&r)?;
-                    logs.push(TransactionLogs {
-                        hash: *r,
-                        lines: receipt_result.logs.clone(),
-                        receipts: receipt_result.receipts.clone(),
-                        result: receipt_result.result.clone(),
-                    });
-                    match self.collect_transaction_final_result(&receipt_result, logs)? {
-                        FinalTransactionStatus::Failed => {
-                            return Ok(FinalTransactionStatus::Failed)
-                        }
-                        FinalTransactionStatus::Completed => {}
-                        _ => return Ok(FinalTransactionStatus::Started),
-                    };
-                }
-                Ok(FinalTransactionStatus::Completed)
-            }
-        }
+        Ok(ViewClientActor { chain, runtime_adapter, subscriptions: Subscriptions::default() })
     }
 }
 
@@ -88,12 +51,43 @@ impl Handler<Query> for ViewClientActor {
     type Result = Result<QueryResponse, String>;
 
     fn handle(&mut self, msg: Query, _: &mut Context<Self>) -> Self::Result {
-        let head = self.chain.head().map_err(|err| err.to_string())?;
-        let state_root =
-            self.chain.get_post_state_root(&head.last_block_hash).map_err(|err| err.to_string())?;
-        self.runtime_adapter
-            .query(*state_root, head.height, &msg.path, &msg.data)
-            .map_err(|err| err.to_string())
+        let header = match msg.block_id {
+            BlockId::Best => self.chain.head_header(),
+            BlockId::Number(height) => self.chain.get_header_by_height(height),
+            BlockId::Hash(hash) => self.chain.get_block_header(&hash),
+        }
+        .map(Clone::clone)
+        .map_err(|err| err.to_string())?;
+        // Queries are about the account that follows the kind of the query in the path, the state
+        // of its shard is queried.
+        let account_id = msg.path.split('/').nth(1).unwrap_or_default().to_string();
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(&account_id);
+        let state_root = match self.chain.get_post_state_root(&header.hash(), shard_id) {
+            Ok(state_root) => state_root,
+            Err(err) => match err.kind() {
+                ErrorKind::DBNotFoundErr(_) => {
+                    return Err(format!(
+                        "State for block {} at height {} is missing or has been pruned",
+                        header.hash(),
+                        header.height
+                    ))
+                }
+                _ => return Err(err.to_string()),
+            },
+        };
+        if msg.prove {
+            self.runtime_adapter
+                .query_proof(state_root, &msg.path, &msg.data)
+                .map(|proof| {
+                    let block_hash = header.hash();
+                    QueryResponse::Proof(ProofResult { block_hash, state_root, proof })
+                })
+                .map_err(|err| err.to_string())
+        } else {
+            self.runtime_adapter
+                .query(state_root, header.height, &msg.path, &msg.data)
+                .map_err(|err| err.to_string())
+        }
     }
 }
 
@@ -118,19 +112,7 @@ impl Handler<TxStatus> for ViewClientActor {
     type Result = Result<FinalTransactionResult, String>;
 
     fn handle(&mut self, msg: TxStatus, _: &mut Context<Self>) -> Self::Result {
-        let transaction_result = self.get_transaction_result(&msg.tx_hash)?;
-        let mut result = FinalTransactionResult {
-            status: FinalTransactionStatus::Unknown,
-            logs: vec![TransactionLogs {
-                hash: msg.tx_hash,
-                lines: transaction_result.logs.clone(),
-                receipts: transaction_result.receipts.clone(),
-                result: transaction_result.result.clone(),
-            }],
-        };
-        result.status =
-            self.collect_transaction_final_result(&transaction_result, &mut result.logs)?;
-        Ok(result)
+        self.chain.get_final_transaction_result(&msg.tx_hash).map_err(|err| err.to_string())
     }
 }
 
@@ -138,6 +120,78 @@ impl Handler<TxDetails> for ViewClientActor {
     type Result = Result<TransactionResult, String>;
 
     fn handle(&mut self, msg: TxDetails, _: &mut Context<Self>) -> Self::Result {
-        self.get_transaction_result(&msg.tx_hash)
+        self.chain.get_transaction_result_or_unknown(&msg.tx_hash).map_err(|err| err.to_string())
+    }
+}
+
+/// Handles retrieving events from the blocks of the main chain up to the head.
+impl Handler<GetEvents> for ViewClientActor {
+    type Result = Result<Vec<BlockEvents>, String>;
+
+    fn handle(&mut self, msg: GetEvents, _: &mut Context<Self>) -> Self::Result {
+        if msg.to_height < msg.from_height {
+            return Err(format!(
+                "Invalid block range from {} to {}",
+                msg.from_height, msg.to_height
+            ));
+        }
+        if msg.to_height - msg.from_height >= MAX_EVENTS_BLOCK_RANGE {
+            return Err(format!(
+                "Block range from {} to {} exceeds the limit of {} blocks",
+                msg.from_height, msg.to_height, MAX_EVENTS_BLOCK_RANGE
+            ));
+        }
+        let head = self.chain.head().map_err(|err| err.to_string())?;
+        let to_height = min(msg.to_height, head.height);
+        self.chain
+            .get_events(&msg.account_id, &msg.topic, msg.from_height, to_height)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Handles dry run of the transaction on top of the current head, as if it was in the next block.
+impl Handler<SimulateTx> for ViewClientActor {
+    type Result = Result<SimulateTxResult, String>;
+
+    fn handle(&mut self, msg: SimulateTx, _: &mut Context<Self>) -> Self::Result {
+        let head = self.chain.head().map_err(|err| err.to_string())?;
+        let state_roots = self
+            .chain
+            .get_post_state_roots(&head.last_block_hash)
+            .map(Clone::clone)
+            .map_err(|err| err.to_string())?;
+        self.runtime_adapter
+            .simulate_transaction(
+                &state_roots,
+                head.height + 1,
+                &head.last_block_hash,
+                &msg.transaction,
+            )
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Handler<Subscribe> for ViewClientActor {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: Subscribe, _: &mut Context<Self>) -> Self::Result {
+        self.subscriptions.subscribe(msg)
+    }
+}
+
+impl Handler<Unsubscribe> for ViewClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Context<Self>) {
+        self.subscriptions.unsubscribe(&msg.id);
+    }
+}
+
+/// Pushes events about the new head of the chain to the subscribers.
+impl Handler<NewHead> for ViewClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: NewHead, _: &mut Context<Self>) {
+        self.subscriptions.on_new_head(&mut self.chain, &*self.runtime_adapter, &msg.block);
     }
 }
@@ -5,16 +5,16 @@ use std::sync::{Arc, RwLock};
 use actix::System;
 use futures::{future, Future};
 
-use near_chain::{Block, BlockApproval};
-use near_client::test_utils::setup_mock;
-use near_client::GetBlock;
+use near_chain::{Block, BlockApproval, BlockHeader};
+use near_client::test_utils::{setup_mock, setup_no_network};
+use near_client::{GetBlock, Status};
 use near_network::test_utils::wait_or_panic;
 use near_network::types::{FullPeerInfo, PeerChainInfo};
 use near_network::{NetworkClientMessages, NetworkRequests, NetworkResponses, PeerInfo};
 use near_primitives::crypto::signer::InMemorySigner;
 use near_primitives::hash::hash;
 use near_primitives::test_utils::init_test_logger;
-use near_primitives::transaction::SignedTransaction;
+use near_primitives::transaction::{SignedTransaction, TransactionBody};
 use near_primitives::types::MerkleHash;
 
 /// Runs block producing client and stops after network mock received two blocks.
@@ -90,7 +90,7 @@ fn receive_network_block() {
             let block = Block::produce(
                 &last_block.header,
                 last_block.header.height + 1,
-                MerkleHash::default(),
+                vec![MerkleHash::default()],
                 vec![],
                 HashMap::default(),
                 vec![],
@@ -103,6 +103,46 @@ fn receive_network_block() {
     .unwrap();
 }
 
+/// Runs client that switches to a heavier fork, the transactions of the abandoned branch are
+/// returned to the pool.
+#[test]
+fn reorg_returns_transactions_to_pool() {
+    init_test_logger();
+    System::run(|| {
+        let (client, view_client) = setup_no_network(vec!["test"], "other", true);
+        actix::spawn(view_client.send(GetBlock::Best).then(move |res| {
+            let genesis = res.unwrap().unwrap();
+            let signer = Arc::new(InMemorySigner::from_seed("test", "test"));
+            let produce = |prev_header: &BlockHeader, transactions: Vec<SignedTransaction>| {
+                Block::produce(
+                    prev_header,
+                    prev_header.height + 1,
+                    vec![MerkleHash::default()],
+                    transactions,
+                    HashMap::default(),
+                    vec![],
+                    signer.clone(),
+                )
+            };
+            let transaction = TransactionBody::send_money(1, "test", "other", 1).sign(&*signer);
+            let old_block = produce(&genesis.header, vec![transaction]);
+            let fork_block1 = produce(&genesis.header, vec![]);
+            let fork_block2 = produce(&fork_block1.header, vec![]);
+            for block in vec![old_block, fork_block1, fork_block2] {
+                client.do_send(NetworkClientMessages::Block(block, PeerInfo::random().id, false));
+            }
+            client.send(Status {}).then(|res| {
+                let status = res.unwrap().unwrap();
+                assert_eq!(status.sync_info.latest_block_height, 2);
+                assert_eq!(status.tx_pool_info.num_transactions, 1);
+                System::current().stop();
+                future::result(Ok(()))
+            })
+        }));
+    })
+    .unwrap();
+}
+
 /// Runs client that receives a block from network and announces header to the network.
 #[test]
 fn receive_network_block_header() {
@@ -138,7 +178,7 @@ fn receive_network_block_header() {
             let block = Block::produce(
                 &last_block.header,
                 last_block.header.height + 1,
-                MerkleHash::default(),
+                vec![MerkleHash::default()],
                 vec![],
                 HashMap::default(),
                 vec![],
@@ -179,7 +219,7 @@ fn produce_block_with_approvals() {
             let block = Block::produce(
                 &last_block.header,
                 last_block.header.height + 1,
-                MerkleHash::default(),
+                vec![MerkleHash::default()],
                 vec![],
                 HashMap::default(),
                 vec![],
@@ -211,7 +251,7 @@ fn invalid_blocks() {
                 match msg {
                     NetworkRequests::BlockHeaderAnnounce { header, approval } => {
                         assert_eq!(header.height, 1);
-                        assert_eq!(header.prev_state_root, MerkleHash::default());
+                        assert_eq!(header.prev_state_roots, vec![MerkleHash::default()]);
                         assert_eq!(*approval, None);
                         System::current().stop();
                     }
@@ -227,7 +267,7 @@ fn invalid_blocks() {
             let block = Block::produce(
                 &last_block.header,
                 last_block.header.height + 1,
-                hash(&[0]),
+                vec![hash(&[0])],
                 vec![],
                 HashMap::default(),
                 vec![],
@@ -242,7 +282,7 @@ fn invalid_blocks() {
             let block2 = Block::produce(
                 &block.header,
                 block.header.height + 1,
-                hash(&[1]),
+                vec![hash(&[1])],
                 vec![],
                 HashMap::default(),
                 vec![],
@@ -253,7 +293,7 @@ fn invalid_blocks() {
             let block3 = Block::produce(
                 &last_block.header,
                 last_block.header.height + 1,
-                MerkleHash::default(),
+                vec![MerkleHash::default()],
                 vec![],
                 HashMap::default(),
                 vec![],
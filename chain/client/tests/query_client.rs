@@ -6,6 +6,7 @@ use near_client::test_utils::setup_no_network;
 use near_client::Query;
 use near_primitives::rpc::QueryResponse;
 use near_primitives::test_utils::init_test_logger;
+use near_primitives::types::BlockId;
 
 /// Query account from view client
 #[test]
@@ -14,16 +15,21 @@ fn query_client() {
     System::run(|| {
         let (_, view_client) = setup_no_network(vec!["test"], "other", true);
         actix::spawn(
-            view_client.send(Query { path: "account/test".to_string(), data: vec![] }).then(
-                |res| {
+            view_client
+                .send(Query {
+                    path: "account/test".to_string(),
+                    data: vec![],
+                    block_id: BlockId::Best,
+                    prove: false,
+                })
+                .then(|res| {
                     match res {
                         Ok(Ok(QueryResponse::ViewAccount(_))) => (),
                         _ => panic!("Invalid response"),
                     }
                     System::current().stop();
                     future::result(Ok(()))
-                },
-            ),
+                }),
         );
     })
     .unwrap();
@@ -0,0 +1,76 @@
+use actix::{Actor, Context, Handler, System};
+use futures::future;
+use futures::future::Future;
+
+use near_client::test_utils::{setup_mock, setup_no_network};
+use near_client::{Subscribe, Subscription, SubscriptionEvent, SubscriptionResult};
+use near_network::NetworkResponses;
+use near_primitives::test_utils::init_test_logger;
+
+/// Stops the system once it receives a new block header.
+struct HeaderListener;
+
+impl Actor for HeaderListener {
+    type Context = Context<Self>;
+}
+
+impl Handler<SubscriptionEvent> for HeaderListener {
+    type Result = ();
+
+    fn handle(&mut self, event: SubscriptionEvent, _: &mut Context<Self>) {
+        assert_eq!(event.id, "headers");
+        match event.result {
+            SubscriptionResult::NewBlockHeader(header) => {
+                assert!(header.height > 0);
+                System::current().stop();
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+}
+
+/// Runs block producing client and waits for it to push header of the produced block.
+#[test]
+fn subscribe_new_block_headers() {
+    init_test_logger();
+    System::run(|| {
+        let (_, view_client) = setup_mock(
+            vec!["test"],
+            "test",
+            true,
+            Box::new(|_, _, _| NetworkResponses::NoResponse),
+        );
+        let listener = HeaderListener.start();
+        view_client.do_send(Subscribe {
+            id: "headers".to_string(),
+            subscription: Subscription::NewBlockHeaders,
+            subscriber: listener.recipient(),
+        });
+    })
+    .unwrap();
+}
+
+/// Subscriptions over the limit of the node are rejected.
+#[test]
+fn subscriptions_limit() {
+    init_test_logger();
+    System::run(|| {
+        let (_, view_client) = setup_no_network(vec!["test"], "other", true);
+        let listener = HeaderListener.start().recipient();
+        let requests = (0..1001).map(|i| {
+            view_client.send(Subscribe {
+                id: i.to_string(),
+                subscription: Subscription::NewBlockHeaders,
+                subscriber: listener.clone(),
+            })
+        });
+        actix::spawn(future::join_all(requests).then(|res| {
+            let results = res.unwrap();
+            assert!(results[..1000].iter().all(Result::is_ok));
+            assert!(results[1000].is_err());
+            System::current().stop();
+            future::result(Ok(()))
+        }));
+    })
+    .unwrap();
+}
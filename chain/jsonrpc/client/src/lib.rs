@@ -1,16 +1,20 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use actix_web::client::Client;
 use futures::Future;
 use serde::Serialize;
+use serde_json::Value;
 
 use near_primitives::block::Block;
-use near_primitives::rpc::{QueryResponse, StatusResponse};
+use near_primitives::rpc::{
+    BlockEvents, BlockReference, QueryResponse, SimulateTxResult, StatusResponse,
+};
 use near_primitives::transaction::{FinalTransactionResult, TransactionResult};
-use near_primitives::types::BlockIndex;
+use near_primitives::types::{AccountId, BlockIndex};
 
 pub mod message;
-use crate::message::{from_slice, Message};
+use crate::message::{from_slice, Message, RpcError};
 
 /// Timeout for establishing connection.
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
@@ -51,6 +55,55 @@ where
     )
 }
 
+/// Prepare a batch `RPCRequest` with given methods and parameters.
+/// Results are returned in the order of the requests.
+fn call_batch(
+    client: &Client,
+    server_addr: &str,
+    requests: Vec<(String, Value)>,
+) -> RpcRequest<Vec<Result<Value, RpcError>>> {
+    let requests: Vec<Message> = requests
+        .into_iter()
+        .map(|(method, params)| Message::request(method, Some(params)))
+        .collect();
+    let ids: Vec<Value> = requests.iter().map(Message::id).collect();
+    Box::new(
+        client
+            .post(server_addr)
+            .header("Content-Type", "application/json")
+            .send_json(&Message::batch(requests))
+            .map_err(|err| err.to_string())
+            .and_then(|mut response| {
+                response.body().then(|body| match body {
+                    Ok(bytes) => {
+                        from_slice(&bytes).map_err(|err| format!("Error {:?} in {:?}", err, bytes))
+                    }
+                    Err(_) => Err("Payload error: {:?}".to_string()),
+                })
+            })
+            .and_then(move |message| match message {
+                Message::Batch(responses) => {
+                    let mut results: HashMap<String, Result<Value, RpcError>> = responses
+                        .into_iter()
+                        .filter_map(|message| match message {
+                            Message::Response(resp) => Some((resp.id.to_string(), resp.result)),
+                            _ => None,
+                        })
+                        .collect();
+                    ids.iter()
+                        .map(|id| {
+                            results.remove(&id.to_string()).ok_or_else(|| {
+                                format!("Missing response for request {}", id)
+                            })
+                        })
+                        .collect()
+                }
+                Message::Response(resp) => Err(format!("{:?}", resp.result)),
+                _ => Err("Invalid message type".to_string()),
+            }),
+    )
+}
+
 /// Prepare a `HttpRequest` with a given client, server address and parameters.
 fn call_http_get<R, P>(
     client: &Client,
@@ -164,14 +217,53 @@ macro_rules! jsonrpc_client {
 jsonrpc_client!(pub struct JsonRpcClient {
     pub fn broadcast_tx_async(&mut self, tx: String) -> RpcRequest<String>;
     pub fn broadcast_tx_commit(&mut self, tx: String) -> RpcRequest<FinalTransactionResult>;
-    pub fn query(&mut self, path: String, data: String) -> RpcRequest<QueryResponse>;
+    pub fn query(&mut self, path: String, data: String, block_id: BlockReference)
+        -> RpcRequest<QueryResponse>;
+    pub fn simulate_tx(&mut self, tx: String) -> RpcRequest<SimulateTxResult>;
     pub fn status(&mut self) -> RpcRequest<StatusResponse>;
     pub fn health(&mut self) -> RpcRequest<()>;
     pub fn tx(&mut self, hash: String) -> RpcRequest<FinalTransactionResult>;
     pub fn tx_details(&mut self, hash: String) -> RpcRequest<TransactionResult>;
     pub fn block(&mut self, height: BlockIndex) -> RpcRequest<Block>;
+    pub fn events(
+        &mut self,
+        account_id: AccountId,
+        topic: String,
+        from_height: BlockIndex,
+        to_height: BlockIndex
+    ) -> RpcRequest<Vec<BlockEvents>>;
 });
 
+impl JsonRpcClient {
+    /// Queries raw value at given path together with the proof against the block's state root.
+    pub fn query_proof(
+        &mut self,
+        path: String,
+        data: String,
+        block_id: BlockReference,
+    ) -> RpcRequest<QueryResponse> {
+        call_method(&self.client, &self.server_addr, "query", (path, data, block_id, true))
+    }
+
+    /// Sends given methods with parameters in a single batch request.
+    pub fn batch(
+        &mut self,
+        requests: Vec<(String, Value)>,
+    ) -> RpcRequest<Vec<Result<Value, RpcError>>> {
+        call_batch(&self.client, &self.server_addr, requests)
+    }
+
+    /// Retrieves block with given base encoded hash.
+    pub fn block_by_hash(&mut self, hash: String) -> RpcRequest<Block> {
+        call_method(&self.client, &self.server_addr, "block", (hash,))
+    }
+
+    /// Retrieves the latest block.
+    pub fn latest_block(&mut self) -> RpcRequest<Block> {
+        call_method(&self.client, &self.server_addr, "block", ("latest",))
+    }
+}
+
 /// Create new JSON RPC client that connects to the given address.
 pub fn new_client(server_addr: &str) -> JsonRpcClient {
     let client = Client::build().timeout(CONNECT_TIMEOUT).finish();
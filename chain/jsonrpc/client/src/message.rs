@@ -199,7 +199,7 @@ pub struct Notification {
 /// Since the protocol allows one endpoint to be both client and server at the same time, the
 /// message can decode and encode both directions of the protocol.
 ///
-/// The `Batch` variant is supposed to be created directly, without a constructor.
+/// The `Batch` variant can be created directly or with the [batch](#method.batch) constructor.
 ///
 /// The `UnmatchedSub` variant is used when a request is an array and some of the subrequests
 /// aren't recognized as valid json rpc 2.0 messages. This is never returned as a top-level
@@ -218,7 +218,7 @@ pub enum Message {
     /// The protocol allows bundling multiple requests, notifications or responses to a single
     /// message.
     ///
-    /// This variant has no direct constructor and is expected to be constructed manually.
+    /// A batch can be created with the [batch](#method.batch) constructor.
     Batch(Vec<Message>),
     /// An unmatched sub entry in a `Batch`.
     ///
@@ -241,6 +241,10 @@ impl Message {
             id: Value::String(Uuid::new_v4().hyphenated().to_string()),
         })
     }
+    /// A constructor for a batch of requests or notifications.
+    pub fn batch(messages: Vec<Message>) -> Self {
+        Message::Batch(messages)
+    }
     /// Create a top-level error (without an ID).
     pub fn error(error: RpcError) -> Self {
         Message::Response(Response { jsonrpc: Version, result: Err(error), id: Value::Null })
@@ -538,6 +542,17 @@ mod tests {
         } else {
             panic!("Not a response");
         }
+        // A batch keeps the ids of its requests
+        if let Message::Batch(messages) = Message::batch(vec![
+            Message::request("call".to_owned(), None),
+            Message::notification("notif".to_owned(), None),
+        ]) {
+            assert_eq!(messages.len(), 2);
+            assert!(messages[0].id().is_string());
+            assert_eq!(messages[1].id(), Value::Null);
+        } else {
+            panic!("Not a batch");
+        }
         // When we have unmatched, we generate a top-level error with Null id.
         if let Message::Response(ref resp) =
             Message::error(RpcError::new(43, "Also wrong!".to_owned(), None))
@@ -7,7 +7,9 @@ use std::time::Duration;
 use actix::{Addr, MailboxError};
 use actix_cors::Cors;
 use actix_web::{App, Error as HttpError, http, HttpResponse, HttpServer, middleware, web};
-use futures03::{compat::Future01CompatExt as _, FutureExt as _, TryFutureExt as _};
+use futures03::{
+    compat::Future01CompatExt as _, future::join_all, FutureExt as _, TryFutureExt as _,
+};
 use futures::future::Future;
 use protobuf::parse_from_bytes;
 use serde::de::DeserializeOwned;
@@ -15,19 +17,24 @@ use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
 
 use async_utils::{delay, timeout};
-use message::{Request, RpcError};
+use message::{Notification, Request, RpcError};
 use message::Message;
-use near_client::{ClientActor, GetBlock, Query, Status, TxDetails, TxStatus, ViewClientActor};
+use near_client::{
+    ClientActor, GetBlock, GetEvents, Query, SimulateTx, Status, TxDetails, TxStatus,
+    ViewClientActor,
+};
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_client::message as message;
 use near_network::{NetworkClientMessages, NetworkClientResponses};
 use near_primitives::hash::CryptoHash;
+use near_primitives::rpc::BlockReference;
 use near_primitives::serialize::{BaseEncode, from_base, from_base64};
 use near_primitives::transaction::{FinalTransactionStatus, SignedTransaction};
-use near_primitives::types::BlockIndex;
+use near_primitives::types::{AccountId, BlockId, BlockIndex};
 use near_protos::signed_transaction as transaction_proto;
 
 pub mod test_utils;
+mod websocket;
 
 /// Maximum byte size of the json payload.
 const JSON_PAYLOAD_MAX_SIZE: usize = 2 * 1024 * 1024;
@@ -116,6 +123,21 @@ fn parse_hash(params: Option<Value>) -> Result<CryptoHash, RpcError> {
     })
 }
 
+/// Parses `query` params: path, data and optionally block id and whether to return a proof.
+fn parse_query(params: Option<Value>) -> Result<(String, String, BlockId, bool), RpcError> {
+    if let Ok((path, data)) = parse_params::<(String, String)>(params.clone()) {
+        return Ok((path, data, BlockId::Best, false));
+    }
+    if let Ok((path, data, block_reference)) =
+        parse_params::<(String, String, BlockReference)>(params.clone())
+    {
+        return Ok((path, data, block_reference.into(), false));
+    }
+    let (path, data, block_reference, prove) =
+        parse_params::<(String, String, BlockReference, bool)>(params)?;
+    Ok((path, data, block_reference.into(), prove))
+}
+
 struct JsonRpcHandler {
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
@@ -123,27 +145,59 @@ struct JsonRpcHandler {
 }
 
 impl JsonRpcHandler {
-    pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
-        let id = message.id();
+    /// Processes single message or a batch. Returns `None` if there is nothing to respond with,
+    /// i.e. message consisted only of notifications.
+    pub async fn process(&self, message: Message) -> Result<Option<Message>, HttpError> {
         match message {
-            Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+            Message::Batch(messages) => {
+                if messages.is_empty() {
+                    return Ok(Some(Message::error(RpcError::invalid_request())));
+                }
+                let responses =
+                    join_all(messages.into_iter().map(|message| self.process_single(message)))
+                        .await;
+                let responses: Vec<Message> = responses.into_iter().filter_map(|r| r).collect();
+                if responses.is_empty() {
+                    Ok(None)
+                } else {
+                    Ok(Some(Message::Batch(responses)))
+                }
             }
-            _ => Ok(Message::error(RpcError::invalid_request())),
+            message => Ok(self.process_single(message).await),
         }
     }
 
-    async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
-        match request.method.as_ref() {
-            "broadcast_tx_async" => self.send_tx_async(request.params).await,
-            "broadcast_tx_commit" => self.send_tx_commit(request.params).await,
-            "query" => self.query(request.params).await,
+    /// Processes a message that is not a batch. Notifications are executed without a response.
+    async fn process_single(&self, message: Message) -> Option<Message> {
+        match message {
+            Message::Request(Request { method, params, id, .. }) => {
+                Some(Message::response(id, self.process_request(method, params).await))
+            }
+            Message::Notification(Notification { method, params, .. }) => {
+                let _ = self.process_request(method, params).await;
+                None
+            }
+            _ => Some(Message::error(RpcError::invalid_request())),
+        }
+    }
+
+    async fn process_request(
+        &self,
+        method: String,
+        params: Option<Value>,
+    ) -> Result<Value, RpcError> {
+        match method.as_ref() {
+            "broadcast_tx_async" => self.send_tx_async(params).await,
+            "broadcast_tx_commit" => self.send_tx_commit(params).await,
+            "query" => self.query(params).await,
+            "simulate_tx" => self.simulate_tx(params).await,
             "health" => self.health().await,
             "status" => self.status().await,
-            "tx" => self.tx_status(request.params).await,
-            "tx_details" => self.tx_details(request.params).await,
-            "block" => self.block(request.params).await,
-            _ => Err(RpcError::method_not_found(request.method)),
+            "tx" => self.tx_status(params).await,
+            "tx_details" => self.tx_details(params).await,
+            "block" => self.block(params).await,
+            "events" => self.events(params).await,
+            _ => Err(RpcError::method_not_found(method)),
         }
     }
 
@@ -202,9 +256,14 @@ impl JsonRpcHandler {
     }
 
     async fn query(&self, params: Option<Value>) -> Result<Value, RpcError> {
-        let (path, data) = parse_params::<(String, String)>(params)?;
+        let (path, data, block_id, prove) = parse_query(params)?;
         let data = from_base_or_parse_err(data)?;
-        jsonify(self.view_client_addr.send(Query { path, data }).compat().await)
+        jsonify(self.view_client_addr.send(Query { path, data, block_id, prove }).compat().await)
+    }
+
+    async fn simulate_tx(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let transaction = parse_tx(params)?;
+        jsonify(self.view_client_addr.send(SimulateTx { transaction }).compat().await)
     }
 
     async fn tx_status(&self, params: Option<Value>) -> Result<Value, RpcError> {
@@ -217,9 +276,28 @@ impl JsonRpcHandler {
         jsonify(self.view_client_addr.send(TxDetails { tx_hash }).compat().await)
     }
 
+    async fn events(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (account_id, topic, from_height, to_height) =
+            parse_params::<(AccountId, String, BlockIndex, BlockIndex)>(params)?;
+        jsonify(
+            self.view_client_addr
+                .send(GetEvents { account_id, topic, from_height, to_height })
+                .compat()
+                .await,
+        )
+    }
+
     async fn block(&self, params: Option<Value>) -> Result<Value, RpcError> {
-        let (height,) = parse_params::<(BlockIndex,)>(params)?;
-        jsonify(self.view_client_addr.send(GetBlock::Height(height)).compat().await)
+        let block_id = match parse_params::<(String,)>(params.clone()) {
+            Ok((ref name,)) if name == "latest" => BlockId::Best,
+            _ => parse_params::<(BlockReference,)>(params)?.0.into(),
+        };
+        let msg = match block_id {
+            BlockId::Best => GetBlock::Best,
+            BlockId::Number(height) => GetBlock::Height(height),
+            BlockId::Hash(hash) => GetBlock::Hash(hash),
+        };
+        jsonify(self.view_client_addr.send(msg).compat().await)
     }
 }
 
@@ -228,8 +306,10 @@ fn rpc_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Item = HttpResponse, Error = HttpError> {
     let response = async move {
-        let message = handler.process(message.0).await?;
-        Ok(HttpResponse::Ok().json(message))
+        match handler.process(message.0).await? {
+            Some(message) => Ok(HttpResponse::Ok().json(message)),
+            None => Ok(HttpResponse::NoContent().finish()),
+        }
     };
     response.boxed().compat()
 }
@@ -275,6 +355,7 @@ pub fn start_http(
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to_async(rpc_handler)))
             .service(web::resource("/status").route(web::get().to_async(status_handler)))
+            .service(web::resource("/ws").route(web::get().to(websocket::ws_handler)))
     })
     .bind(addr)
     .unwrap()
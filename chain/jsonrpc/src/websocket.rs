@@ -0,0 +1,143 @@
+//! WebSocket endpoint, that allows to subscribe to chain events instead of polling.
+//! Requests and responses are JSON-RPC messages, events are pushed as `subscription` notifications.
+
+use std::collections::HashSet;
+
+use actix::{
+    Actor, ActorContext, ActorFuture, Addr, AsyncContext, ContextFutureSpawner, Handler,
+    StreamHandler, WrapFuture,
+};
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use near_client::{Subscribe, Subscription, SubscriptionEvent, Unsubscribe, ViewClientActor};
+
+use crate::message::{from_str, Message, Request, RpcError};
+use crate::{parse_params, JsonRpcHandler};
+
+/// Maximum number of subscriptions a single session can have at the same time.
+const MAX_SUBSCRIPTIONS_PER_SESSION: usize = 16;
+
+/// WebSocket connection with a single client.
+struct WebSocketSession {
+    view_client_addr: Addr<ViewClientActor>,
+    /// Ids of subscriptions created in this session.
+    subscriptions: HashSet<String>,
+}
+
+impl WebSocketSession {
+    fn new(view_client_addr: Addr<ViewClientActor>) -> Self {
+        WebSocketSession { view_client_addr, subscriptions: HashSet::default() }
+    }
+
+    fn process_request(&mut self, request: Request, ctx: &mut ws::WebsocketContext<Self>) {
+        let id = request.id.clone();
+        let result = match request.method.as_ref() {
+            "subscribe" => return self.subscribe(request, ctx),
+            "unsubscribe" => self.unsubscribe(request),
+            _ => Err(RpcError::method_not_found(request.method)),
+        };
+        reply(ctx, Message::response(id, result));
+    }
+
+    /// Registers the subscription with the view client and replies with its id once the view
+    /// client accepts it.
+    fn subscribe(&mut self, request: Request, ctx: &mut ws::WebsocketContext<Self>) {
+        let request_id = request.id;
+        let subscription = match parse_params::<(Subscription,)>(request.params) {
+            Ok((subscription,)) => subscription,
+            Err(err) => return reply(ctx, Message::response(request_id, Err(err))),
+        };
+        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_SESSION {
+            let err = RpcError::server_error(Some(format!(
+                "Session has reached the limit of {} subscriptions",
+                MAX_SUBSCRIPTIONS_PER_SESSION
+            )));
+            return reply(ctx, Message::response(request_id, Err(err)));
+        }
+        let id = Uuid::new_v4().hyphenated().to_string();
+        // Counted right away, so that requests waiting for the view client respect the limit.
+        self.subscriptions.insert(id.clone());
+        self.view_client_addr
+            .send(Subscribe { id: id.clone(), subscription, subscriber: ctx.address().recipient() })
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                let result = match res {
+                    Ok(Ok(())) => Ok(Value::String(id)),
+                    Ok(Err(err)) => {
+                        act.subscriptions.remove(&id);
+                        Err(RpcError::server_error(Some(err)))
+                    }
+                    Err(err) => {
+                        act.subscriptions.remove(&id);
+                        Err(RpcError::server_error(Some(err.to_string())))
+                    }
+                };
+                reply(ctx, Message::response(request_id, result));
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    fn unsubscribe(&mut self, request: Request) -> Result<Value, RpcError> {
+        let (id,) = parse_params::<(String,)>(request.params)?;
+        if self.subscriptions.remove(&id) {
+            self.view_client_addr.do_send(Unsubscribe { id });
+            Ok(Value::Bool(true))
+        } else {
+            Ok(Value::Bool(false))
+        }
+    }
+}
+
+impl Actor for WebSocketSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        for id in self.subscriptions.drain() {
+            self.view_client_addr.do_send(Unsubscribe { id });
+        }
+    }
+}
+
+impl StreamHandler<ws::Message, ws::ProtocolError> for WebSocketSession {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Text(text) => match from_str(&text) {
+                Ok(Message::Request(request)) => self.process_request(request, ctx),
+                Ok(_) => reply(ctx, Message::error(RpcError::invalid_request())),
+                Err(broken) => reply(ctx, broken.reply()),
+            },
+            ws::Message::Close(_) => ctx.stop(),
+            _ => {}
+        }
+    }
+}
+
+fn reply(ctx: &mut ws::WebsocketContext<WebSocketSession>, message: Message) {
+    let message: String = message.into();
+    ctx.text(message);
+}
+
+/// Pushes chain events from the client as JSON-RPC notifications.
+impl Handler<SubscriptionEvent> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, event: SubscriptionEvent, ctx: &mut Self::Context) {
+        let params = json!({ "subscription": event.id, "result": event.result });
+        let notification: String =
+            Message::notification("subscription".to_string(), Some(params)).into();
+        ctx.text(notification);
+    }
+}
+
+pub(crate) fn ws_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    ws::start(WebSocketSession::new(handler.view_client_addr.clone()), &req, stream)
+}
@@ -1,9 +1,14 @@
+use std::convert::TryInto;
+
 use actix::System;
 use futures::future;
 use futures::future::Future;
+use serde_json::{json, Value};
 
 use near_jsonrpc::client::new_client;
 use near_jsonrpc::test_utils::start_all;
+use near_primitives::rpc::{BlockReference, ProofResult};
+use near_primitives::serialize::BaseEncode;
 use near_primitives::test_utils::init_test_logger;
 
 /// Retrieve blocks via json rpc
@@ -24,16 +29,44 @@ fn test_block() {
     .unwrap();
 }
 
-/// Connect to json rpc and query the client.
+/// Retrieve genesis block by hash via json rpc
 #[test]
-fn test_query() {
+fn test_block_by_hash() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .block(0)
+                .and_then(move |block| {
+                    let hash = block.header.hash();
+                    client.block_by_hash(hash.to_base()).map(move |block| (hash, block))
+                })
+                .then(|res| {
+                    let (hash, block) = res.unwrap();
+                    assert_eq!(block.header.hash(), hash);
+                    assert_eq!(block.header.height, 0);
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
+/// Retrieve the latest block via json rpc
+#[test]
+fn test_latest_block() {
     init_test_logger();
 
     System::run(|| {
         let (_view_client_addr, addr) = start_all(false);
 
         let mut client = new_client(&format!("http://{}", addr));
-        actix::spawn(client.query("account/test".to_string(), "".to_string()).then(|res| {
+        actix::spawn(client.latest_block().then(|res| {
             assert!(res.is_ok());
             System::current().stop();
             future::result(Ok(()))
@@ -42,6 +75,125 @@ fn test_query() {
     .unwrap();
 }
 
+/// Connect to json rpc and query the client.
+#[test]
+fn test_query() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .query("account/test".to_string(), "".to_string(), BlockReference::Best)
+                .then(|res| {
+                    assert!(res.is_ok());
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
+/// Query the state as of the genesis block via json rpc.
+#[test]
+fn test_query_by_height() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .query("account/test".to_string(), "".to_string(), BlockReference::Number(0))
+                .then(|res| {
+                    assert!(res.is_ok());
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
+/// Querying the state at an unknown block returns an error.
+#[test]
+fn test_query_unknown_block() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .query("account/test".to_string(), "".to_string(), BlockReference::Number(1000))
+                .then(|res| {
+                    assert!(res.is_err());
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
+/// Query the state with a proof and verify it against the returned state root.
+#[test]
+fn test_query_proof() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .query_proof("account/test".to_string(), "".to_string(), BlockReference::Best)
+                .then(|res| {
+                    let proof: ProofResult = res.unwrap().try_into().unwrap();
+                    assert!(proof.verify());
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
+/// Send several requests in a single batch.
+#[test]
+fn test_batch() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(false);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        actix::spawn(
+            client
+                .batch(vec![
+                    ("health".to_string(), json!([])),
+                    ("block".to_string(), json!([0])),
+                    ("unknown".to_string(), json!([])),
+                ])
+                .then(|res| {
+                    let results = res.unwrap();
+                    assert_eq!(results.len(), 3);
+                    assert_eq!(results[0], Ok(Value::Null));
+                    assert_eq!(results[1].as_ref().unwrap()["header"]["height"], json!(0));
+                    assert!(results[2].is_err());
+                    System::current().stop();
+                    future::result(Ok(()))
+                }),
+        );
+    })
+    .unwrap();
+}
+
 /// Retrieve client status via JSON RPC.
 #[test]
 fn test_status() {
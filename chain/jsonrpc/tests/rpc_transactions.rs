@@ -8,7 +8,7 @@ use near_network::test_utils::{wait_or_panic, WaitOrTimeout};
 use near_primitives::crypto::signer::InMemorySigner;
 use near_primitives::serialize::to_base64;
 use near_primitives::test_utils::init_test_logger;
-use near_primitives::transaction::{FinalTransactionStatus, TransactionBody};
+use near_primitives::transaction::{FinalTransactionStatus, TransactionBody, TransactionStatus};
 use near_protos::signed_transaction as transaction_proto;
 
 /// Test sending transaction via json rpc without waiting.
@@ -79,3 +79,39 @@ fn test_send_tx_commit() {
     })
     .unwrap();
 }
+
+/// Test simulating transaction, which must not be submitted to the chain.
+#[test]
+fn test_simulate_tx() {
+    init_test_logger();
+
+    System::run(|| {
+        let (_view_client_addr, addr) = start_all(true);
+
+        let mut client = new_client(&format!("http://{}", addr));
+        let signer = InMemorySigner::from_seed("test1", "test1");
+        let tx = TransactionBody::send_money(1, "test1", "test2", 100).sign(&signer);
+        let tx_hash: String = (&tx.get_hash()).into();
+        let proto: transaction_proto::SignedTransaction = tx.into();
+        actix::spawn(
+            client
+                .simulate_tx(to_base64(&proto.write_to_bytes().unwrap()))
+                .and_then(move |result| {
+                    assert_eq!(result.result.status, TransactionStatus::Completed);
+                    // Sending money doesn't call any contract.
+                    assert!(result.traces.is_empty());
+                    client.tx(tx_hash)
+                })
+                .map_err(|why| {
+                    System::current().stop();
+                    panic!(why);
+                })
+                .map(|result| {
+                    assert_eq!(result.status, FinalTransactionStatus::Unknown);
+                    System::current().stop();
+                }),
+        );
+        wait_or_panic(10000);
+    })
+    .unwrap();
+}
@@ -31,6 +31,10 @@ const MAX_TRACK_SIZE: usize = 30;
 // TODO: current limit is way to high due to us sending lots of messages during sync.
 const MAX_PEER_MSG_PER_MIN: u64 = 50000;
 
+/// Maximum number of state part requests per minute from single peer, since each of them makes
+/// the client read a part of the state from the disk.
+const MAX_PEER_STATE_REQUESTS_PER_MIN: u64 = 120;
+
 /// Keeps track of requests and received hashes of transactions and blocks.
 /// Also keeps track of number of bytes sent and received from this peer to prevent abuse.
 pub struct Tracker {
@@ -38,6 +42,8 @@ pub struct Tracker {
     sent_bytes: RateCounter,
     /// Bytes we've received.
     received_bytes: RateCounter,
+    /// State part requests we've received, including the dropped ones.
+    state_requests: RateCounter,
     /// Sent requests.
     requested: Vec<CryptoHash>,
     /// Received elements.
@@ -49,6 +55,7 @@ impl Default for Tracker {
         Tracker {
             sent_bytes: RateCounter::new(),
             received_bytes: RateCounter::new(),
+            state_requests: RateCounter::new(),
             requested: Default::default(),
             received: Default::default(),
         }
@@ -64,6 +71,12 @@ impl Tracker {
         self.sent_bytes.increment(size);
     }
 
+    /// Counts the state request and checks that the peer doesn't send them too often.
+    fn allow_state_request(&mut self) -> bool {
+        self.state_requests.increment(1);
+        self.state_requests.count_per_min() <= MAX_PEER_STATE_REQUESTS_PER_MIN
+    }
+
     fn has_received(&self, hash: CryptoHash) -> bool {
         self.received.contains(&hash)
     }
@@ -252,11 +265,15 @@ impl Peer {
             PeerMessage::BlockHeaders(headers) => {
                 NetworkClientMessages::BlockHeaders(headers, peer_id)
             }
-            PeerMessage::StateRequest(shard_id, hash) => {
-                NetworkClientMessages::StateRequest(shard_id, hash)
-            },
-            PeerMessage::StateResponse(shard_id, hash, payload, receipts) => {
-                NetworkClientMessages::StateResponse(shard_id, hash, payload, receipts)
+            PeerMessage::StateRequest(shard_id, hash, part_id) => {
+                if !self.tracker.allow_state_request() {
+                    debug!(target: "network", "Dropping state request from {}: too many requests", self.peer_info);
+                    return;
+                }
+                NetworkClientMessages::StateRequest(shard_id, hash, part_id)
+            }
+            PeerMessage::StateResponse(shard_id, hash, part, receipts) => {
+                NetworkClientMessages::StateResponse(shard_id, hash, part, receipts)
             }
             _ => unreachable!(),
         };
@@ -280,8 +297,8 @@ impl Peer {
                     Ok(NetworkClientResponses::BlockHeaders(headers)) => {
                         act.send_message(PeerMessage::BlockHeaders(headers))
                     }
-                    Ok(NetworkClientResponses::StateResponse { shard_id, hash, payload, receipts }) => {
-                        act.send_message(PeerMessage::StateResponse(shard_id, hash, payload, receipts))
+                    Ok(NetworkClientResponses::StateResponse { shard_id, hash, part, receipts }) => {
+                        act.send_message(PeerMessage::StateResponse(shard_id, hash, part, receipts))
                     }
                     Err(err) => {
                         error!(
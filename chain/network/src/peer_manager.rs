@@ -436,9 +436,9 @@ impl Handler<NetworkRequests> for PeerManagerActor {
                 }
                 NetworkResponses::NoResponse
             }
-            NetworkRequests::StateRequest { shard_id, hash, peer_id } => {
+            NetworkRequests::StateRequest { shard_id, hash, part_id, peer_id } => {
                 if let Some(active_peer) = self.active_peers.get(&peer_id) {
-                    active_peer.addr.do_send(SendMessage { message: PeerMessage::StateRequest(shard_id, hash) });
+                    active_peer.addr.do_send(SendMessage { message: PeerMessage::StateRequest(shard_id, hash, part_id) });
                 }
                 NetworkResponses::NoResponse
             }
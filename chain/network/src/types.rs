@@ -20,7 +20,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::logging::pretty_str;
 use near_primitives::serialize::{BaseEncode, Decode};
 use near_primitives::transaction::{SignedTransaction, ReceiptTransaction};
-use near_primitives::types::{AccountId, BlockIndex, ShardId};
+use near_primitives::types::{AccountId, BlockIndex, ShardId, StatePart};
 use near_primitives::utils::{proto_to_type, to_string_value};
 use near_protos::network as network_proto;
 
@@ -281,8 +281,8 @@ pub enum PeerMessage {
 
     Transaction(SignedTransaction),
 
-    StateRequest(ShardId, CryptoHash),
-    StateResponse(ShardId, CryptoHash, Vec<u8>, Vec<ReceiptTransaction>),
+    StateRequest(ShardId, CryptoHash, u64),
+    StateResponse(ShardId, CryptoHash, StatePart, Vec<ReceiptTransaction>),
 }
 
 impl fmt::Display for PeerMessage {
@@ -298,7 +298,7 @@ impl fmt::Display for PeerMessage {
             PeerMessage::Block(_) => f.write_str("Block"),
             PeerMessage::BlockApproval(_, _, _) => f.write_str("BlockApproval"),
             PeerMessage::Transaction(_) => f.write_str("Transaction"),
-            PeerMessage::StateRequest(_, _) => f.write_str("StateRequest"),
+            PeerMessage::StateRequest(_, _, _) => f.write_str("StateRequest"),
             PeerMessage::StateResponse(_, _, _, _) => f.write_str("StateResponse"),
         }
     }
@@ -364,13 +364,20 @@ impl TryFrom<network_proto::PeerMessage> for PeerMessage {
                 Ok(PeerMessage::StateRequest(
                     state_request.shard_id,
                     state_request.hash.try_into()?,
+                    state_request.part_id,
                 ))
             }
             Some(network_proto::PeerMessage_oneof_message_type::state_response(state_response)) => {
+                let part = StatePart {
+                    part_id: state_response.part_id,
+                    num_parts: state_response.num_parts,
+                    data: state_response.data,
+                    proof: state_response.proof.into_vec(),
+                };
                 Ok(PeerMessage::StateResponse(
                     state_response.shard_id,
                     state_response.hash.try_into()?,
-                    state_response.payload,
+                    part,
                     state_response.receipts.into_iter().map(TryInto::try_into).collect::<Result<Vec<_>, _>>()?,
                 ))
             }
@@ -440,20 +447,24 @@ impl From<PeerMessage> for network_proto::PeerMessage {
                 };
                 Some(network_proto::PeerMessage_oneof_message_type::block_headers(block_headers))
             }
-            PeerMessage::StateRequest(shard_id, hash) => {
+            PeerMessage::StateRequest(shard_id, hash, part_id) => {
                 let state_request = network_proto::StateRequest {
                     shard_id,
                     hash: hash.into(),
+                    part_id,
                     cached_size: Default::default(),
                     unknown_fields: Default::default(),
                 };
                 Some(network_proto::PeerMessage_oneof_message_type::state_request(state_request))
             }
-            PeerMessage::StateResponse(shard_id, hash, payload, receipts) => {
+            PeerMessage::StateResponse(shard_id, hash, part, receipts) => {
                 let state_response = network_proto::StateResponse {
                     shard_id,
                     hash: hash.into(),
-                    payload,
+                    part_id: part.part_id,
+                    num_parts: part.num_parts,
+                    data: part.data,
+                    proof: RepeatedField::from_vec(part.proof),
                     receipts: RepeatedField::from_iter(receipts.into_iter().map(std::convert::Into::into)),
                     cached_size: Default::default(),
                     unknown_fields: Default::default(),
@@ -639,10 +650,11 @@ pub enum NetworkRequests {
         hashes: Vec<CryptoHash>,
         peer_id: PeerId,
     },
-    /// Request state for given shard at given state root.
+    /// Request part of the state for given shard at given state root.
     StateRequest {
         shard_id: ShardId,
         hash: CryptoHash,
+        part_id: u64,
         peer_id: PeerId,
     },
     /// Ban given peer.
@@ -704,10 +716,10 @@ pub enum NetworkClientMessages {
     BlockHeadersRequest(Vec<CryptoHash>),
     /// Request a block.
     BlockRequest(CryptoHash),
-    /// State request.
-    StateRequest(ShardId, CryptoHash),
-    /// State response.
-    StateResponse(ShardId, CryptoHash, Vec<u8>, Vec<ReceiptTransaction>),
+    /// State part request.
+    StateRequest(ShardId, CryptoHash, u64),
+    /// State part response.
+    StateResponse(ShardId, CryptoHash, StatePart, Vec<ReceiptTransaction>),
 }
 
 pub enum NetworkClientResponses {
@@ -726,7 +738,7 @@ pub enum NetworkClientResponses {
     /// Headers response.
     BlockHeaders(Vec<BlockHeader>),
     /// Response to state request.
-    StateResponse { shard_id: ShardId, hash: CryptoHash, payload: Vec<u8>, receipts: Vec<ReceiptTransaction> },
+    StateResponse { shard_id: ShardId, hash: CryptoHash, part: StatePart, receipts: Vec<ReceiptTransaction> },
 }
 
 impl<A, M> MessageResponse<A, M> for NetworkClientResponses
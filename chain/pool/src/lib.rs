@@ -2,87 +2,230 @@ use std::collections::btree_map::BTreeMap;
 use std::collections::HashMap;
 
 use near_chain::{Block, ValidTransaction};
+use near_primitives::rpc::StatusTxPoolInfo;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, Nonce};
+use near_primitives::types::{AccountId, Balance, Nonce};
 
-pub use crate::types::Error;
+pub use crate::types::{Error, PoolConfig};
 
 pub mod types;
 
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
 pub struct TransactionPool {
+    config: PoolConfig,
     num_transactions: usize,
     /// Transactions grouped by account and ordered by nonce.
-    pub transactions: HashMap<AccountId, BTreeMap<Nonce, SignedTransaction>>,
+    pub transactions: HashMap<AccountId, BTreeMap<Nonce, ValidTransaction>>,
+    /// Number of transactions evicted in favor of ones with higher priority.
+    num_evicted: u64,
+    /// Number of transactions rejected because of the limits.
+    num_rejected: u64,
 }
 
 impl TransactionPool {
-    pub fn new() -> Self {
-        TransactionPool { num_transactions: 0, transactions: HashMap::default() }
+    pub fn new(config: PoolConfig) -> Self {
+        TransactionPool {
+            config,
+            num_transactions: 0,
+            transactions: HashMap::default(),
+            num_evicted: 0,
+            num_rejected: 0,
+        }
     }
 
     /// Insert a valid transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, valid_transaction: ValidTransaction) {
+    /// If the pool or the account limit is full, the transaction with the lowest priority is
+    /// evicted, or the given one is rejected if it has the lowest priority itself.
+    pub fn insert_transaction(&mut self, valid_transaction: ValidTransaction) -> Result<(), Error> {
         let account = valid_transaction.transaction.body.get_originator();
         let nonce = valid_transaction.transaction.body.get_nonce();
+        let fee = valid_transaction.fee;
+
+        // Replacing the transaction with the same nonce doesn't change the size of the pool.
+        if let Some(transaction) =
+            self.transactions.get_mut(&account).and_then(|map| map.get_mut(&nonce))
+        {
+            *transaction = valid_transaction;
+            return Ok(());
+        }
+
+        let account_len = self.transactions.get(&account).map_or(0, BTreeMap::len);
+        if account_len >= self.config.max_transactions_per_account {
+            // Only the transaction with the highest nonce can be dropped without invalidating
+            // the rest of the transactions from this account.
+            let last_nonce = self.last_nonce(&account).unwrap_or_default();
+            if nonce > last_nonce {
+                self.num_rejected += 1;
+                return Err(Error::AccountLimit(account));
+            }
+            self.remove_transaction(&account, last_nonce);
+            self.num_evicted += 1;
+        } else if self.num_transactions >= self.config.max_transactions {
+            let lowest = self.lowest_priority_transaction();
+            // Evicting a lower nonce of the same account would leave a gap before this one.
+            let evict = match &lowest {
+                Some((lowest_account, lowest_nonce, _))
+                    if *lowest_account == account && nonce > *lowest_nonce =>
+                {
+                    false
+                }
+                Some((_, _, lowest_fee)) => *lowest_fee < fee,
+                None => false,
+            };
+            if !evict {
+                self.num_rejected += 1;
+                return Err(Error::PoolFull);
+            }
+            if let Some((lowest_account, lowest_nonce, _)) = lowest {
+                self.remove_transaction(&lowest_account, lowest_nonce);
+                self.num_evicted += 1;
+            }
+        }
+
         self.num_transactions += 1;
         self.transactions
             .entry(account)
             .or_insert_with(BTreeMap::new)
-            .insert(nonce, valid_transaction.transaction);
+            .insert(nonce, valid_transaction);
+        Ok(())
     }
 
     /// Take transactions from the pool, in the appropriate order to be put in a new block.
     /// Ensure that on average they will fit into expected weight.
+    /// Accounts take turns to add their transactions in the nonce order, one per turn. Within a
+    /// turn, accounts whose next transaction pays higher fee go first.
     pub fn prepare_transactions(
         &mut self,
         expected_weight: u32,
     ) -> Result<Vec<SignedTransaction>, Error> {
-        // TODO: pack transactions better.
-        let result = self
+        let limit = expected_weight as usize;
+        let accounts: Vec<(&AccountId, Vec<&ValidTransaction>)> = self
             .transactions
-            .values()
-            .flat_map(BTreeMap::values)
-            .take(expected_weight as usize)
-            .cloned()
+            .iter()
+            .map(|(account, map)| (account, map.values().collect()))
             .collect();
+        let mut result = vec![];
+        let mut turn = 0;
+        while result.len() < limit {
+            let mut candidates: Vec<_> = accounts
+                .iter()
+                .filter_map(|(account, transactions)| {
+                    transactions.get(turn).map(|transaction| (*account, *transaction))
+                })
+                .collect();
+            if candidates.is_empty() {
+                break;
+            }
+            candidates.sort_by(|(account1, transaction1), (account2, transaction2)| {
+                transaction2.fee.cmp(&transaction1.fee).then_with(|| account1.cmp(account2))
+            });
+            let left = limit - result.len();
+            result.extend(
+                candidates.into_iter().take(left).map(|(_, tx)| tx.transaction.clone()),
+            );
+            turn += 1;
+        }
         Ok(result)
     }
 
+    /// Returns the highest nonce of the pending transactions from given account.
+    fn last_nonce(&self, account: &AccountId) -> Option<Nonce> {
+        self.transactions.get(account).and_then(|map| map.keys().next_back().cloned())
+    }
+
+    /// Finds the transaction with the lowest fee that can be evicted, i.e. one with the highest
+    /// nonce of its account. Returns its account, nonce and fee.
+    fn lowest_priority_transaction(&self) -> Option<(AccountId, Nonce, Balance)> {
+        self.transactions
+            .iter()
+            .filter_map(|(account, map)| {
+                map.iter().next_back().map(|(nonce, transaction)| {
+                    (account.clone(), *nonce, transaction.fee)
+                })
+            })
+            .min_by(|(account1, _, fee1), (account2, _, fee2)| {
+                fee1.cmp(fee2).then_with(|| account2.cmp(account1))
+            })
+    }
+
+    fn remove_transaction(&mut self, account: &AccountId, nonce: Nonce) {
+        let mut remove_map = false;
+        if let Some(map) = self.transactions.get_mut(account) {
+            if map.remove(&nonce).is_some() {
+                self.num_transactions -= 1;
+            }
+            remove_map = map.is_empty();
+        }
+        if remove_map {
+            self.transactions.remove(account);
+        }
+    }
+
     /// Quick reconciliation step - evict all transactions that already in the block
     /// or became invalid after it.
     pub fn reconcile_block(&mut self, block: &Block) {
         for transaction in block.transactions.iter() {
             let account = transaction.body.get_originator();
             let nonce = transaction.body.get_nonce();
-            let mut remove_map = false;
-            if let Some(map) = self.transactions.get_mut(&account) {
-                map.remove(&nonce);
-                remove_map = map.is_empty();
-            }
-            if remove_map {
-                self.num_transactions -= 1;
-                self.transactions.remove(&account);
-            }
+            self.remove_transaction(&account, nonce);
+        }
+        // Transactions that can't be included into the next block have expired.
+        let next_index = block.header.height + 1;
+        let expired: Vec<(AccountId, Nonce)> = self
+            .transactions
+            .iter()
+            .flat_map(|(account, map)| {
+                map.iter()
+                    .filter(|(_, transaction)| transaction.valid_until < next_index)
+                    .map(move |(nonce, _)| (account.clone(), *nonce))
+            })
+            .collect();
+        for (account, nonce) in expired {
+            self.remove_transaction(&account, nonce);
         }
     }
 
     pub fn len(&self) -> usize {
         self.num_transactions
     }
+
+    /// Statistics of the pool to be reported in the node status.
+    pub fn info(&self) -> StatusTxPoolInfo {
+        StatusTxPoolInfo {
+            num_transactions: self.num_transactions,
+            num_accounts: self.transactions.len(),
+            capacity: self.config.max_transactions,
+            num_evicted: self.num_evicted,
+            num_rejected: self.num_rejected,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
     use rand::seq::SliceRandom;
     use rand::thread_rng;
 
-    use near_chain::ValidTransaction;
+    use near_chain::{Block, ValidTransaction};
     use near_primitives::crypto::signer::InMemorySigner;
-    use near_primitives::transaction::TransactionBody;
+    use near_primitives::transaction::{SignedTransaction, TransactionBody};
+    use near_primitives::types::{Balance, BlockIndex, Nonce};
 
-    use crate::TransactionPool;
-    use near_primitives::types::Balance;
+    use crate::{Error, PoolConfig, TransactionPool};
+
+    fn send_money(account_id: &str, nonce: Nonce, fee: Balance) -> ValidTransaction {
+        let signer = InMemorySigner::from_seed(account_id, account_id);
+        let transaction =
+            TransactionBody::send_money(nonce, account_id, "bob.near", 1).sign(&signer);
+        ValidTransaction { transaction, fee, valid_until: BlockIndex::max_value() }
+    }
+
+    fn accounts_and_nonces(transactions: Vec<SignedTransaction>) -> Vec<(String, Nonce)> {
+        transactions.iter().map(|tx| (tx.body.get_originator(), tx.body.get_nonce())).collect()
+    }
 
     /// Add transactions of nonce from 1..10 in random order. Check that mempool
     /// orders them correctly.
@@ -92,15 +235,192 @@ mod tests {
         let mut transactions: Vec<_> = (1..10)
             .map(|i| TransactionBody::send_money(i, "alice.near", "bob.near", i as Balance).sign(&signer))
             .collect();
-        let mut pool = TransactionPool::new();
+        let mut pool = TransactionPool::new(PoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
-            pool.insert_transaction(ValidTransaction { transaction: tx });
+            pool.insert_transaction(ValidTransaction {
+                transaction: tx,
+                fee: 0,
+                valid_until: BlockIndex::max_value(),
+            })
+            .unwrap();
         }
         let transactions = pool.prepare_transactions(10).unwrap();
         let nonces: Vec<u64> = transactions.iter().map(|tx| tx.body.get_nonce()).collect();
         assert_eq!(nonces, (1..10).collect::<Vec<u64>>())
     }
 
+    /// Accounts take turns, the account with the higher fee goes first in each turn.
+    #[test]
+    fn test_round_robin_by_fee() {
+        let mut pool = TransactionPool::new(PoolConfig::default());
+        for nonce in 1..4 {
+            pool.insert_transaction(send_money("alice.near", nonce, 1)).unwrap();
+        }
+        for nonce in 1..3 {
+            pool.insert_transaction(send_money("carol.near", nonce, 2)).unwrap();
+        }
+        let transactions = pool.prepare_transactions(4).unwrap();
+        assert_eq!(
+            accounts_and_nonces(transactions),
+            vec![
+                ("carol.near".to_string(), 1),
+                ("alice.near".to_string(), 1),
+                ("carol.near".to_string(), 2),
+                ("alice.near".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pool_limit() {
+        let config = PoolConfig { max_transactions: 2, max_transactions_per_account: 2 };
+        let mut pool = TransactionPool::new(config);
+        pool.insert_transaction(send_money("alice.near", 1, 1)).unwrap();
+        pool.insert_transaction(send_money("alice.near", 2, 1)).unwrap();
+        match pool.insert_transaction(send_money("carol.near", 1, 1)) {
+            Err(Error::PoolFull) => {}
+            _ => panic!("Transaction with the same fee must be rejected"),
+        }
+        // The last transaction of alice is evicted.
+        pool.insert_transaction(send_money("carol.near", 1, 2)).unwrap();
+        assert_eq!(pool.len(), 2);
+        let transactions = pool.prepare_transactions(10).unwrap();
+        assert_eq!(
+            accounts_and_nonces(transactions),
+            vec![("carol.near".to_string(), 1), ("alice.near".to_string(), 1)]
+        );
+        let info = pool.info();
+        assert_eq!((info.num_accounts, info.num_evicted, info.num_rejected), (2, 1, 1));
+    }
+
+    #[test]
+    fn test_pool_limit_same_account() {
+        let config = PoolConfig { max_transactions: 2, max_transactions_per_account: 3 };
+        let mut pool = TransactionPool::new(config);
+        pool.insert_transaction(send_money("alice.near", 1, 1)).unwrap();
+        pool.insert_transaction(send_money("alice.near", 2, 1)).unwrap();
+        // Evicting the nonce 2 in favor of the nonce 3 would make the latter never executable.
+        match pool.insert_transaction(send_money("alice.near", 3, 2)) {
+            Err(Error::PoolFull) => {}
+            _ => panic!("Transaction after the evicted nonce of its account must be rejected"),
+        }
+        let transactions = pool.prepare_transactions(10).unwrap();
+        assert_eq!(
+            accounts_and_nonces(transactions),
+            vec![("alice.near".to_string(), 1), ("alice.near".to_string(), 2)]
+        );
+        let info = pool.info();
+        assert_eq!((info.num_evicted, info.num_rejected), (0, 1));
+    }
+
+    #[test]
+    fn test_account_limit() {
+        let config = PoolConfig { max_transactions: 10, max_transactions_per_account: 2 };
+        let mut pool = TransactionPool::new(config);
+        pool.insert_transaction(send_money("alice.near", 2, 1)).unwrap();
+        pool.insert_transaction(send_money("alice.near", 3, 1)).unwrap();
+        match pool.insert_transaction(send_money("alice.near", 4, 1)) {
+            Err(Error::AccountLimit(_)) => {}
+            _ => panic!("Transaction over the account limit must be rejected"),
+        }
+        // Transaction with the lower nonce replaces the one with the highest nonce.
+        pool.insert_transaction(send_money("alice.near", 1, 1)).unwrap();
+        let transactions = pool.prepare_transactions(10).unwrap();
+        let nonces: Vec<_> = transactions.iter().map(|tx| tx.body.get_nonce()).collect();
+        assert_eq!(nonces, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_reconcile_block() {
+        let mut pool = TransactionPool::new(PoolConfig::default());
+        for nonce in 1..4 {
+            pool.insert_transaction(send_money("alice.near", nonce, 1)).unwrap();
+        }
+        pool.insert_transaction(send_money("carol.near", 1, 1)).unwrap();
+        let signer = Arc::new(InMemorySigner::from_seed("test", "test"));
+        let genesis = Block::genesis(Default::default(), chrono::Utc::now());
+        let transactions = vec![
+            send_money("alice.near", 1, 1).transaction,
+            send_money("alice.near", 2, 1).transaction,
+        ];
+        let block = Block::produce(
+            &genesis.header,
+            1,
+            Default::default(),
+            transactions,
+            HashMap::default(),
+            vec![],
+            signer,
+        );
+        pool.reconcile_block(&block);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.info().num_accounts, 2);
+    }
+
+    /// Transactions of the abandoned fork return to the pool and are counted again.
+    #[test]
+    fn test_reconcile_block_reorg() {
+        let mut pool = TransactionPool::new(PoolConfig::default());
+        for nonce in 1..4 {
+            pool.insert_transaction(send_money("alice.near", nonce, 1)).unwrap();
+        }
+        let signer = Arc::new(InMemorySigner::from_seed("test", "test"));
+        let genesis = Block::genesis(Default::default(), chrono::Utc::now());
+        let produce = |transactions| {
+            Block::produce(
+                &genesis.header,
+                1,
+                Default::default(),
+                transactions,
+                HashMap::default(),
+                vec![],
+                signer.clone(),
+            )
+        };
+        let fork_transactions = vec![
+            send_money("alice.near", 1, 1).transaction,
+            send_money("alice.near", 2, 1).transaction,
+        ];
+        pool.reconcile_block(&produce(fork_transactions));
+        assert_eq!(pool.len(), 1);
+
+        for nonce in 1..3 {
+            pool.insert_transaction(send_money("alice.near", nonce, 1)).unwrap();
+        }
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.info().num_accounts, 1);
+
+        pool.reconcile_block(&produce(vec![send_money("alice.near", 1, 1).transaction]));
+        assert_eq!(pool.len(), 2);
+        let transactions = pool.prepare_transactions(10).unwrap();
+        let nonces: Vec<_> = transactions.iter().map(|tx| tx.body.get_nonce()).collect();
+        assert_eq!(nonces, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_reconcile_block_expired() {
+        let mut pool = TransactionPool::new(PoolConfig::default());
+        for nonce in 1..4 {
+            let mut transaction = send_money("alice.near", nonce, 1);
+            transaction.valid_until = nonce;
+            pool.insert_transaction(transaction).unwrap();
+        }
+        let signer = Arc::new(InMemorySigner::from_seed("test", "test"));
+        let genesis = Block::genesis(Default::default(), chrono::Utc::now());
+        let block = Block::produce(
+            &genesis.header,
+            1,
+            Default::default(),
+            vec![],
+            HashMap::default(),
+            vec![],
+            signer,
+        );
+        pool.reconcile_block(&block);
+        let transactions = pool.prepare_transactions(10).unwrap();
+        let nonces: Vec<_> = transactions.iter().map(|tx| tx.body.get_nonce()).collect();
+        assert_eq!(nonces, vec![2, 3]);
+    }
 }
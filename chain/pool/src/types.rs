@@ -1,6 +1,32 @@
 use failure::Fail;
+use serde_derive::{Deserialize, Serialize};
 
 use near_chain::ValidTransaction;
+use near_primitives::types::AccountId;
+
+/// Default maximum number of transactions in the pool.
+pub const DEFAULT_MAX_TRANSACTIONS: usize = 10_000;
+
+/// Default maximum number of transactions from a single account in the pool.
+pub const DEFAULT_MAX_TRANSACTIONS_PER_ACCOUNT: usize = 100;
+
+/// Limits of the transaction pool.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PoolConfig {
+    /// Maximum number of transactions in the pool.
+    pub max_transactions: usize,
+    /// Maximum number of transactions from a single account in the pool.
+    pub max_transactions_per_account: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_transactions: DEFAULT_MAX_TRANSACTIONS,
+            max_transactions_per_account: DEFAULT_MAX_TRANSACTIONS_PER_ACCOUNT,
+        }
+    }
+}
 
 /// Possible errors whe interacting with transaction pool.
 #[derive(Debug, Fail)]
@@ -8,6 +34,12 @@ pub enum Error {
     /// An invalid pool entry caused by underlying tx validation error
     #[fail(display = "Invalid Tx {}", _0)]
     InvalidTx(String),
+    /// The pool is full of transactions with higher priority.
+    #[fail(display = "Transaction pool is full")]
+    PoolFull,
+    /// The account has too many pending transactions with lower nonces.
+    #[fail(display = "Too many pending transactions from {}", _0)]
+    AccountLimit(AccountId),
     /// Other kinds of error (not yet pulled out into meaningful errors).
     #[fail(display = "General pool error {}", _0)]
     Other(String),
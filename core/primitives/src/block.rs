@@ -27,9 +27,9 @@ pub struct BlockHeader {
     /// Hash of the block previous to this in the chain.
     #[serde(with = "base_format")]
     pub prev_hash: CryptoHash,
-    /// Root hash of the state at the previous block.
-    #[serde(with = "base_format")]
-    pub prev_state_root: MerkleHash,
+    /// Root hashes of the states of the shards at the previous block, indexed by the shard id.
+    #[serde(with = "vec_base_format")]
+    pub prev_state_roots: Vec<MerkleHash>,
     /// Root hash of the transactions in the given block.
     #[serde(with = "base_format")]
     pub tx_root: MerkleHash,
@@ -57,7 +57,7 @@ impl BlockHeader {
     fn header_body(
         height: BlockIndex,
         prev_hash: CryptoHash,
-        prev_state_root: MerkleHash,
+        prev_state_roots: Vec<MerkleHash>,
         tx_root: MerkleHash,
         timestamp: DateTime<Utc>,
         approval_mask: Vec<bool>,
@@ -68,7 +68,9 @@ impl BlockHeader {
         chain_proto::BlockHeaderBody {
             height,
             prev_hash: prev_hash.into(),
-            prev_state_root: prev_state_root.into(),
+            prev_state_roots: RepeatedField::from_iter(
+                prev_state_roots.into_iter().map(std::convert::Into::into),
+            ),
             tx_root: tx_root.into(),
             timestamp: timestamp.timestamp_nanos() as u64,
             approval_mask,
@@ -86,7 +88,7 @@ impl BlockHeader {
     pub fn new(
         height: BlockIndex,
         prev_hash: CryptoHash,
-        prev_state_root: MerkleHash,
+        prev_state_roots: Vec<MerkleHash>,
         tx_root: MerkleHash,
         timestamp: DateTime<Utc>,
         approval_mask: Vec<bool>,
@@ -98,7 +100,7 @@ impl BlockHeader {
         let hb = Self::header_body(
             height,
             prev_hash,
-            prev_state_root,
+            prev_state_roots,
             tx_root,
             timestamp,
             approval_mask,
@@ -116,12 +118,12 @@ impl BlockHeader {
         h.try_into().expect("Failed to parse just created header")
     }
 
-    pub fn genesis(state_root: MerkleHash, timestamp: DateTime<Utc>) -> Self {
+    pub fn genesis(state_roots: Vec<MerkleHash>, timestamp: DateTime<Utc>) -> Self {
         chain_proto::BlockHeader {
             body: SingularPtrField::some(Self::header_body(
                 0,
                 CryptoHash::default(),
-                state_root,
+                state_roots,
                 MerkleHash::default(),
                 timestamp,
                 vec![],
@@ -155,7 +157,11 @@ impl TryFrom<chain_proto::BlockHeader> for BlockHeader {
         let hash = hash(&bytes);
         let height = body.height;
         let prev_hash = body.prev_hash.try_into()?;
-        let prev_state_root = body.prev_state_root.try_into()?;
+        let prev_state_roots = body
+            .prev_state_roots
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()?;
         let tx_root = body.tx_root.try_into()?;
         let timestamp = DateTime::from_utc(
             NaiveDateTime::from_timestamp(
@@ -177,7 +183,7 @@ impl TryFrom<chain_proto::BlockHeader> for BlockHeader {
         Ok(BlockHeader {
             height,
             prev_hash,
-            prev_state_root,
+            prev_state_roots,
             tx_root,
             timestamp,
             approval_mask,
@@ -196,7 +202,9 @@ impl From<BlockHeader> for chain_proto::BlockHeader {
             body: SingularPtrField::some(chain_proto::BlockHeaderBody {
                 height: header.height,
                 prev_hash: header.prev_hash.into(),
-                prev_state_root: header.prev_state_root.into(),
+                prev_state_roots: RepeatedField::from_iter(
+                    header.prev_state_roots.drain(..).map(std::convert::Into::into),
+                ),
                 tx_root: header.tx_root.into(),
                 timestamp: header.timestamp.timestamp_nanos() as u64,
                 approval_mask: header.approval_mask,
@@ -225,16 +233,17 @@ pub struct Block {
 }
 
 impl Block {
-    /// Returns genesis block for given genesis date and state root.
-    pub fn genesis(state_root: MerkleHash, timestamp: DateTime<Utc>) -> Self {
-        Block { header: BlockHeader::genesis(state_root, timestamp), transactions: vec![] }
+    /// Returns genesis block for given genesis date and state roots of the shards.
+    pub fn genesis(state_roots: Vec<MerkleHash>, timestamp: DateTime<Utc>) -> Self {
+        Block { header: BlockHeader::genesis(state_roots, timestamp), transactions: vec![] }
     }
 
-    /// Produces new block from header of previous block, current state root and set of transactions.
+    /// Produces new block from header of previous block, current state roots of the shards and
+    /// set of transactions.
     pub fn produce(
         prev: &BlockHeader,
         height: BlockIndex,
-        state_root: MerkleHash,
+        state_roots: Vec<MerkleHash>,
         transactions: Vec<SignedTransaction>,
         mut approvals: HashMap<usize, Signature>,
         validator_proposal: Vec<ValidatorStake>,
@@ -255,7 +264,7 @@ impl Block {
             header: BlockHeader::new(
                 height,
                 prev.hash(),
-                state_root,
+                state_roots,
                 tx_root,
                 Utc::now(),
                 approval_mask,
@@ -277,7 +286,7 @@ impl Block {
         Block::produce(
             prev,
             prev.height + 1,
-            prev.prev_state_root,
+            prev.prev_state_roots.clone(),
             vec![],
             HashMap::default(),
             vec![],
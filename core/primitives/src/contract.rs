@@ -20,3 +20,46 @@ impl ContractCode {
         &self.code
     }
 }
+
+/// Error that can occur while preparing or executing wasm smart-contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrepareError {
+    /// Error happened while serializing the module.
+    Serialization,
+
+    /// Error happened while deserializing the module.
+    Deserialization,
+
+    /// Internal memory declaration has been found in the module.
+    InternalMemoryDeclared,
+
+    /// Gas instrumentation failed.
+    ///
+    /// This most likely indicates the module isn't valid.
+    GasInstrumentation,
+
+    /// Stack instrumentation failed.
+    ///
+    /// This  most likely indicates the module isn't valid.
+    StackHeightInstrumentation,
+
+    /// Error happened during invocation of the contract's entrypoint.
+    ///
+    /// Most likely because of trap.
+    Invoke,
+
+    /// Error happened during instantiation.
+    ///
+    /// This might indicate that `start` function trapped, or module isn't
+    /// instantiable and/or unlinkable.
+    Instantiate,
+
+    /// Memory error.
+    Memory,
+
+    /// Contract code is larger than permitted by the config.
+    CodeTooLarge,
+
+    /// Module uses floating point types or operations.
+    FloatingPoint,
+}
@@ -26,7 +26,10 @@ pub mod receipt;
 pub mod rpc;
 pub mod serialize;
 pub mod sharding;
+pub mod state_proof;
 pub mod test_utils;
+pub mod trace;
 pub mod transaction;
+pub mod trie_node;
 pub mod types;
 pub mod utils;
@@ -8,9 +8,32 @@ use crate::account::AccessKey;
 use crate::crypto::signature::PublicKey;
 use crate::hash::CryptoHash;
 use crate::serialize::{base_format, u128_dec_format, vec_base_format};
-use crate::types::{AccountId, Balance, BlockIndex, MerkleHash, Nonce, Version};
+use crate::state_proof::StateProof;
+use crate::trace::ExecutionTrace;
+use crate::transaction::{ContractEvent, LogEntry, ReceiptTransaction, TransactionResult};
+use crate::types::{AccountId, Balance, BlockId, BlockIndex, Gas, MerkleHash, Nonce, Version};
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+/// Reference to a block in the RPC parameters: best block (`null` in JSON), height or base
+/// encoded hash. Keeps the JSON form of `BlockId` unchanged for its other users.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(untagged)]
+pub enum BlockReference {
+    Best,
+    Number(BlockIndex),
+    Hash(#[serde(with = "base_format")] CryptoHash),
+}
+
+impl From<BlockReference> for BlockId {
+    fn from(block_reference: BlockReference) -> Self {
+        match block_reference {
+            BlockReference::Best => BlockId::Best,
+            BlockReference::Number(height) => BlockId::Number(height),
+            BlockReference::Hash(hash) => BlockId::Hash(hash),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct AccountViewCallResult {
     pub account_id: AccountId,
     pub nonce: Nonce,
@@ -41,6 +64,49 @@ pub struct QueryError {
     pub logs: Vec<String>,
 }
 
+/// Raw values read from the state at given block, with the proof against block's state root.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProofResult {
+    #[serde(with = "base_format")]
+    pub block_hash: CryptoHash,
+    #[serde(with = "base_format")]
+    pub state_root: MerkleHash,
+    pub proof: StateProof,
+}
+
+impl ProofResult {
+    /// Checks that the proof matches the state root.
+    pub fn verify(&self) -> bool {
+        self.proof.verify(&self.state_root)
+    }
+}
+
+/// Outcome of executing a transaction on top of the latest state, without committing it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimulateTxResult {
+    /// Result the transaction would have if it was included in the next block.
+    pub result: TransactionResult,
+    pub logs: Vec<LogEntry>,
+    /// Receipts the transaction would generate.
+    pub receipts: Vec<ReceiptTransaction>,
+    /// Balance charged for the transaction itself, see `TransactionsCosts`.
+    #[serde(with = "u128_dec_format")]
+    pub cost: Balance,
+    /// Gas burnt by the contract execution.
+    pub gas_used: Gas,
+    /// Traces of the contract calls made by the transaction and by the receipts it generates.
+    pub traces: Vec<ExecutionTrace>,
+}
+
+/// Events with the same account and topic emitted in the block of the main chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockEvents {
+    pub block_index: BlockIndex,
+    #[serde(with = "base_format")]
+    pub block_hash: CryptoHash,
+    pub events: Vec<ContractEvent>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum QueryResponse {
@@ -50,6 +116,7 @@ pub enum QueryResponse {
     Error(QueryError),
     AccessKey(Option<AccessKey>),
     AccessKeyList(Vec<(PublicKey, AccessKey)>),
+    Proof(ProofResult),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -57,12 +124,37 @@ pub struct StatusSyncInfo {
     #[serde(with = "base_format")]
     pub latest_block_hash: CryptoHash,
     pub latest_block_height: BlockIndex,
-    #[serde(with = "base_format")]
-    pub latest_state_root: MerkleHash,
+    /// State roots of the shards after the latest block, indexed by the shard id.
+    #[serde(with = "vec_base_format")]
+    pub latest_state_roots: Vec<MerkleHash>,
     pub latest_block_time: DateTime<Utc>,
     pub syncing: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusTxPoolInfo {
+    /// Number of pending transactions.
+    pub num_transactions: usize,
+    /// Number of accounts with pending transactions.
+    pub num_accounts: usize,
+    /// Maximum number of pending transactions.
+    pub capacity: usize,
+    /// Number of transactions evicted in favor of ones with higher priority.
+    pub num_evicted: u64,
+    /// Number of transactions rejected because the pool or the account limit was full.
+    pub num_rejected: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StatusContractCacheInfo {
+    /// Number of compiled contracts loaded from the on-disk cache.
+    pub hits: usize,
+    /// Number of contracts that were compiled because they were not in the on-disk cache.
+    pub misses: usize,
+    /// Share of the lookups that didn't require compilation.
+    pub hit_rate: f64,
+}
+
 // TODO: add more information to status.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StatusResponse {
@@ -76,6 +168,10 @@ pub struct StatusResponse {
     pub validators: Vec<AccountId>,
     /// Sync status of the node.
     pub sync_info: StatusSyncInfo,
+    /// Transaction pool of the node.
+    pub tx_pool_info: StatusTxPoolInfo,
+    /// Cache of the compiled contracts of the node.
+    pub contract_cache_info: StatusContractCacheInfo,
 }
 
 impl TryFrom<QueryResponse> for AccountViewCallResult {
@@ -110,3 +206,14 @@ impl TryFrom<QueryResponse> for Option<AccessKey> {
         }
     }
 }
+
+impl TryFrom<QueryResponse> for ProofResult {
+    type Error = String;
+
+    fn try_from(query_response: QueryResponse) -> Result<Self, Self::Error> {
+        match query_response {
+            QueryResponse::Proof(proof) => Ok(proof),
+            _ => Err("Invalid type of response".into()),
+        }
+    }
+}
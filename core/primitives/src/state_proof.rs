@@ -0,0 +1,232 @@
+//! Verification of state trie proofs, so clients don't need to trust the node they query.
+
+use std::collections::HashMap;
+
+use crate::hash::{hash, CryptoHash};
+use crate::trie_node::RawTrieNode;
+use crate::types::MerkleHash;
+
+/// Values read from the state with trie nodes visited while looking them up.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum StateProof {
+    /// Value stored under given key, or its absence.
+    Value { key: Vec<u8>, value: Option<Vec<u8>>, nodes: Vec<Vec<u8>> },
+    /// All values stored under the keys that start with given prefix, ordered by key. Nodes
+    /// cover the whole subtree of the prefix, so no value can be left out.
+    Prefix { prefix: Vec<u8>, values: Vec<(Vec<u8>, Vec<u8>)>, nodes: Vec<Vec<u8>> },
+}
+
+impl StateProof {
+    /// Checks this proof against given state root.
+    pub fn verify(&self, state_root: &MerkleHash) -> bool {
+        match self {
+            StateProof::Value { key, value, nodes } => {
+                let value = value.as_ref().map(|value| &value[..]);
+                verify_state_proof(state_root, key, value, nodes)
+            }
+            StateProof::Prefix { prefix, values, nodes } => {
+                verify_prefix_proof(state_root, prefix, values, nodes)
+            }
+        }
+    }
+}
+
+/// Splits bytes into nibbles.
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|byte| vec![byte >> 4, byte & 15]).collect()
+}
+
+/// Joins nibbles back into bytes, `None` if there is an odd number of them.
+fn from_nibbles(nibbles: &[u8]) -> Option<Vec<u8>> {
+    if nibbles.len() % 2 != 0 {
+        return None;
+    }
+    Some(nibbles.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
+
+/// Nibbles of a hex-prefix encoded partial key, as stored in leaf and extension nodes.
+fn encoded_to_nibbles(encoded: &[u8]) -> Option<Vec<u8>> {
+    let first = *encoded.first()?;
+    let offset = if first & 16 == 16 { 1 } else { 2 };
+    Some(to_nibbles(encoded).split_off(offset))
+}
+
+/// Nodes of the proof by their hashes, a node referenced by a missing hash fails the proof.
+fn nodes_by_hash(nodes: &[Vec<u8>]) -> HashMap<CryptoHash, &Vec<u8>> {
+    nodes.iter().map(|node| (hash(node), node)).collect()
+}
+
+fn proof_node(
+    nodes: &HashMap<CryptoHash, &Vec<u8>>,
+    node_hash: &CryptoHash,
+) -> Option<RawTrieNode> {
+    nodes.get(node_hash).and_then(|bytes| RawTrieNode::decode(bytes).ok())
+}
+
+/// Verifies that `nodes` prove `value` to be stored under `key` in the state with given root,
+/// or when `value` is `None`, that there is no value under `key`.
+pub fn verify_state_proof(
+    state_root: &MerkleHash,
+    key: &[u8],
+    value: Option<&[u8]>,
+    nodes: &[Vec<u8>],
+) -> bool {
+    let nodes = nodes_by_hash(nodes);
+    let key = to_nibbles(key);
+    let mut key = &key[..];
+    let mut node_hash = *state_root;
+    loop {
+        // Empty root corresponds to the empty state.
+        if node_hash == CryptoHash::default() {
+            return value.is_none();
+        }
+        let node = match proof_node(&nodes, &node_hash) {
+            Some(node) => node,
+            None => return false,
+        };
+        match node {
+            RawTrieNode::Leaf(existing_key, existing_value) => {
+                let existing_key = match encoded_to_nibbles(&existing_key) {
+                    Some(existing_key) => existing_key,
+                    None => return false,
+                };
+                return if existing_key[..] == *key {
+                    value == Some(&existing_value[..])
+                } else {
+                    value.is_none()
+                };
+            }
+            RawTrieNode::Extension(existing_key, child) => {
+                let existing_key = match encoded_to_nibbles(&existing_key) {
+                    Some(existing_key) => existing_key,
+                    None => return false,
+                };
+                if key.starts_with(&existing_key) {
+                    key = &key[existing_key.len()..];
+                    node_hash = child;
+                } else {
+                    return value.is_none();
+                }
+            }
+            RawTrieNode::Branch(children, existing_value) => {
+                if key.is_empty() {
+                    return value == existing_value.as_ref().map(|value| &value[..]);
+                }
+                match children[key[0] as usize] {
+                    Some(child) => {
+                        key = &key[1..];
+                        node_hash = child;
+                    }
+                    None => return value.is_none(),
+                }
+            }
+        }
+    }
+}
+
+/// Collects the values under the keys that start with `path` + `prefix` from the subtree of the
+/// node with given hash, where `path` holds the nibbles of the key leading to the node. Returns
+/// `false` if any node of the subtree is missing from the proof.
+fn collect_prefix_values(
+    nodes: &HashMap<CryptoHash, &Vec<u8>>,
+    node_hash: &CryptoHash,
+    path: &mut Vec<u8>,
+    prefix: &[u8],
+    values: &mut Vec<(Vec<u8>, Vec<u8>)>,
+) -> bool {
+    if *node_hash == CryptoHash::default() {
+        return true;
+    }
+    let node = match proof_node(nodes, node_hash) {
+        Some(node) => node,
+        None => return false,
+    };
+    let path_len = path.len();
+    let result = match node {
+        RawTrieNode::Leaf(key, value) => match encoded_to_nibbles(&key) {
+            Some(ref key) if key.starts_with(prefix) => {
+                path.extend_from_slice(key);
+                match from_nibbles(path) {
+                    Some(key) => {
+                        values.push((key, value));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Some(_) => true,
+            None => false,
+        },
+        RawTrieNode::Extension(key, child) => match encoded_to_nibbles(&key) {
+            Some(key) => {
+                let common = std::cmp::min(key.len(), prefix.len());
+                if key[..common] != prefix[..common] {
+                    true
+                } else {
+                    path.extend_from_slice(&key);
+                    collect_prefix_values(nodes, &child, path, &prefix[common..], values)
+                }
+            }
+            None => false,
+        },
+        RawTrieNode::Branch(children, value) => {
+            if prefix.is_empty() {
+                let value_ok = match value {
+                    Some(value) => match from_nibbles(path) {
+                        Some(key) => {
+                            values.push((key, value));
+                            true
+                        }
+                        None => false,
+                    },
+                    None => true,
+                };
+                value_ok
+                    && children.iter().enumerate().all(|(index, child)| match child {
+                        Some(child) => {
+                            path.push(index as u8);
+                            let result = collect_prefix_values(nodes, child, path, &[], values);
+                            path.pop();
+                            result
+                        }
+                        None => true,
+                    })
+            } else {
+                match children[prefix[0] as usize] {
+                    Some(child) => {
+                        path.push(prefix[0]);
+                        collect_prefix_values(nodes, &child, path, &prefix[1..], values)
+                    }
+                    None => true,
+                }
+            }
+        }
+    };
+    path.truncate(path_len);
+    result
+}
+
+/// Verifies that `values` are exactly the values stored under the keys that start with `prefix`
+/// in the state with given root.
+pub fn verify_prefix_proof(
+    state_root: &MerkleHash,
+    prefix: &[u8],
+    values: &[(Vec<u8>, Vec<u8>)],
+    nodes: &[Vec<u8>],
+) -> bool {
+    let nodes = nodes_by_hash(nodes);
+    let mut proven_values = vec![];
+    if !collect_prefix_values(
+        &nodes,
+        state_root,
+        &mut vec![],
+        &to_nibbles(prefix),
+        &mut proven_values,
+    ) {
+        return false;
+    }
+    proven_values.sort();
+    let mut values = values.to_vec();
+    values.sort();
+    proven_values == values
+}
@@ -9,6 +9,7 @@ use rand_xorshift::XorShiftRng;
 use crate::crypto::aggregate_signature::{BlsPublicKey, BlsSecretKey};
 use crate::crypto::signature::{PublicKey, SecretKey};
 use crate::crypto::signer::{EDSigner, InMemorySigner};
+use crate::hash::CryptoHash;
 use crate::transaction::{SignedTransaction, TransactionBody};
 
 pub fn init_test_logger() {
@@ -71,8 +72,24 @@ impl InMemorySigner {
 }
 
 impl TransactionBody {
+    /// Signs transaction without a reference block, for the tests that don't run the chain.
     pub fn sign(self, signer: &dyn EDSigner) -> SignedTransaction {
-        let signature = signer.sign(self.get_hash().as_ref());
-        SignedTransaction::new(signature, self, Some(signer.public_key()))
+        self.sign_for_block(signer, CryptoHash::default(), "")
+    }
+
+    pub fn sign_for_block(
+        self,
+        signer: &dyn EDSigner,
+        block_hash: CryptoHash,
+        chain_id: &str,
+    ) -> SignedTransaction {
+        let signature = signer.sign(self.get_signed_hash(&block_hash, chain_id).as_ref());
+        SignedTransaction::new(
+            signature,
+            self,
+            Some(signer.public_key()),
+            block_hash,
+            chain_id.to_string(),
+        )
     }
 }
@@ -0,0 +1,79 @@
+//! Structured trace of the contract execution, recorded when the tracing is enabled.
+use crate::serialize::{base_bytes_format, u128_dec_format};
+use crate::types::{AccountId, Balance, Gas};
+
+/// Everything the contract did through the host functions during one execution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExecutionTrace {
+    /// Host function calls in the order they were made.
+    pub host_calls: Vec<HostFunctionCall>,
+    /// Gas charged by the injected gas meter for the executed wasm operations.
+    pub ops_gas: Gas,
+    /// Storage reads and writes in the order they were made.
+    pub storage: Vec<StorageAccess>,
+    /// Promises created by the contract.
+    pub promises: Vec<PromiseTrace>,
+}
+
+impl ExecutionTrace {
+    /// Total gas charged by the host functions.
+    pub fn host_gas(&self) -> Gas {
+        self.host_calls.iter().map(|call| call.gas).sum()
+    }
+}
+
+/// Single call of the host function.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HostFunctionCall {
+    pub name: String,
+    /// Raw arguments the function was called with, by parameter name.
+    pub args: Vec<(String, u64)>,
+    /// Gas charged by the call, also when the call failed.
+    pub gas: Gas,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum StorageAccess {
+    Read {
+        #[serde(with = "base_bytes_format")]
+        key: Vec<u8>,
+        /// Length of the value, `None` if the key is missing.
+        value_len: Option<u64>,
+    },
+    Write {
+        #[serde(with = "base_bytes_format")]
+        key: Vec<u8>,
+        value_len: u64,
+        /// Length of the overwritten value, `None` if the key is new.
+        evicted_len: Option<u64>,
+    },
+    Remove {
+        #[serde(with = "base_bytes_format")]
+        key: Vec<u8>,
+        /// Length of the removed value, `None` if the key was missing.
+        removed_len: Option<u64>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PromiseTrace {
+    /// Async call of another contract.
+    Create {
+        receiver_id: AccountId,
+        method_name: String,
+        args_len: u64,
+        #[serde(with = "u128_dec_format")]
+        amount: Balance,
+        prepaid_gas: Gas,
+    },
+    /// Callback attached to the promise with the given index.
+    Then {
+        promise_index: u32,
+        method_name: String,
+        args_len: u64,
+        #[serde(with = "u128_dec_format")]
+        amount: Balance,
+    },
+    /// Two promises joined together.
+    And { promise_index1: u32, promise_index2: u32 },
+}
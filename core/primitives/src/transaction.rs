@@ -12,11 +12,13 @@ use near_protos::signed_transaction as transaction_proto;
 use near_protos::Message as ProtoMessage;
 
 use crate::account::AccessKey;
+use crate::contract::PrepareError;
 use crate::crypto::signature::{verify, PublicKey, Signature, DEFAULT_SIGNATURE};
 use crate::hash::{hash, CryptoHash};
 use crate::logging;
-use crate::serialize::base_format;
-use crate::types::{AccountId, Balance, CallbackId, Nonce, ShardId, StructSignature};
+use crate::serialize::{base_bytes_format, base_format};
+use crate::trace::ExecutionTrace;
+use crate::types::{AccountId, Balance, CallbackId, Gas, Nonce, ShardId, StructSignature};
 use crate::utils::{account_to_shard_id, proto_to_result};
 
 pub type LogEntry = String;
@@ -31,6 +33,8 @@ pub enum TransactionBody {
     SwapKey(SwapKeyTransaction),
     AddKey(AddKeyTransaction),
     DeleteKey(DeleteKeyTransaction),
+    Batch(BatchTransaction),
+    DeleteAccount(DeleteAccountTransaction),
 }
 
 impl TransactionBody {
@@ -97,6 +101,9 @@ pub struct DeployContractTransaction {
     pub nonce: Nonce,
     pub contract_id: AccountId,
     pub wasm_byte_array: Vec<u8>,
+    /// Method of the new code called right after it is deployed. If the call fails, the code
+    /// and the state changes of the call are rolled back.
+    pub migration: Option<ContractMigration>,
 }
 
 impl fmt::Debug for DeployContractTransaction {
@@ -108,17 +115,24 @@ impl fmt::Debug for DeployContractTransaction {
                 "wasm_byte_array",
                 &format_args!("{}", logging::pretty_utf8(&self.wasm_byte_array)),
             )
+            .field("migration", &self.migration)
             .finish()
     }
 }
 
-impl From<transaction_proto::DeployContractTransaction> for DeployContractTransaction {
-    fn from(t: transaction_proto::DeployContractTransaction) -> Self {
-        DeployContractTransaction {
+impl TryFrom<transaction_proto::DeployContractTransaction> for DeployContractTransaction {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(t: transaction_proto::DeployContractTransaction) -> Result<Self, Self::Error> {
+        Ok(DeployContractTransaction {
             nonce: t.nonce,
             contract_id: t.contract_id,
             wasm_byte_array: t.wasm_byte_array,
-        }
+            migration: t
+                .migration
+                .into_option()
+                .map_or(Ok(None), |x| ContractMigration::try_from(x).map(Some))?,
+        })
     }
 }
 
@@ -128,11 +142,58 @@ impl From<DeployContractTransaction> for transaction_proto::DeployContractTransa
             nonce: t.nonce,
             contract_id: t.contract_id,
             wasm_byte_array: t.wasm_byte_array,
+            migration: SingularPtrField::from_option(t.migration.map(std::convert::Into::into)),
             ..Default::default()
         }
     }
 }
 
+#[derive(Hash, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct ContractMigration {
+    pub method_name: Vec<u8>,
+    pub args: Vec<u8>,
+    /// Deposit attached to the migration call, pays for its gas.
+    pub amount: Balance,
+    /// Maximum amount of gas the migration call is allowed to burn.
+    pub prepaid_gas: Gas,
+}
+
+impl TryFrom<transaction_proto::ContractMigration> for ContractMigration {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(t: transaction_proto::ContractMigration) -> Result<Self, Self::Error> {
+        Ok(ContractMigration {
+            method_name: t.method_name,
+            args: t.args,
+            amount: t.amount.unwrap_or_default().try_into()?,
+            prepaid_gas: t.prepaid_gas,
+        })
+    }
+}
+
+impl From<ContractMigration> for transaction_proto::ContractMigration {
+    fn from(t: ContractMigration) -> Self {
+        transaction_proto::ContractMigration {
+            method_name: t.method_name,
+            args: t.args,
+            amount: SingularPtrField::some(t.amount.into()),
+            prepaid_gas: t.prepaid_gas,
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Debug for ContractMigration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ContractMigration")
+            .field("method_name", &format_args!("{}", logging::pretty_utf8(&self.method_name)))
+            .field("args", &format_args!("{}", logging::pretty_utf8(&self.args)))
+            .field("amount", &format_args!("{}", &self.amount))
+            .field("prepaid_gas", &format_args!("{}", &self.prepaid_gas))
+            .finish()
+    }
+}
+
 #[derive(Hash, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct FunctionCallTransaction {
     pub nonce: Nonce,
@@ -141,6 +202,8 @@ pub struct FunctionCallTransaction {
     pub method_name: Vec<u8>,
     pub args: Vec<u8>,
     pub amount: Balance,
+    /// Maximum amount of gas the contract call is allowed to burn.
+    pub prepaid_gas: Gas,
 }
 
 impl TryFrom<transaction_proto::FunctionCallTransaction> for FunctionCallTransaction {
@@ -154,6 +217,7 @@ impl TryFrom<transaction_proto::FunctionCallTransaction> for FunctionCallTransac
             method_name: t.method_name,
             args: t.args,
             amount: t.amount.unwrap_or_default().try_into()?,
+            prepaid_gas: t.prepaid_gas,
         })
     }
 }
@@ -167,6 +231,7 @@ impl From<FunctionCallTransaction> for transaction_proto::FunctionCallTransactio
             method_name: t.method_name,
             args: t.args,
             amount: SingularPtrField::some(t.amount.into()),
+            prepaid_gas: t.prepaid_gas,
             ..Default::default()
         }
     }
@@ -181,6 +246,7 @@ impl fmt::Debug for FunctionCallTransaction {
             .field("method_name", &format_args!("{}", logging::pretty_utf8(&self.method_name)))
             .field("args", &format_args!("{}", logging::pretty_utf8(&self.args)))
             .field("amount", &format_args!("{}", &self.amount))
+            .field("prepaid_gas", &format_args!("{}", &self.prepaid_gas))
             .finish()
     }
 }
@@ -300,6 +366,9 @@ pub struct AddKeyTransaction {
     pub originator: AccountId,
     pub new_key: Vec<u8>,
     pub access_key: Option<AccessKey>,
+    /// Adds the amount of `access_key` to the allowance of the existing access key `new_key`,
+    /// instead of creating a new key.
+    pub top_up: bool,
 }
 
 impl TryFrom<transaction_proto::AddKeyTransaction> for AddKeyTransaction {
@@ -314,6 +383,7 @@ impl TryFrom<transaction_proto::AddKeyTransaction> for AddKeyTransaction {
                 .access_key
                 .into_option()
                 .map_or(Ok(None), |x| AccessKey::try_from(x).map(Some))?,
+            top_up: t.top_up,
         })
     }
 }
@@ -325,6 +395,7 @@ impl From<AddKeyTransaction> for transaction_proto::AddKeyTransaction {
             originator: t.originator,
             new_key: t.new_key,
             access_key: SingularPtrField::from_option(t.access_key.map(std::convert::Into::into)),
+            top_up: t.top_up,
             ..Default::default()
         }
     }
@@ -336,6 +407,8 @@ impl fmt::Debug for AddKeyTransaction {
             .field("nonce", &format_args!("{}", &self.nonce))
             .field("originator", &format_args!("{}", &self.originator))
             .field("new_key", &format_args!("{}", logging::pretty_utf8(&self.new_key)))
+            .field("access_key", &self.access_key)
+            .field("top_up", &self.top_up)
             .finish()
     }
 }
@@ -374,6 +447,319 @@ impl fmt::Debug for DeleteKeyTransaction {
     }
 }
 
+#[derive(Hash, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct DeleteAccountTransaction {
+    pub nonce: Nonce,
+    pub originator: AccountId,
+    pub beneficiary_id: AccountId,
+}
+
+impl From<transaction_proto::DeleteAccountTransaction> for DeleteAccountTransaction {
+    fn from(t: transaction_proto::DeleteAccountTransaction) -> Self {
+        DeleteAccountTransaction {
+            nonce: t.nonce,
+            originator: t.originator,
+            beneficiary_id: t.beneficiary_id,
+        }
+    }
+}
+
+impl From<DeleteAccountTransaction> for transaction_proto::DeleteAccountTransaction {
+    fn from(t: DeleteAccountTransaction) -> transaction_proto::DeleteAccountTransaction {
+        transaction_proto::DeleteAccountTransaction {
+            nonce: t.nonce,
+            originator: t.originator,
+            beneficiary_id: t.beneficiary_id,
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Debug for DeleteAccountTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeleteAccountTransaction")
+            .field("nonce", &format_args!("{}", &self.nonce))
+            .field("originator", &format_args!("{}", &self.originator))
+            .field("beneficiary_id", &format_args!("{}", &self.beneficiary_id))
+            .finish()
+    }
+}
+
+/// Single operation of a batched transaction, applied to the receiver of the batch.
+#[derive(Hash, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum Action {
+    CreateAccount { amount: Balance, public_key: Vec<u8> },
+    DeployContract { code: Vec<u8> },
+    FunctionCall { method_name: Vec<u8>, args: Vec<u8>, amount: Balance, prepaid_gas: Gas },
+    Transfer { amount: Balance },
+    Stake { amount: Balance, public_key: String },
+    AddKey { new_key: Vec<u8>, access_key: Option<AccessKey> },
+    DeleteKey { cur_key: Vec<u8> },
+}
+
+impl Action {
+    /// Balance the originator attaches to the action for the receiver.
+    pub fn deposit(&self) -> Balance {
+        match self {
+            Action::CreateAccount { amount, .. }
+            | Action::FunctionCall { amount, .. }
+            | Action::Transfer { amount } => *amount,
+            Action::DeployContract { .. }
+            | Action::Stake { .. }
+            | Action::AddKey { .. }
+            | Action::DeleteKey { .. } => 0,
+        }
+    }
+
+    /// Whether the action changes the account itself, so it can only be applied by its owner.
+    pub fn is_owner_only(&self) -> bool {
+        match self {
+            Action::DeployContract { .. }
+            | Action::Stake { .. }
+            | Action::AddKey { .. }
+            | Action::DeleteKey { .. } => true,
+            Action::CreateAccount { .. }
+            | Action::FunctionCall { .. }
+            | Action::Transfer { .. } => false,
+        }
+    }
+}
+
+impl TryFrom<transaction_proto::Action> for Action {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(a: transaction_proto::Action) -> Result<Self, Self::Error> {
+        match a.action {
+            Some(transaction_proto::Action_oneof_action::create_account(a)) => {
+                Ok(Action::CreateAccount {
+                    amount: a.amount.unwrap_or_default().try_into()?,
+                    public_key: a.public_key,
+                })
+            }
+            Some(transaction_proto::Action_oneof_action::deploy_contract(a)) => {
+                Ok(Action::DeployContract { code: a.code })
+            }
+            Some(transaction_proto::Action_oneof_action::function_call(a)) => {
+                Ok(Action::FunctionCall {
+                    method_name: a.method_name,
+                    args: a.args,
+                    amount: a.amount.unwrap_or_default().try_into()?,
+                    prepaid_gas: a.prepaid_gas,
+                })
+            }
+            Some(transaction_proto::Action_oneof_action::transfer(a)) => {
+                Ok(Action::Transfer { amount: a.amount.unwrap_or_default().try_into()? })
+            }
+            Some(transaction_proto::Action_oneof_action::stake(a)) => Ok(Action::Stake {
+                amount: a.amount.unwrap_or_default().try_into()?,
+                public_key: a.public_key,
+            }),
+            Some(transaction_proto::Action_oneof_action::add_key(a)) => Ok(Action::AddKey {
+                new_key: a.new_key,
+                access_key: a
+                    .access_key
+                    .into_option()
+                    .map_or(Ok(None), |x| AccessKey::try_from(x).map(Some))?,
+            }),
+            Some(transaction_proto::Action_oneof_action::delete_key(a)) => {
+                Ok(Action::DeleteKey { cur_key: a.cur_key })
+            }
+            None => Err("No such action type".into()),
+        }
+    }
+}
+
+impl From<Action> for transaction_proto::Action {
+    fn from(action: Action) -> transaction_proto::Action {
+        let action = match action {
+            Action::CreateAccount { amount, public_key } => {
+                transaction_proto::Action_oneof_action::create_account(
+                    transaction_proto::CreateAccountAction {
+                        amount: SingularPtrField::some(amount.into()),
+                        public_key,
+                        ..Default::default()
+                    },
+                )
+            }
+            Action::DeployContract { code } => {
+                transaction_proto::Action_oneof_action::deploy_contract(
+                    transaction_proto::DeployContractAction { code, ..Default::default() },
+                )
+            }
+            Action::FunctionCall { method_name, args, amount, prepaid_gas } => {
+                transaction_proto::Action_oneof_action::function_call(
+                    transaction_proto::FunctionCallAction {
+                        method_name,
+                        args,
+                        amount: SingularPtrField::some(amount.into()),
+                        prepaid_gas,
+                        ..Default::default()
+                    },
+                )
+            }
+            Action::Transfer { amount } => transaction_proto::Action_oneof_action::transfer(
+                transaction_proto::TransferAction {
+                    amount: SingularPtrField::some(amount.into()),
+                    ..Default::default()
+                },
+            ),
+            Action::Stake { amount, public_key } => transaction_proto::Action_oneof_action::stake(
+                transaction_proto::StakeAction {
+                    amount: SingularPtrField::some(amount.into()),
+                    public_key,
+                    ..Default::default()
+                },
+            ),
+            Action::AddKey { new_key, access_key } => {
+                transaction_proto::Action_oneof_action::add_key(transaction_proto::AddKeyAction {
+                    new_key,
+                    access_key: SingularPtrField::from_option(
+                        access_key.map(std::convert::Into::into),
+                    ),
+                    ..Default::default()
+                })
+            }
+            Action::DeleteKey { cur_key } => transaction_proto::Action_oneof_action::delete_key(
+                transaction_proto::DeleteKeyAction { cur_key, ..Default::default() },
+            ),
+        };
+        transaction_proto::Action { action: Some(action), ..Default::default() }
+    }
+}
+
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::CreateAccount { amount, public_key } => f
+                .debug_struct("CreateAccount")
+                .field("amount", &format_args!("{}", amount))
+                .field("public_key", &format_args!("{}", logging::pretty_utf8(public_key)))
+                .finish(),
+            Action::DeployContract { code } => f
+                .debug_struct("DeployContract")
+                .field("code", &format_args!("{}", logging::pretty_utf8(code)))
+                .finish(),
+            Action::FunctionCall { method_name, args, amount, prepaid_gas } => f
+                .debug_struct("FunctionCall")
+                .field("method_name", &format_args!("{}", logging::pretty_utf8(method_name)))
+                .field("args", &format_args!("{}", logging::pretty_utf8(args)))
+                .field("amount", &format_args!("{}", amount))
+                .field("prepaid_gas", &format_args!("{}", prepaid_gas))
+                .finish(),
+            Action::Transfer { amount } => {
+                f.debug_struct("Transfer").field("amount", &format_args!("{}", amount)).finish()
+            }
+            Action::Stake { amount, public_key } => f
+                .debug_struct("Stake")
+                .field("amount", &format_args!("{}", amount))
+                .field("public_key", &format_args!("{}", public_key))
+                .finish(),
+            Action::AddKey { new_key, access_key } => f
+                .debug_struct("AddKey")
+                .field("new_key", &format_args!("{}", logging::pretty_utf8(new_key)))
+                .field("access_key", access_key)
+                .finish(),
+            Action::DeleteKey { cur_key } => f
+                .debug_struct("DeleteKey")
+                .field("cur_key", &format_args!("{}", logging::pretty_utf8(cur_key)))
+                .finish(),
+        }
+    }
+}
+
+/// Ordered list of actions for a single receiver. Actions are applied one after another and
+/// if any of them fails, none of them takes effect.
+#[derive(Hash, Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct BatchTransaction {
+    pub nonce: Nonce,
+    pub originator: AccountId,
+    pub receiver: AccountId,
+    pub actions: Vec<Action>,
+}
+
+impl BatchTransaction {
+    /// Body of a single transaction, that has the same effect as the given action of the batch.
+    pub fn action_body(&self, action: &Action) -> TransactionBody {
+        let nonce = self.nonce;
+        let originator = self.originator.clone();
+        let receiver = self.receiver.clone();
+        match action.clone() {
+            Action::CreateAccount { amount, public_key } => {
+                TransactionBody::CreateAccount(CreateAccountTransaction {
+                    nonce,
+                    originator,
+                    new_account_id: receiver,
+                    amount,
+                    public_key,
+                })
+            }
+            Action::DeployContract { code } => {
+                TransactionBody::DeployContract(DeployContractTransaction {
+                    nonce,
+                    contract_id: receiver,
+                    wasm_byte_array: code,
+                    migration: None,
+                })
+            }
+            Action::FunctionCall { method_name, args, amount, prepaid_gas } => {
+                TransactionBody::FunctionCall(FunctionCallTransaction {
+                    nonce,
+                    originator,
+                    contract_id: receiver,
+                    method_name,
+                    args,
+                    amount,
+                    prepaid_gas,
+                })
+            }
+            Action::Transfer { amount } => TransactionBody::SendMoney(SendMoneyTransaction {
+                nonce,
+                originator,
+                receiver,
+                amount,
+            }),
+            Action::Stake { amount, public_key } => {
+                TransactionBody::Stake(StakeTransaction { nonce, originator, amount, public_key })
+            }
+            Action::AddKey { new_key, access_key } => TransactionBody::AddKey(AddKeyTransaction {
+                nonce,
+                originator,
+                new_key,
+                access_key,
+                top_up: false,
+            }),
+            Action::DeleteKey { cur_key } => {
+                TransactionBody::DeleteKey(DeleteKeyTransaction { nonce, originator, cur_key })
+            }
+        }
+    }
+}
+
+impl TryFrom<transaction_proto::BatchTransaction> for BatchTransaction {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(t: transaction_proto::BatchTransaction) -> Result<Self, Self::Error> {
+        Ok(BatchTransaction {
+            nonce: t.nonce,
+            originator: t.originator,
+            receiver: t.receiver,
+            actions: t.actions.into_iter().map(Action::try_from).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<BatchTransaction> for transaction_proto::BatchTransaction {
+    fn from(t: BatchTransaction) -> transaction_proto::BatchTransaction {
+        transaction_proto::BatchTransaction {
+            nonce: t.nonce,
+            originator: t.originator,
+            receiver: t.receiver,
+            actions: RepeatedField::from_iter(t.actions.into_iter().map(std::convert::Into::into)),
+            ..Default::default()
+        }
+    }
+}
+
 impl TransactionBody {
     pub fn get_nonce(&self) -> u64 {
         match self {
@@ -385,6 +771,8 @@ impl TransactionBody {
             TransactionBody::SwapKey(t) => t.nonce,
             TransactionBody::AddKey(t) => t.nonce,
             TransactionBody::DeleteKey(t) => t.nonce,
+            TransactionBody::Batch(t) => t.nonce,
+            TransactionBody::DeleteAccount(t) => t.nonce,
         }
     }
 
@@ -398,6 +786,8 @@ impl TransactionBody {
             TransactionBody::SwapKey(t) => t.originator.clone(),
             TransactionBody::AddKey(t) => t.originator.clone(),
             TransactionBody::DeleteKey(t) => t.originator.clone(),
+            TransactionBody::Batch(t) => t.originator.clone(),
+            TransactionBody::DeleteAccount(t) => t.originator.clone(),
         }
     }
 
@@ -412,10 +802,21 @@ impl TransactionBody {
             TransactionBody::SwapKey(_) => None,
             TransactionBody::AddKey(_) => None,
             TransactionBody::DeleteKey(_) => None,
+            TransactionBody::Batch(t) => Some(t.receiver.clone()),
+            TransactionBody::DeleteAccount(t) => Some(t.beneficiary_id.clone()),
         }
     }
 
     pub fn get_hash(&self) -> CryptoHash {
+        hash(&self.to_bytes())
+    }
+
+    /// Hash of the body bound to the reference block and the chain, that is signed by originator.
+    pub fn get_signed_hash(&self, block_hash: &CryptoHash, chain_id: &str) -> CryptoHash {
+        signed_payload_hash(&self.to_bytes(), block_hash, chain_id)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
         let bytes = match self.clone() {
             TransactionBody::CreateAccount(t) => {
                 let proto: transaction_proto::CreateAccountTransaction = t.into();
@@ -449,18 +850,37 @@ impl TransactionBody {
                 let proto: transaction_proto::DeleteKeyTransaction = t.into();
                 proto.write_to_bytes()
             }
+            TransactionBody::Batch(t) => {
+                let proto: transaction_proto::BatchTransaction = t.into();
+                proto.write_to_bytes()
+            }
+            TransactionBody::DeleteAccount(t) => {
+                let proto: transaction_proto::DeleteAccountTransaction = t.into();
+                proto.write_to_bytes()
+            }
         };
-        let bytes = bytes.unwrap();
-        hash(&bytes)
+        bytes.unwrap()
     }
 }
 
+/// Hash of the transaction body bytes, followed by the reference block hash and the chain id.
+fn signed_payload_hash(body_bytes: &[u8], block_hash: &CryptoHash, chain_id: &str) -> CryptoHash {
+    let mut bytes = body_bytes.to_vec();
+    bytes.extend_from_slice(block_hash.as_ref());
+    bytes.extend_from_slice(chain_id.as_bytes());
+    hash(&bytes)
+}
+
 #[derive(Eq, Debug, Clone, Serialize, Deserialize)]
 pub struct SignedTransaction {
     pub body: TransactionBody,
     pub signature: StructSignature,
     // In case this TX uses AccessKey, it needs to provide the public_key
     pub public_key: Option<PublicKey>,
+    /// Hash of a recent block, transaction expires when this block becomes too old.
+    pub block_hash: CryptoHash,
+    /// Id of the chain transaction is signed for, protects from replaying it on other chains.
+    pub chain_id: String,
     hash: CryptoHash,
 }
 
@@ -469,9 +889,11 @@ impl SignedTransaction {
         signature: StructSignature,
         body: TransactionBody,
         public_key: Option<PublicKey>,
+        block_hash: CryptoHash,
+        chain_id: String,
     ) -> Self {
-        let hash = body.get_hash();
-        Self { signature, body, public_key, hash }
+        let hash = body.get_signed_hash(&block_hash, &chain_id);
+        Self { signature, body, public_key, block_hash, chain_id, hash }
     }
 
     pub fn get_hash(&self) -> CryptoHash {
@@ -490,6 +912,8 @@ impl SignedTransaction {
             signature: DEFAULT_SIGNATURE,
             body,
             public_key: None,
+            block_hash: CryptoHash::default(),
+            chain_id: String::new(),
             hash: CryptoHash::default(),
         }
     }
@@ -521,7 +945,7 @@ impl TryFrom<transaction_proto::SignedTransaction> for SignedTransaction {
             }
             Some(transaction_proto::SignedTransaction_oneof_body::deploy_contract(t)) => {
                 bytes = t.write_to_bytes();
-                TransactionBody::DeployContract(DeployContractTransaction::from(t))
+                TransactionBody::DeployContract(DeployContractTransaction::try_from(t)?)
             }
             Some(transaction_proto::SignedTransaction_oneof_body::function_call(t)) => {
                 bytes = t.write_to_bytes();
@@ -547,10 +971,19 @@ impl TryFrom<transaction_proto::SignedTransaction> for SignedTransaction {
                 bytes = t.write_to_bytes();
                 TransactionBody::DeleteKey(DeleteKeyTransaction::try_from(t)?)
             }
+            Some(transaction_proto::SignedTransaction_oneof_body::batch(t)) => {
+                bytes = t.write_to_bytes();
+                TransactionBody::Batch(BatchTransaction::try_from(t)?)
+            }
+            Some(transaction_proto::SignedTransaction_oneof_body::delete_account(t)) => {
+                bytes = t.write_to_bytes();
+                TransactionBody::DeleteAccount(DeleteAccountTransaction::from(t))
+            }
             None => return Err("No such transaction body type".into()),
         };
         let bytes = bytes.map_err(|e| format!("{}", e))?;
-        let hash = hash(&bytes);
+        let block_hash = CryptoHash::try_from(t.block_hash)?;
+        let hash = signed_payload_hash(&bytes, &block_hash, &t.chain_id);
         let public_key: Option<PublicKey> = t
             .public_key
             .into_option()
@@ -559,7 +992,14 @@ impl TryFrom<transaction_proto::SignedTransaction> for SignedTransaction {
             .map_err(|e| format!("{}", e))?;
         let signature: Signature =
             Signature::try_from(&t.signature as &[u8]).map_err(|e| format!("{}", e))?;
-        Ok(SignedTransaction { body, signature, public_key, hash })
+        Ok(SignedTransaction {
+            body,
+            signature,
+            public_key,
+            block_hash,
+            chain_id: t.chain_id,
+            hash,
+        })
     }
 }
 
@@ -590,6 +1030,12 @@ impl From<SignedTransaction> for transaction_proto::SignedTransaction {
             TransactionBody::DeleteKey(t) => {
                 transaction_proto::SignedTransaction_oneof_body::delete_key(t.into())
             }
+            TransactionBody::Batch(t) => {
+                transaction_proto::SignedTransaction_oneof_body::batch(t.into())
+            }
+            TransactionBody::DeleteAccount(t) => {
+                transaction_proto::SignedTransaction_oneof_body::delete_account(t.into())
+            }
         };
         transaction_proto::SignedTransaction {
             body: Some(body),
@@ -599,6 +1045,8 @@ impl From<SignedTransaction> for transaction_proto::SignedTransaction {
                 res.set_value((&v).into());
                 res
             })),
+            block_hash: tx.block_hash.as_ref().to_vec(),
+            chain_id: tx.chain_id,
             ..Default::default()
         }
     }
@@ -609,6 +1057,52 @@ pub enum ReceiptBody {
     NewCall(AsyncCall),
     Callback(CallbackResult),
     Refund(Balance),
+    Batch(BatchCall),
+}
+
+/// Actions of a batched transaction sent to another account, applied by the receiver together.
+#[derive(Hash, Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct BatchCall {
+    pub actions: Vec<Action>,
+    pub refund_account: AccountId,
+    /// Account ID of the account who signed the initial transaction.
+    pub originator_id: AccountId,
+    /// The public key used to sign the initial transaction.
+    pub public_key: PublicKey,
+}
+
+impl BatchCall {
+    /// Balance the originator attached to the actions.
+    pub fn deposit(&self) -> Balance {
+        self.actions.iter().map(Action::deposit).sum()
+    }
+}
+
+impl TryFrom<receipt_proto::BatchCall> for BatchCall {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(proto: receipt_proto::BatchCall) -> Result<Self, Self::Error> {
+        Ok(BatchCall {
+            actions: proto.actions.into_iter().map(Action::try_from).collect::<Result<_, _>>()?,
+            refund_account: proto.refund_account,
+            originator_id: proto.originator_id,
+            public_key: PublicKey::try_from(&proto.public_key as &[u8])?,
+        })
+    }
+}
+
+impl From<BatchCall> for receipt_proto::BatchCall {
+    fn from(call: BatchCall) -> Self {
+        receipt_proto::BatchCall {
+            actions: RepeatedField::from_iter(
+                call.actions.into_iter().map(std::convert::Into::into),
+            ),
+            refund_account: call.refund_account,
+            originator_id: call.originator_id,
+            public_key: call.public_key.as_ref().to_vec(),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Hash, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -622,6 +1116,8 @@ pub struct AsyncCall {
     pub originator_id: AccountId,
     /// The public key used to sign the initial transaction.
     pub public_key: PublicKey,
+    /// Maximum amount of gas the call is allowed to burn.
+    pub prepaid_gas: Gas,
 }
 
 impl TryFrom<receipt_proto::AsyncCall> for AsyncCall {
@@ -636,6 +1132,7 @@ impl TryFrom<receipt_proto::AsyncCall> for AsyncCall {
             refund_account: proto.refund_account,
             originator_id: proto.originator_id,
             public_key: PublicKey::try_from(&proto.public_key as &[u8])?,
+            prepaid_gas: proto.prepaid_gas,
         })
     }
 }
@@ -650,6 +1147,7 @@ impl From<AsyncCall> for receipt_proto::AsyncCall {
             refund_account: call.refund_account,
             originator_id: call.originator_id,
             public_key: call.public_key.as_ref().to_vec(),
+            prepaid_gas: call.prepaid_gas,
             ..Default::default()
         }
     }
@@ -663,6 +1161,7 @@ impl AsyncCall {
         refund_account: AccountId,
         originator_id: AccountId,
         public_key: PublicKey,
+        prepaid_gas: Gas,
     ) -> Self {
         AsyncCall {
             amount,
@@ -672,6 +1171,7 @@ impl AsyncCall {
             refund_account,
             originator_id,
             public_key,
+            prepaid_gas,
         }
     }
 }
@@ -686,6 +1186,7 @@ impl fmt::Debug for AsyncCall {
             .field("refund_account", &self.refund_account)
             .field("originator_id", &self.originator_id)
             .field("public_key", &self.public_key)
+            .field("prepaid_gas", &self.prepaid_gas)
             .finish()
     }
 }
@@ -908,6 +1409,9 @@ impl TryFrom<receipt_proto::ReceiptTransaction> for ReceiptTransaction {
             Some(receipt_proto::ReceiptTransaction_oneof_body::refund(refund)) => {
                 Ok(ReceiptBody::Refund(refund.try_into()?))
             }
+            Some(receipt_proto::ReceiptTransaction_oneof_body::batch(batch)) => {
+                batch.try_into().map(ReceiptBody::Batch)
+            }
             None => Err("No such receipt body type".into()),
         };
         match body {
@@ -934,6 +1438,9 @@ impl From<ReceiptTransaction> for receipt_proto::ReceiptTransaction {
             ReceiptBody::Refund(refund) => {
                 receipt_proto::ReceiptTransaction_oneof_body::refund(refund.into())
             }
+            ReceiptBody::Batch(batch) => {
+                receipt_proto::ReceiptTransaction_oneof_body::batch(batch.into())
+            }
         };
         receipt_proto::ReceiptTransaction {
             originator: t.originator,
@@ -961,8 +1468,9 @@ impl ReceiptTransaction {
         ReceiptTransaction { originator, receiver, nonce, body }
     }
 
-    pub fn shard_id(&self) -> ShardId {
-        account_to_shard_id(&self.receiver)
+    /// Shard of the receiver, where the receipt is executed.
+    pub fn shard_id(&self, num_shards: ShardId) -> ShardId {
+        account_to_shard_id(&self.receiver, num_shards)
     }
 
     pub fn get_hash(&self) -> CryptoHash {
@@ -1008,16 +1516,48 @@ impl Default for TransactionStatus {
     }
 }
 
+/// Typed event emitted by the contract, indexed by the node by the account and the topic.
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ContractEvent {
+    /// Account of the contract that emitted the event.
+    pub account_id: AccountId,
+    pub topic: String,
+    #[serde(with = "base_bytes_format")]
+    pub data: Vec<u8>,
+}
+
+impl fmt::Debug for ContractEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ContractEvent")
+            .field("account_id", &format_args!("{}", &self.account_id))
+            .field("topic", &format_args!("{}", &self.topic))
+            .field("data", &format_args!("{}", logging::pretty_utf8(&self.data)))
+            .finish()
+    }
+}
+
 #[derive(PartialEq, Clone, Serialize, Deserialize, Default)]
 pub struct TransactionResult {
     /// Transaction status.
     pub status: TransactionStatus,
     /// Logs from this transaction.
     pub logs: Vec<LogEntry>,
+    /// Events emitted by the contracts executed by this transaction.
+    pub events: Vec<ContractEvent>,
     /// Receipt ids generated by this transaction.
     pub receipts: Vec<CryptoHash>,
     /// Execution Result
     pub result: Option<Vec<u8>>,
+    /// Gas burnt by the contract execution.
+    pub gas_used: Gas,
+    /// Hash of the code the deployed contract replaced.
+    pub previous_code_hash: Option<CryptoHash>,
+    /// Why the code of the deployed contract was rejected.
+    pub prepare_error: Option<PrepareError>,
+    /// Traces of the contract executions in the order of the calls, only recorded by the runtime
+    /// with tracing enabled. Never stored or sent along with the result.
+    #[serde(skip)]
+    pub traces: Vec<ExecutionTrace>,
 }
 
 impl fmt::Debug for TransactionResult {
@@ -1025,8 +1565,12 @@ impl fmt::Debug for TransactionResult {
         f.debug_struct("TransactionResult")
             .field("status", &self.status)
             .field("logs", &format_args!("{}", logging::pretty_vec(&self.logs)))
+            .field("events", &format_args!("{}", logging::pretty_vec(&self.events)))
             .field("receipts", &format_args!("{}", logging::pretty_vec(&self.receipts)))
             .field("result", &format_args!("{}", logging::pretty_result(&self.result)))
+            .field("gas_used", &self.gas_used)
+            .field("previous_code_hash", &self.previous_code_hash)
+            .field("prepare_error", &self.prepare_error)
             .finish()
     }
 }
@@ -1116,6 +1660,7 @@ pub fn verify_transaction_signature(
 #[cfg(test)]
 mod tests {
     use crate::crypto::signature::{get_key_pair, sign};
+    use crate::crypto::signer::InMemorySigner;
 
     use super::*;
 
@@ -1131,4 +1676,20 @@ mod tests {
         let invalid_keys = vec![wrong_public_key];
         assert!(!verify_transaction_signature(&transaction, &invalid_keys));
     }
+    #[test]
+    fn test_transaction_bound_to_chain() {
+        let signer = InMemorySigner::from_seed("alice.near", "alice.near");
+        let body = TransactionBody::send_money(1, "alice.near", "bob.near", 10);
+        let transaction = body.clone().sign_for_block(&signer, hash(b"block"), "test-chain");
+        assert!(verify_transaction_signature(&transaction, &[signer.public_key]));
+
+        let proto: transaction_proto::SignedTransaction = transaction.clone().into();
+        let decoded = SignedTransaction::try_from(proto).unwrap();
+        assert_eq!(decoded, transaction);
+        assert_eq!(decoded.block_hash, hash(b"block"));
+        assert_eq!(decoded.chain_id, "test-chain");
+
+        let other_chain = body.sign_for_block(&signer, hash(b"block"), "other-chain");
+        assert_ne!(other_chain.get_hash(), transaction.get_hash());
+    }
 }
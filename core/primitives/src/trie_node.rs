@@ -0,0 +1,129 @@
+//! Serialization of the state trie nodes. Shared by the trie in `near-store` and the verifier of
+//! the state proofs, so the proofs are checked against exactly the encoding that is hashed.
+use std::convert::TryFrom;
+use std::io::{Cursor, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::hash::CryptoHash;
+
+const LEAF_NODE: u8 = 0;
+const BRANCH_NODE_NO_VALUE: u8 = 1;
+const BRANCH_NODE_WITH_VALUE: u8 = 2;
+const EXTENSION_NODE: u8 = 3;
+
+/// Trie node as it is stored, with the children referenced by their hashes. Keys of the leaf and
+/// extension nodes are hex-prefix encoded.
+#[derive(Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum RawTrieNode {
+    Leaf(Vec<u8>, Vec<u8>),
+    Branch([Option<CryptoHash>; 16], Option<Vec<u8>>),
+    Extension(Vec<u8>, CryptoHash),
+}
+
+fn read_bytes(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>, std::io::Error> {
+    let length = cursor.read_u32::<LittleEndian>()?;
+    let mut bytes = vec![0; length as usize];
+    cursor.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_hash(cursor: &mut Cursor<&[u8]>) -> Result<CryptoHash, std::io::Error> {
+    let mut bytes = [0; 32];
+    cursor.read_exact(&mut bytes)?;
+    CryptoHash::try_from(&bytes[..])
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))
+}
+
+fn decode_children(
+    cursor: &mut Cursor<&[u8]>,
+) -> Result<[Option<CryptoHash>; 16], std::io::Error> {
+    let mut children: [Option<CryptoHash>; 16] = Default::default();
+    let bitmap = cursor.read_u16::<LittleEndian>()?;
+    let mut pos = 1;
+    for child in &mut children {
+        if bitmap & pos != 0 {
+            *child = Some(read_hash(cursor)?);
+        }
+        pos <<= 1;
+    }
+    Ok(children)
+}
+
+impl RawTrieNode {
+    pub fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), std::io::Error> {
+        let mut cursor = Cursor::new(out);
+        match &self {
+            RawTrieNode::Leaf(key, value) => {
+                cursor.write_u8(LEAF_NODE)?;
+                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
+                cursor.write_all(&key)?;
+                cursor.write_u32::<LittleEndian>(value.len() as u32)?;
+                cursor.write_all(&value)?;
+            }
+            RawTrieNode::Branch(children, value) => {
+                if let Some(bytes) = value {
+                    cursor.write_u8(BRANCH_NODE_WITH_VALUE)?;
+                    cursor.write_u32::<LittleEndian>(bytes.len() as u32)?;
+                    cursor.write_all(&bytes)?;
+                } else {
+                    cursor.write_u8(BRANCH_NODE_NO_VALUE)?;
+                }
+                let mut bitmap: u16 = 0;
+                let mut pos: u16 = 1;
+                for child in children.iter() {
+                    if child.is_some() {
+                        bitmap |= pos
+                    }
+                    pos <<= 1;
+                }
+                cursor.write_u16::<LittleEndian>(bitmap)?;
+                for child in children.iter() {
+                    if let Some(hash) = child {
+                        cursor.write_all(hash.as_ref())?;
+                    }
+                }
+            }
+            RawTrieNode::Extension(key, child) => {
+                cursor.write_u8(EXTENSION_NODE)?;
+                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
+                cursor.write_all(&key)?;
+                cursor.write_all(child.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, std::io::Error> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
+        let mut cursor = Cursor::new(bytes);
+        match cursor.read_u8()? {
+            LEAF_NODE => {
+                let key = read_bytes(&mut cursor)?;
+                let value = read_bytes(&mut cursor)?;
+                Ok(RawTrieNode::Leaf(key, value))
+            }
+            BRANCH_NODE_NO_VALUE => {
+                let children = decode_children(&mut cursor)?;
+                Ok(RawTrieNode::Branch(children, None))
+            }
+            BRANCH_NODE_WITH_VALUE => {
+                let value = read_bytes(&mut cursor)?;
+                let children = decode_children(&mut cursor)?;
+                Ok(RawTrieNode::Branch(children, Some(value)))
+            }
+            EXTENSION_NODE => {
+                let key = read_bytes(&mut cursor)?;
+                let child = read_hash(&mut cursor)?;
+                Ok(RawTrieNode::Extension(key, child))
+            }
+            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type")),
+        }
+    }
+}
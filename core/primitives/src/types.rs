@@ -40,6 +40,8 @@ pub type BlockIndex = u64;
 pub type ShardId = u64;
 
 pub type Balance = u128;
+/// Gas is a type for storing amount of gas burnt by contract execution.
+pub type Gas = u64;
 
 pub type ReceiptId = Vec<u8>;
 pub type CallbackId = Vec<u8>;
@@ -112,3 +114,14 @@ pub struct Version {
     pub version: String,
     pub build: String,
 }
+
+/// Part of the state of a shard, used to download the state in chunks during state sync.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatePart {
+    pub part_id: u64,
+    pub num_parts: u64,
+    /// Compressed trie nodes of this part.
+    pub data: Vec<u8>,
+    /// Trie nodes on the path from the state root to the first node of this part.
+    pub proof: Vec<Vec<u8>>,
+}
@@ -1,7 +1,7 @@
 use std::convert::{AsRef, TryFrom, TryInto};
 use std::fmt;
 
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use protobuf::{well_known_types::StringValue, SingularPtrField};
 use regex::Regex;
 
@@ -63,10 +63,10 @@ pub fn index_to_bytes(index: u64) -> Vec<u8> {
     bytes
 }
 
-#[allow(unused)]
-pub fn account_to_shard_id(account_id: &AccountId) -> ShardId {
-    // TODO: change to real sharding
-    0
+/// Shard that holds the state of the account, given the number of shards from the genesis.
+/// Accounts are spread by the first 8 bytes of the hash of the account id.
+pub fn account_to_shard_id(account_id: &AccountId, num_shards: ShardId) -> ShardId {
+    LittleEndian::read_u64(&hash(account_id.as_bytes()).as_ref()[..8]) % num_shards
 }
 
 lazy_static! {
@@ -22,8 +22,13 @@ use near_protos::account as account_proto;
 use near_protos::receipt as receipt_proto;
 
 pub use crate::trie::{
-    update::TrieUpdate, update::TrieUpdateIterator, Trie, TrieChanges, TrieIterator,
-    WrappedTrieChanges,
+    state_parts::{
+        apply_state_part, check_state_complete, clear_state_parts, get_state_part,
+        MAX_STATE_PARTS, STATE_PART_SIZE,
+    },
+    update::TrieUpdate,
+    update::TrieUpdateIterator,
+    Trie, TrieChanges, TrieIterator, WrappedTrieChanges,
 };
 
 pub mod test_utils;
@@ -42,7 +47,11 @@ pub const COL_PROPOSALS: Option<u32> = Some(9);
 pub const COL_VALIDATORS: Option<u32> = Some(10);
 pub const COL_LAST_EPOCH_PROPOSALS: Option<u32> = Some(11);
 pub const COL_RETURN_STAKE_VALIDATORS: Option<u32> = Some(12);
-const NUM_COLS: u32 = 13;
+pub const COL_STATE_PARTS: Option<u32> = Some(13);
+pub const COL_TRIE_CHANGES: Option<u32> = Some(14);
+pub const COL_BLOCK_PER_HEIGHT: Option<u32> = Some(15);
+pub const COL_EVENTS: Option<u32> = Some(16);
+const NUM_COLS: u32 = 17;
 
 pub struct Store {
     storage: Arc<dyn KeyValueDB>,
@@ -86,6 +95,14 @@ impl Store {
     ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
         self.storage.iter(column)
     }
+
+    pub fn iter_prefix<'a>(
+        &'a self,
+        column: Option<u32>,
+        key_prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        self.storage.iter_from_prefix(column, key_prefix)
+    }
 }
 
 /// Keeps track of current changes to the database and can commit all of them to the database.
@@ -1,7 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
-use std::io::{Cursor, ErrorKind, Read, Write};
+use std::io::{Cursor, ErrorKind, Write};
 use std::sync::{Arc, Mutex};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
@@ -9,14 +9,18 @@ use cached::{Cached, SizedCache};
 pub use kvdb::DBValue;
 use kvdb::{DBOp, DBTransaction};
 use log::error;
+use serde_derive::{Deserialize, Serialize};
 
 use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::state_proof::StateProof;
+use near_primitives::trie_node::RawTrieNode;
 
 use crate::{Store, StoreUpdate, COL_STATE};
 
 use self::nibble_slice::NibbleSlice;
 
 mod nibble_slice;
+pub mod state_parts;
 pub mod update;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
@@ -126,14 +130,6 @@ impl TrieNode {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
-#[allow(clippy::large_enum_variant)]
-enum RawTrieNode {
-    Leaf(Vec<u8>, Vec<u8>),
-    Branch([Option<CryptoHash>; 16], Option<Vec<u8>>),
-    Extension(Vec<u8>, CryptoHash),
-}
-
 struct NodesStorage {
     nodes: Vec<Option<TrieNode>>,
     refcount_changes: HashMap<CryptoHash, (Vec<u8>, i32)>,
@@ -174,119 +170,12 @@ impl NodesStorage {
     }
 }
 
-const LEAF_NODE: u8 = 0;
-const BRANCH_NODE_NO_VALUE: u8 = 1;
-const BRANCH_NODE_WITH_VALUE: u8 = 2;
-const EXTENSION_NODE: u8 = 3;
-
 #[derive(Debug, Eq, PartialEq)]
 struct RcTrieNode {
     data: RawTrieNode,
     rc: u32,
 }
 
-fn decode_children(cursor: &mut Cursor<&[u8]>) -> Result<[Option<CryptoHash>; 16], std::io::Error> {
-    let mut children: [Option<CryptoHash>; 16] = Default::default();
-    let bitmap = cursor.read_u16::<LittleEndian>()?;
-    let mut pos = 1;
-    for child in &mut children {
-        if bitmap & pos != 0 {
-            let mut arr = [0; 32];
-            cursor.read_exact(&mut arr)?;
-            *child = Some(CryptoHash::try_from(&arr[..]).unwrap());
-        }
-        pos <<= 1;
-    }
-    Ok(children)
-}
-
-impl RawTrieNode {
-    fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), std::io::Error> {
-        let mut cursor = Cursor::new(out);
-        match &self {
-            RawTrieNode::Leaf(key, value) => {
-                cursor.write_u8(LEAF_NODE)?;
-                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
-                cursor.write_all(&key)?;
-                cursor.write_u32::<LittleEndian>(value.len() as u32)?;
-                cursor.write_all(&value)?;
-            }
-            RawTrieNode::Branch(children, value) => {
-                if let Some(bytes) = value {
-                    cursor.write_u8(BRANCH_NODE_WITH_VALUE)?;
-                    cursor.write_u32::<LittleEndian>(bytes.len() as u32)?;
-                    cursor.write_all(&bytes)?;
-                } else {
-                    cursor.write_u8(BRANCH_NODE_NO_VALUE)?;
-                }
-                let mut bitmap: u16 = 0;
-                let mut pos: u16 = 1;
-                for child in children.iter() {
-                    if child.is_some() {
-                        bitmap |= pos
-                    }
-                    pos <<= 1;
-                }
-                cursor.write_u16::<LittleEndian>(bitmap)?;
-                for child in children.iter() {
-                    if let Some(hash) = child {
-                        cursor.write_all(hash.as_ref())?;
-                    }
-                }
-            }
-            RawTrieNode::Extension(key, child) => {
-                cursor.write_u8(EXTENSION_NODE)?;
-                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
-                cursor.write_all(&key)?;
-                cursor.write_all(child.as_ref())?;
-            }
-        }
-        Ok(())
-    }
-
-    #[allow(dead_code)]
-    fn encode(&self) -> Result<Vec<u8>, std::io::Error> {
-        let mut out = Vec::new();
-        self.encode_into(&mut out)?;
-        Ok(out)
-    }
-
-    fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
-        let mut cursor = Cursor::new(bytes);
-        match cursor.read_u8()? {
-            LEAF_NODE => {
-                let key_length = cursor.read_u32::<LittleEndian>()?;
-                let mut key = vec![0; key_length as usize];
-                cursor.read_exact(&mut key)?;
-                let value_length = cursor.read_u32::<LittleEndian>()?;
-                let mut value = vec![0; value_length as usize];
-                cursor.read_exact(&mut value)?;
-                Ok(RawTrieNode::Leaf(key, value))
-            }
-            BRANCH_NODE_NO_VALUE => {
-                let children = decode_children(&mut cursor)?;
-                Ok(RawTrieNode::Branch(children, None))
-            }
-            BRANCH_NODE_WITH_VALUE => {
-                let value_length = cursor.read_u32::<LittleEndian>()?;
-                let mut value = vec![0; value_length as usize];
-                cursor.read_exact(&mut value)?;
-                let children = decode_children(&mut cursor)?;
-                Ok(RawTrieNode::Branch(children, Some(value)))
-            }
-            EXTENSION_NODE => {
-                let key_length = cursor.read_u32::<LittleEndian>()?;
-                let mut key = vec![0; key_length as usize];
-                cursor.read_exact(&mut key)?;
-                let mut child = vec![0; 32];
-                cursor.read_exact(&mut child)?;
-                Ok(RawTrieNode::Extension(key, CryptoHash::try_from(child).unwrap()))
-            }
-            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type")),
-        }
-    }
-}
-
 impl RcTrieNode {
     fn encode(data: &[u8], rc: u32) -> Result<Vec<u8>, std::io::Error> {
         let mut cursor = Cursor::new(Vec::with_capacity(data.len() + 4));
@@ -309,15 +198,23 @@ impl RcTrieNode {
     }
 }
 
+#[derive(Clone)]
 pub struct TrieCachingStorage {
     store: Arc<Store>,
     cache: Arc<Mutex<SizedCache<CryptoHash, Option<Vec<u8>>>>>,
+    /// Serialized sizes of the large subtrees, used to find the state parts without walking
+    /// the whole trie. Nodes are immutable, so the sizes never get stale.
+    subtree_sizes: Arc<Mutex<SizedCache<CryptoHash, u64>>>,
 }
 
 impl TrieCachingStorage {
     fn new(store: Arc<Store>) -> TrieCachingStorage {
         // TODO defend from huge values in cache
-        TrieCachingStorage { store, cache: Arc::new(Mutex::new(SizedCache::with_size(10000))) }
+        TrieCachingStorage {
+            store,
+            cache: Arc::new(Mutex::new(SizedCache::with_size(10000))),
+            subtree_sizes: Arc::new(Mutex::new(SizedCache::with_size(100_000))),
+        }
     }
 
     fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Option<(Vec<u8>)> {
@@ -366,6 +263,8 @@ impl TrieCachingStorage {
 
 pub struct Trie {
     storage: TrieCachingStorage,
+    /// If set, raw nodes visited during lookups are recorded here to serve as a proof.
+    recorded_nodes: Option<Mutex<Vec<Vec<u8>>>>,
 }
 
 ///
@@ -391,6 +290,7 @@ pub struct Trie {
 /// Having old_root and values in deletions allows to apply TrieChanges in reverse
 ///
 /// StoreUpdate are the changes from current state refcount to refcount + delta.
+#[derive(Serialize, Deserialize)]
 pub struct TrieChanges {
     #[allow(dead_code)]
     old_root: CryptoHash,
@@ -421,9 +321,26 @@ impl TrieChanges {
         &self,
         trie: Arc<Trie>,
         store_update: &mut StoreUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Self::decrement_refcounts(&self.deletions, trie, store_update)
+    }
+
+    /// Opposite of `insertions_into`, discards the new state (e.g. when its fork is abandoned).
+    pub fn revert_insertions_into(
+        &self,
+        trie: Arc<Trie>,
+        store_update: &mut StoreUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Self::decrement_refcounts(&self.insertions, trie, store_update)
+    }
+
+    fn decrement_refcounts(
+        changes: &[(CryptoHash, Vec<u8>, u32)],
+        trie: Arc<Trie>,
+        store_update: &mut StoreUpdate,
     ) -> Result<(), Box<dyn std::error::Error>> {
         store_update.trie = Some(trie.clone());
-        for (key, value, rc) in self.deletions.iter() {
+        for (key, value, rc) in changes.iter() {
             let storage_rc = trie.storage.retrieve_rc(&key).unwrap_or_default();
             assert!(*rc <= storage_rc);
             if *rc < storage_rc {
@@ -471,6 +388,10 @@ impl WrappedTrieChanges {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.trie_changes.deletions_into(self.trie.clone(), store_update)
     }
+
+    pub fn trie_changes(&self) -> &TrieChanges {
+        &self.trie_changes
+    }
 }
 
 enum FlattenNodesCrumb {
@@ -481,7 +402,56 @@ enum FlattenNodesCrumb {
 
 impl Trie {
     pub fn new(store: Arc<Store>) -> Self {
-        Trie { storage: TrieCachingStorage::new(store) }
+        Trie { storage: TrieCachingStorage::new(store), recorded_nodes: None }
+    }
+
+    /// Creates trie over the same storage, that records all nodes visited during lookups.
+    pub fn recording_reads(&self) -> Self {
+        Trie { storage: self.storage.clone(), recorded_nodes: Some(Mutex::new(vec![])) }
+    }
+
+    /// Returns nodes recorded so far, that prove values read from this trie.
+    pub fn recorded_nodes(&self) -> Vec<Vec<u8>> {
+        match &self.recorded_nodes {
+            Some(nodes) => nodes.lock().expect(POISONED_LOCK_ERR).clone(),
+            None => vec![],
+        }
+    }
+
+    /// Reads the value under the key together with the nodes proving it.
+    pub fn prove_value(&self, root: &CryptoHash, key: &[u8]) -> StateProof {
+        let trie = self.recording_reads();
+        let value = trie.get(root, key);
+        StateProof::Value { key: key.to_vec(), value, nodes: trie.recorded_nodes() }
+    }
+
+    /// Reads all values under the keys that start with the prefix, together with the nodes of
+    /// the whole subtree of the prefix, so the proof also shows that no value was left out.
+    pub fn prove_prefix(
+        &self,
+        root: &CryptoHash,
+        prefix: &[u8],
+    ) -> Result<StateProof, Box<dyn std::error::Error>> {
+        let trie = self.recording_reads();
+        // Every node of the subtree is on the path to some value, the lookup of the prefix
+        // itself covers the path to the subtree, also when the subtree is empty.
+        trie.get(root, prefix);
+        let mut values = vec![];
+        let mut iter = self.iter(root)?;
+        iter.seek(prefix)?;
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            let value =
+                trie.get(root, &key).ok_or_else(|| format!("Missing value of {:?}", key))?;
+            values.push((key, value));
+        }
+        let mut nodes = trie.recorded_nodes();
+        let mut seen = HashSet::new();
+        nodes.retain(|node| seen.insert(hash(node)));
+        Ok(StateProof::Prefix { prefix: prefix.to_vec(), values, nodes })
     }
 
     pub fn empty_root() -> CryptoHash {
@@ -530,6 +500,11 @@ impl Trie {
         self.storage.retrieve_node(hash)
     }
 
+    /// Checks whether the root node of the given state is present in the storage.
+    pub fn contains_root(&self, root: &CryptoHash) -> bool {
+        self.retrieve_node(root).is_ok()
+    }
+
     fn lookup(&self, root: &CryptoHash, mut key: NibbleSlice) -> Result<Option<Vec<u8>>, String> {
         let mut hash = *root;
 
@@ -538,9 +513,16 @@ impl Trie {
                 return Ok(None);
             }
             let node = match self.storage.retrieve_raw_bytes(&hash) {
-                Some(bytes) => RcTrieNode::decode(&bytes)
-                    .map(|trie_node| trie_node.0)
-                    .map_err(|_| "Failed to decode node".to_string())?,
+                Some(bytes) => {
+                    if let Some(nodes) = &self.recorded_nodes {
+                        let (data, _) = RcTrieNode::decode_raw(&bytes)
+                            .map_err(|_| "Failed to decode node".to_string())?;
+                        nodes.lock().expect(POISONED_LOCK_ERR).push(data.to_vec());
+                    }
+                    RcTrieNode::decode(&bytes)
+                        .map(|trie_node| trie_node.0)
+                        .map_err(|_| "Failed to decode node".to_string())?
+                }
                 _ => return Err(format!("Node {} not found in storage", hash)),
             };
 
@@ -1312,6 +1294,8 @@ mod tests {
     use rand::seq::SliceRandom;
     use rand::{rngs::ThreadRng, Rng};
 
+    use near_primitives::state_proof::{verify_prefix_proof, verify_state_proof};
+
     use crate::test_utils::{create_test_store, create_trie};
 
     use super::*;
@@ -1381,6 +1365,67 @@ mod tests {
         assert_eq!(trie.iter(&new_root).unwrap().fold(0, |acc, _| acc + 1), 0);
     }
 
+    #[test]
+    fn test_trie_recorded_proofs() {
+        let trie = create_trie();
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+            (b"do".to_vec(), Some(b"verb".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"h".to_vec(), Some(b"value".to_vec())),
+        ];
+        let root = test_populate_trie(trie.clone(), &Trie::empty_root(), changes.clone());
+        for (key, value) in changes.into_iter().chain(vec![(b"dot".to_vec(), None)]) {
+            let recording_trie = trie.recording_reads();
+            assert_eq!(recording_trie.get(&root, &key), value);
+            let nodes = recording_trie.recorded_nodes();
+            assert!(verify_state_proof(&root, &key, value.as_ref().map(|v| &v[..]), &nodes));
+            assert!(!verify_state_proof(&root, &key, Some(&b"other"[..]), &nodes));
+            let other_root = hash(b"other root");
+            assert!(!verify_state_proof(&other_root, &key, value.as_ref().map(|v| &v[..]), &nodes));
+        }
+    }
+
+    #[test]
+    fn test_trie_prefix_proofs() {
+        let trie = create_trie();
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+            (b"do".to_vec(), Some(b"verb".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"h".to_vec(), Some(b"value".to_vec())),
+        ];
+        let root = test_populate_trie(trie.clone(), &Trie::empty_root(), changes.clone());
+        let prefixes: Vec<&[u8]> = vec![b"", b"d", b"do", b"dog", b"doge", b"dogx", b"h", b"x"];
+        for prefix in prefixes {
+            let proof = trie.prove_prefix(&root, prefix).unwrap();
+            assert!(proof.verify(&root));
+            let (values, nodes) = match proof {
+                StateProof::Prefix { values, nodes, .. } => (values, nodes),
+                _ => panic!("Expected prefix proof"),
+            };
+            let mut expected: Vec<_> = changes
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, value)| (key.clone(), value.clone().unwrap()))
+                .collect();
+            expected.sort();
+            assert_eq!(values, expected);
+            assert!(!verify_prefix_proof(&hash(b"other root"), prefix, &values, &nodes));
+            let mut extra_values = values.clone();
+            extra_values.push((prefix.to_vec(), b"other".to_vec()));
+            assert!(!verify_prefix_proof(&root, prefix, &extra_values, &nodes));
+            assert!(!verify_prefix_proof(&root, prefix, &values, &nodes[1..]));
+            if !values.is_empty() {
+                assert!(!verify_prefix_proof(&root, prefix, &values[1..], &nodes));
+            }
+        }
+    }
+
     #[test]
     fn test_trie_iter() {
         let trie = create_trie();
@@ -1600,6 +1645,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_trie_gc() {
+        let store = create_test_store();
+        let trie = Arc::new(Trie::new(store.clone()));
+        let empty_root = Trie::empty_root();
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let root = test_populate_trie(trie.clone(), &empty_root, changes);
+
+        // Two forks from the same state, only insertions are applied to keep the old state.
+        let changes1 = vec![
+            (b"doge".to_vec(), Some(b"puppy".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+        ];
+        let trie_changes1 = trie.update(&root, changes1.into_iter()).unwrap();
+        let changes2 = vec![(b"horse".to_vec(), None)];
+        let trie_changes2 = trie.update(&root, changes2.into_iter()).unwrap();
+        for trie_changes in [&trie_changes1, &trie_changes2].iter() {
+            let mut store_update = store.store_update();
+            trie_changes.insertions_into(trie.clone(), &mut store_update).unwrap();
+            store_update.commit().unwrap();
+        }
+
+        // Abandon the second fork, then discard the state both forks were built on.
+        let mut store_update = store.store_update();
+        trie_changes2.revert_insertions_into(trie.clone(), &mut store_update).unwrap();
+        store_update.commit().unwrap();
+        let mut store_update = store.store_update();
+        trie_changes1.deletions_into(trie.clone(), &mut store_update).unwrap();
+        store_update.commit().unwrap();
+
+        // Only nodes of the remaining state are left.
+        let new_root = trie_changes1.new_root;
+        let expected_store = create_test_store();
+        let expected_trie = Arc::new(Trie::new(expected_store.clone()));
+        let expected_changes = vec![
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"doge".to_vec(), Some(b"puppy".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let expected_root = test_populate_trie(expected_trie, &empty_root, expected_changes);
+        assert_eq!(new_root, expected_root);
+        assert_eq!(trie.get(&new_root, b"horse"), Some(b"stallion".to_vec()));
+        assert_eq!(store.iter(COL_STATE).count(), expected_store.iter(COL_STATE).count());
+    }
+
     #[test]
     fn test_trie_restart() {
         let store = create_test_store();
@@ -0,0 +1,362 @@
+//! Splitting of the state into parts of bounded size, so it can be downloaded in chunks
+//! and from several peers during state sync.
+//!
+//! Trie nodes are ordered by depth-first traversal from the root, each part holds nodes that
+//! start within its `STATE_PART_SIZE` range of the serialized state. The range is found by
+//! descending from the root and skipping the subtrees by their sizes. To be able to check a part
+//! on its own, it comes with nodes on the path from the root to its first node.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cached::Cached;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::trie_node::RawTrieNode;
+use near_primitives::types::{MerkleHash, StatePart};
+
+use crate::trie::{RcTrieNode, Trie, POISONED_LOCK_ERR};
+use crate::{StoreUpdate, COL_STATE, COL_STATE_PARTS};
+
+/// Size of the serialized trie nodes in a single state part.
+pub const STATE_PART_SIZE: u64 = 1024 * 1024;
+
+/// Limit on the number of parts, so the peers can't make the node track an arbitrary number of
+/// parts. Bounds the size of the state to 64 GiB.
+pub const MAX_STATE_PARTS: u64 = 64 * 1024;
+
+/// Limit on the size of decompressed part, single node can be larger than `STATE_PART_SIZE`.
+const MAX_STATE_PART_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Subtrees smaller than this are measured by walking them instead of caching their sizes.
+const MIN_CACHED_SUBTREE_SIZE: u64 = STATE_PART_SIZE / 16;
+
+fn node_children(node: &RawTrieNode) -> Vec<CryptoHash> {
+    match node {
+        RawTrieNode::Leaf(_, _) => vec![],
+        RawTrieNode::Branch(children, _) => children.iter().filter_map(|child| *child).collect(),
+        RawTrieNode::Extension(_, child) => vec![*child],
+    }
+}
+
+fn retrieve_raw_node(trie: &Trie, hash: &CryptoHash) -> Result<Vec<u8>, String> {
+    let bytes = trie
+        .storage
+        .retrieve_raw_bytes(hash)
+        .ok_or_else(|| format!("Node {} not found in storage", hash))?;
+    let (data, _) = RcTrieNode::decode_raw(&bytes).map_err(|err| err.to_string())?;
+    Ok(data.to_vec())
+}
+
+fn encode_nodes(nodes: &[Vec<u8>]) -> Result<Vec<u8>, std::io::Error> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    for node in nodes {
+        encoder.write_u32::<LittleEndian>(node.len() as u32)?;
+        encoder.write_all(node)?;
+    }
+    encoder.finish()
+}
+
+fn decode_nodes(data: &[u8]) -> Result<Vec<Vec<u8>>, std::io::Error> {
+    let mut bytes = vec![];
+    DeflateDecoder::new(data).take(MAX_STATE_PART_DECOMPRESSED_SIZE).read_to_end(&mut bytes)?;
+    let mut cursor = Cursor::new(&bytes[..]);
+    let mut nodes = vec![];
+    while (cursor.position() as usize) < bytes.len() {
+        let length = cursor.read_u32::<LittleEndian>()?;
+        let mut node = vec![0; length as usize];
+        cursor.read_exact(&mut node)?;
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+/// Size of the serialized subtree of the node with given hash, as it is laid out in the parts.
+/// Sizes of the subtrees that are large enough are cached, smaller ones are cheap to walk.
+fn subtree_size(trie: &Trie, hash: &CryptoHash) -> Result<u64, String> {
+    let cached_size =
+        trie.storage.subtree_sizes.lock().expect(POISONED_LOCK_ERR).cache_get(hash).cloned();
+    if let Some(size) = cached_size {
+        return Ok(size);
+    }
+    let data = retrieve_raw_node(trie, hash)?;
+    let mut size = data.len() as u64 + 4;
+    for child in node_children(&RawTrieNode::decode(&data).map_err(|err| err.to_string())?) {
+        size += subtree_size(trie, &child)?;
+    }
+    if size >= MIN_CACHED_SUBTREE_SIZE {
+        trie.storage.subtree_sizes.lock().expect(POISONED_LOCK_ERR).cache_set(*hash, size);
+    }
+    Ok(size)
+}
+
+/// Nodes of a state part being collected, with the proof of the first of them.
+#[derive(Default)]
+struct PartNodes {
+    nodes: Vec<Vec<u8>>,
+    proof: Vec<Vec<u8>>,
+}
+
+/// Descends into the subtree of the node with given hash, which starts at `offset`, and adds
+/// the nodes that start within `start..end` to the part. Subtrees outside of the range are
+/// skipped by their size, `path` holds the nodes from the root to the current one.
+fn collect_part_nodes(
+    trie: &Trie,
+    hash: &CryptoHash,
+    mut offset: u64,
+    start: u64,
+    end: u64,
+    path: &mut Vec<Vec<u8>>,
+    part: &mut PartNodes,
+) -> Result<(), String> {
+    let data = retrieve_raw_node(trie, hash)?;
+    let children = node_children(&RawTrieNode::decode(&data).map_err(|err| err.to_string())?);
+    if offset >= start && offset < end {
+        if part.nodes.is_empty() {
+            part.proof = path.clone();
+        }
+        part.nodes.push(data.clone());
+    }
+    offset += data.len() as u64 + 4;
+    path.push(data);
+    for child in children {
+        if offset >= end {
+            break;
+        }
+        let size = subtree_size(trie, &child)?;
+        if offset + size > start {
+            collect_part_nodes(trie, &child, offset, start, end, path, part)?;
+        }
+        offset += size;
+    }
+    path.pop();
+    Ok(())
+}
+
+/// Returns part `part_id` of the state with given root.
+pub fn get_state_part(
+    trie: &Trie,
+    state_root: &MerkleHash,
+    part_id: u64,
+) -> Result<StatePart, Box<dyn std::error::Error>> {
+    let size = if *state_root == Trie::empty_root() { 0 } else { subtree_size(trie, state_root)? };
+    let num_parts = std::cmp::max(1, (size + STATE_PART_SIZE - 1) / STATE_PART_SIZE);
+    if num_parts > MAX_STATE_PARTS {
+        return Err(format!("State {} has too many parts: {}", state_root, num_parts).into());
+    }
+    if part_id >= num_parts {
+        return Err(format!("State part {} is out of {} parts", part_id, num_parts).into());
+    }
+    let mut part = PartNodes::default();
+    if *state_root != Trie::empty_root() {
+        let start = part_id * STATE_PART_SIZE;
+        let end = start + STATE_PART_SIZE;
+        collect_part_nodes(trie, state_root, 0, start, end, &mut vec![], &mut part)?;
+    }
+    Ok(StatePart { part_id, num_parts, data: encode_nodes(&part.nodes)?, proof: part.proof })
+}
+
+/// Checks that every node of the part is linked to the state root and counts references to them.
+fn verify_state_part(
+    state_root: &MerkleHash,
+    part_id: u64,
+    proof: &[Vec<u8>],
+    nodes: Vec<Vec<u8>>,
+) -> Result<HashMap<CryptoHash, (Vec<u8>, u32)>, String> {
+    let mut result: HashMap<CryptoHash, (Vec<u8>, u32)> = HashMap::new();
+    let first_hash = match nodes.first() {
+        Some(node) => hash(node),
+        None if proof.is_empty() => return Ok(result),
+        None => return Err("Empty state part with proof".to_string()),
+    };
+    if (part_id == 0) != proof.is_empty() {
+        return Err(format!("Wrong proof for state part {}", part_id));
+    }
+    // Hashes of nodes that may appear in this part, with number of references to them.
+    let mut allowed: HashMap<CryptoHash, u32> = HashMap::new();
+    let mut expected = *state_root;
+    for (i, node) in proof.iter().enumerate() {
+        if hash(node) != expected {
+            return Err(format!("Proof of state part {} doesn't match state root", part_id));
+        }
+        let next = proof.get(i + 1).map(|node| hash(node)).unwrap_or(first_hash);
+        let children = node_children(&RawTrieNode::decode(node).map_err(|err| err.to_string())?);
+        let position = children
+            .iter()
+            .position(|child| *child == next)
+            .ok_or_else(|| format!("Broken proof of state part {}", part_id))?;
+        // Earlier children belong to the previous parts.
+        for child in children[position + 1..].iter() {
+            *allowed.entry(*child).or_insert(0) += 1;
+        }
+        expected = next;
+    }
+    *allowed.entry(expected).or_insert(0) += 1;
+    for node in nodes {
+        let node_hash = hash(&node);
+        let count = allowed.entry(node_hash).or_insert(0);
+        if *count == 0 {
+            return Err(format!("Node {} is not a part of the state", node_hash));
+        }
+        *count -= 1;
+        let children = node_children(&RawTrieNode::decode(&node).map_err(|err| err.to_string())?);
+        for child in children {
+            *allowed.entry(child).or_insert(0) += 1;
+        }
+        result.entry(node_hash).or_insert_with(|| (node, 0)).1 += 1;
+    }
+    Ok(result)
+}
+
+fn state_part_key(state_root: &MerkleHash, part_id: u64) -> Vec<u8> {
+    let mut key = state_root.as_ref().to_vec();
+    key.extend_from_slice(&part_id.to_le_bytes());
+    key
+}
+
+/// Checks the part against the state root and returns update that stores its nodes.
+/// Parts that were already applied are skipped, so downloading a part again is harmless.
+pub fn apply_state_part(
+    trie: Arc<Trie>,
+    state_root: &MerkleHash,
+    part: &StatePart,
+) -> Result<StoreUpdate, Box<dyn std::error::Error>> {
+    if part.num_parts > MAX_STATE_PARTS || part.part_id >= part.num_parts {
+        return Err(format!("Wrong state part {}/{}", part.part_id, part.num_parts).into());
+    }
+    let mut store_update =
+        StoreUpdate::new_with_trie(trie.storage.store.storage.clone(), trie.clone());
+    let key = state_part_key(state_root, part.part_id);
+    if trie.storage.store.exists(COL_STATE_PARTS, &key)? {
+        return Ok(store_update);
+    }
+    let nodes = decode_nodes(&part.data)?;
+    for (hash, (data, rc)) in verify_state_part(state_root, part.part_id, &part.proof, nodes)? {
+        let storage_rc = trie.storage.retrieve_rc(&hash).unwrap_or_default();
+        store_update.set(COL_STATE, hash.as_ref(), &RcTrieNode::encode(&data, storage_rc + rc)?);
+    }
+    store_update.set(COL_STATE_PARTS, &key, &[]);
+    Ok(store_update)
+}
+
+/// Checks that all nodes of the state are present, i.e. all of its parts were applied.
+pub fn check_state_complete(
+    trie: &Trie,
+    state_root: &MerkleHash,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if *state_root == Trie::empty_root() {
+        return Ok(());
+    }
+    for item in trie.iter(state_root)? {
+        item?;
+    }
+    Ok(())
+}
+
+/// Returns update that forgets which parts of the state were applied.
+pub fn clear_state_parts(trie: &Trie, state_root: &MerkleHash) -> StoreUpdate {
+    let store = &trie.storage.store;
+    let mut store_update = store.store_update();
+    for (key, _) in store.iter(COL_STATE_PARTS) {
+        if key.starts_with(state_root.as_ref()) {
+            store_update.delete(COL_STATE_PARTS, &key);
+        }
+    }
+    store_update
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use crate::test_utils::create_trie;
+
+    use super::*;
+
+    fn create_state(trie: Arc<Trie>, num_keys: usize) -> MerkleHash {
+        let mut rng = rand::thread_rng();
+        let changes: Vec<_> = (0..num_keys)
+            .map(|_| {
+                let key: Vec<u8> = (0..rng.gen_range(1, 10)).map(|_| rng.gen()).collect();
+                let value: Vec<u8> = (0..rng.gen_range(1, 1000)).map(|_| rng.gen()).collect();
+                (key, Some(value))
+            })
+            .collect();
+        let (store_update, root) =
+            trie.update(&Trie::empty_root(), changes.into_iter()).unwrap().into(trie).unwrap();
+        store_update.commit().unwrap();
+        root
+    }
+
+    fn get_all_parts(trie: &Trie, state_root: &MerkleHash) -> Vec<StatePart> {
+        let first = get_state_part(trie, state_root, 0).unwrap();
+        let mut parts = vec![first.clone()];
+        for part_id in 1..first.num_parts {
+            let part = get_state_part(trie, state_root, part_id).unwrap();
+            assert_eq!(part.num_parts, first.num_parts);
+            parts.push(part);
+        }
+        parts
+    }
+
+    #[test]
+    fn test_state_parts_round_trip() {
+        let trie = create_trie();
+        let state_root = create_state(trie.clone(), 5000);
+        let parts = get_all_parts(&trie, &state_root);
+        assert!(parts.len() > 1);
+        assert!(get_state_part(&trie, &state_root, parts.len() as u64).is_err());
+
+        let new_trie = create_trie();
+        // Parts can be applied in any order and more than once.
+        for part in parts.iter().rev().chain(parts.iter()) {
+            assert!(check_state_complete(&new_trie, &state_root).is_err());
+            apply_state_part(new_trie.clone(), &state_root, part).unwrap().commit().unwrap();
+        }
+        check_state_complete(&new_trie, &state_root).unwrap();
+        let values: Vec<_> = trie.iter(&state_root).unwrap().map(Result::unwrap).collect();
+        let new_values: Vec<_> =
+            new_trie.iter(&state_root).unwrap().map(Result::unwrap).collect();
+        assert_eq!(values, new_values);
+
+        clear_state_parts(&new_trie, &state_root).commit().unwrap();
+        assert_eq!(new_trie.storage.store.iter(COL_STATE_PARTS).count(), 0);
+    }
+
+    #[test]
+    fn test_empty_state_part() {
+        let trie = create_trie();
+        let part = get_state_part(&trie, &Trie::empty_root(), 0).unwrap();
+        assert_eq!(part.num_parts, 1);
+        apply_state_part(create_trie(), &Trie::empty_root(), &part).unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_state_parts() {
+        let trie = create_trie();
+        let state_root = create_state(trie.clone(), 5000);
+        let other_root = create_state(trie.clone(), 100);
+        let parts = get_all_parts(&trie, &state_root);
+
+        // Part of a different state.
+        let other_part = get_state_part(&trie, &other_root, 0).unwrap();
+        assert!(apply_state_part(create_trie(), &state_root, &other_part).is_err());
+
+        // Proof that doesn't lead to the first node of the part.
+        let mut part = parts[1].clone();
+        part.proof.pop();
+        assert!(apply_state_part(create_trie(), &state_root, &part).is_err());
+
+        // Nodes with extra node that isn't linked to the state root.
+        let mut nodes = decode_nodes(&parts[1].data).unwrap();
+        nodes.push(decode_nodes(&other_part.data).unwrap().pop().unwrap());
+        let mut part = parts[1].clone();
+        part.data = encode_nodes(&nodes).unwrap();
+        assert!(apply_state_part(create_trie(), &state_root, &part).is_err());
+    }
+}
@@ -19,11 +19,16 @@ use near_jsonrpc::RpcConfig;
 use near_network::test_utils::open_port;
 use near_network::types::PROTOCOL_VERSION;
 use near_network::NetworkConfig;
+use near_pool::PoolConfig;
 use near_primitives::account::Account;
 use near_primitives::crypto::signer::{EDSigner, InMemorySigner, KeyFile};
 use near_primitives::hash::hash;
 use near_primitives::serialize::{to_base64, u128_dec_format};
-use near_primitives::types::{AccountId, Balance, BlockIndex, ReadablePublicKey, ValidatorId};
+use near_primitives::types::{
+    AccountId, Balance, BlockIndex, ReadablePublicKey, ShardId, ValidatorId,
+};
+use near_primitives::utils::account_to_shard_id;
+use node_runtime::economics_config::EconomicsConfig;
 use node_runtime::StateRecord;
 
 /// Initial balance used in tests.
@@ -61,6 +66,15 @@ pub const FAST_MIN_BLOCK_PRODUCTION_DELAY: u64 = 10;
 pub const FAST_MAX_BLOCK_PRODUCTION_DELAY: u64 = 100;
 pub const FAST_EPOCH_LENGTH: u64 = 60;
 
+/// Number of epochs of blocks and states a non-archival node keeps.
+pub const GC_NUM_EPOCHS_TO_KEEP: BlockIndex = 5;
+
+/// Period between garbage collections of old blocks and states, in seconds.
+pub const GC_PERIOD: u64 = 1;
+
+/// Number of blocks transaction stays valid after its reference block, about an hour.
+pub const TRANSACTION_VALIDITY_PERIOD: BlockIndex = 60 * 60 / MIN_BLOCK_PRODUCTION_DELAY;
+
 pub const CONFIG_FILENAME: &str = "config.json";
 pub const GENESIS_CONFIG_FILENAME: &str = "genesis.json";
 pub const NODE_KEY_FILE: &str = "node_key.json";
@@ -133,6 +147,26 @@ pub struct Config {
     pub rpc: RpcConfig,
     pub network: Network,
     pub consensus: Consensus,
+    /// Keep all the blocks and states instead of garbage collecting them.
+    #[serde(default)]
+    pub archive: bool,
+    /// Number of epochs of blocks and states to keep, when not archival.
+    #[serde(default = "default_gc_num_epochs_to_keep")]
+    pub gc_num_epochs_to_keep: BlockIndex,
+    /// How often to garbage collect old blocks and states.
+    #[serde(default = "default_gc_period")]
+    pub gc_period: Duration,
+    /// Limits of the transaction pool.
+    #[serde(default)]
+    pub pool: PoolConfig,
+}
+
+fn default_gc_num_epochs_to_keep() -> BlockIndex {
+    GC_NUM_EPOCHS_TO_KEEP
+}
+
+fn default_gc_period() -> Duration {
+    Duration::from_secs(GC_PERIOD)
 }
 
 impl Default for Config {
@@ -144,6 +178,10 @@ impl Default for Config {
             rpc: RpcConfig::default(),
             network: Network::default(),
             consensus: Consensus::default(),
+            archive: false,
+            gc_num_epochs_to_keep: GC_NUM_EPOCHS_TO_KEEP,
+            gc_period: default_gc_period(),
+            pool: PoolConfig::default(),
         }
     }
 }
@@ -210,6 +248,10 @@ impl NearConfig {
                 // TODO(1047): this should be adjusted depending on the speed of sync of state.
                 block_fetch_horizon: 50,
                 state_fetch_horizon: 5,
+                archive: config.archive,
+                gc_num_epochs_to_keep: config.gc_num_epochs_to_keep,
+                gc_period: config.gc_period,
+                pool_config: config.pool.clone(),
             },
             network_config: NetworkConfig {
                 public_key: network_key_pair.public_key,
@@ -299,16 +341,37 @@ pub struct GenesisConfig {
     pub epoch_length: BlockIndex,
     /// Criterion for kicking out validators
     pub validator_kickout_threshold: f64,
+    /// Number of blocks transaction stays valid after the block it references.
+    #[serde(default = "default_transaction_validity_period")]
+    pub transaction_validity_period: BlockIndex,
+    /// Costs of the transactions and of the contract execution.
+    #[serde(default)]
+    pub economics_config: EconomicsConfig,
     /// List of initial validators.
     pub validators: Vec<AccountInfo>,
     /// Records in storage per each shard at genesis.
     pub records: Vec<Vec<StateRecord>>,
 }
 
+fn default_transaction_validity_period() -> BlockIndex {
+    TRANSACTION_VALIDITY_PERIOD
+}
+
 impl GenesisConfig {
     pub fn legacy_test(seeds: Vec<&str>, num_validators: usize) -> Self {
+        Self::test_sharded(seeds, num_validators, vec![num_validators])
+    }
+
+    /// Test genesis with the given number of block producers per shard, where the records of
+    /// every account are put into the shard the account is mapped to.
+    pub fn test_sharded(
+        seeds: Vec<&str>,
+        num_validators: usize,
+        block_producers_per_shard: Vec<ValidatorId>,
+    ) -> Self {
+        let num_shards = block_producers_per_shard.len() as ShardId;
         let mut validators = vec![];
-        let mut records = vec![vec![]];
+        let mut records = vec![vec![]; num_shards as usize];
         let default_test_contract =
             include_bytes!("../../runtime/wasm/runtest/res/wasm_with_mem.wasm").as_ref();
         let encoded_test_contract = to_base64(default_test_contract);
@@ -322,7 +385,8 @@ impl GenesisConfig {
                     amount: TESTING_INIT_STAKE,
                 });
             }
-            records[0].push(StateRecord::Account {
+            let shard_id = account_to_shard_id(&account.to_string(), num_shards) as usize;
+            records[shard_id].push(StateRecord::Account {
                 account_id: account.to_string(),
                 account: Account {
                     nonce: 0,
@@ -335,7 +399,7 @@ impl GenesisConfig {
                     storage_paid_at: 0,
                 },
             });
-            records[0].push(StateRecord::Contract {
+            records[shard_id].push(StateRecord::Contract {
                 account_id: account.to_string(),
                 code: encoded_test_contract.clone(),
             });
@@ -345,11 +409,13 @@ impl GenesisConfig {
             genesis_time: Utc::now(),
             chain_id: random_chain_id(),
             num_block_producers: num_validators,
-            block_producers_per_shard: vec![num_validators],
-            avg_fisherman_per_shard: vec![0],
+            avg_fisherman_per_shard: vec![0; block_producers_per_shard.len()],
+            block_producers_per_shard,
             dynamic_resharding: false,
             epoch_length: FAST_EPOCH_LENGTH,
             validator_kickout_threshold: VALIDATOR_KICKOUT_THRESHOLD,
+            transaction_validity_period: TRANSACTION_VALIDITY_PERIOD,
+            economics_config: EconomicsConfig::default(),
             validators,
             records,
         }
@@ -390,6 +456,8 @@ impl GenesisConfig {
             dynamic_resharding: false,
             epoch_length: FAST_EPOCH_LENGTH,
             validator_kickout_threshold: VALIDATOR_KICKOUT_THRESHOLD,
+            transaction_validity_period: TRANSACTION_VALIDITY_PERIOD,
+            economics_config: EconomicsConfig::default(),
             validators,
             records: vec![records],
         }
@@ -522,6 +590,8 @@ pub fn init_configs(
                 dynamic_resharding: false,
                 epoch_length: if fast { FAST_EPOCH_LENGTH } else { EXPECTED_EPOCH_LENGTH },
                 validator_kickout_threshold: VALIDATOR_KICKOUT_THRESHOLD,
+                transaction_validity_period: TRANSACTION_VALIDITY_PERIOD,
+                economics_config: EconomicsConfig::default(),
                 validators: vec![AccountInfo {
                     account_id: account_id.clone(),
                     public_key: signer.public_key.to_readable(),
@@ -579,6 +649,8 @@ pub fn create_testnet_configs_from_seeds(
         dynamic_resharding: false,
         epoch_length: FAST_EPOCH_LENGTH,
         validator_kickout_threshold: VALIDATOR_KICKOUT_THRESHOLD,
+        transaction_validity_period: TRANSACTION_VALIDITY_PERIOD,
+        economics_config: EconomicsConfig::default(),
         validators,
         records,
     };
@@ -665,6 +737,8 @@ pub fn load_test_config(seed: &str, port: u16, genesis_config: &GenesisConfig) -
         Duration::from_millis(FAST_MIN_BLOCK_PRODUCTION_DELAY);
     config.consensus.max_block_production_delay =
         Duration::from_millis(FAST_MAX_BLOCK_PRODUCTION_DELAY);
+    // Tests look up old blocks and transaction results, they enable garbage collection if needed.
+    config.archive = true;
     let signer = Arc::new(InMemorySigner::from_seed(seed, seed));
     let block_producer = BlockProducer::from(signer.clone());
     NearConfig::new(config, &genesis_config, signer.into(), Some(block_producer))
@@ -14,9 +14,11 @@ pub use crate::config::{
     init_configs, load_config, load_test_config, GenesisConfig, NearConfig, NEAR_BASE,
 };
 pub use crate::runtime::NightshadeRuntime;
+pub use crate::snapshot::{export_snapshot, import_snapshot};
 
 pub mod config;
 mod runtime;
+mod snapshot;
 #[cfg(test)]
 mod test_utils;
 mod validator_manager;
@@ -70,7 +72,7 @@ pub fn start_with_config(
                 .unwrap()
                 .start();
 
-        start_http(config.rpc_config, ctx.address(), view_client1);
+        start_http(config.rpc_config, ctx.address(), view_client1.clone());
 
         ClientActor::new(
             config.client_config,
@@ -79,6 +81,7 @@ pub fn start_with_config(
             runtime,
             network_actor.recipient(),
             config.block_producer,
+            view_client1.recipient(),
         )
         .unwrap()
     });
@@ -7,7 +7,10 @@ use clap::{App, Arg, crate_version, SubCommand};
 use log::{info, LevelFilter};
 
 use git_version::git_version;
-use near::{get_default_home, get_store_path, init_configs, load_config, start_with_config};
+use near::{
+    export_snapshot, get_default_home, get_store_path, import_snapshot, init_configs, load_config,
+    start_with_config,
+};
 use near::config::init_testnet_configs;
 use near_primitives::types::Version;
 
@@ -58,6 +61,16 @@ fn main() {
             .arg(Arg::with_name("min-peers").long("min-peers").help("Minimum number of peers to start syncing / producing blocks").takes_value(true))
             .arg(Arg::with_name("network-addr").long("network-addr").help("Customize network listening address (useful for running multiple nodes on the same machine)").takes_value(true))
             .arg(Arg::with_name("rpc-addr").long("rpc-addr").help("Customize RPC listening address (useful for running multiple nodes on the same machine)").takes_value(true))
+            .arg(Arg::with_name("archive").long("archive").help("Keep all the blocks and states instead of garbage collecting old ones").takes_value(false))
+        )
+        .subcommand(SubCommand::with_name("snapshot").about("Exports or imports snapshot of the state, to start a node without syncing from genesis")
+            .subcommand(SubCommand::with_name("export").about("Exports state before the block at given height into a file")
+                .arg(Arg::with_name("height").long("height").takes_value(true).required(true).help("Height of the block to export state before"))
+                .arg(Arg::with_name("output").long("output").takes_value(true).help("Path to the snapshot file (default \"snapshot.bin\")"))
+            )
+            .subcommand(SubCommand::with_name("import").about("Initializes empty data directory with the state from a file")
+                .arg(Arg::with_name("input").long("input").takes_value(true).help("Path to the snapshot file (default \"snapshot.bin\")"))
+            )
         )
         .subcommand(SubCommand::with_name("unsafe_reset_data").about("(unsafe) Remove all the data, effectively resetting node to genesis state (keeps genesis and config)"))
         .subcommand(SubCommand::with_name("unsafe_reset_all").about("(unsafe) Remove all the config, keys, data and effectively removing all information about the network"))
@@ -124,11 +137,34 @@ fn main() {
             if let Some(rpc_addr) = args.value_of("rpc-addr") {
                 near_config.rpc_config.addr = rpc_addr.to_string();
             }
+            if args.is_present("archive") {
+                near_config.client_config.archive = true;
+            }
 
             let system = System::new("NEAR");
             start_with_config(home_dir, near_config);
             system.run().unwrap();
         }
+        ("snapshot", Some(args)) => {
+            let near_config = load_config(home_dir);
+            match args.subcommand() {
+                ("export", Some(args)) => {
+                    let height = args
+                        .value_of("height")
+                        .map(|x| x.parse().expect("Failed to parse number for height"))
+                        .unwrap();
+                    let output = Path::new(args.value_of("output").unwrap_or("snapshot.bin"));
+                    export_snapshot(home_dir, &near_config, height, output)
+                        .expect("Exporting snapshot failed");
+                }
+                ("import", Some(args)) => {
+                    let input = Path::new(args.value_of("input").unwrap_or("snapshot.bin"));
+                    import_snapshot(home_dir, &near_config, input)
+                        .expect("Importing snapshot failed");
+                }
+                (_, _) => panic!("Expected `export` or `import` snapshot subcommand"),
+            }
+        }
         ("unsafe_reset_data", Some(_args)) => {
             let store_path = get_store_path(home_dir);
             info!(target: "near", "Removing all data from {}", store_path);
@@ -1,9 +1,8 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::io::{Cursor, Read, Write};
 use std::path::Path;
 use std::sync::{Arc, Mutex, RwLock};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use log::{debug, error, info};
 
 use near_chain::{
@@ -11,24 +10,30 @@ use near_chain::{
 };
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::crypto::signature::{PublicKey, Signature};
-use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::rpc::{AccountViewCallResult, QueryResponse, ViewStateResult};
+use near_primitives::hash::CryptoHash;
+use near_primitives::rpc::{
+    AccountViewCallResult, QueryResponse, SimulateTxResult, StatusContractCacheInfo,
+    ViewStateResult,
+};
+use near_primitives::state_proof::StateProof;
 use near_primitives::transaction::{ReceiptTransaction, SignedTransaction, TransactionResult};
-use near_primitives::types::{AccountId, BlockIndex, MerkleHash, ShardId, ValidatorStake};
-use near_primitives::utils::prefix_for_access_key;
+use near_primitives::types::{
+    AccountId, BlockIndex, MerkleHash, ShardId, StatePart, ValidatorStake,
+};
+use near_primitives::utils::{account_to_shard_id, prefix_for_access_key};
 use near_store::{
-    get_access_key_raw, get_account, set_account, Store, StoreUpdate, Trie, TrieUpdate,
-    WrappedTrieChanges,
+    apply_state_part, check_state_complete, clear_state_parts, get_access_key_raw, get_account,
+    get_state_part, set_account, Store, StoreUpdate, Trie, TrieUpdate, WrappedTrieChanges,
 };
-use near_verifier::TransactionVerifier;
-use node_runtime::adapter::query_client;
+use near_verifier::{ChainContext, TransactionVerifier};
+use node_runtime::adapter::{query_client, query_keys, QueryKeys};
 use node_runtime::ethereum::EthashProvider;
 use node_runtime::state_viewer::TrieViewer;
-use node_runtime::{ApplyState, Runtime, ETHASH_CACHE_PATH};
+use node_runtime::{ApplyChainContext, ApplyState, Runtime, ETHASH_CACHE_PATH};
+use wasm::cache::{PersistentCache, WASM_CACHE_PATH};
 
 use crate::config::GenesisConfig;
-use crate::validator_manager::{ValidatorEpochConfig, ValidatorManager};
-use kvdb::DBValue;
+use crate::validator_manager::{ValidatorEpochConfig, ValidatorManager, ValidatorSnapshot};
 use std::cmp::max;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
@@ -43,6 +48,8 @@ pub struct NightshadeRuntime {
     trie_viewer: TrieViewer,
     runtime: Runtime,
     validator_manager: RwLock<ValidatorManager>,
+    /// Compiled contracts stored under the home directory, shared by the runtime and the viewer.
+    wasm_cache: Option<Arc<PersistentCache>>,
 }
 
 impl NightshadeRuntime {
@@ -51,12 +58,27 @@ impl NightshadeRuntime {
         let mut ethash_dir = home_dir.to_owned();
         ethash_dir.push(ETHASH_CACHE_PATH);
         let ethash_provider = Arc::new(Mutex::new(EthashProvider::new(ethash_dir.as_path())));
-        let runtime = Runtime::new(ethash_provider.clone());
-        let trie_viewer = TrieViewer::new(ethash_provider);
+        let wasm_cache_dir = home_dir.join(WASM_CACHE_PATH);
+        let wasm_cache = match PersistentCache::new(&wasm_cache_dir) {
+            Ok(cache) => Some(Arc::new(cache)),
+            Err(err) => {
+                error!(target: "near", "Failed to open {:?}: {}", wasm_cache_dir, err);
+                None
+            }
+        };
+        let num_shards = genesis_config.block_producers_per_shard.len() as ShardId;
+        let mut runtime =
+            Runtime::new(ethash_provider.clone(), genesis_config.economics_config.clone())
+                .with_num_shards(num_shards);
+        let mut trie_viewer = TrieViewer::new(ethash_provider);
+        if let Some(cache) = &wasm_cache {
+            runtime = runtime.with_wasm_cache(cache.clone());
+            trie_viewer = trie_viewer.with_wasm_cache(cache.clone());
+        }
         let initial_epoch_config = ValidatorEpochConfig {
             epoch_length: genesis_config.epoch_length,
             rng_seed: [0; 32],
-            num_shards: genesis_config.block_producers_per_shard.len() as ShardId,
+            num_shards,
             num_block_producers: genesis_config.num_block_producers,
             block_producers_per_shard: genesis_config.block_producers_per_shard.clone(),
             avg_fisherman_per_shard: genesis_config.avg_fisherman_per_shard.clone(),
@@ -79,7 +101,79 @@ impl NightshadeRuntime {
             )
             .expect("Failed to start Validator Manager"),
         );
-        NightshadeRuntime { genesis_config, store, trie, runtime, trie_viewer, validator_manager }
+        NightshadeRuntime {
+            genesis_config,
+            store,
+            trie,
+            runtime,
+            trie_viewer,
+            validator_manager,
+            wasm_cache,
+        }
+    }
+
+    /// Validator information needed to continue the chain from the given block.
+    pub(crate) fn export_validators(
+        &self,
+        block_hash: &CryptoHash,
+    ) -> Result<ValidatorSnapshot, Box<dyn std::error::Error>> {
+        let mut vm = self.validator_manager.write().expect(POISONED_LOCK_ERR);
+        Ok(vm.export_snapshot(block_hash)?)
+    }
+
+    pub(crate) fn import_validators(
+        &self,
+        snapshot: ValidatorSnapshot,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut vm = self.validator_manager.write().expect(POISONED_LOCK_ERR);
+        Ok(vm.import_snapshot(snapshot)?)
+    }
+
+    /// Applies the receipts one after another on top of the given states of the shards, with the
+    /// contract calls traced. Used to inspect historical executions, the changes are not committed.
+    pub fn replay_receipts(
+        &self,
+        state_roots: &[MerkleHash],
+        block_index: BlockIndex,
+        receipts: &[ReceiptTransaction],
+    ) -> Result<Vec<TransactionResult>, Box<dyn std::error::Error>> {
+        let mut state_updates = self.state_updates(state_roots)?;
+        let runtime = self.runtime.with_trace();
+        let mut new_receipts = HashMap::new();
+        Ok(receipts
+            .iter()
+            .map(|receipt| {
+                let shard_id = self.account_id_to_shard_id(&receipt.receiver);
+                runtime.process_receipt(
+                    &mut state_updates[shard_id as usize],
+                    shard_id,
+                    block_index,
+                    receipt,
+                    &mut new_receipts,
+                )
+            })
+            .collect())
+    }
+
+    /// Updates on top of the states of all the shards, indexed by the shard id.
+    fn state_updates(
+        &self,
+        state_roots: &[MerkleHash],
+    ) -> Result<Vec<TrieUpdate>, Box<dyn std::error::Error>> {
+        if state_roots.len() as ShardId != self.num_shards() {
+            return Err(format!("Expected state roots of {} shards", self.num_shards()).into());
+        }
+        state_roots
+            .iter()
+            .map(|state_root| {
+                if !self.trie.contains_root(state_root) {
+                    return Err(
+                        format!("State {} is missing or has been pruned", state_root).into()
+                    );
+                }
+                Ok(TrieUpdate::new(self.trie.clone(), *state_root))
+            })
+            .collect()
     }
 }
 
@@ -116,6 +210,10 @@ impl RuntimeAdapter for NightshadeRuntime {
         (store_update, state_roots)
     }
 
+    fn get_trie(&self) -> Arc<Trie> {
+        self.trie.clone()
+    }
+
     fn compute_block_weight(
         &self,
         prev_header: &BlockHeader,
@@ -188,24 +286,34 @@ impl RuntimeAdapter for NightshadeRuntime {
     }
 
     fn account_id_to_shard_id(&self, account_id: &AccountId) -> ShardId {
-        let mut cursor = Cursor::new((hash(&account_id.clone().into_bytes()).0).0);
-        cursor.read_u64::<LittleEndian>().expect("Must not happened")
-            % (self.genesis_config.block_producers_per_shard.len() as ShardId)
+        account_to_shard_id(account_id, self.num_shards())
     }
 
     fn validate_tx(
         &self,
         _shard_id: ShardId,
         state_root: MerkleHash,
+        block_index: BlockIndex,
+        reference_index: Option<BlockIndex>,
         transaction: SignedTransaction,
     ) -> Result<ValidTransaction, String> {
         let state_update = TrieUpdate::new(self.trie.clone(), state_root);
-        let verifier = TransactionVerifier::new(&state_update);
+        let validity_period = self.genesis_config.transaction_validity_period;
+        let verifier = TransactionVerifier::new(&state_update)
+            .with_chain_context(ChainContext {
+                chain_id: &self.genesis_config.chain_id,
+                block_index,
+                reference_index,
+                validity_period,
+            })
+            .with_transaction_cost(self.runtime.transaction_cost(&transaction.body));
         if let Err(err) = verifier.verify_transaction(&transaction) {
             debug!(target: "runtime", "Tx {:?} validation failed: {:?}", transaction, err);
             return Err(err);
         }
-        Ok(ValidTransaction { transaction })
+        let fee = self.runtime.transaction_fee(&transaction.body);
+        let valid_until = reference_index.unwrap_or_default() + validity_period;
+        Ok(ValidTransaction { transaction, fee, valid_until })
     }
 
     fn add_validator_proposals(
@@ -233,6 +341,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         block_hash: &CryptoHash,
         receipts: &Vec<Vec<ReceiptTransaction>>,
         transactions: &Vec<SignedTransaction>,
+        reference_indices: &HashMap<CryptoHash, BlockIndex>,
     ) -> Result<
         (
             WrappedTrieChanges,
@@ -270,6 +379,11 @@ impl RuntimeAdapter for NightshadeRuntime {
             shard_id,
             block_index,
             parent_block_hash: *prev_block_hash,
+            chain_context: Some(ApplyChainContext {
+                chain_id: self.genesis_config.chain_id.clone(),
+                reference_indices: reference_indices.clone(),
+                validity_period: self.genesis_config.transaction_validity_period,
+            }),
         };
 
         let apply_result =
@@ -291,56 +405,124 @@ impl RuntimeAdapter for NightshadeRuntime {
         path: &str,
         data: &[u8],
     ) -> Result<QueryResponse, Box<dyn std::error::Error>> {
+        if !self.trie.contains_root(&state_root) {
+            return Err(format!(
+                "State {} at height {} is missing or has been pruned",
+                state_root, height
+            )
+            .into());
+        }
         query_client(self, state_root, height, path, data)
     }
 
+    fn query_proof(
+        &self,
+        state_root: MerkleHash,
+        path: &str,
+        data: &[u8],
+    ) -> Result<StateProof, Box<dyn std::error::Error>> {
+        if !self.trie.contains_root(&state_root) {
+            return Err(format!("State {} is missing or has been pruned", state_root).into());
+        }
+        match query_keys(path, data)? {
+            QueryKeys::Key(key) => Ok(self.trie.prove_value(&state_root, &key)),
+            QueryKeys::Prefix(prefix) => self.trie.prove_prefix(&state_root, &prefix),
+        }
+    }
+
+    fn simulate_transaction(
+        &self,
+        state_roots: &[MerkleHash],
+        block_index: BlockIndex,
+        _prev_block_hash: &CryptoHash,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulateTxResult, Box<dyn std::error::Error>> {
+        // Changes are never finalized, so they are dropped together with the state updates.
+        let mut state_updates = self.state_updates(state_roots)?;
+        let runtime = self.runtime.with_trace();
+        let mut new_receipts = HashMap::new();
+        let shard_id = self.account_id_to_shard_id(&transaction.body.get_originator());
+        let result = runtime.process_transaction(
+            &mut state_updates[shard_id as usize],
+            block_index,
+            transaction,
+            None,
+            &mut new_receipts,
+            &mut vec![],
+        );
+        let receipts: Vec<_> = new_receipts.drain().flat_map(|(_, receipts)| receipts).collect();
+        let mut traces = result.traces.clone();
+        // Contracts are called by the receipts, apply them on top of the transaction to trace the
+        // calls. Receipts they generate in turn are not applied.
+        for receipt in receipts.iter() {
+            let shard_id = self.account_id_to_shard_id(&receipt.receiver);
+            let receipt_result = runtime.process_receipt(
+                &mut state_updates[shard_id as usize],
+                shard_id,
+                block_index,
+                receipt,
+                &mut HashMap::new(),
+            );
+            traces.extend(receipt_result.traces);
+        }
+        Ok(SimulateTxResult {
+            logs: result.logs.clone(),
+            receipts,
+            cost: self.runtime.transaction_cost(&transaction.body),
+            gas_used: result.gas_used,
+            result,
+            traces,
+        })
+    }
+
     fn dump_state(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        // TODO(1052): make sure state_root is present in the trie.
-        // create snapshot.
-        let mut result = vec![];
-        let mut cursor = Cursor::new(&mut result);
-        for item in self.trie.iter(&state_root)? {
-            let (key, value) = item?;
-            cursor.write_u32::<LittleEndian>(key.len() as u32)?;
-            cursor.write_all(&key)?;
-            cursor.write_u32::<LittleEndian>(value.len() as u32)?;
-            cursor.write_all(value.as_ref())?;
-        }
-        // TODO(1048): Save on disk an snapshot, split into chunks and compressed. Send chunks instead of single blob.
-        info!(target: "runtime", "Dumped state for shard #{} @ {}, size = {}", shard_id, state_root, result.len());
-        Ok(result)
+        part_id: u64,
+    ) -> Result<StatePart, Box<dyn std::error::Error>> {
+        let part = get_state_part(&self.trie, &state_root, part_id)?;
+        debug!(target: "runtime", "Dumped state part {}/{} for shard #{} @ {}, size = {}", part_id, part.num_parts, shard_id, state_root, part.data.len());
+        Ok(part)
     }
 
     fn set_state(
         &self,
         shard_id: ShardId,
         state_root: MerkleHash,
-        payload: Vec<u8>,
+        part: StatePart,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        info!(target: "runtime", "Setting state for shard #{} @ {}, size = {}", shard_id, state_root, payload.len());
-        let mut state_update = TrieUpdate::new(self.trie.clone(), CryptoHash::default());
-        let payload_len = payload.len();
-        let mut cursor = Cursor::new(payload);
-        while cursor.position() < payload_len as u64 {
-            let key_len = cursor.read_u32::<LittleEndian>()? as usize;
-            let mut key = vec![0; key_len];
-            cursor.read_exact(&mut key)?;
-            let value_len = cursor.read_u32::<LittleEndian>()? as usize;
-            let mut value = vec![0; value_len];
-            cursor.read_exact(&mut value)?;
-            state_update.set(key, DBValue::from_slice(&value));
-        }
-        let (store_update, root) = state_update.finalize()?.into(self.trie.clone())?;
-        if root != state_root {
-            return Err("Invalid state root".into());
-        }
-        store_update.commit()?;
+        debug!(target: "runtime", "Setting state part {}/{} for shard #{} @ {}, size = {}", part.part_id, part.num_parts, shard_id, state_root, part.data.len());
+        apply_state_part(self.trie.clone(), &state_root, &part)?.commit()?;
+        Ok(())
+    }
+
+    fn confirm_state(
+        &self,
+        shard_id: ShardId,
+        state_root: MerkleHash,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let result = check_state_complete(&self.trie, &state_root);
+        // Parts are applied again, if the state has to be downloaded one more time.
+        clear_state_parts(&self.trie, &state_root).commit()?;
+        result?;
+        info!(target: "runtime", "Set state for shard #{} @ {}", shard_id, state_root);
         Ok(())
     }
+
+    fn contract_cache_info(&self) -> StatusContractCacheInfo {
+        match &self.wasm_cache {
+            Some(cache) => {
+                let stats = cache.stats();
+                StatusContractCacheInfo {
+                    hits: stats.hits,
+                    misses: stats.misses,
+                    hit_rate: stats.hit_rate(),
+                }
+            }
+            None => StatusContractCacheInfo::default(),
+        }
+    }
 }
 
 impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
@@ -416,30 +598,40 @@ mod test {
     use crate::{get_store_path, GenesisConfig, NightshadeRuntime};
     use near_chain::RuntimeAdapter;
     use near_client::BlockProducer;
-    use near_primitives::crypto::signer::InMemorySigner;
+    use near_primitives::crypto::signer::{EDSigner, InMemorySigner};
     use near_primitives::hash::{hash, CryptoHash};
     use near_primitives::rpc::AccountViewCallResult;
     use near_primitives::serialize::BaseEncode;
     use near_primitives::transaction::{
         CreateAccountTransaction, ReceiptTransaction, SignedTransaction, StakeTransaction,
-        TransactionBody,
+        TransactionBody, TransactionStatus,
     };
     use near_primitives::types::{Balance, BlockIndex, Nonce, ValidatorStake};
     use near_store::create_store;
     use node_runtime::adapter::ViewRuntimeAdapter;
     use tempdir::TempDir;
 
-    fn stake(nonce: Nonce, sender: &BlockProducer, amount: Balance) -> SignedTransaction {
-        TransactionBody::Stake(StakeTransaction {
-            nonce,
-            originator: sender.account_id.clone(),
-            amount,
-            public_key: sender.signer.public_key().to_base(),
-        })
-        .sign(&*sender.signer.clone())
-    }
-
     impl NightshadeRuntime {
+        /// Signs the transaction for the chain of this runtime, referencing the genesis.
+        fn sign(&self, body: TransactionBody, signer: &dyn EDSigner) -> SignedTransaction {
+            body.sign_for_block(signer, CryptoHash::default(), &self.genesis_config.chain_id)
+        }
+
+        fn stake(
+            &self,
+            nonce: Nonce,
+            sender: &BlockProducer,
+            amount: Balance,
+        ) -> SignedTransaction {
+            let body = TransactionBody::Stake(StakeTransaction {
+                nonce,
+                originator: sender.account_id.clone(),
+                amount,
+                public_key: sender.signer.public_key().to_base(),
+            });
+            self.sign(body, &*sender.signer)
+        }
+
         fn update(
             &self,
             state_root: &CryptoHash,
@@ -459,6 +651,7 @@ mod test {
                     block_hash,
                     receipts,
                     transactions,
+                    &vec![(CryptoHash::default(), 0)].into_iter().collect(),
                 )
                 .unwrap();
             let mut store_update = self.store.store_update();
@@ -503,7 +696,7 @@ mod test {
             hash(&[8]),
         );
 
-        let staking_transaction = stake(1, &block_producers[0], TESTING_INIT_STAKE * 2);
+        let staking_transaction = nightshade.stake(1, &block_producers[0], TESTING_INIT_STAKE * 2);
         // test1 stakes twice the current stake, because test1 and test2 have the same amount of stake before, test2 will be
         // kicked out.
         let (new_root, validator_stakes, _) = nightshade.update(
@@ -543,15 +736,17 @@ mod test {
         let new_account = format!("test{}", num_nodes + 1);
         let new_validator: BlockProducer =
             InMemorySigner::from_seed(&new_account, &new_account).into();
-        let create_account_transaction = TransactionBody::CreateAccount(CreateAccountTransaction {
-            nonce: 2,
-            originator: block_producers[0].account_id.clone(),
-            new_account_id: new_account,
-            amount: TESTING_INIT_STAKE * 3,
-            public_key: new_validator.signer.public_key().0[..].to_vec(),
-        })
-        .sign(&*block_producers[0].signer.clone());
-        let staking_transaction = stake(1, &new_validator, TESTING_INIT_STAKE * 2);
+        let create_account_transaction = nightshade.sign(
+            TransactionBody::CreateAccount(CreateAccountTransaction {
+                nonce: 2,
+                originator: block_producers[0].account_id.clone(),
+                new_account_id: new_account,
+                amount: TESTING_INIT_STAKE * 3,
+                public_key: new_validator.signer.public_key().0[..].to_vec(),
+            }),
+            &*block_producers[0].signer,
+        );
+        let staking_transaction = nightshade.stake(1, &new_validator, TESTING_INIT_STAKE * 2);
 
         let (new_root, _, receipts) =
             nightshade.update(&state_root, 1, &h0, &h1, &vec![], &vec![create_account_transaction]);
@@ -677,7 +872,7 @@ mod test {
             validators.iter().map(|id| InMemorySigner::from_seed(id, id).into()).collect();
         let (h0, h1, h2, h3, h4, h5, h6) =
             (hash(&[0]), hash(&[1]), hash(&[2]), hash(&[3]), hash(&[4]), hash(&[5]), hash(&[6]));
-        let staking_transaction = stake(1, &block_producers[0], TESTING_INIT_STAKE - 1);
+        let staking_transaction = nightshade.stake(1, &block_producers[0], TESTING_INIT_STAKE - 1);
         let (new_root, validator_stakes, _) = nightshade.update(
             &state_root,
             0,
@@ -774,9 +969,9 @@ mod test {
             validators.iter().map(|id| InMemorySigner::from_seed(id, id).into()).collect();
         let (h0, h1, h2, h3, h4, h5, h6) =
             (hash(&[0]), hash(&[1]), hash(&[2]), hash(&[3]), hash(&[4]), hash(&[5]), hash(&[6]));
-        let staking_transaction = stake(1, &block_producers[0], TESTING_INIT_STAKE - 1);
-        let staking_transaction1 = stake(2, &block_producers[0], TESTING_INIT_STAKE - 2);
-        let staking_transaction2 = stake(1, &block_producers[1], TESTING_INIT_STAKE + 1);
+        let staking_transaction = nightshade.stake(1, &block_producers[0], TESTING_INIT_STAKE - 1);
+        let staking_transaction1 = nightshade.stake(2, &block_producers[0], TESTING_INIT_STAKE - 2);
+        let staking_transaction2 = nightshade.stake(1, &block_producers[1], TESTING_INIT_STAKE + 1);
         let (new_root, validator_stakes, _) = nightshade.update(
             &state_root,
             0,
@@ -806,9 +1001,9 @@ mod test {
         state_root = nightshade.update(&state_root, 1, &h0, &h1, &vec![], &vec![]).0;
         nightshade.add_validator_proposals(h0, h1, 1, vec![], vec![]).unwrap();
 
-        let staking_transaction = stake(3, &block_producers[0], TESTING_INIT_STAKE + 1);
-        let staking_transaction1 = stake(2, &block_producers[1], TESTING_INIT_STAKE + 2);
-        let staking_transaction2 = stake(3, &block_producers[1], TESTING_INIT_STAKE - 1);
+        let staking_transaction = nightshade.stake(3, &block_producers[0], TESTING_INIT_STAKE + 1);
+        let staking_transaction1 = nightshade.stake(2, &block_producers[1], TESTING_INIT_STAKE + 2);
+        let staking_transaction2 = nightshade.stake(3, &block_producers[1], TESTING_INIT_STAKE - 1);
 
         let (new_root, validator_stakes, _) = nightshade.update(
             &state_root,
@@ -885,4 +1080,55 @@ mod test {
             }
         );
     }
+
+    /// Transactions of the block that reference an unknown or too old block fail.
+    #[test]
+    fn test_expired_transactions_in_block() {
+        let dir = TempDir::new("expired_transactions").unwrap();
+        let store = create_store(&get_store_path(dir.path()));
+        let mut genesis_config = GenesisConfig::test(vec!["test1", "test2"]);
+        genesis_config.transaction_validity_period = 2;
+        let nightshade = NightshadeRuntime::new(dir.path(), store, genesis_config);
+        let (store_update, state_roots) = nightshade.genesis_state();
+        store_update.commit().unwrap();
+        let signer = InMemorySigner::from_seed("test1", "test1");
+        let chain_id = nightshade.genesis_config.chain_id.clone();
+        let send_money = |nonce, block_hash| {
+            TransactionBody::send_money(nonce, "test1", "test2", 1)
+                .sign_for_block(&signer, block_hash, &chain_id)
+        };
+        let (h0, h1, h2) = (hash(&[0]), hash(&[1]), hash(&[2]));
+        let mut state_root = state_roots[0];
+        for (index, (prev_hash, block_hash)) in
+            vec![(CryptoHash::default(), h0), (h0, h1), (h1, h2)].into_iter().enumerate()
+        {
+            let index = index as BlockIndex;
+            state_root =
+                nightshade.update(&state_root, index, &prev_hash, &block_hash, &vec![], &vec![]).0;
+            nightshade
+                .add_validator_proposals(prev_hash, block_hash, index, vec![], vec![])
+                .unwrap();
+        }
+        let transactions = vec![send_money(1, h2), send_money(2, h0), send_money(3, hash(&[9]))];
+        let reference_indices = vec![(h0, 0), (h2, 2)].into_iter().collect();
+        let (_, state_root, tx_results, _, _) = nightshade
+            .apply_transactions(
+                0,
+                &state_root,
+                3,
+                &h2,
+                &hash(&[3]),
+                &vec![],
+                &transactions,
+                &reference_indices,
+            )
+            .unwrap();
+        let statuses: Vec<_> = tx_results.iter().map(|result| result.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![TransactionStatus::Completed, TransactionStatus::Failed, TransactionStatus::Failed]
+        );
+        let account = nightshade.view_account(state_root, &"test1".to_string()).unwrap();
+        assert_eq!(account.nonce, 1);
+    }
 }
@@ -0,0 +1,157 @@
+//! State snapshots, that allow to start a new node from a recent block instead of syncing
+//! the whole chain from genesis.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+
+use near_chain::{
+    BlockHeader, Chain, ChainStore, ChainStoreAccess, Error, ErrorKind, RuntimeAdapter,
+};
+use near_primitives::serialize::{Decode, Encode};
+use near_primitives::transaction::ReceiptTransaction;
+use near_primitives::types::{BlockIndex, ShardId, StatePart};
+use near_store::create_store;
+
+use crate::config::NearConfig;
+use crate::get_store_path;
+use crate::runtime::NightshadeRuntime;
+use crate::validator_manager::ValidatorSnapshot;
+
+/// Version of the snapshot file format, must be changed on any change of `SnapshotHeader` or of
+/// the layout of the file.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Everything about the snapshot except the state itself. State of the chain is taken before
+/// `header`, it is verified against the state root in this header and the chain continues
+/// from its previous block.
+///
+/// The snapshot file consists of the version, the header and the state parts of all shards,
+/// each of them prefixed with its length, so that the state is written and read part by part.
+#[derive(Serialize, Deserialize)]
+struct SnapshotHeader {
+    chain_id: String,
+    prev_header: BlockHeader,
+    header: BlockHeader,
+    /// Receipts produced by the previous block.
+    receipts: Vec<ReceiptTransaction>,
+    validators: ValidatorSnapshot,
+}
+
+fn write_record<T: Encode>(
+    writer: &mut impl Write,
+    value: &T,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = value.encode()?;
+    writer.write_u64::<LittleEndian>(bytes.len() as u64)?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn read_record<T: Decode>(reader: &mut impl Read) -> Result<T, Box<dyn std::error::Error>> {
+    let len = reader.read_u64::<LittleEndian>()?;
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err("Snapshot file is truncated".into());
+    }
+    Ok(T::decode(&bytes)?)
+}
+
+/// Writes snapshot of the state before the block at given height into `path`.
+pub fn export_snapshot(
+    home_dir: &Path,
+    config: &NearConfig,
+    height: BlockIndex,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = create_store(&get_store_path(home_dir));
+    let runtime = NightshadeRuntime::new(home_dir, store.clone(), config.genesis_config.clone());
+    let mut chain_store = ChainStore::new(store);
+
+    let hash = chain_store.get_block_hash_by_height(height)?;
+    let header = chain_store.get_block_header(&hash)?.clone();
+    let prev_header = chain_store.get_block_header(&header.prev_hash)?.clone();
+    let receipts = chain_store.get_receipts(&header.prev_hash)?.clone();
+    let state_roots = header.prev_state_roots.clone();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_u32::<LittleEndian>(SNAPSHOT_VERSION)?;
+    let snapshot_header = SnapshotHeader {
+        chain_id: config.genesis_config.chain_id.clone(),
+        validators: runtime.export_validators(&header.prev_hash)?,
+        prev_header,
+        header,
+        receipts,
+    };
+    write_record(&mut writer, &snapshot_header)?;
+    // Parts are dumped one at a time, so that the whole state is never kept in memory.
+    for (shard_id, state_root) in state_roots.into_iter().enumerate() {
+        let shard_id = shard_id as ShardId;
+        let mut part_id = 0;
+        loop {
+            let part = runtime.dump_state(shard_id, state_root, part_id)?;
+            let num_parts = part.num_parts;
+            write_record(&mut writer, &(shard_id, part))?;
+            part_id += 1;
+            if part_id >= num_parts {
+                break;
+            }
+        }
+    }
+    writer.flush()?;
+    info!(target: "near", "Exported state at #{} {} into {}", height, hash, path.display());
+    Ok(())
+}
+
+/// Initializes empty store with the state from snapshot at `path`.
+pub fn import_snapshot(
+    home_dir: &Path,
+    config: &NearConfig,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported snapshot version {}, expected {}",
+            version, SNAPSHOT_VERSION
+        )
+        .into());
+    }
+    let snapshot_header: SnapshotHeader = read_record(&mut reader)?;
+    if snapshot_header.chain_id != config.genesis_config.chain_id {
+        return Err(format!(
+            "Snapshot of chain {} doesn't match chain {}",
+            snapshot_header.chain_id, config.genesis_config.chain_id
+        )
+        .into());
+    }
+
+    let store = create_store(&get_store_path(home_dir));
+    let runtime =
+        Arc::new(NightshadeRuntime::new(home_dir, store.clone(), config.genesis_config.clone()));
+    let mut chain = Chain::new(store, runtime.clone(), config.genesis_config.genesis_time)?;
+    runtime.import_validators(snapshot_header.validators)?;
+    let height = snapshot_header.prev_header.height;
+    // State parts are read from the file as they are applied, up to the end of the file.
+    let state_parts = std::iter::from_fn(|| match reader.fill_buf() {
+        Ok(buf) if buf.is_empty() => None,
+        Ok(_) => Some(read_record::<(ShardId, StatePart)>(&mut reader)),
+        Err(err) => Some(Err(err.into())),
+    })
+    .map(|part| part.map_err(|err| Error::from(ErrorKind::Other(err.to_string()))));
+    chain.import_state_snapshot(
+        snapshot_header.prev_header,
+        snapshot_header.header,
+        snapshot_header.receipts,
+        state_parts,
+    )?;
+    info!(target: "near", "Imported state at #{} from {}", height, path.display());
+    Ok(())
+}
@@ -335,6 +335,15 @@ pub struct ValidatorIndexInfo {
     pub validator_mask: Vec<bool>,
 }
 
+/// Validator information needed to process blocks after given one, stored in state snapshots.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ValidatorSnapshot {
+    pub last_epoch: Option<CryptoHash>,
+    pub index_infos: Vec<(CryptoHash, ValidatorIndexInfo)>,
+    pub assignments: Vec<(CryptoHash, ValidatorAssignment)>,
+    pub last_epoch_proposals: Vec<(CryptoHash, Vec<ValidatorStake>)>,
+}
+
 /// Manages current validators and validator proposals in the current epoch across different forks.
 pub struct ValidatorManager {
     store: Arc<Store>,
@@ -623,6 +632,62 @@ impl ValidatorManager {
         Ok(store_update)
     }
 
+    /// Collects information about blocks of the current and previous epochs of the given block,
+    /// which is enough to continue processing the chain from it.
+    pub fn export_snapshot(
+        &mut self,
+        last_hash: &CryptoHash,
+    ) -> Result<ValidatorSnapshot, ValidatorError> {
+        let mut index_infos = vec![];
+        let mut epoch_hashes = HashSet::new();
+        let mut hash = *last_hash;
+        loop {
+            let info = self.get_index_info(&hash)?;
+            let prev_hash = info.prev_hash;
+            epoch_hashes.insert(info.epoch_start_hash);
+            index_infos.push((hash, info));
+            // Stop at the start of the previous epoch or at the pre-genesis block.
+            if epoch_hashes.len() > 2 || hash == CryptoHash::default() {
+                break;
+            }
+            hash = prev_hash;
+        }
+        let mut assignments = vec![];
+        let mut last_epoch_proposals = vec![];
+        for epoch_hash in epoch_hashes {
+            if let Some(assignment) = self.store.get_ser(COL_VALIDATORS, epoch_hash.as_ref())? {
+                assignments.push((epoch_hash, assignment));
+            }
+            if let Some(proposals) =
+                self.store.get_ser(COL_LAST_EPOCH_PROPOSALS, epoch_hash.as_ref())?
+            {
+                last_epoch_proposals.push((epoch_hash, proposals));
+            }
+        }
+        let last_epoch = self.store.get_ser(COL_PROPOSALS, LAST_EPOCH_KEY)?;
+        Ok(ValidatorSnapshot { last_epoch, index_infos, assignments, last_epoch_proposals })
+    }
+
+    /// Stores validator information from a snapshot.
+    pub fn import_snapshot(&mut self, snapshot: ValidatorSnapshot) -> Result<(), ValidatorError> {
+        let mut store_update = self.store.store_update();
+        for (hash, info) in snapshot.index_infos {
+            store_update.set_ser(COL_PROPOSALS, hash.as_ref(), &info)?;
+        }
+        for (epoch_hash, assignment) in snapshot.assignments {
+            self.set_validators(&epoch_hash, assignment, &mut store_update)?;
+        }
+        for (epoch_hash, proposals) in snapshot.last_epoch_proposals {
+            store_update.set_ser(COL_LAST_EPOCH_PROPOSALS, epoch_hash.as_ref(), &proposals)?;
+        }
+        if let Some(last_epoch) = snapshot.last_epoch {
+            store_update.set_ser(COL_PROPOSALS, LAST_EPOCH_KEY, &last_epoch)?;
+            self.last_epoch = last_epoch;
+        }
+        store_update.commit()?;
+        Ok(())
+    }
+
     pub fn get_block_proposer_info(
         &mut self,
         parent_hash: CryptoHash,
@@ -1048,4 +1113,38 @@ mod test {
             )
         )
     }
+
+    #[test]
+    fn test_export_import_snapshot() {
+        let config = config(2, 1, 2, 0, 0.9);
+        let validators = vec![stake("test1", 1_000_000), stake("test2", 1_000_000)];
+        let mut vm =
+            ValidatorManager::new(config.clone(), validators.clone(), create_test_store()).unwrap();
+        let hashes: Vec<_> = (0..9).map(|i| hash(&vec![i])).collect();
+        vm.add_proposals(CryptoHash::default(), hashes[0], 0, vec![], vec![])
+            .unwrap()
+            .commit()
+            .unwrap();
+        for i in 1..8 {
+            vm.add_proposals(hashes[i - 1], hashes[i], i as BlockIndex, vec![], vec![])
+                .unwrap()
+                .commit()
+                .unwrap();
+        }
+        let snapshot = vm.export_snapshot(&hashes[7]).unwrap();
+        let mut new_vm = ValidatorManager::new(config, validators, create_test_store()).unwrap();
+        new_vm.import_snapshot(snapshot).unwrap();
+        // Next epoch is finalized the same way by both managers.
+        for vm in vec![&mut vm, &mut new_vm] {
+            vm.add_proposals(hashes[7], hashes[8], 8, vec![], vec![]).unwrap().commit().unwrap();
+        }
+        assert_eq!(
+            vm.get_validators(hashes[8]).unwrap(),
+            new_vm.get_validators(hashes[8]).unwrap()
+        );
+        assert_eq!(
+            vm.get_block_proposer_info(hashes[8], 9).unwrap(),
+            new_vm.get_block_proposer_info(hashes[8], 9).unwrap()
+        );
+    }
 }
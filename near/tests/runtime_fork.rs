@@ -7,7 +7,7 @@ use near::{get_store_path, GenesisConfig, NightshadeRuntime};
 use near_chain::{Block, Chain, Provenance};
 use near_primitives::crypto::signer::InMemorySigner;
 use near_primitives::test_utils::init_test_logger;
-use near_primitives::transaction::TransactionBody;
+use near_primitives::transaction::{TransactionBody, TransactionStatus};
 use near_store::create_store;
 
 #[test]
@@ -26,11 +26,11 @@ fn runtime_hanldle_fork() {
     let tx1 = TransactionBody::send_money(1, "near.0", "near.1", 100).sign(&*signer);
     let tx2 = TransactionBody::send_money(1, "near.0", "near.1", 500).sign(&*signer);
     let tx3 = TransactionBody::send_money(2, "near.0", "near.1", 100).sign(&*signer);
-    let state_root = chain.get_post_state_root(&chain.genesis().hash()).unwrap().clone();
+    let state_roots = chain.get_post_state_roots(&chain.genesis().hash()).unwrap().clone();
     let b1 = Block::produce(
         chain.genesis(),
         1,
-        state_root,
+        state_roots.clone(),
         vec![tx1],
         HashMap::default(),
         vec![],
@@ -40,18 +40,18 @@ fn runtime_hanldle_fork() {
     let b2 = Block::produce(
         chain.genesis(),
         2,
-        state_root,
+        state_roots,
         vec![tx2],
         HashMap::default(),
         vec![],
         signer.clone(),
     );
     chain.process_block(b2, Provenance::NONE, |_, _, _| {}).unwrap();
-    let state_root3 = chain.get_post_state_root(&b1.hash()).unwrap().clone();
+    let state_roots3 = chain.get_post_state_roots(&b1.hash()).unwrap().clone();
     let b3 = Block::produce(
         &b1.header,
         3,
-        state_root3,
+        state_roots3,
         vec![tx3],
         HashMap::default(),
         vec![],
@@ -59,3 +59,58 @@ fn runtime_hanldle_fork() {
     );
     chain.process_block(b3, Provenance::NONE, |_, _, _| {}).unwrap();
 }
+
+/// Transactions can only reference the blocks on the chain of the block they are included into.
+#[test]
+fn transaction_referencing_fork_block() {
+    init_test_logger();
+
+    let tmp_dir = TempDir::new("fork_reference").unwrap();
+    let store = create_store(&get_store_path(tmp_dir.path()));
+    let genesis_config = GenesisConfig::testing_spec(2, 1);
+    let signer = Arc::new(InMemorySigner::from_seed("near.0", "near.0"));
+    let runtime =
+        Arc::new(NightshadeRuntime::new(tmp_dir.path(), store.clone(), genesis_config.clone()));
+
+    let mut chain = Chain::new(store, runtime, genesis_config.genesis_time).unwrap();
+
+    let produce = |chain: &mut Chain, prev: &Block, height, transactions| {
+        let state_roots = chain.get_post_state_roots(&prev.hash()).unwrap().clone();
+        let block = Block::produce(
+            &prev.header,
+            height,
+            state_roots,
+            transactions,
+            HashMap::default(),
+            vec![],
+            signer.clone(),
+        );
+        chain.process_block(block.clone(), Provenance::NONE, |_, _, _| {}).unwrap();
+        block
+    };
+    let genesis = chain.get_block(&chain.genesis().hash()).unwrap().clone();
+    let b1 = produce(&mut chain, &genesis, 1, vec![]);
+    let b2 = produce(&mut chain, &b1, 2, vec![]);
+    let fork = produce(&mut chain, &genesis, 2, vec![]);
+    let tx1 = TransactionBody::send_money(1, "near.0", "near.1", 100).sign_for_block(
+        &*signer,
+        fork.hash(),
+        &genesis_config.chain_id,
+    );
+    let tx2 = TransactionBody::send_money(2, "near.0", "near.1", 100).sign_for_block(
+        &*signer,
+        b2.hash(),
+        &genesis_config.chain_id,
+    );
+    let b3 = produce(&mut chain, &b2, 3, vec![tx1.clone(), tx2.clone()]);
+    assert_eq!(chain.head().unwrap().last_block_hash, b3.hash());
+
+    assert_eq!(
+        chain.get_transaction_result(&tx1.get_hash()).unwrap().status,
+        TransactionStatus::Failed
+    );
+    assert_eq!(
+        chain.get_transaction_result(&tx2.get_hash()).unwrap().status,
+        TransactionStatus::Completed
+    );
+}
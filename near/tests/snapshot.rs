@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tempdir::TempDir;
+
+use near::{
+    export_snapshot, get_store_path, import_snapshot, load_test_config, GenesisConfig,
+    NightshadeRuntime,
+};
+use near_chain::{Block, Chain, Provenance, RuntimeAdapter};
+use near_primitives::crypto::signer::InMemorySigner;
+use near_primitives::rpc::{AccountViewCallResult, QueryResponse};
+use near_primitives::test_utils::init_test_logger;
+use near_primitives::transaction::TransactionBody;
+use near_primitives::types::{BlockIndex, MerkleHash};
+use near_store::create_store;
+
+fn view_account(
+    runtime: &NightshadeRuntime,
+    state_root: MerkleHash,
+    height: BlockIndex,
+    account_id: &str,
+) -> AccountViewCallResult {
+    match runtime.query(state_root, height, &format!("account/{}", account_id), &[]).unwrap() {
+        QueryResponse::ViewAccount(account) => account,
+        _ => panic!("Unexpected query response"),
+    }
+}
+
+/// Exports the state before the last block of the chain and imports it into a fresh node, which
+/// continues from the previous block with the same state.
+#[test]
+fn export_import_snapshot() {
+    init_test_logger();
+    let genesis_config = GenesisConfig::testing_spec(2, 1);
+    let config = load_test_config("near.0", 0, &genesis_config);
+    let export_dir = TempDir::new("snapshot_export").unwrap();
+    let import_dir = TempDir::new("snapshot_import").unwrap();
+    let path = export_dir.path().join("snapshot.bin");
+
+    let (prev_hash, state_roots, account) = {
+        let store = create_store(&get_store_path(export_dir.path()));
+        let runtime = Arc::new(NightshadeRuntime::new(
+            export_dir.path(),
+            store.clone(),
+            genesis_config.clone(),
+        ));
+        let mut chain = Chain::new(store, runtime.clone(), genesis_config.genesis_time).unwrap();
+        let signer = Arc::new(InMemorySigner::from_seed("near.0", "near.0"));
+        let mut transactions = vec![TransactionBody::send_money(1, "near.0", "near.1", 100)
+            .sign_for_block(&*signer, chain.genesis().hash(), &genesis_config.chain_id)];
+        let mut prev_header = chain.genesis().clone();
+        for height in 1..4 {
+            let state_roots = chain.get_post_state_roots(&prev_header.hash()).unwrap().clone();
+            let block = Block::produce(
+                &prev_header,
+                height,
+                state_roots,
+                transactions.drain(..).collect(),
+                HashMap::default(),
+                vec![],
+                signer.clone(),
+            );
+            chain.process_block(block.clone(), Provenance::PRODUCED, |_, _, _| {}).unwrap();
+            prev_header = block.header;
+        }
+        let prev_hash = prev_header.prev_hash;
+        let state_roots = chain.get_post_state_roots(&prev_hash).unwrap().clone();
+        let account = view_account(&runtime, state_roots[0], 2, "near.1");
+        (prev_hash, state_roots, account)
+    };
+
+    export_snapshot(export_dir.path(), &config, 3, &path).unwrap();
+    import_snapshot(import_dir.path(), &config, &path).unwrap();
+
+    let store = create_store(&get_store_path(import_dir.path()));
+    let runtime =
+        Arc::new(NightshadeRuntime::new(import_dir.path(), store.clone(), genesis_config.clone()));
+    let mut chain = Chain::new(store, runtime.clone(), genesis_config.genesis_time).unwrap();
+    assert_eq!(chain.head().unwrap().last_block_hash, prev_hash);
+    assert_eq!(chain.get_post_state_roots(&prev_hash).unwrap(), &state_roots);
+    assert_eq!(view_account(&runtime, state_roots[0], 2, "near.1"), account);
+}
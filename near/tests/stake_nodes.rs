@@ -5,16 +5,17 @@ use tempdir::TempDir;
 
 use near::config::{TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
 use near::{load_test_config, start_with_config, GenesisConfig, NearConfig};
-use near_client::{ClientActor, Query, Status, ViewClientActor};
+use near_client::{ClientActor, GetBlock, Query, Status, ViewClientActor};
 use near_network::test_utils::{convert_boot_nodes, open_port, WaitOrTimeout};
 use near_network::NetworkClientMessages;
+use near_primitives::crypto::signer::EDSigner;
 use near_primitives::rpc::QueryResponse;
 use near_primitives::serialize::BaseEncode;
 use near_primitives::test_utils::init_integration_logger;
 use near_primitives::transaction::{StakeTransaction, TransactionBody};
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, BlockId};
 use rand::Rng;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 lazy_static! {
     static ref HEAVY_TESTS_LOCK: Mutex<()> = Mutex::new(());
@@ -66,6 +67,17 @@ fn init_test_staking(num_accounts: usize, num_nodes: usize, epoch_length: u64) -
         .collect()
 }
 
+/// Signs transaction for the latest block of the node and sends it to the node.
+fn send_transaction(test_node: &TestNode, body: TransactionBody, signer: Arc<dyn EDSigner>) {
+    let client = test_node.client.clone();
+    let chain_id = test_node.config.genesis_config.chain_id.clone();
+    actix::spawn(test_node.view_client.send(GetBlock::Best).then(move |res| {
+        let block_hash = res.unwrap().unwrap().hash();
+        let tx = body.sign_for_block(&*signer, block_hash, &chain_id);
+        client.send(NetworkClientMessages::Transaction(tx)).map(|_| ()).map_err(|_| ())
+    }));
+}
+
 /// Runs one validator network, sends staking transaction for the second node and
 /// waits until it becomes a validator.
 #[test]
@@ -86,14 +98,11 @@ fn test_stake_nodes() {
                 .signer
                 .public_key()
                 .to_base(),
-        })
-        .sign(&*test_nodes[1].config.block_producer.clone().unwrap().signer);
-        actix::spawn(
-            test_nodes[0]
-                .client
-                .send(NetworkClientMessages::Transaction(tx))
-                .map(|_| ())
-                .map_err(|_| ()),
+        });
+        send_transaction(
+            &test_nodes[0],
+            tx,
+            test_nodes[1].config.block_producer.clone().unwrap().signer,
         );
 
         WaitOrTimeout::new(
@@ -137,18 +146,12 @@ fn test_kickout() {
                     .public_key()
                     .to_base(),
             })
-            .sign(&*test_node.config.block_producer.as_ref().unwrap().signer)
         });
 
         for (i, stake_transaction) in stake_transactions.enumerate() {
             let test_node = &test_nodes[i];
-            actix::spawn(
-                test_node
-                    .client
-                    .send(NetworkClientMessages::Transaction(stake_transaction))
-                    .map(|_| ())
-                    .map_err(|_| ()),
-            );
+            let signer = test_node.config.block_producer.as_ref().unwrap().signer.clone();
+            send_transaction(test_node, stake_transaction, signer);
         }
 
         WaitOrTimeout::new(
@@ -172,6 +175,8 @@ fn test_kickout() {
                                             test_nodes[i].account_id.clone()
                                         ),
                                         data: vec![],
+                                        block_id: BlockId::Best,
+                                        prove: false,
                                     })
                                     .then(|res| match res.unwrap().unwrap() {
                                         QueryResponse::ViewAccount(result) => {
@@ -193,6 +198,8 @@ fn test_kickout() {
                                             test_nodes[i].account_id.clone()
                                         ),
                                         data: vec![],
+                                        block_id: BlockId::Best,
+                                        prove: false,
                                     })
                                     .then(|res| match res.unwrap().unwrap() {
                                         QueryResponse::ViewAccount(result) => {
@@ -238,8 +245,7 @@ fn test_validator_join() {
                 .signer
                 .public_key()
                 .to_base(),
-        })
-        .sign(&*test_nodes[1].config.block_producer.as_ref().unwrap().signer);
+        });
         let stake_transaction = TransactionBody::Stake(StakeTransaction {
             nonce: 1,
             originator: test_nodes[2].account_id.clone(),
@@ -252,21 +258,16 @@ fn test_validator_join() {
                 .signer
                 .public_key()
                 .to_base(),
-        })
-        .sign(&*test_nodes[2].config.block_producer.as_ref().unwrap().signer);
-        actix::spawn(
-            test_nodes[1]
-                .client
-                .send(NetworkClientMessages::Transaction(unstake_transaction))
-                .map(|_| ())
-                .map_err(|_| ()),
+        });
+        send_transaction(
+            &test_nodes[1],
+            unstake_transaction,
+            test_nodes[1].config.block_producer.clone().unwrap().signer,
         );
-        actix::spawn(
-            test_nodes[0]
-                .client
-                .send(NetworkClientMessages::Transaction(stake_transaction))
-                .map(|_| ())
-                .map_err(|_| ()),
+        send_transaction(
+            &test_nodes[0],
+            stake_transaction,
+            test_nodes[2].config.block_producer.clone().unwrap().signer,
         );
 
         WaitOrTimeout::new(
@@ -287,6 +288,8 @@ fn test_validator_join() {
                                 .send(Query {
                                     path: format!("account/{}", test_nodes[1].account_id.clone()),
                                     data: vec![],
+                                    block_id: BlockId::Best,
+                                    prove: false,
                                 })
                                 .then(|res| match res.unwrap().unwrap() {
                                     QueryResponse::ViewAccount(result) => {
@@ -303,6 +306,8 @@ fn test_validator_join() {
                                 .send(Query {
                                     path: format!("account/{}", test_nodes[2].account_id.clone()),
                                     data: vec![],
+                                    block_id: BlockId::Best,
+                                    prove: false,
                                 })
                                 .then(|res| match res.unwrap().unwrap() {
                                     QueryResponse::ViewAccount(result) => {
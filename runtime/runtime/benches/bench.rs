@@ -4,9 +4,7 @@ extern crate bencher;
 use bencher::Bencher;
 
 use near_primitives::contract::ContractCode;
-use near_primitives::transaction::{
-    CreateAccountTransaction, DeployContractTransaction, TransactionBody,
-};
+use near_primitives::transaction::{CreateAccountTransaction, TransactionBody};
 use near_primitives::types::Balance;
 use testlib::node::{Node, RuntimeNode};
 
@@ -33,13 +31,7 @@ fn setup_test_contract(wasm_binary: &[u8]) -> RuntimeNode {
     let user = node.user();
     user.add_transaction(transaction).unwrap();
 
-    let transaction = TransactionBody::DeployContract(DeployContractTransaction {
-        nonce: node.get_account_nonce(&account_id).unwrap_or_default() + 1,
-        contract_id: "test_contract".to_string(),
-        wasm_byte_array: wasm_binary.to_vec(),
-    })
-    .sign(&*node.signer());
-    user.add_transaction(transaction).unwrap();
+    node.set_code_unchecked(&"test_contract".to_string(), wasm_binary);
     node
 }
 
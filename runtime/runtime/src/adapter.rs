@@ -5,6 +5,9 @@ use near_primitives::rpc::{
 };
 use near_primitives::serialize::BaseDecode;
 use near_primitives::types::{AccountId, BlockIndex, MerkleHash};
+use near_primitives::utils::{key_for_access_key, key_for_account};
+
+use crate::ext::ACCOUNT_DATA_SEPARATOR;
 
 /// Adapter for querying runtime.
 pub trait ViewRuntimeAdapter {
@@ -45,7 +48,7 @@ pub trait ViewRuntimeAdapter {
 }
 
 /// Facade to query given client with <path> + <data> at <block height> with optional merkle prove request.
-/// Given implementation only supports latest height, thus ignoring it.
+/// Caller is responsible for resolving the state root that corresponds to the given height.
 pub fn query_client(
     adapter: &dyn ViewRuntimeAdapter,
     state_root: MerkleHash,
@@ -106,3 +109,31 @@ pub fn query_client(
         _ => Err(format!("Unknown path {}", path).into()),
     }
 }
+
+/// State keys read by a query, that have to be covered by its proof.
+pub enum QueryKeys {
+    /// Single value under the key.
+    Key(Vec<u8>),
+    /// All values under the keys that start with the prefix.
+    Prefix(Vec<u8>),
+}
+
+/// Returns the state keys read by query with given <path> + <data>. Only queries that read the
+/// state directly are supported: account, access key and contract state.
+pub fn query_keys(path: &str, _data: &[u8]) -> Result<QueryKeys, Box<dyn std::error::Error>> {
+    let path_parts: Vec<&str> = path.split('/').collect();
+    match (path_parts[0], path_parts.len()) {
+        ("account", 2) => Ok(QueryKeys::Key(key_for_account(&AccountId::from(path_parts[1])))),
+        ("access_key", 3) => Ok(QueryKeys::Key(key_for_access_key(
+            &AccountId::from(path_parts[1]),
+            &PublicKey::from_base(path_parts[2])?,
+        ))),
+        ("contract", 2) => {
+            // Same prefix as the one of `TrieViewer::view_state`.
+            let mut prefix = key_for_account(&AccountId::from(path_parts[1]));
+            prefix.extend_from_slice(ACCOUNT_DATA_SEPARATOR);
+            Ok(QueryKeys::Prefix(prefix))
+        }
+        _ => Err(format!("Proofs are not supported for path {}", path).into()),
+    }
+}
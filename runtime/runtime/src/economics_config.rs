@@ -1,4 +1,5 @@
 //! Settings of the parameters of the economics.
+use near_primitives::serialize::u128_dec_format;
 use near_primitives::transaction::TransactionBody;
 use near_primitives::types::Balance;
 use wasm::types::Config;
@@ -7,24 +8,36 @@ use wasm::types::Config;
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct EconomicsConfig {
     /// The cost to store one byte of storage per block.
+    #[serde(with = "u128_dec_format")]
     pub storage_cost_byte_per_block: Balance,
     pub transactions_costs: TransactionsCosts,
-    /// Config of wasm operations.
+    /// Config of wasm operations, including the gas costs of the operations and host functions.
     pub wasm_config: Config,
 }
 
 /// The costs of the transactions.
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionsCosts {
+    #[serde(with = "u128_dec_format")]
     pub create_account: Balance,
+    #[serde(with = "u128_dec_format")]
     pub deploy_contract: Balance,
+    #[serde(with = "u128_dec_format")]
     pub function_call: Balance,
+    #[serde(with = "u128_dec_format")]
     pub self_function_call: Balance,
+    #[serde(with = "u128_dec_format")]
     pub send_money: Balance,
+    #[serde(with = "u128_dec_format")]
     pub stake: Balance,
+    #[serde(with = "u128_dec_format")]
     pub swap_key: Balance,
+    #[serde(with = "u128_dec_format")]
     pub add_key: Balance,
+    #[serde(with = "u128_dec_format")]
     pub delete_key: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub delete_account: Balance,
 }
 
 impl TransactionsCosts {
@@ -46,6 +59,8 @@ impl TransactionsCosts {
             SwapKey(_) => self.swap_key.clone(),
             AddKey(_) => self.add_key.clone(),
             DeleteKey(_) => self.delete_key.clone(),
+            DeleteAccount(_) => self.delete_account.clone(),
+            Batch(t) => t.actions.iter().map(|action| self.cost(&t.action_body(action))).sum(),
         }
     }
 }
@@ -9,7 +9,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::{
     AsyncCall, Callback, CallbackInfo, ReceiptBody, ReceiptTransaction,
 };
-use near_primitives::types::{AccountId, Balance, CallbackId, Nonce, PromiseId, ReceiptId};
+use near_primitives::types::{AccountId, Balance, CallbackId, Gas, Nonce, PromiseId, ReceiptId};
 use near_primitives::utils::{create_nonce_with_nonce, key_for_account};
 use near_store::{set_callback, TrieUpdate, TrieUpdateIterator};
 use wasm::ext::{Error as ExtError, External, Result as ExtResult};
@@ -164,6 +164,7 @@ impl<'a> External for RuntimeExt<'a> {
         method_name: Vec<u8>,
         arguments: Vec<u8>,
         amount: Balance,
+        prepaid_gas: Gas,
     ) -> ExtResult<PromiseId> {
         let nonce = self.create_nonce();
         let receipt = ReceiptTransaction::new(
@@ -177,6 +178,7 @@ impl<'a> External for RuntimeExt<'a> {
                 self.refund_account_id.clone(),
                 self.originator_id.clone(),
                 self.public_key.clone(),
+                prepaid_gas,
             )),
         );
         let promise_id = PromiseId::Receipt(nonce.as_ref().to_vec());
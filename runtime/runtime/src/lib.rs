@@ -15,23 +15,25 @@ use near_primitives::crypto::signature::PublicKey;
 use near_primitives::hash::CryptoHash;
 use near_primitives::serialize::from_base64;
 use near_primitives::transaction::{
-    AsyncCall, Callback, CallbackInfo, CallbackResult, FunctionCallTransaction, LogEntry,
-    ReceiptBody, ReceiptTransaction, SignedTransaction, TransactionBody, TransactionResult,
-    TransactionStatus,
+    Action, AddKeyTransaction, AsyncCall, BatchCall, BatchTransaction, Callback, CallbackInfo,
+    CallbackResult, DeleteKeyTransaction, DeployContractTransaction, FunctionCallTransaction,
+    LogEntry, ReceiptBody, ReceiptTransaction, SignedTransaction, TransactionBody,
+    TransactionResult, TransactionStatus,
 };
 use near_primitives::types::StorageUsage;
 use near_primitives::types::{
-    AccountId, Balance, BlockIndex, MerkleHash, PromiseId, ReadablePublicKey, ShardId,
+    AccountId, Balance, BlockIndex, Gas, MerkleHash, PromiseId, ReadablePublicKey, ShardId,
     ValidatorStake,
 };
 use near_primitives::utils::{
-    account_to_shard_id, create_nonce_with_nonce, key_for_account, key_for_callback, system_account,
+    create_nonce_with_nonce, key_for_account, key_for_callback, system_account,
 };
 use near_store::{
     account_storage_size, get_access_key, get_account, get_callback, get_code, set_access_key,
     set_account, set_callback, set_code, StoreUpdate, TrieChanges, TrieUpdate,
 };
-use near_verifier::{TransactionVerifier, VerificationData};
+use near_verifier::{ChainContext, TransactionVerifier, VerificationData};
+use wasm::cache::PersistentCache;
 use wasm::executor;
 use wasm::types::{ReturnData, RuntimeContext};
 
@@ -58,6 +60,33 @@ pub struct ApplyState {
     pub shard_id: ShardId,
     pub block_index: u64,
     pub parent_block_hash: CryptoHash,
+    /// Chain the block is applied on, `None` to not check the transactions against the chain.
+    pub chain_context: Option<ApplyChainContext>,
+}
+
+/// Chain the transactions of the applied block must be signed for and be recent enough on.
+#[derive(Debug)]
+pub struct ApplyChainContext {
+    pub chain_id: String,
+    /// Heights of the known blocks referenced by the transactions.
+    pub reference_indices: HashMap<CryptoHash, BlockIndex>,
+    /// Number of blocks transaction stays valid after the block it references.
+    pub validity_period: BlockIndex,
+}
+
+impl ApplyChainContext {
+    fn for_transaction(
+        &self,
+        block_index: BlockIndex,
+        transaction: &SignedTransaction,
+    ) -> ChainContext {
+        ChainContext {
+            chain_id: &self.chain_id,
+            block_index,
+            reference_index: self.reference_indices.get(&transaction.block_hash).cloned(),
+            validity_period: self.validity_period,
+        }
+    }
 }
 
 pub struct ApplyResult {
@@ -73,11 +102,46 @@ pub struct ApplyResult {
 pub struct Runtime {
     ethash_provider: Arc<Mutex<EthashProvider>>,
     economics_config: EconomicsConfig,
+    /// Whether contract executions are traced into `TransactionResult::trace`.
+    trace: bool,
+    /// Number of shards the accounts are spread across, to route the receipts.
+    num_shards: ShardId,
+    /// On-disk cache of the compiled contracts, if the runtime has a home directory.
+    wasm_cache: Option<Arc<PersistentCache>>,
 }
 
 impl Runtime {
-    pub fn new(ethash_provider: Arc<Mutex<EthashProvider>>) -> Self {
-        Runtime { ethash_provider, economics_config: Default::default() }
+    pub fn new(
+        ethash_provider: Arc<Mutex<EthashProvider>>,
+        economics_config: EconomicsConfig,
+    ) -> Self {
+        Runtime { ethash_provider, economics_config, trace: false, num_shards: 1, wasm_cache: None }
+    }
+
+    /// Same runtime for the chain with the given number of shards.
+    pub fn with_num_shards(self, num_shards: ShardId) -> Self {
+        Runtime { num_shards, ..self }
+    }
+
+    /// Same runtime, that keeps the compiled contracts in the given cache.
+    pub fn with_wasm_cache(self, wasm_cache: Arc<PersistentCache>) -> Self {
+        Runtime { wasm_cache: Some(wasm_cache), ..self }
+    }
+
+    fn wasm_cache(&self) -> Option<&PersistentCache> {
+        self.wasm_cache.as_ref().map(|cache| &**cache)
+    }
+
+    /// Same runtime, that also records the traces of the contract executions. Used to debug the
+    /// calls, the traces are not part of the state and are dropped when the results are stored.
+    pub fn with_trace(&self) -> Self {
+        Runtime {
+            ethash_provider: self.ethash_provider.clone(),
+            economics_config: self.economics_config.clone(),
+            trace: true,
+            num_shards: self.num_shards,
+            wasm_cache: self.wasm_cache.clone(),
+        }
     }
 
     fn call_function(
@@ -117,6 +181,7 @@ impl Runtime {
                     refund_account_id.clone(),
                     transaction.originator.clone(),
                     public_key,
+                    transaction.prepaid_gas,
                 )),
             );
             Ok(vec![receipt])
@@ -178,7 +243,7 @@ impl Runtime {
 
         let mut leftover_balance = 0;
 
-        let res = self.apply_async_call(
+        let mut res = self.apply_async_call(
             state_update,
             &AsyncCall::new(
                 transaction.method_name.clone(),
@@ -187,6 +252,7 @@ impl Runtime {
                 refund_account_id.clone(),
                 transaction.originator.clone(),
                 public_key.clone(),
+                transaction.prepaid_gas,
             ),
             &transaction.originator,
             &transaction.originator,
@@ -198,12 +264,102 @@ impl Runtime {
         );
 
         if leftover_balance > 0 {
-            account.amount += leftover_balance;
-            set_account(state_update, &transaction.originator, account);
+            if refund_account_id == &transaction.originator {
+                account.amount += leftover_balance;
+                set_account(state_update, &transaction.originator, account);
+            } else {
+                res = res.map(|mut receipts| {
+                    receipts.push(ReceiptTransaction::new(
+                        transaction.originator.clone(),
+                        refund_account_id.clone(),
+                        // Nonces of the receipts created by the contract are derived from `hash`.
+                        create_nonce_with_nonce(&hash, u64::max_value()),
+                        ReceiptBody::Refund(leftover_balance),
+                    ));
+                    receipts
+                });
+            }
         }
         res
     }
 
+    /// Replaces the code of the account and calls the migration method of the new code, if any.
+    /// A failed migration fails the transaction, so the old code and state are kept.
+    fn deploy_contract(
+        &self,
+        state_update: &mut TrieUpdate,
+        transaction: &DeployContractTransaction,
+        hash: CryptoHash,
+        account: &mut Account,
+        refund_account_id: &AccountId,
+        public_key: PublicKey,
+        block_index: BlockIndex,
+        transaction_result: &mut TransactionResult,
+    ) -> Result<Vec<ReceiptTransaction>, String> {
+        let previous_code_hash = account.code_hash;
+        system::deploy(
+            state_update,
+            &transaction.contract_id,
+            &transaction.wasm_byte_array,
+            account,
+            &self.economics_config.wasm_config,
+            transaction_result,
+        )?;
+        let receipts = match transaction.migration {
+            Some(ref migration) => self.self_function_call(
+                state_update,
+                &FunctionCallTransaction {
+                    nonce: transaction.nonce,
+                    originator: transaction.contract_id.clone(),
+                    contract_id: transaction.contract_id.clone(),
+                    method_name: migration.method_name.clone(),
+                    args: migration.args.clone(),
+                    amount: migration.amount,
+                    prepaid_gas: migration.prepaid_gas,
+                },
+                hash,
+                account,
+                refund_account_id,
+                public_key,
+                block_index,
+                transaction_result,
+            )?,
+            None => vec![],
+        };
+        transaction_result.previous_code_hash = Some(previous_code_hash);
+        Ok(receipts)
+    }
+
+    /// Balance charged for the given transaction before it is executed, excluding storage rent.
+    pub fn transaction_cost(&self, transaction_body: &TransactionBody) -> Balance {
+        self.economics_config.transactions_costs.cost(transaction_body)
+    }
+
+    /// Most the given transaction can pay for its execution: the transaction cost and the gas
+    /// prepaid by its function calls, as far as their amounts, that the gas is charged from,
+    /// cover it. Used to prioritize the transactions.
+    pub fn transaction_fee(&self, transaction_body: &TransactionBody) -> Balance {
+        let usage_limit = Balance::from(self.economics_config.wasm_config.usage_limit);
+        let gas_fee = |amount: Balance, prepaid_gas: Gas| {
+            std::cmp::min(amount, std::cmp::min(Balance::from(prepaid_gas), usage_limit))
+        };
+        let prepaid = match transaction_body {
+            TransactionBody::FunctionCall(t) => gas_fee(t.amount, t.prepaid_gas),
+            TransactionBody::Batch(t) => t
+                .actions
+                .iter()
+                .map(|action| match action {
+                    Action::FunctionCall { amount, prepaid_gas, .. } => {
+                        gas_fee(*amount, *prepaid_gas)
+                    }
+                    _ => 0,
+                })
+                .sum(),
+            _ => 0,
+        };
+        self.transaction_cost(transaction_body).saturating_add(prepaid)
+    }
+
     /// Subtracts the storage rent from the given account balance.
     fn apply_rent(&self, account_id: &AccountId, account: &mut Account, block_index: BlockIndex) {
         // The number of bytes the account occupies in the Trie.
@@ -224,43 +380,185 @@ impl Runtime {
         state_update: &mut TrieUpdate,
         block_index: BlockIndex,
         transaction: &SignedTransaction,
+        chain_context: Option<ChainContext>,
         validator_proposals: &mut Vec<ValidatorStake>,
         transaction_result: &mut TransactionResult,
     ) -> Result<Vec<ReceiptTransaction>, String> {
-        let VerificationData { originator_id, mut originator, public_key, .. } = {
-            let verifier = TransactionVerifier::new(state_update);
+        let VerificationData { originator_id, mut originator, public_key, access_key } = {
+            let verifier = TransactionVerifier::new(state_update)
+                .with_transaction_cost(self.transaction_cost(&transaction.body));
+            let verifier = match chain_context {
+                Some(chain_context) => verifier.with_chain_context(chain_context),
+                None => verifier,
+            };
             verifier.verify_transaction(transaction)?
         };
         originator.nonce = transaction.body.get_nonce();
-        let transaction_cost = self.economics_config.transactions_costs.cost(&transaction.body);
-        originator.checked_sub(transaction_cost)?;
+        let transaction_cost = self.transaction_cost(&transaction.body);
+        let refund_account_id = match access_key {
+            Some(mut access_key) => {
+                // Access keys pay for the transaction and its deposit from their own allowance.
+                let deposit = match transaction.body {
+                    TransactionBody::FunctionCall(ref t) => t.amount,
+                    _ => 0,
+                };
+                let total_cost = transaction_cost + deposit;
+                access_key.amount = access_key.amount.checked_sub(total_cost).ok_or_else(|| {
+                    format!(
+                        "Access key {} of account {} has allowance {}, but transaction costs {}",
+                        public_key, originator_id, access_key.amount, total_cost
+                    )
+                })?;
+                set_access_key(state_update, &originator_id, &public_key, &access_key);
+                // The deposit is passed through the account, which forwards it to the receiver.
+                originator.amount += deposit;
+                access_key.balance_owner.unwrap_or_else(|| originator_id.clone())
+            }
+            None => {
+                originator.checked_sub(transaction_cost)?;
+                originator_id.clone()
+            }
+        };
         self.apply_rent(&originator_id, &mut originator, block_index);
         set_account(state_update, &originator_id, &originator);
         state_update.commit();
 
-        let refund_account_id = &originator_id;
-        match transaction.body {
-            TransactionBody::SendMoney(ref t) => system::send_money(
+        if let TransactionBody::Batch(ref batch) = transaction.body {
+            if batch.receiver != originator_id {
+                return Self::send_batch(
+                    state_update,
+                    batch,
+                    transaction.get_hash(),
+                    &originator_id,
+                    &mut originator,
+                    &refund_account_id,
+                    public_key,
+                );
+            }
+            // Effects of the actions stay uncommitted until the whole batch succeeds.
+            let mut receipts = vec![];
+            for (i, action) in batch.actions.iter().enumerate() {
+                receipts.append(&mut self.apply_transaction_body(
+                    state_update,
+                    block_index,
+                    &batch.action_body(action),
+                    create_nonce_with_nonce(&transaction.get_hash(), i as u64),
+                    &originator_id,
+                    &mut originator,
+                    &refund_account_id,
+                    public_key,
+                    validator_proposals,
+                    transaction_result,
+                )?);
+            }
+            Ok(receipts)
+        } else {
+            self.apply_transaction_body(
                 state_update,
-                &t,
+                block_index,
+                &transaction.body,
                 transaction.get_hash(),
+                &originator_id,
                 &mut originator,
+                &refund_account_id,
+                public_key,
+                validator_proposals,
+                transaction_result,
+            )
+        }
+    }
+
+    /// Checks the actions of the batch for another account, charges their deposit from the
+    /// originator and sends them to the receiver, which applies them together. The actions that
+    /// can only be applied by the owner of the account are allowed if the batch starts with
+    /// creating the receiver, since the originator then sets up its own new account.
+    fn send_batch(
+        state_update: &mut TrieUpdate,
+        batch: &BatchTransaction,
+        hash: CryptoHash,
+        originator_id: &AccountId,
+        originator: &mut Account,
+        refund_account_id: &AccountId,
+        public_key: PublicKey,
+    ) -> Result<Vec<ReceiptTransaction>, String> {
+        let creates_receiver = match batch.actions.first() {
+            Some(Action::CreateAccount { .. }) => true,
+            _ => false,
+        };
+        for (i, action) in batch.actions.iter().enumerate() {
+            let allowed = match action {
+                Action::CreateAccount { .. } => i == 0,
+                // Validator proposals of the receiver can't be made from a receipt.
+                Action::Stake { .. } => false,
+                _ => !action.is_owner_only() || creates_receiver,
+            };
+            if !allowed {
+                return Err(format!(
+                    "Account {} tries to apply {:?} to account {}",
+                    originator_id, action, batch.receiver
+                ));
+            }
+        }
+        let call = BatchCall {
+            actions: batch.actions.clone(),
+            refund_account: refund_account_id.clone(),
+            originator_id: originator_id.clone(),
+            public_key,
+        };
+        let deposit = call.deposit();
+        originator.amount = originator.amount.checked_sub(deposit).ok_or_else(|| {
+            format!(
+                "Account {} tries to send batch with {} attached, but only has {}",
+                originator_id, deposit, originator.amount
+            )
+        })?;
+        set_account(state_update, originator_id, originator);
+        Ok(vec![ReceiptTransaction::new(
+            originator_id.clone(),
+            batch.receiver.clone(),
+            create_nonce_with_nonce(&hash, 0),
+            ReceiptBody::Batch(call),
+        )])
+    }
+
+    /// Applies a single (not batched) transaction body on behalf of the originator. `hash` is
+    /// used to derive nonces of the produced receipts, unused balance is refunded to
+    /// `refund_account_id`.
+    fn apply_transaction_body(
+        &self,
+        state_update: &mut TrieUpdate,
+        block_index: BlockIndex,
+        body: &TransactionBody,
+        hash: CryptoHash,
+        originator_id: &AccountId,
+        originator: &mut Account,
+        refund_account_id: &AccountId,
+        public_key: PublicKey,
+        validator_proposals: &mut Vec<ValidatorStake>,
+        transaction_result: &mut TransactionResult,
+    ) -> Result<Vec<ReceiptTransaction>, String> {
+        match *body {
+            TransactionBody::SendMoney(ref t) => system::send_money(
+                state_update,
+                &t,
+                hash,
+                originator,
                 refund_account_id,
                 public_key,
             ),
             TransactionBody::Stake(ref t) => system::staking(
                 state_update,
                 &t,
-                &originator_id,
-                &mut originator,
+                originator_id,
+                originator,
                 validator_proposals,
             ),
-            TransactionBody::FunctionCall(ref t) if originator_id == t.contract_id => self
+            TransactionBody::FunctionCall(ref t) if originator_id == &t.contract_id => self
                 .self_function_call(
                     state_update,
                     &t,
-                    transaction.get_hash(),
-                    &mut originator,
+                    hash,
+                    originator,
                     refund_account_id,
                     public_key,
                     block_index,
@@ -269,27 +567,45 @@ impl Runtime {
             TransactionBody::FunctionCall(ref t) => self.call_function(
                 state_update,
                 &t,
-                transaction.get_hash(),
-                &mut originator,
+                hash,
+                originator,
                 refund_account_id,
                 public_key,
             ),
-            TransactionBody::DeployContract(ref t) => {
-                system::deploy(state_update, &t.contract_id, &t.wasm_byte_array, &mut originator)
-            }
+            TransactionBody::DeployContract(ref t) => self.deploy_contract(
+                state_update,
+                &t,
+                hash,
+                originator,
+                refund_account_id,
+                public_key,
+                block_index,
+                transaction_result,
+            ),
             TransactionBody::CreateAccount(ref t) => system::create_account(
                 state_update,
                 t,
-                transaction.get_hash(),
-                &mut originator,
+                hash,
+                originator,
                 refund_account_id,
                 public_key,
             ),
-            TransactionBody::SwapKey(ref t) => system::swap_key(state_update, t, &mut originator),
-            TransactionBody::AddKey(ref t) => system::add_key(state_update, t, &mut originator),
+            TransactionBody::SwapKey(ref t) => system::swap_key(state_update, t, originator),
+            TransactionBody::AddKey(ref t) => system::add_key(state_update, t, originator),
             TransactionBody::DeleteKey(ref t) => {
-                system::delete_key(state_update, t, &mut originator, transaction.get_hash())
+                system::delete_key(state_update, t, originator, hash)
+            }
+            TransactionBody::DeleteAccount(ref t) => {
+                system::delete_account(
+                    state_update,
+                    t,
+                    hash,
+                    originator,
+                    public_key,
+                    self.num_shards,
+                )
             }
+            TransactionBody::Batch(_) => Err("Batched transactions can't be nested".to_string()),
         }
     }
 
@@ -408,22 +724,28 @@ impl Runtime {
                 &async_call.args,
                 &[],
                 &mut runtime_ext,
-                &wasm::types::Config::default(),
+                &self.economics_config.wasm_config,
                 &RuntimeContext::new(
                     receiver.amount,
                     async_call.amount,
+                    async_call.prepaid_gas,
                     sender_id,
                     receiver_id,
                     receiver.storage_usage,
                     block_index,
                     nonce.as_ref().to_vec(),
                     false,
+                    self.trace,
                     &async_call.originator_id,
                     &async_call.public_key,
                 ),
+                self.wasm_cache(),
             )
             .map_err(|e| format!("wasm async call preparation failed with error: {:?}", e))?;
             transaction_result.logs.append(&mut wasm_res.logs);
+            transaction_result.events.append(&mut wasm_res.events);
+            transaction_result.gas_used += wasm_res.gas_used;
+            transaction_result.traces.extend(wasm_res.trace.take());
             let balance = wasm_res.frozen_balance;
             *leftover_balance = wasm_res.liquid_balance;
             let storage_usage = wasm_res.storage_usage;
@@ -447,6 +769,116 @@ impl Runtime {
         result
     }
 
+    /// Applies the actions of the batch sent by another account to the receiver, creating the
+    /// receiver first if the batch starts with `CreateAccount`. Fails as a whole, so the caller
+    /// rolls back the effects of the applied actions and refunds the deposit.
+    fn apply_batch_call(
+        &self,
+        state_update: &mut TrieUpdate,
+        batch: &BatchCall,
+        receipt: &ReceiptTransaction,
+        receiver: Option<Account>,
+        leftover_balance: &mut Balance,
+        block_index: BlockIndex,
+        transaction_result: &mut TransactionResult,
+    ) -> Result<Vec<ReceiptTransaction>, String> {
+        let receiver_id = &receipt.receiver;
+        // The action creating the receiver is applied before the others.
+        let (mut receiver, created) = match (receiver, batch.actions.first()) {
+            (Some(_), Some(Action::CreateAccount { .. })) => {
+                return Err(format!("Account {} already exists", receiver_id));
+            }
+            (Some(receiver), _) => (receiver, 0),
+            (None, Some(Action::CreateAccount { amount, public_key })) => {
+                let call = AsyncCall::new(
+                    SYSTEM_METHOD_CREATE_ACCOUNT.to_vec(),
+                    public_key.clone(),
+                    *amount,
+                    batch.refund_account.clone(),
+                    batch.originator_id.clone(),
+                    batch.public_key,
+                    0,
+                );
+                system_create_account(state_update, &call, receiver_id)?;
+                let receiver = get_account(state_update, receiver_id)
+                    .ok_or_else(|| format!("Account {} was not created", receiver_id))?;
+                (receiver, 1)
+            }
+            (None, _) => return Err(format!("receiver {} does not exist", receiver_id)),
+        };
+        let mut receipts = vec![];
+        let mut leftover = 0;
+        for (i, action) in batch.actions.iter().enumerate().skip(created) {
+            let nonce = create_nonce_with_nonce(&receipt.nonce, i as u64);
+            let mut new_receipts = match action.clone() {
+                Action::CreateAccount { .. } => {
+                    Err("Account can only be created by the first action".to_string())
+                }
+                Action::Transfer { amount } => {
+                    receiver.amount += amount;
+                    set_account(state_update, receiver_id, &receiver);
+                    Ok(vec![])
+                }
+                Action::DeployContract { code } => system::deploy(
+                    state_update,
+                    receiver_id,
+                    &code,
+                    &mut receiver,
+                    &self.economics_config.wasm_config,
+                    transaction_result,
+                ),
+                Action::FunctionCall { method_name, args, amount, prepaid_gas } => {
+                    let call = AsyncCall::new(
+                        method_name,
+                        args,
+                        amount,
+                        batch.refund_account.clone(),
+                        batch.originator_id.clone(),
+                        batch.public_key,
+                        prepaid_gas,
+                    );
+                    let mut call_leftover = 0;
+                    let result = self.apply_async_call(
+                        state_update,
+                        &call,
+                        &receipt.originator,
+                        receiver_id,
+                        &nonce,
+                        &mut receiver,
+                        &mut call_leftover,
+                        block_index,
+                        transaction_result,
+                    );
+                    leftover += call_leftover;
+                    result
+                }
+                Action::Stake { .. } => {
+                    Err(format!("Account {} can't be staked from a receipt", receiver_id))
+                }
+                Action::AddKey { new_key, access_key } => system::add_key(
+                    state_update,
+                    &AddKeyTransaction {
+                        nonce: 0,
+                        originator: receiver_id.clone(),
+                        new_key,
+                        access_key,
+                        top_up: false,
+                    },
+                    &mut receiver,
+                ),
+                Action::DeleteKey { cur_key } => system::delete_key(
+                    state_update,
+                    &DeleteKeyTransaction { nonce: 0, originator: receiver_id.clone(), cur_key },
+                    &mut receiver,
+                    nonce,
+                ),
+            }?;
+            receipts.append(&mut new_receipts);
+        }
+        *leftover_balance = leftover;
+        Ok(receipts)
+    }
+
     fn apply_callback(
         &self,
         state_update: &mut TrieUpdate,
@@ -482,29 +914,37 @@ impl Runtime {
 
                     *refund_account = callback.refund_account.clone();
                     needs_removal = true;
+                    let config = &self.economics_config.wasm_config;
                     executor::execute(
                         &code,
                         &callback.method_name,
                         &callback.args,
                         &callback.results,
                         &mut runtime_ext,
-                        &wasm::types::Config::default(),
+                        config,
                         &RuntimeContext::new(
                             receiver.amount,
                             callback.amount,
+                            // Callbacks don't have gas attached, they are only bounded by config.
+                            config.usage_limit,
                             sender_id,
                             receiver_id,
                             receiver.storage_usage,
                             block_index,
                             nonce.as_ref().to_vec(),
                             false,
+                            self.trace,
                             &callback.originator_id,
                             &callback.public_key,
                         ),
+                        self.wasm_cache(),
                     )
                     .map_err(|e| format!("wasm callback execution failed with error: {:?}", e))
                     .and_then(|mut res| {
                         transaction_result.logs.append(&mut res.logs);
+                        transaction_result.events.append(&mut res.events);
+                        transaction_result.gas_used += res.gas_used;
+                        transaction_result.traces.extend(res.trace.take());
                         let balance = res.frozen_balance;
                         *leftover_balance = res.liquid_balance;
                         let storage_usage = res.storage_usage;
@@ -619,18 +1059,45 @@ impl Runtime {
                     set_account(state_update, &receipt.receiver, &receiver);
                     Ok(vec![])
                 }
+                ReceiptBody::Batch(batch) => {
+                    amount = batch.deposit();
+                    refund_account = batch.refund_account.clone();
+                    self.apply_batch_call(
+                        state_update,
+                        batch,
+                        receipt,
+                        Some(receiver),
+                        &mut leftover_balance,
+                        block_index,
+                        transaction_result,
+                    )
+                }
             },
             _ => {
                 let err = Err(format!("receiver {} does not exist", receipt.receiver));
-                if let ReceiptBody::NewCall(call) = &receipt.body {
-                    amount = call.amount;
-                    if call.method_name == SYSTEM_METHOD_CREATE_ACCOUNT {
-                        system_create_account(state_update, &call, &receipt.receiver)
-                    } else {
-                        err
+                match &receipt.body {
+                    ReceiptBody::NewCall(call) => {
+                        amount = call.amount;
+                        if call.method_name == SYSTEM_METHOD_CREATE_ACCOUNT {
+                            system_create_account(state_update, &call, &receipt.receiver)
+                        } else {
+                            err
+                        }
                     }
-                } else {
-                    err
+                    ReceiptBody::Batch(batch) => {
+                        amount = batch.deposit();
+                        refund_account = batch.refund_account.clone();
+                        self.apply_batch_call(
+                            state_update,
+                            batch,
+                            receipt,
+                            None,
+                            &mut leftover_balance,
+                            block_index,
+                            transaction_result,
+                        )
+                    }
+                    _ => err,
                 }
             }
         };
@@ -694,6 +1161,7 @@ impl Runtime {
         state_update: &mut TrieUpdate,
         block_index: BlockIndex,
         transaction: &SignedTransaction,
+        chain_context: Option<ChainContext>,
         new_receipts: &mut HashMap<ShardId, Vec<ReceiptTransaction>>,
         validator_proposals: &mut Vec<ValidatorStake>,
     ) -> TransactionResult {
@@ -702,13 +1170,14 @@ impl Runtime {
             state_update,
             block_index,
             transaction,
+            chain_context,
             validator_proposals,
             &mut result,
         ) {
             Ok(receipts) => {
                 for receipt in receipts {
                     result.receipts.push(receipt.nonce);
-                    let shard_id = receipt.shard_id();
+                    let shard_id = receipt.shard_id(self.num_shards);
                     new_receipts.entry(shard_id).or_insert_with(|| vec![]).push(receipt);
                 }
                 state_update.commit();
@@ -716,6 +1185,8 @@ impl Runtime {
             }
             Err(s) => {
                 state_update.rollback();
+                // Events of the rolled back executions never took effect.
+                result.events.clear();
                 result.logs.push(format!("Runtime error: {}", s));
                 result.status = TransactionStatus::Failed;
             }
@@ -733,7 +1204,7 @@ impl Runtime {
         new_receipts: &mut HashMap<ShardId, Vec<ReceiptTransaction>>,
     ) -> TransactionResult {
         let mut result = TransactionResult::default();
-        if account_to_shard_id(&receipt.receiver) == shard_id {
+        if receipt.shard_id(self.num_shards) == shard_id {
             let mut tmp_new_receipts = vec![];
             let apply_result = self.apply_receipt(
                 state_update,
@@ -744,7 +1215,7 @@ impl Runtime {
             );
             for receipt in tmp_new_receipts {
                 result.receipts.push(receipt.nonce);
-                let shard_id = receipt.shard_id();
+                let shard_id = receipt.shard_id(self.num_shards);
                 new_receipts.entry(shard_id).or_insert_with(|| vec![]).push(receipt);
             }
             match apply_result {
@@ -754,6 +1225,7 @@ impl Runtime {
                 }
                 Err(s) => {
                     state_update.rollback();
+                    result.events.clear();
                     result.logs.push(format!("Runtime error: {}", s));
                     result.status = TransactionStatus::Failed;
                 }
@@ -803,10 +1275,15 @@ impl Runtime {
                 }
             };
 
+            let chain_context = apply_state
+                .chain_context
+                .as_ref()
+                .map(|chain_context| chain_context.for_transaction(block_index, transaction));
             tx_result.push(self.process_transaction(
                 &mut state_update,
                 block_index,
                 transaction,
+                chain_context,
                 &mut new_receipts,
                 &mut validator_proposals,
             ));
@@ -910,4 +1387,29 @@ mod tests {
         let get_res = get_account(&new_state_update, &account_id).unwrap();
         assert_eq!(test_account, get_res);
     }
+
+    #[test]
+    fn test_transaction_fee() {
+        let dir = tempdir::TempDir::new("ethash_test").unwrap();
+        let mut economics_config = EconomicsConfig::default();
+        economics_config.transactions_costs.function_call = 10;
+        economics_config.wasm_config.usage_limit = 1_000;
+        let runtime =
+            Runtime::new(Arc::new(Mutex::new(EthashProvider::new(dir.path()))), economics_config);
+        let call = |amount, prepaid_gas| {
+            TransactionBody::FunctionCall(FunctionCallTransaction {
+                nonce: 1,
+                originator: "alice.near".to_string(),
+                contract_id: bob_account(),
+                method_name: b"run_test".to_vec(),
+                args: vec![],
+                amount,
+                prepaid_gas,
+            })
+        };
+        assert_eq!(runtime.transaction_fee(&call(100, 50)), 60);
+        // Gas is only paid as far as the attached amount and the usage limit cover it.
+        assert_eq!(runtime.transaction_fee(&call(20, 50)), 30);
+        assert_eq!(runtime.transaction_fee(&call(5_000, 2_000)), 1_010);
+    }
 }
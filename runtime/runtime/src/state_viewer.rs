@@ -7,9 +7,10 @@ use near_primitives::account::AccessKey;
 use near_primitives::crypto::signature::PublicKey;
 use near_primitives::hash::CryptoHash;
 use near_primitives::rpc::{AccountViewCallResult, ViewStateResult};
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Gas};
 use near_primitives::utils::{is_valid_account_id, key_for_account};
 use near_store::{get_access_key, get_account, TrieUpdate};
+use wasm::cache::PersistentCache;
 use wasm::executor;
 use wasm::types::{ReturnData, RuntimeContext};
 
@@ -21,11 +22,18 @@ use super::RuntimeExt;
 
 pub struct TrieViewer {
     ethash_provider: Arc<Mutex<EthashProvider>>,
+    /// On-disk cache of the compiled contracts, shared with the runtime.
+    wasm_cache: Option<Arc<PersistentCache>>,
 }
 
 impl TrieViewer {
     pub fn new(ethash_provider: Arc<Mutex<EthashProvider>>) -> Self {
-        Self { ethash_provider }
+        Self { ethash_provider, wasm_cache: None }
+    }
+
+    /// Same viewer, that keeps the compiled contracts in the given cache.
+    pub fn with_wasm_cache(self, wasm_cache: Arc<PersistentCache>) -> Self {
+        Self { wasm_cache: Some(wasm_cache), ..self }
     }
 
     pub fn view_account(
@@ -130,15 +138,18 @@ impl TrieViewer {
                     &RuntimeContext::new(
                         account.amount,
                         0,
+                        Gas::max_value(),
                         originator_id,
                         contract_id,
                         0,
                         block_index,
                         root.as_ref().into(),
                         true,
+                        false,
                         originator_id,
                         &public_key,
                     ),
+                    self.wasm_cache.as_ref().map(|cache| &**cache),
                 )
             }
             None => return Err(format!("contract {} does not exist", contract_id).into()),
@@ -6,13 +6,19 @@ use near_primitives::crypto::signature::PublicKey;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::{
     AddKeyTransaction, AsyncCall, CallbackInfo, CallbackResult, CreateAccountTransaction,
-    DeleteKeyTransaction, ReceiptBody, ReceiptTransaction, SendMoneyTransaction, StakeTransaction,
-    SwapKeyTransaction,
+    DeleteAccountTransaction, DeleteKeyTransaction, ReceiptBody, ReceiptTransaction,
+    SendMoneyTransaction, StakeTransaction, SwapKeyTransaction, TransactionResult,
 };
-use near_primitives::types::{AccountId, Balance, ValidatorStake};
-use near_primitives::utils::{create_nonce_with_nonce, is_valid_account_id, key_for_access_key};
-use near_store::TrieUpdate;
+use near_primitives::types::{AccountId, Balance, ShardId, ValidatorStake};
+use near_primitives::utils::{
+    account_to_shard_id, create_nonce_with_nonce, is_valid_account_id, key_for_access_key,
+    key_for_account, key_for_code, prefix_for_access_key,
+};
+use near_store::{get_access_key_raw, get_account, TrieUpdate};
+use wasm::prepare;
+use wasm::types::Config;
 
+use crate::ext::ACCOUNT_DATA_SEPARATOR;
 use crate::{get_access_key, set_access_key, set_account, set_code};
 
 pub const SYSTEM_METHOD_CREATE_ACCOUNT: &[u8] = b"_sys:create_account";
@@ -46,6 +52,7 @@ pub fn send_money(
                 refund_account_id.clone(),
                 transaction.originator.clone(),
                 public_key,
+                0,
             )),
         );
         Ok(vec![receipt])
@@ -147,6 +154,7 @@ pub fn create_account(
                 refund_account_id.clone(),
                 body.originator.clone(),
                 public_key,
+                0,
             )),
         );
         Ok(vec![receipt])
@@ -163,8 +171,15 @@ pub fn deploy(
     sender_id: &AccountId,
     code: &[u8],
     sender: &mut Account,
+    config: &Config,
+    transaction_result: &mut TransactionResult,
 ) -> Result<Vec<ReceiptTransaction>, String> {
     let code = ContractCode::new(code.to_vec());
+    if let Err(err) = prepare::validate_contract(&code, config) {
+        let message = format!("Contract preparation failed with error: {:?}", err);
+        transaction_result.prepare_error = Some(err);
+        return Err(message);
+    }
     // Signature should be already checked at this point
     sender.code_hash = code.get_hash();
     set_code(state_update, &sender_id, &code);
@@ -195,6 +210,9 @@ pub fn add_key(
     account: &mut Account,
 ) -> Result<Vec<ReceiptTransaction>, String> {
     let new_key = PublicKey::try_from(&body.new_key as &[u8]).map_err(|e| format!("{}", e))?;
+    if body.top_up {
+        return top_up_access_key(state_update, body, &new_key, account);
+    }
     let num_keys = account.public_keys.len();
     account.public_keys.retain(|&x| x != new_key);
     if account.public_keys.len() < num_keys {
@@ -233,6 +251,40 @@ pub fn add_key(
     Ok(vec![])
 }
 
+/// Moves the amount of the given access key from the account balance to the allowance of the
+/// existing access key. Permissions of the existing access key must match the given ones.
+fn top_up_access_key(
+    state_update: &mut TrieUpdate,
+    body: &AddKeyTransaction,
+    public_key: &PublicKey,
+    account: &mut Account,
+) -> Result<Vec<ReceiptTransaction>, String> {
+    let top_up = body.access_key.as_ref().ok_or_else(|| {
+        "Access key with the amount is required to top up the allowance".to_string()
+    })?;
+    let mut access_key =
+        get_access_key(state_update, &body.originator, public_key).ok_or_else(|| {
+            format!("Account {} doesn't have access key {} to top up", body.originator, public_key)
+        })?;
+    if access_key.balance_owner != top_up.balance_owner
+        || access_key.contract_id != top_up.contract_id
+        || access_key.method_name != top_up.method_name
+    {
+        return Err("Cannot change permissions of the access key while topping it up".to_string());
+    }
+    if account.amount < top_up.amount {
+        return Err(format!(
+            "Account {} tries to top up access key with {} amount, but only has {}",
+            body.originator, top_up.amount, account.amount
+        ));
+    }
+    account.amount -= top_up.amount;
+    set_account(state_update, &body.originator, &account);
+    access_key.amount += top_up.amount;
+    set_access_key(state_update, &body.originator, public_key, &access_key);
+    Ok(vec![])
+}
+
 pub fn delete_key(
     state_update: &mut TrieUpdate,
     body: &DeleteKeyTransaction,
@@ -275,6 +327,95 @@ pub fn delete_key(
     Ok(new_receipts)
 }
 
+/// Removes the account together with its code, access keys and contract data. The liquid balance
+/// is deposited to the beneficiary, balances of access keys are refunded to their balance owners.
+/// Fails if the beneficiary is not a valid account id, or doesn't exist on the same shard, so that
+/// the deposit isn't lost for the lack of the receiver.
+pub fn delete_account(
+    state_update: &mut TrieUpdate,
+    body: &DeleteAccountTransaction,
+    hash: CryptoHash,
+    account: &Account,
+    public_key: PublicKey,
+    num_shards: ShardId,
+) -> Result<Vec<ReceiptTransaction>, String> {
+    if account.staked > 0 {
+        return Err(format!(
+            "Account {} tries to delete itself, but has staked {}",
+            body.originator, account.staked
+        ));
+    }
+    if body.beneficiary_id == body.originator {
+        return Err(format!("Account {} can't be the beneficiary of itself", body.originator));
+    }
+    if !is_valid_account_id(&body.beneficiary_id) {
+        return Err(format!("Beneficiary {} {}", body.beneficiary_id, INVALID_ACCOUNT_ID));
+    }
+    let same_shard = account_to_shard_id(&body.beneficiary_id, num_shards)
+        == account_to_shard_id(&body.originator, num_shards);
+    if same_shard && get_account(state_update, &body.beneficiary_id).is_none() {
+        return Err(format!(
+            "Account {} tries to delete itself, but the beneficiary {} does not exist",
+            body.originator, body.beneficiary_id
+        ));
+    }
+    let mut amount = account.amount;
+    let mut refunds = vec![];
+
+    let mut access_keys = vec![];
+    state_update.for_keys_with_prefix(&prefix_for_access_key(&body.originator), |key| {
+        access_keys.push(key.to_vec())
+    });
+    for key in access_keys {
+        if let Some(access_key) = get_access_key_raw(state_update, &key) {
+            let balance_owner_id: &AccountId =
+                access_key.balance_owner.as_ref().unwrap_or(&body.originator);
+            if balance_owner_id != &body.originator && access_key.amount > 0 {
+                refunds.push(ReceiptTransaction::new(
+                    body.originator.clone(),
+                    balance_owner_id.clone(),
+                    create_nonce_with_nonce(&hash, refunds.len() as u64 + 1),
+                    ReceiptBody::Refund(access_key.amount),
+                ));
+            } else {
+                amount += access_key.amount;
+            }
+        }
+        state_update.remove(&key);
+    }
+
+    let mut data_prefix = key_for_account(&body.originator);
+    data_prefix.extend_from_slice(ACCOUNT_DATA_SEPARATOR);
+    let mut data_keys = vec![];
+    state_update.for_keys_with_prefix(&data_prefix, |key| data_keys.push(key.to_vec()));
+    for key in data_keys {
+        state_update.remove(&key);
+    }
+    state_update.remove(&key_for_code(&body.originator));
+    state_update.remove(&key_for_account(&body.originator));
+
+    let mut receipts = vec![];
+    if amount > 0 {
+        receipts.push(ReceiptTransaction::new(
+            body.originator.clone(),
+            body.beneficiary_id.clone(),
+            create_nonce_with_nonce(&hash, 0),
+            ReceiptBody::NewCall(AsyncCall::new(
+                // Empty method name is used for deposit
+                vec![],
+                vec![],
+                amount,
+                body.originator.clone(),
+                body.originator.clone(),
+                public_key,
+                0,
+            )),
+        ));
+    }
+    receipts.append(&mut refunds);
+    Ok(receipts)
+}
+
 pub fn system_create_account(
     state_update: &mut TrieUpdate,
     call: &AsyncCall,
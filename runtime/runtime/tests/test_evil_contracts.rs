@@ -1,5 +1,6 @@
+use near_primitives::contract::PrepareError;
 use near_primitives::transaction::{
-    CreateAccountTransaction, DeployContractTransaction, TransactionBody,
+    CreateAccountTransaction, DeployContractTransaction, TransactionBody, TransactionStatus,
 };
 use near_primitives::types::Balance;
 use testlib::node::{Node, RuntimeNode};
@@ -20,14 +21,28 @@ fn setup_test_contract(wasm_binary: &[u8]) -> RuntimeNode {
     let user = node.user();
     user.add_transaction(transaction).unwrap();
 
+    node.set_code_unchecked(&"test_contract".to_string(), wasm_binary);
+    node
+}
+
+/// Floating point operations emitted by AssemblyScript are rejected when the contract is deployed.
+#[test]
+fn test_evil_deploy_floats() {
+    let node = RuntimeNode::new(&"alice.near".to_string());
+    let account_id = node.account_id().unwrap();
     let transaction = TransactionBody::DeployContract(DeployContractTransaction {
         nonce: node.get_account_nonce(&account_id).unwrap_or_default() + 1,
-        contract_id: "test_contract".to_string(),
-        wasm_byte_array: wasm_binary.to_vec(),
+        contract_id: account_id.clone(),
+        wasm_byte_array: include_bytes!("../../../tests/hello.wasm").to_vec(),
     })
     .sign(&*node.signer());
+    let tx_hash = transaction.get_hash();
+    let user = node.user();
     user.add_transaction(transaction).unwrap();
-    node
+
+    let transaction_result = user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.prepare_error, Some(PrepareError::FloatingPoint));
 }
 
 #[test]
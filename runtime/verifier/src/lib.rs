@@ -2,7 +2,7 @@ use near_primitives::account::{AccessKey, Account};
 use near_primitives::crypto::signature::{verify, PublicKey};
 use near_primitives::logging;
 use near_primitives::transaction::{SignedTransaction, TransactionBody};
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Balance, BlockIndex};
 use near_primitives::utils::is_valid_account_id;
 use near_store::{get_access_key, get_account, TrieUpdate};
 
@@ -13,19 +13,73 @@ pub struct VerificationData {
     pub access_key: Option<AccessKey>,
 }
 
+/// Chain the transaction is checked against before it is accepted for inclusion.
+pub struct ChainContext<'a> {
+    /// Id of the chain, transaction must be signed for.
+    pub chain_id: &'a str,
+    /// Height of the block transaction is going to be included into.
+    pub block_index: BlockIndex,
+    /// Height of the block referenced by the transaction, `None` if this block is unknown.
+    pub reference_index: Option<BlockIndex>,
+    /// Number of blocks transaction stays valid after the block it references.
+    pub validity_period: BlockIndex,
+}
+
+impl<'a> ChainContext<'a> {
+    fn verify(&self, transaction: &SignedTransaction) -> Result<(), String> {
+        if transaction.chain_id != self.chain_id {
+            return Err(format!(
+                "Transaction is signed for chain {:?}, expected {:?}",
+                transaction.chain_id, self.chain_id
+            ));
+        }
+        match self.reference_index {
+            None => Err(format!("Transaction references unknown block {}", transaction.block_hash)),
+            Some(index) if index + self.validity_period < self.block_index => Err(format!(
+                "Transaction expired, it references block {} at height {}, which is more than {} blocks behind {}",
+                transaction.block_hash, index, self.validity_period, self.block_index
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
 pub struct TransactionVerifier<'a> {
     state_update: &'a TrieUpdate,
+    chain_context: Option<ChainContext<'a>>,
+    /// Charged for the transaction, access keys must cover it on top of the attached amount.
+    transaction_cost: Balance,
 }
 
 impl<'a> TransactionVerifier<'a> {
     pub fn new(state_update: &'a TrieUpdate) -> Self {
-        TransactionVerifier { state_update }
+        TransactionVerifier { state_update, chain_context: None, transaction_cost: 0 }
+    }
+
+    /// Also checks that transactions are signed for the given chain and didn't expire.
+    pub fn with_chain_context(mut self, chain_context: ChainContext<'a>) -> Self {
+        self.chain_context = Some(chain_context);
+        self
+    }
+
+    /// Also checks that access keys can pay the given cost of the transaction.
+    pub fn with_transaction_cost(mut self, transaction_cost: Balance) -> Self {
+        self.transaction_cost = transaction_cost;
+        self
     }
 
     pub fn verify_transaction(
         &self,
         transaction: &SignedTransaction,
     ) -> Result<VerificationData, String> {
+        if let Some(ref chain_context) = self.chain_context {
+            chain_context.verify(transaction)?;
+        }
+        if let TransactionBody::Batch(ref batch) = transaction.body {
+            if batch.actions.is_empty() {
+                return Err("Batched transaction must have at least one action".to_string());
+            }
+        }
         let originator_id = transaction.body.get_originator();
         let originator = get_account(self.state_update, &originator_id);
         match originator {
@@ -93,6 +147,14 @@ impl<'a> TransactionVerifier<'a> {
                                         ));
                                     }
                                 }
+                                let total_cost =
+                                    function_call.amount.saturating_add(self.transaction_cost);
+                                if total_cost > access_key.amount {
+                                    return Err(format!(
+                                        "Access key allowance {} is not enough to attach {} to the function call and pay {} for it",
+                                        access_key.amount, function_call.amount, self.transaction_cost,
+                                    ));
+                                }
                                 Ok(VerificationData {
                                     originator_id,
                                     originator,
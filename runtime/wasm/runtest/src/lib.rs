@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use near_primitives::types::{AccountId, Balance, PromiseId, ReceiptId};
+use near_primitives::types::{AccountId, Balance, Gas, PromiseId, ReceiptId};
 use wasm::ext::{Error as ExtError, External, Result as ExtResult};
 
 #[derive(Default)]
@@ -71,6 +71,7 @@ impl External for MyExt {
         _method_name: Vec<u8>,
         _arguments: Vec<u8>,
         _amount: Balance,
+        _prepaid_gas: Gas,
     ) -> ExtResult<PromiseId> {
         match self.num_receipts {
             0 => assert_eq!(&account_id, &"test1".to_string()),
@@ -119,8 +120,10 @@ mod tests {
     use byteorder::{ByteOrder, LittleEndian};
 
     use near_primitives::contract::ContractCode;
-    use near_primitives::crypto::signature::PublicKey;
+    use near_primitives::crypto::signature::{get_key_pair, sign, PublicKey};
     use near_primitives::hash::hash;
+    use near_primitives::trace::{PromiseTrace, StorageAccess};
+    use near_primitives::transaction::ContractEvent;
     use near_primitives::types::StorageUsage;
     use testlib::runtime_utils::{alice_account, bob_account};
     use wasm::executor::{self, ExecutionOutcome};
@@ -128,6 +131,8 @@ mod tests {
 
     use super::*;
 
+    const PREPAID_GAS: Gas = 1024 * 1024 * 1024;
+
     fn infinite_initializer_contract() -> Vec<u8> {
         wabt::wat2wasm(
             r#" (module
@@ -140,6 +145,7 @@ mod tests {
         .unwrap()
     }
 
+    #[cfg(not(feature = "interpreter"))]
     fn run_wasm_binary(
         wasm_binary: Vec<u8>,
         method_name: &[u8],
@@ -160,9 +166,41 @@ mod tests {
             &mut ext,
             &config,
             &context,
+            None,
         )
     }
 
+    /// Differential mode, checks that both backends have the same outcome and side effects.
+    #[cfg(feature = "interpreter")]
+    fn run_wasm_binary(
+        wasm_binary: Vec<u8>,
+        method_name: &[u8],
+        input_data: &[u8],
+        result_data: &[Option<Vec<u8>>],
+        context: &RuntimeContext,
+    ) -> Result<ExecutionOutcome, Error> {
+        let code = ContractCode::new(wasm_binary);
+
+        let mut ext = MyExt::default();
+        let mut reference_ext = MyExt::default();
+        let config = Config::default();
+
+        let outcome = executor::execute_differential(
+            &code,
+            &method_name,
+            &input_data,
+            &result_data,
+            &mut ext,
+            &mut reference_ext,
+            &config,
+            &context,
+            None,
+        );
+        assert_eq!(ext.storage, reference_ext.storage);
+        assert_eq!(ext.num_receipts, reference_ext.num_receipts);
+        outcome
+    }
+
     fn run_with_filename(
         method_name: &[u8],
         input_data: &[u8],
@@ -213,12 +251,14 @@ mod tests {
         RuntimeContext::new(
             balance.into(),
             amount.into(),
+            PREPAID_GAS,
             sender_id,
             account_id,
             storage_usage,
             123,
             b"yolo".to_vec(),
             false,
+            false,
             &"alice.near".to_string(),
             &PublicKey::empty(),
         )
@@ -319,6 +359,29 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_promises_prepaid_gas() {
+        let mut context = runtime_context(0, 1_000_000, 0, &alice_account(), &bob_account());
+        context.trace = true;
+        let outcome = run(b"create_promises_and_join", &[], &[], &context).expect("ok");
+        assert!(outcome.return_data.is_ok());
+
+        let promised_gas: Vec<_> = outcome
+            .trace
+            .expect("trace is recorded")
+            .promises
+            .into_iter()
+            .filter_map(|promise| match promise {
+                PromiseTrace::Create { prepaid_gas, .. } => Some(prepaid_gas),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(promised_gas.len(), 2);
+        assert!(promised_gas.iter().all(|gas| *gas > 0));
+        assert!(promised_gas[1] < promised_gas[0]);
+        assert!(promised_gas.iter().sum::<Gas>() + outcome.gas_used <= PREPAID_GAS);
+    }
+
     #[test]
     fn test_assert_sum_ok() {
         let input_data = [10u8, 0, 0, 0, 30u8, 0, 0, 0, 40u8, 0, 0, 0];
@@ -381,13 +444,13 @@ mod tests {
             &runtime_context(0, 100, 0, &alice_account(), &bob_account()),
         )
         .expect("ok");
-        // At the moment of measurement the liquid balance is at 97 which is the value returned.
+        // At the moment of measurement the liquid balance is at 73 which is the value returned.
         // However returning the value itself costs additional balance which results in final
-        // liquid balance being 55.
+        // liquid balance being 37.
         println!("{:?}", outcome);
-        assert_eq!(outcome.liquid_balance, 55);
+        assert_eq!(outcome.liquid_balance, 37);
         match outcome.return_data {
-            Ok(ReturnData::Value(output_data)) => assert_eq!(decode_u128(&output_data), 74),
+            Ok(ReturnData::Value(output_data)) => assert_eq!(decode_u128(&output_data), 73),
             _ => assert!(false, "Expected returned value"),
         };
     }
@@ -429,6 +492,204 @@ mod tests {
         assert_eq!(outcome.storage_usage, 52);
     }
 
+    #[test]
+    fn test_storage_write_cost() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("res/wasm_with_mem.wasm");
+        let code = ContractCode::new(fs::read(path).expect("Unable to read file"));
+        let context = runtime_context(0, 1_000_000_000, 10, &alice_account(), &bob_account());
+        let gas_used = |config: &Config| {
+            let mut ext = MyExt::default();
+            let outcome = executor::execute(
+                &code,
+                b"run_test_with_storage_change",
+                &[],
+                &[],
+                &mut ext,
+                config,
+                &context,
+                None,
+            )
+            .expect("ok");
+            assert!(outcome.return_data.is_ok());
+            outcome.gas_used
+        };
+
+        let config = Config::default();
+        let mut expensive_config = config.clone();
+        expensive_config.ext_costs.storage_write_base += 1000;
+        // The contract writes into the storage at least 3 times.
+        assert!(gas_used(&expensive_config) >= gas_used(&config) + 3000);
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut context = runtime_context(0, 1_000_000_000, 10, &alice_account(), &bob_account());
+        context.trace = true;
+        let outcome = run(b"run_test_with_storage_change", &[], &[], &context).expect("ok");
+        assert!(outcome.return_data.is_ok());
+        let trace = outcome.trace.expect("trace is recorded");
+
+        let key = |key: &str| format!("key: {:0>10}", key).into_bytes();
+        assert_eq!(
+            trace.storage,
+            vec![
+                StorageAccess::Write { key: key("10"), value_len: 4, evicted_len: None },
+                StorageAccess::Write { key: key("50"), value_len: 4, evicted_len: None },
+                StorageAccess::Write { key: key("100"), value_len: 4, evicted_len: None },
+                StorageAccess::Read { key: key("50"), value_len: Some(4) },
+                StorageAccess::Remove { key: key("50"), removed_len: Some(4) },
+                StorageAccess::Read { key: key("50"), value_len: None },
+                StorageAccess::Write { key: key("100"), value_len: 8, evicted_len: Some(4) },
+                StorageAccess::Read { key: key("10"), value_len: Some(4) },
+            ]
+        );
+        assert!(trace.promises.is_empty());
+        let writes: Vec<_> =
+            trace.host_calls.iter().filter(|call| call.name == "storage_write").collect();
+        assert_eq!(writes.len(), 4);
+        assert!(writes[3].args.contains(&("value_len".to_string(), 8)));
+        assert_eq!(trace.host_gas() + trace.ops_gas, outcome.gas_used);
+
+        context.trace = false;
+        let outcome = run(b"run_test_with_storage_change", &[], &[], &context).expect("ok");
+        assert!(outcome.trace.is_none());
+    }
+
+    /// Emits the event with the topic "transfer", or with a topic that is not utf-8.
+    fn event_contract() -> Vec<u8> {
+        wabt::wat2wasm(
+            r#" (module
+                       (import "env" "memory" (memory 17 32))
+                       (import "env" "emit_event" (func $emit_event (param i32 i32 i32 i32)))
+                       (data (i32.const 0) "transfer\ff\01")
+                       (func (export "emit")
+                         (call $emit_event
+                           (i32.const 8) (i32.const 0) (i32.const 2) (i32.const 8)))
+                       (func (export "emit_bad_topic")
+                         (call $emit_event
+                           (i32.const 1) (i32.const 8) (i32.const 0) (i32.const 0))))"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_emit_event() {
+        let context = runtime_context(0, 1_000_000_000, 0, &alice_account(), &bob_account());
+        let outcome = run_wasm_binary(event_contract(), b"emit", &[], &[], &context).expect("ok");
+        assert!(outcome.return_data.is_ok());
+        assert_eq!(
+            outcome.events,
+            vec![ContractEvent {
+                account_id: bob_account(),
+                topic: "transfer".to_string(),
+                data: vec![0xff, 0x01],
+            }]
+        );
+
+        let outcome =
+            run_wasm_binary(event_contract(), b"emit_bad_topic", &[], &[], &context).expect("ok");
+        match outcome.return_data {
+            Err(Error::Runtime(RuntimeError::BadUtf8)) => {}
+            _ => panic!("unexpected outcome"),
+        }
+        assert!(outcome.events.is_empty());
+    }
+
+    /// Calls the crypto host functions on the input data and returns their results.
+    /// `keccak256` hashes the input. `ed25519_verify` takes the public key, the signature and
+    /// the message. `ecrecover` takes the hash, the signature and the recovery id as one byte,
+    /// and returns the result of the call followed by the recovered public key.
+    fn crypto_contract() -> Vec<u8> {
+        wabt::wat2wasm(
+            r#" (module
+                       (import "env" "memory" (memory 17 32))
+                       (import "env" "data_read"
+                         (func $data_read (param i32 i32 i32 i32 i32) (result i32)))
+                       (import "env" "return_value" (func $return_value (param i32 i32)))
+                       (import "env" "keccak256" (func $keccak256 (param i32 i32 i32)))
+                       (import "env" "ed25519_verify"
+                         (func $ed25519_verify (param i32 i32 i32 i32) (result i32)))
+                       (import "env" "ecrecover"
+                         (func $ecrecover (param i32 i32 i32 i32) (result i32)))
+                       (func $read_input (result i32)
+                         (call $data_read
+                           (i32.const 4) (i32.const 0) (i32.const 0)
+                           (i32.const 1024) (i32.const 0)))
+                       (func (export "keccak256")
+                         (call $keccak256 (call $read_input) (i32.const 0) (i32.const 2048))
+                         (call $return_value (i32.const 32) (i32.const 2048)))
+                       (func (export "ed25519_verify")
+                         (i32.store (i32.const 2048)
+                           (call $ed25519_verify
+                             (i32.sub (call $read_input) (i32.const 96))
+                             (i32.const 96) (i32.const 32) (i32.const 0)))
+                         (call $return_value (i32.const 4) (i32.const 2048)))
+                       (func (export "ecrecover")
+                         (drop (call $read_input))
+                         (i32.store (i32.const 2044)
+                           (call $ecrecover
+                             (i32.const 0) (i32.const 32) (i32.load8_u (i32.const 96))
+                             (i32.const 2048)))
+                         (call $return_value (i32.const 68) (i32.const 2044))))"#,
+        )
+        .unwrap()
+    }
+
+    fn run_crypto(method_name: &[u8], input_data: &[u8]) -> Vec<u8> {
+        let context = runtime_context(0, 1_000_000_000, 0, &alice_account(), &bob_account());
+        let outcome =
+            run_wasm_binary(crypto_contract(), method_name, input_data, &[], &context).expect("ok");
+        match outcome.return_data {
+            Ok(ReturnData::Value(output_data)) => output_data,
+            _ => panic!("Expected returned value"),
+        }
+    }
+
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            hex::encode(run_crypto(b"keccak256", b"hello")),
+            "1c8aff950685c2ed4bc3174f3472287b56d9517b9c948127319a09a7a36deac8"
+        );
+    }
+
+    #[test]
+    fn test_ed25519_verify() {
+        let (public_key, secret_key) = get_key_pair();
+        let signature = sign(b"message", &secret_key);
+        let input = |message: &[u8]| [public_key.as_ref(), signature.as_ref(), message].concat();
+        assert_eq!(LittleEndian::read_u32(&run_crypto(b"ed25519_verify", &input(b"message"))), 1);
+        assert_eq!(LittleEndian::read_u32(&run_crypto(b"ed25519_verify", &input(b"massage"))), 0);
+    }
+
+    #[test]
+    fn test_ecrecover() {
+        let signature = hex::decode(
+            "17f53289eac961e5adc858d3ca50dab056ddca7a1a906c0815a0369312d1aa49\
+             35444965d5cdacbcebeb3adbc34bc28f08b30422d37e51dd2bd78cabd7798e72",
+        )
+        .unwrap();
+        let public_key = hex::decode(
+            "bfbd22a503fbfd720b8dec07ccc9997f7bcc00c3e2fc74f5f17898b7c8d91de7\
+             205de9d515024ad9c940b4463b173a95adc7535e06bea45d37a7004385d20174",
+        )
+        .unwrap();
+        let input = |v: u8| [hash(b"hello").as_ref(), &signature, &[v]].concat();
+
+        let output = run_crypto(b"ecrecover", &input(0));
+        assert_eq!(LittleEndian::read_u32(&output[..4]), 1);
+        assert_eq!(&output[4..], &public_key[..]);
+
+        // Recovers some other key with the wrong recovery id.
+        let output = run_crypto(b"ecrecover", &input(1));
+        assert_eq!(LittleEndian::read_u32(&output[..4]), 1);
+        assert_ne!(&output[4..], &public_key[..]);
+
+        let output = run_crypto(b"ecrecover", &input(27));
+        assert_eq!(LittleEndian::read_u32(&output[..4]), 0);
+    }
+
     #[test]
     fn test_hello_name() {
         let input_data = b"{\"name\": \"Alice\"}";
@@ -761,6 +1022,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_prepaid_gas_exceeded() {
+        let mut context = runtime_context(0, 1_000_000, 0, &alice_account(), &bob_account());
+        context.prepaid_gas = 1_000;
+        let outcome =
+            run_wasm_binary(infinite_initializer_contract(), b"hello", &[], &[], &context)
+                .expect("expect");
+        println!("{:?}", outcome);
+        match outcome.return_data {
+            Err(Error::Runtime(RuntimeError::PrepaidGasExceeded)) => {}
+            _ => panic!("unexpected outcome"),
+        }
+        assert!(outcome.gas_used <= 1_000);
+        assert_eq!(outcome.liquid_balance, 1_000_000 - outcome.gas_used as Balance);
+    }
+
     #[test]
     // Current behavior is to run the initializer even if the method doesn't exist
     fn test_infinite_initializer_export_not_found() {
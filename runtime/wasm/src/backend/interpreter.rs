@@ -0,0 +1,137 @@
+use wasmi::memory_units::{Bytes, Pages};
+use wasmi::{
+    Externals, FuncInstance, FuncRef, HostError, ImportsBuilder, MemoryDescriptor, MemoryInstance,
+    MemoryRef, ModuleImportResolver, ModuleInstance, RuntimeArgs, RuntimeValue, Signature, Trap,
+    TrapKind,
+};
+
+use near_primitives::contract::ContractCode;
+
+use crate::backend::Backend;
+use crate::cache::PersistentCache;
+use crate::prepare;
+use crate::runtime::{imports, MemoryLike, Runtime};
+use crate::types::{Config, Error, RuntimeError};
+
+/// Interprets the contracts with wasmi. Much slower than the compiled contracts, but doesn't
+/// generate any machine code, so it serves as a reference to cross-check the determinism.
+pub(crate) struct WasmiBackend;
+
+impl HostError for RuntimeError {}
+
+impl From<wasmi::Error> for Error {
+    fn from(e: wasmi::Error) -> Self {
+        let runtime_error = match &e {
+            wasmi::Error::Trap(trap) => match trap.kind() {
+                TrapKind::Host(err) => err.downcast_ref::<RuntimeError>(),
+                _ => None,
+            },
+            wasmi::Error::Host(err) => err.downcast_ref::<RuntimeError>(),
+            _ => None,
+        };
+        match runtime_error {
+            Some(err) => Error::Runtime(err.clone()),
+            None => Error::Interpreter(format!("{}", e)),
+        }
+    }
+}
+
+impl MemoryLike for MemoryRef {
+    fn fits_memory(&self, offset: usize, len: usize) -> bool {
+        match offset.checked_add(len) {
+            None => false,
+            Some(end) => Bytes::from(self.current_size()) >= Bytes(end),
+        }
+    }
+
+    fn read_memory(&self, offset: usize, len: usize) -> Vec<u8> {
+        self.get(offset as u32, len).expect("memory range is checked by the runtime")
+    }
+
+    fn write_memory(&self, offset: usize, buf: &[u8]) {
+        self.set(offset as u32, buf).expect("memory range is checked by the runtime")
+    }
+}
+
+/// Resolves the imports of the "env" module into the host functions and the given memory.
+struct EnvResolver {
+    memory: MemoryRef,
+}
+
+impl ModuleImportResolver for EnvResolver {
+    fn resolve_func(
+        &self,
+        field_name: &str,
+        signature: &Signature,
+    ) -> Result<FuncRef, wasmi::Error> {
+        // Signatures of the imports are checked while preparing the contract.
+        match imports::NAMES.iter().position(|name| *name == field_name) {
+            Some(index) => Ok(FuncInstance::alloc_host(signature.clone(), index)),
+            None => Err(wasmi::Error::Instantiation(format!("Unknown import {}", field_name))),
+        }
+    }
+
+    fn resolve_memory(
+        &self,
+        field_name: &str,
+        _descriptor: &MemoryDescriptor,
+    ) -> Result<MemoryRef, wasmi::Error> {
+        if field_name == "memory" {
+            Ok(self.memory.clone())
+        } else {
+            Err(wasmi::Error::Instantiation(format!("Unknown memory import {}", field_name)))
+        }
+    }
+}
+
+struct HostExternals<'a, 'b> {
+    runtime: &'a mut Runtime<'b>,
+}
+
+impl<'a, 'b> Externals for HostExternals<'a, 'b> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        imports::invoke(self.runtime, imports::NAMES[index], args.as_ref())
+    }
+}
+
+impl Backend for WasmiBackend {
+    type Memory = MemoryRef;
+    type Module = wasmi::Module;
+
+    fn compile(
+        code: &ContractCode,
+        config: &Config,
+        _cache: Option<&PersistentCache>,
+    ) -> Result<Self::Module, Error> {
+        let prepared_code = prepare::prepare_contract(code, config).map_err(Error::Prepare)?;
+        wasmi::Module::from_buffer(&prepared_code).map_err(Into::into)
+    }
+
+    fn create_memory(config: &Config) -> Result<Self::Memory, Error> {
+        MemoryInstance::alloc(
+            Pages(config.initial_memory_pages as usize),
+            Some(Pages(config.max_memory_pages as usize)),
+        )
+        .map_err(Into::into)
+    }
+
+    fn call(
+        module: &Self::Module,
+        memory: Self::Memory,
+        runtime: &mut Runtime,
+        method_name: &str,
+    ) -> Result<(), Error> {
+        let resolver = EnvResolver { memory };
+        let env = ImportsBuilder::new().with_resolver("env", &resolver);
+        let mut externals = HostExternals { runtime };
+        ModuleInstance::new(module, &env)
+            .and_then(|instance| instance.run_start(&mut externals).map_err(Into::into))
+            .and_then(|instance| instance.invoke_export(method_name, &[], &mut externals))
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+}
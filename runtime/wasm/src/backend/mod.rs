@@ -0,0 +1,42 @@
+//! Engines that compile the prepared contracts and call their methods. Both share the host
+//! functions of `runtime::imports` and the gas metering injected by `prepare`.
+use near_primitives::contract::ContractCode;
+
+use crate::cache::PersistentCache;
+use crate::runtime::{MemoryLike, Runtime};
+use crate::types::{Config, Error};
+
+#[cfg(feature = "interpreter")]
+pub(crate) mod interpreter;
+pub(crate) mod wasmer;
+
+/// Backend used by `executor::execute`.
+#[cfg(not(feature = "interpreter"))]
+pub(crate) type DefaultBackend = self::wasmer::WasmerBackend;
+#[cfg(feature = "interpreter")]
+pub(crate) type DefaultBackend = self::interpreter::WasmiBackend;
+
+pub(crate) trait Backend {
+    /// Linear memory of the contract instance, imported by the module and used by the runtime.
+    type Memory: MemoryLike + Clone + 'static;
+    type Module;
+
+    /// Prepares and compiles the contract code, backends that compile to native code may keep
+    /// the result in the `cache`.
+    fn compile(
+        code: &ContractCode,
+        config: &Config,
+        cache: Option<&PersistentCache>,
+    ) -> Result<Self::Module, Error>;
+
+    fn create_memory(config: &Config) -> Result<Self::Memory, Error>;
+
+    /// Instantiates the module with the host functions of the `runtime`, which must be created
+    /// with the same `memory`, and calls the method.
+    fn call(
+        module: &Self::Module,
+        memory: Self::Memory,
+        runtime: &mut Runtime,
+        method_name: &str,
+    ) -> Result<(), Error>;
+}
@@ -0,0 +1,71 @@
+use std::cell::Cell;
+use std::ffi::c_void;
+
+use wasmer_runtime::{self, memory::Memory, units::Bytes, units::Pages, wasm::MemoryDescriptor};
+
+use near_primitives::contract::ContractCode;
+
+use crate::backend::Backend;
+use crate::cache::{self, PersistentCache};
+use crate::runtime::{self, MemoryLike, Runtime};
+use crate::types::{Config, Error};
+
+/// Compiles the contracts with the wasmer single pass compiler.
+pub(crate) struct WasmerBackend;
+
+impl MemoryLike for Memory {
+    fn fits_memory(&self, offset: usize, len: usize) -> bool {
+        match offset.checked_add(len) {
+            None => false,
+            Some(end) => self.size().bytes() >= Bytes(end),
+        }
+    }
+
+    fn read_memory(&self, offset: usize, len: usize) -> Vec<u8> {
+        self.view()[offset..(offset + len)].iter().map(Cell::get).collect()
+    }
+
+    fn write_memory(&self, offset: usize, buf: &[u8]) {
+        self.view()[offset..(offset + buf.len())]
+            .iter()
+            .zip(buf.iter())
+            .for_each(|(cell, v)| cell.set(*v));
+    }
+}
+
+impl Backend for WasmerBackend {
+    type Memory = Memory;
+    type Module = wasmer_runtime::Module;
+
+    fn compile(
+        code: &ContractCode,
+        config: &Config,
+        cache: Option<&PersistentCache>,
+    ) -> Result<Self::Module, Error> {
+        cache::compile_cached_module(code, config, cache)
+    }
+
+    fn create_memory(config: &Config) -> Result<Self::Memory, Error> {
+        Memory::new(MemoryDescriptor {
+            minimum: Pages(config.initial_memory_pages),
+            maximum: Some(Pages(config.max_memory_pages)),
+            shared: false,
+        })
+        .map_err(|e| Into::<wasmer_runtime::error::Error>::into(e).into())
+    }
+
+    fn call(
+        module: &Self::Module,
+        memory: Self::Memory,
+        runtime: &mut Runtime,
+        method_name: &str,
+    ) -> Result<(), Error> {
+        let raw_ptr = runtime as *mut _ as *mut c_void;
+        let import_object = runtime::imports::build(memory, raw_ptr);
+        module
+            .instantiate(&import_object)
+            .and_then(|instance| instance.call(method_name, &[]).map_err(|e| e.into()))
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+}
@@ -1,7 +1,12 @@
-use std::sync::Arc;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use cached::SizedCache;
 use wasmer_runtime;
+use wasmer_runtime::cache::{Cache, FileSystemCache, WasmHash};
 
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::serialize::Encode;
@@ -12,7 +17,111 @@ use crate::types::{Config, Error};
 
 /// Cache size in number of cached modules to hold.
 const CACHE_SIZE: usize = 1024;
-// TODO: store a larger on-disk cache
+
+/// Name of the directory under the node home that holds compiled contracts.
+pub const WASM_CACHE_PATH: &str = "wasm_cache";
+
+const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
+
+/// Hits and misses of the on-disk cache of compiled modules since it was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CacheStats {
+    /// Share of the lookups that didn't require compilation.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// On-disk cache of compiled modules, used when the in-memory cache misses. Owned by the runtime
+/// that opened it, so runtimes with different home directories don't overwrite each other.
+pub struct PersistentCache {
+    cache: Mutex<FileSystemCache>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl PersistentCache {
+    /// Opens the cache stored in `dir`. Modules are kept in a subdirectory named after the wasmer
+    /// version, caches of other versions are removed since they can't be loaded.
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_name() != *wasmer_runtime::VERSION {
+                info!(target: "runtime", "Removing stale compiled contracts cache {:?}", entry.path());
+                if entry.file_type()?.is_dir() {
+                    fs::remove_dir_all(entry.path())?;
+                } else {
+                    fs::remove_file(entry.path())?;
+                }
+            }
+        }
+        let path = dir.join(wasmer_runtime::VERSION);
+        fs::create_dir_all(&path)?;
+        // Safe as long as nothing else writes into the directory.
+        let cache = unsafe { FileSystemCache::new(path)? };
+        Ok(PersistentCache {
+            cache: Mutex::new(cache),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn load(&self, code: &ContractCode, config: &Config) -> Option<wasmer_runtime::Module> {
+        let result = match self
+            .cache
+            .lock()
+            .expect(POISONED_LOCK_ERR)
+            .load(persistent_cache_key(code, config))
+        {
+            Ok(module) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(module)
+            }
+            Err(_) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        };
+        let hit_rate = self.stats().hit_rate();
+        debug!(target: "runtime", "Compiled contracts cache hit rate: {:.2}", hit_rate);
+        result
+    }
+
+    fn store(&self, code: &ContractCode, config: &Config, module: &wasmer_runtime::Module) {
+        let mut cache = self.cache.lock().expect(POISONED_LOCK_ERR);
+        if let Err(err) = cache.store(persistent_cache_key(code, config), module.clone()) {
+            let code_hash = code.get_hash();
+            warn!(target: "runtime", "Failed to store compiled contract {}: {:?}", code_hash, err);
+        }
+    }
+}
+
+fn config_hash(config: &Config) -> CryptoHash {
+    hash(&config.encode().expect("encoding of config shouldn't fail"))
+}
+
+/// Modules depend on the config used for preparation, so it is a part of the key.
+fn persistent_cache_key(code: &ContractCode, config: &Config) -> WasmHash {
+    WasmHash::generate(&[code.get_hash().as_ref(), config_hash(config).as_ref()].concat())
+}
 
 cached_key! {
     CODE: SizedCache<CryptoHash, Result<Arc<ContractCode>, String>> = SizedCache::with_size(CACHE_SIZE);
@@ -30,13 +139,65 @@ cached_key! {
 cached_key! {
     MODULES: SizedCache<(CryptoHash, CryptoHash), Result<wasmer_runtime::Module, Error>> = SizedCache::with_size(CACHE_SIZE);
     Key = {
-        (code.get_hash(), hash(&config.encode().expect("encoding of config shouldn't fail")))
+        (code.get_hash(), config_hash(config))
     };
 
-    fn compile_cached_module(code: &ContractCode, config: &Config) -> Result<wasmer_runtime::Module, Error> = {
-        let prepared_code = prepare::prepare_contract(code, config).map_err(Error::Prepare)?;
+    fn compile_cached_module(code: &ContractCode, config: &Config, persistent_cache: Option<&PersistentCache>) -> Result<wasmer_runtime::Module, Error> = {
+        match persistent_cache.and_then(|cache| cache.load(code, config)) {
+            Some(module) => Ok(module),
+            None => {
+                let prepared_code = prepare::prepare_contract(code, config).map_err(Error::Prepare)?;
+
+                let module = wasmer_runtime::compile(&prepared_code)
+                    .map_err(|e| Error::Wasmer(format!("{}", e)))?;
+                if let Some(cache) = persistent_cache {
+                    cache.store(code, config, &module);
+                }
+                Ok(module)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use wabt;
+
+    use super::*;
+
+    #[test]
+    fn test_hit_rate() {
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+        assert_eq!(CacheStats { hits: 3, misses: 1 }.hit_rate(), 0.75);
+    }
+
+    #[test]
+    fn test_init_removes_stale_caches() {
+        let dir = TempDir::new("wasm_cache").unwrap();
+        fs::create_dir_all(dir.path().join("0.0.1")).unwrap();
+        PersistentCache::new(dir.path()).unwrap();
+        let entries: Vec<_> =
+            fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0] == *wasmer_runtime::VERSION);
+    }
+
+    #[test]
+    fn test_reopened_cache_loads_modules() {
+        let dir = TempDir::new("wasm_cache").unwrap();
+        let wasm = wabt::Wat2Wasm::new().convert(r#"(module)"#).unwrap();
+        let code = ContractCode::new(wasm.as_ref().to_vec());
+        let config = Config::default();
+        let module = wasmer_runtime::compile(code.code.as_slice()).unwrap();
+
+        let cache = PersistentCache::new(dir.path()).unwrap();
+        assert!(cache.load(&code, &config).is_none());
+        cache.store(&code, &config, &module);
 
-        wasmer_runtime::compile(&prepared_code)
-            .map_err(|e| Error::Wasmer(format!("{}", e)))
+        let reopened = PersistentCache::new(dir.path()).unwrap();
+        assert!(reopened.load(&code, &config).is_some());
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 1 });
+        assert_eq!(reopened.stats(), CacheStats { hits: 1, misses: 0 });
     }
 }
@@ -1,15 +1,15 @@
-use std::ffi::c_void;
 use std::fmt;
 
-use wasmer_runtime::{self, memory::Memory, units::Pages, wasm::MemoryDescriptor};
-
 use near_primitives::contract::ContractCode;
 use near_primitives::logging;
-use near_primitives::types::{Balance, StorageUsage, StorageUsageChange};
+use near_primitives::trace::ExecutionTrace;
+use near_primitives::transaction::ContractEvent;
+use near_primitives::types::{Balance, Gas, StorageUsage, StorageUsageChange};
 
-use crate::cache;
+use crate::backend::{Backend, DefaultBackend};
+use crate::cache::PersistentCache;
 use crate::ext::External;
-use crate::runtime::{self, Runtime};
+use crate::runtime::Runtime;
 use crate::types::{Config, Error, ReturnData, RuntimeContext};
 
 pub struct ExecutionOutcome {
@@ -19,6 +19,12 @@ pub struct ExecutionOutcome {
     pub return_data: Result<ReturnData, Error>,
     pub random_seed: Vec<u8>,
     pub logs: Vec<String>,
+    /// Events emitted by the contract, empty if the execution failed.
+    pub events: Vec<ContractEvent>,
+    /// Gas burnt during the execution.
+    pub gas_used: Gas,
+    /// Trace of the execution, if it was requested by `RuntimeContext::trace`.
+    pub trace: Option<ExecutionTrace>,
 }
 
 impl fmt::Debug for ExecutionOutcome {
@@ -29,6 +35,9 @@ impl fmt::Debug for ExecutionOutcome {
             .field("liquid_balance", &format_args!("{}", &self.liquid_balance))
             .field("random_seed", &format_args!("{}", logging::pretty_utf8(&self.random_seed)))
             .field("logs", &format_args!("{}", logging::pretty_vec(&self.logs)))
+            .field("events", &format_args!("{}", logging::pretty_vec(&self.events)))
+            .field("gas_used", &self.gas_used)
+            .field("trace", &self.trace)
             .finish()
     }
 }
@@ -41,35 +50,128 @@ pub fn execute(
     ext: &mut dyn External,
     config: &Config,
     context: &RuntimeContext,
+    cache: Option<&PersistentCache>,
+) -> Result<ExecutionOutcome, Error> {
+    execute_with::<DefaultBackend>(
+        code,
+        method_name,
+        input_data,
+        result_data,
+        ext,
+        config,
+        context,
+        cache,
+    )
+}
+
+/// Executes the method with both the compiled and the interpreted contract, each against its own
+/// `External`, and returns the outcome of the compiled one.
+///
+/// Panics if the outcomes differ, used to cross-check the determinism of the backends in tests.
+#[cfg(feature = "interpreter")]
+pub fn execute_differential(
+    code: &ContractCode,
+    method_name: &[u8],
+    input_data: &[u8],
+    result_data: &[Option<Vec<u8>>],
+    ext: &mut dyn External,
+    reference_ext: &mut dyn External,
+    config: &Config,
+    context: &RuntimeContext,
+    cache: Option<&PersistentCache>,
+) -> Result<ExecutionOutcome, Error> {
+    use crate::backend::interpreter::WasmiBackend;
+    use crate::backend::wasmer::WasmerBackend;
+
+    let outcome = execute_with::<WasmerBackend>(
+        code,
+        method_name,
+        input_data,
+        result_data,
+        ext,
+        config,
+        context,
+        cache,
+    );
+    let reference = execute_with::<WasmiBackend>(
+        code,
+        method_name,
+        input_data,
+        result_data,
+        reference_ext,
+        config,
+        context,
+        None,
+    );
+    assert_eq!(
+        comparable_outcome(&outcome),
+        comparable_outcome(&reference),
+        "Compiled and interpreted executions of {} differ",
+        logging::pretty_utf8(method_name)
+    );
+    outcome
+}
+
+/// Representation of the outcome that doesn't depend on the backend. Errors of the backends
+/// themselves are only compared by their presence, since the messages are different.
+#[cfg(feature = "interpreter")]
+fn comparable_outcome(outcome: &Result<ExecutionOutcome, Error>) -> String {
+    let comparable_error = |err: &Error| match err {
+        Error::Wasmer(_) | Error::Interpreter(_) => "Backend error".to_string(),
+        err => format!("{:?}", err),
+    };
+    match outcome {
+        Ok(outcome) => format!(
+            "{:?}",
+            (
+                outcome.return_data.as_ref().map_err(comparable_error),
+                outcome.frozen_balance,
+                outcome.liquid_balance,
+                outcome.storage_usage,
+                &outcome.random_seed,
+                &outcome.logs,
+                &outcome.events,
+                outcome.gas_used,
+                &outcome.trace,
+            )
+        ),
+        Err(err) => comparable_error(err),
+    }
+}
+
+fn execute_with<B: Backend>(
+    code: &ContractCode,
+    method_name: &[u8],
+    input_data: &[u8],
+    result_data: &[Option<Vec<u8>>],
+    ext: &mut dyn External,
+    config: &Config,
+    context: &RuntimeContext,
+    cache: Option<&PersistentCache>,
 ) -> Result<ExecutionOutcome, Error> {
     if method_name.is_empty() {
         return Err(Error::EmptyMethodName);
     }
 
-    let module = cache::compile_cached_module(code, config)?;
+    let module = B::compile(code, config, cache)?;
 
     debug!(target:"runtime", "Executing method {:?}", String::from_utf8(method_name.to_vec()).unwrap_or_else(|_| hex::encode(method_name)));
 
-    let memory = Memory::new(MemoryDescriptor {
-        minimum: Pages(config.initial_memory_pages),
-        maximum: Some(Pages(config.max_memory_pages)),
-        shared: false,
-    })
-    .map_err(Into::<wasmer_runtime::error::Error>::into)?;
-
-    let mut runtime =
-        Runtime::new(ext, input_data, result_data, context, config.clone(), memory.clone());
+    let memory = B::create_memory(config)?;
 
-    let raw_ptr = &mut runtime as *mut _ as *mut c_void;
-    let import_object = runtime::imports::build(memory, raw_ptr);
+    let mut runtime = Runtime::new(
+        ext,
+        input_data,
+        result_data,
+        context,
+        config.clone(),
+        Box::new(memory.clone()),
+    );
 
     let method_name = std::str::from_utf8(method_name).map_err(|_| Error::BadUtf8)?;
 
-    match module
-        .instantiate(&import_object)
-        .and_then(|instance| instance.call(&method_name, &[]).map_err(|e| e.into()))
-    {
-        Ok(_) => {
+    match B::call(&module, memory, &mut runtime, method_name) {
+        Ok(()) => {
             let e = ExecutionOutcome {
                 storage_usage: (context.storage_usage as StorageUsageChange
                     + runtime.storage_counter) as StorageUsage,
@@ -78,6 +180,9 @@ pub fn execute(
                 liquid_balance: runtime.liquid_balance,
                 random_seed: runtime.random_seed,
                 logs: runtime.logs,
+                events: runtime.events,
+                gas_used: runtime.gas_used(),
+                trace: runtime.trace,
             };
             debug!(target:"runtime", "{:?}", e);
             Ok(e)
@@ -85,11 +190,14 @@ pub fn execute(
         Err(e) => {
             let e = ExecutionOutcome {
                 storage_usage: context.storage_usage,
-                return_data: Err(Into::<wasmer_runtime::error::Error>::into(e).into()),
+                return_data: Err(e),
                 frozen_balance: runtime.frozen_balance,
                 liquid_balance: runtime.liquid_balance,
                 random_seed: runtime.random_seed,
                 logs: runtime.logs,
+                events: vec![],
+                gas_used: runtime.gas_used(),
+                trace: runtime.trace,
             };
             debug!(target:"runtime", "{:?}", e);
             Ok(e)
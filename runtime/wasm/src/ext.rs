@@ -1,4 +1,4 @@
-use near_primitives::types::{AccountId, Balance, PromiseId};
+use near_primitives::types::{AccountId, Balance, Gas, PromiseId};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
@@ -35,6 +35,7 @@ pub trait External {
         method_name: Vec<u8>,
         arguments: Vec<u8>,
         amount: Balance,
+        prepaid_gas: Gas,
     ) -> Result<PromiseId>;
 
     fn promise_then(
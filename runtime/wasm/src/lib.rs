@@ -8,8 +8,11 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 #[cfg(test)]
+extern crate tempdir;
+#[cfg(test)]
 extern crate wabt;
 
+mod backend;
 pub mod cache;
 pub mod executor;
 pub mod ext;
@@ -2,11 +2,13 @@
 //! wasm module before execution.
 
 use parity_wasm::builder;
-use parity_wasm::elements::{self, External, MemorySection, MemoryType, Type};
+use parity_wasm::elements::{self, External, MemorySection, MemoryType, Type, ValueType};
+use pwasm_utils::rules::{InstructionType, Metering};
 use pwasm_utils::{self, rules};
 
 use near_primitives::contract::ContractCode;
 
+use crate::runtime::imports;
 use crate::types::{Config, PrepareError as Error};
 
 struct ContractModule<'a> {
@@ -19,6 +21,9 @@ struct ContractModule<'a> {
 
 impl<'a> ContractModule<'a> {
     fn init(original_code: &[u8], config: &'a Config) -> Result<ContractModule<'a>, Error> {
+        if original_code.len() > config.max_code_size as usize {
+            return Err(Error::CodeTooLarge);
+        }
         let module =
             elements::deserialize_buffer(original_code).map_err(|_| Error::Deserialization)?;
         Ok(ContractModule { module: Some(module), config })
@@ -61,10 +66,71 @@ impl<'a> ContractModule<'a> {
         Ok(())
     }
 
+    /// Ensures that module doesn't use floating point types and operations, since their results
+    /// are not deterministic across platforms.
+    fn ensure_no_floats(&self) -> Result<(), Error> {
+        let module =
+            self.module.as_ref().expect("On entry to the function `module` can't be `None`; qed");
+        let is_float = |value_type: &ValueType| match *value_type {
+            ValueType::F32 | ValueType::F64 => true,
+            _ => false,
+        };
+
+        let types = module.type_section().map(elements::TypeSection::types).unwrap_or(&[]);
+        for Type::Function(ref func_ty) in types {
+            if func_ty.params().iter().any(is_float)
+                || func_ty.return_type().as_ref().map_or(false, is_float)
+            {
+                return Err(Error::FloatingPoint);
+            }
+        }
+        let globals = module.global_section().map(elements::GlobalSection::entries).unwrap_or(&[]);
+        if globals.iter().any(|global| is_float(&global.global_type().content_type())) {
+            return Err(Error::FloatingPoint);
+        }
+        let bodies = module.code_section().map(elements::CodeSection::bodies).unwrap_or(&[]);
+        for body in bodies {
+            if body.locals().iter().any(|local| is_float(&local.value_type())) {
+                return Err(Error::FloatingPoint);
+            }
+            for instruction in body.code().elements() {
+                match InstructionType::op(instruction) {
+                    InstructionType::Float
+                    | InstructionType::FloatConst
+                    | InstructionType::FloatComparison
+                    | InstructionType::FloatConversion => return Err(Error::FloatingPoint),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn inject_gas_metering(&mut self) -> Result<(), Error> {
         // TODO(#194): Re-enable .with_forbidden_floats() once AssemblyScript is fixed.
-        let gas_rules = rules::Set::new(self.config.regular_op_cost, Default::default())
-            .with_grow_cost(self.config.grow_mem_cost);
+        let op_costs = &self.config.op_costs;
+        let gas_rules = rules::Set::new(
+            self.config.regular_op_cost,
+            vec![
+                (InstructionType::Bit, op_costs.bit),
+                (InstructionType::Add, op_costs.add),
+                (InstructionType::Mul, op_costs.mul),
+                (InstructionType::Div, op_costs.div),
+                (InstructionType::Load, op_costs.load),
+                (InstructionType::Store, op_costs.store),
+                (InstructionType::Const, op_costs.constant),
+                (InstructionType::Local, op_costs.local),
+                (InstructionType::Global, op_costs.global),
+                (InstructionType::ControlFlow, op_costs.control_flow),
+                (InstructionType::IntegerComparison, op_costs.integer_comparison),
+                (InstructionType::Conversion, op_costs.conversion),
+                (InstructionType::CurrentMemory, op_costs.current_memory),
+            ]
+            .into_iter()
+            .map(|(instruction_type, cost)| (instruction_type, Metering::Fixed(cost)))
+            .collect(),
+        )
+        .with_grow_cost(self.config.grow_mem_cost);
 
         let module =
             self.module.take().expect("On entry to the function `module` can't be `None`; qed");
@@ -121,20 +187,14 @@ impl<'a> ContractModule<'a> {
                 _ => continue,
             };
 
-            let Type::Function(ref _func_ty) =
+            let Type::Function(ref func_ty) =
                 types.get(*type_idx as usize).ok_or_else(|| Error::Instantiate)?;
 
-            // TODO: Function type check with Env
-            /*
-
-            let ext_func = env
-                .funcs
-                .get(import.field().as_bytes())
-                .ok_or_else(|| Error::Instantiate)?;
-            if !ext_func.func_type_matches(func_ty) {
+            let (params, return_type) =
+                imports::signature(import.field()).ok_or_else(|| Error::Instantiate)?;
+            if func_ty.params() != params.as_slice() || func_ty.return_type() != return_type {
                 return Err(Error::Instantiate);
             }
-            */
         }
         Ok(imported_mem_type)
     }
@@ -155,6 +215,7 @@ impl<'a> ContractModule<'a> {
 /// - module doesn't define an internal memory instance,
 /// - imported memory (if any) doesn't reserve more memory than permitted by the `config`,
 /// - all imported functions from the external environment matches defined by `env` module,
+/// - code doesn't exceed the size permitted by the `config`.
 ///
 /// The preprocessing includes injecting code for gas metering and metering the height of stack.
 pub fn prepare_contract(original_code: &ContractCode, config: &Config) -> Result<Vec<u8>, Error> {
@@ -179,6 +240,16 @@ pub fn prepare_contract(original_code: &ContractCode, config: &Config) -> Result
     contract_module.into_wasm_code()
 }
 
+/// Checks the contract code that is being deployed. Besides running it through the
+/// `prepare_contract`, rejects modules that use floating point types or operations.
+///
+/// Floats are only checked at the deployment, so that contracts that are already deployed
+/// keep working (TODO(#194): AssemblyScript still emits them for the test contracts).
+pub fn validate_contract(original_code: &ContractCode, config: &Config) -> Result<(), Error> {
+    ContractModule::init(original_code.get_code(), config)?.ensure_no_floats()?;
+    prepare_contract(original_code, config).map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
     use wabt;
@@ -233,8 +304,6 @@ mod tests {
         let r = parse_and_prepare_wat(r#"(module (import "env" "gas" (func (param i32))))"#);
         assert_matches!(r, Ok(_));
 
-        // TODO: Address tests once we check proper function signatures.
-        /*
         // wrong signature
         let r = parse_and_prepare_wat(r#"(module (import "env" "gas" (func (param i64))))"#);
         assert_matches!(r, Err(Error::Instantiate));
@@ -242,6 +311,33 @@ mod tests {
         // unknown function name
         let r = parse_and_prepare_wat(r#"(module (import "env" "unknown_func" (func)))"#);
         assert_matches!(r, Err(Error::Instantiate));
-        */
+    }
+
+    #[test]
+    fn code_size() {
+        let wasm = wabt::Wat2Wasm::new().convert(r#"(module)"#).unwrap();
+        let code = ContractCode::new(wasm.as_ref().to_vec());
+        let config = Config { max_code_size: wasm.as_ref().len() as u32 - 1, ..Default::default() };
+        assert_matches!(prepare_contract(&code, &config), Err(Error::CodeTooLarge));
+    }
+
+    #[test]
+    fn floats() {
+        let validate_wat = |wat: &str| {
+            let wasm = wabt::Wat2Wasm::new().validate(false).convert(wat).unwrap();
+            validate_contract(&ContractCode::new(wasm.as_ref().to_vec()), &Config::default())
+        };
+
+        let r = validate_wat(r#"(module (func (drop (i32.add (i32.const 1) (i32.const 2)))))"#);
+        assert_matches!(r, Ok(_));
+
+        let r = validate_wat(r#"(module (func (drop (f32.add (f32.const 1) (f32.const 2)))))"#);
+        assert_matches!(r, Err(Error::FloatingPoint));
+
+        let r = validate_wat(r#"(module (func (param f64)))"#);
+        assert_matches!(r, Err(Error::FloatingPoint));
+
+        let r = validate_wat(r#"(module (func (local f32)))"#);
+        assert_matches!(r, Err(Error::FloatingPoint));
     }
 }
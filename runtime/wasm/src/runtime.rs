@@ -1,12 +1,16 @@
 use std::collections::HashSet;
+use std::convert::TryFrom;
 
 use byteorder::{ByteOrder, LittleEndian};
-use wasmer_runtime::{memory::Memory, units::Bytes};
+use sha3::{Digest, Keccak256};
 
+use near_primitives::crypto::signature::{verify, PublicKey, Signature};
 use near_primitives::hash::hash;
 use near_primitives::logging::pretty_utf8;
+use near_primitives::trace::{ExecutionTrace, HostFunctionCall, PromiseTrace, StorageAccess};
+use near_primitives::transaction::ContractEvent;
 use near_primitives::types::{
-    AccountId, Balance, PromiseId, ReceiptId, StorageUsage, StorageUsageChange,
+    AccountId, Balance, Gas, PromiseId, ReceiptId, StorageUsage, StorageUsageChange,
 };
 use near_primitives::utils::is_valid_account_id;
 
@@ -24,6 +28,21 @@ pub const DATA_TYPE_INPUT: DataTypeIndex = 4;
 pub const DATA_TYPE_RESULT: DataTypeIndex = 5;
 pub const DATA_TYPE_STORAGE_ITER: DataTypeIndex = 6;
 
+const ED25519_SIGNATURE_LEN: usize = 64;
+const ED25519_PUBLIC_KEY_LEN: usize = 32;
+
+/// Linear memory of the contract instance, implemented by each backend.
+pub(crate) trait MemoryLike {
+    /// Whether `len` bytes starting from `offset` fit into the memory.
+    fn fits_memory(&self, offset: usize, len: usize) -> bool;
+
+    /// Reads `len` bytes starting from `offset`, the range must fit into the memory.
+    fn read_memory(&self, offset: usize, len: usize) -> Vec<u8>;
+
+    /// Writes `buf` starting from `offset`, the range must fit into the memory.
+    fn write_memory(&self, offset: usize, buf: &[u8]);
+}
+
 /// Converts u128 into array of bytes.
 #[inline]
 fn to_uint128<'a>(value: u128) -> &'a [u8] {
@@ -38,7 +57,10 @@ pub struct Runtime<'a> {
     pub liquid_balance: Balance,
     /// Keep track of how much of the liquid balance is used by the contract so far,
     /// without deposits/withdrawals and resending of the balance to other contracts.
-    usage_counter: Balance,
+    pub usage_counter: Balance,
+    /// Part of the prepaid gas given to the promises created by the contract, the execution itself
+    /// can't burn it anymore.
+    promised_gas: Balance,
     context: &'a RuntimeContext<'a>,
     config: Config,
     pub storage_counter: StorageUsageChange,
@@ -47,7 +69,16 @@ pub struct Runtime<'a> {
     pub random_seed: Vec<u8>,
     random_buffer_offset: usize,
     pub logs: Vec<String>,
-    memory: Memory,
+    pub events: Vec<ContractEvent>,
+    /// Recorded only if `RuntimeContext::trace` is set.
+    pub trace: Option<ExecutionTrace>,
+    memory: Box<dyn MemoryLike>,
+}
+
+/// Converts the balance used by the execution into gas, saturating at the maximal gas instead of
+/// truncating.
+fn balance_to_gas(balance: Balance) -> Gas {
+    Gas::try_from(balance).unwrap_or(Gas::max_value())
 }
 
 impl<'a> Runtime<'a> {
@@ -57,7 +88,7 @@ impl<'a> Runtime<'a> {
         result_data: &'a [Option<Vec<u8>>],
         context: &'a RuntimeContext<'a>,
         config: Config,
-        memory: Memory,
+        memory: Box<dyn MemoryLike>,
     ) -> Runtime<'a> {
         Runtime {
             ext,
@@ -66,6 +97,7 @@ impl<'a> Runtime<'a> {
             frozen_balance: context.initial_balance,
             liquid_balance: context.received_amount,
             usage_counter: 0,
+            promised_gas: 0,
             context,
             config,
             storage_counter: 0,
@@ -74,15 +106,14 @@ impl<'a> Runtime<'a> {
             random_seed: hash(&context.random_seed).into(),
             random_buffer_offset: 0,
             logs: Vec::new(),
+            events: Vec::new(),
+            trace: if context.trace { Some(ExecutionTrace::default()) } else { None },
             memory,
         }
     }
 
     fn memory_can_fit(&self, offset: usize, len: usize) -> bool {
-        match offset.checked_add(len) {
-            None => false,
-            Some(end) => self.memory.size().bytes() >= Bytes(end),
-        }
+        self.memory.fits_memory(offset, len)
     }
 
     fn memory_get(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
@@ -91,10 +122,7 @@ impl<'a> Runtime<'a> {
         } else if len == 0 {
             Ok(Vec::new())
         } else {
-            Ok(self.memory.view()[offset..(offset + len)]
-                .iter()
-                .map(std::cell::Cell::get)
-                .collect())
+            Ok(self.memory.read_memory(offset, len))
         }
     }
 
@@ -104,10 +132,7 @@ impl<'a> Runtime<'a> {
         } else if buf.is_empty() {
             Ok(())
         } else {
-            self.memory.view()[offset..(offset + buf.len())]
-                .iter()
-                .zip(buf.iter())
-                .for_each(|(cell, v)| cell.set(*v));
+            self.memory.write_memory(offset, buf);
             Ok(())
         }
     }
@@ -173,20 +198,83 @@ impl<'a> Runtime<'a> {
         }
     }
 
-    /// Attempt to charge liquid balance, respecting usage limit.
+    /// Attempt to charge liquid balance, respecting usage limit and the prepaid gas.
     fn charge_balance_with_limit(&mut self, amount: Balance) -> Result<()> {
         let new_usage = self.usage_counter + amount;
-        if new_usage > self.config.usage_limit as u128 {
-            if self.context.free_of_charge {
-                Ok(())
-            } else {
-                Err(Error::UsageLimit)
-            }
+        let limit_error = if new_usage > self.config.usage_limit as u128 {
+            Some(Error::UsageLimit)
+        } else if new_usage + self.promised_gas > self.context.prepaid_gas as u128 {
+            Some(Error::PrepaidGasExceeded)
         } else {
-            self.charge_balance(amount).map(|res| {
+            None
+        };
+        match limit_error {
+            Some(_) if self.context.free_of_charge => Ok(()),
+            Some(err) => Err(err),
+            None => self.charge_balance(amount).map(|res| {
                 self.usage_counter = new_usage;
                 res
-            })
+            }),
+        }
+    }
+
+    /// Gas that is still available to the execution, neither burnt nor given to the promises.
+    fn remaining_gas(&self) -> Gas {
+        balance_to_gas(
+            (self.context.prepaid_gas as Balance)
+                .saturating_sub(self.usage_counter + self.promised_gas),
+        )
+    }
+
+    /// Gas burnt by the execution so far.
+    pub fn gas_used(&self) -> Gas {
+        balance_to_gas(self.usage_counter)
+    }
+
+    /// Charges the gas for the host function call: `base` plus `per_byte` for each of the
+    /// `num_bytes` processed by it.
+    fn charge_ext(&mut self, base: Gas, per_byte: Gas, num_bytes: usize) -> Result<()> {
+        let gas = Balance::from(base) + Balance::from(per_byte) * num_bytes as Balance;
+        self.charge_balance_with_limit(gas)
+    }
+
+    /// Calls the host function `f`, recording it into the trace if the tracing is enabled.
+    fn call_host<T, F>(&mut self, name: &str, args: &[(&str, u64)], f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        if self.trace.is_none() {
+            return f(self);
+        }
+        let usage_before = self.usage_counter;
+        let result = f(self);
+        self.trace_host_call(name, args, usage_before);
+        result
+    }
+
+    /// Records the host function call with the gas it charged since the usage was `usage_before`.
+    fn trace_host_call(&mut self, name: &str, args: &[(&str, u64)], usage_before: Balance) {
+        let gas = balance_to_gas(self.usage_counter - usage_before);
+        if let Some(trace) = self.trace.as_mut() {
+            // The injected gas meter is called for every block of operations, only keep the sum.
+            if name == "gas" {
+                trace.ops_gas += gas;
+                return;
+            }
+            let args = args.iter().map(|(arg, value)| (arg.to_string(), *value)).collect();
+            trace.host_calls.push(HostFunctionCall { name: name.to_string(), args, gas });
+        }
+    }
+
+    fn trace_storage<F: FnOnce() -> StorageAccess>(&mut self, access: F) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.storage.push(access());
+        }
+    }
+
+    fn trace_promise<F: FnOnce() -> PromiseTrace>(&mut self, promise: F) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.promises.push(promise());
         }
     }
 
@@ -204,10 +292,18 @@ impl<'a> Runtime<'a> {
         value_len: u32,
         value_ptr: u32,
     ) -> Result<()> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.storage_write_base, costs.write_byte);
+        self.charge_ext(base, per_byte, key_len as usize + value_len as usize)?;
         let key = self.memory_get(key_ptr as usize, key_len as usize)?;
         let value = self.memory_get(value_ptr as usize, value_len as usize)?;
 
         let evicted = self.ext.storage_set(&key, &value).map_err(|_| Error::StorageUpdateError)?;
+        self.trace_storage(|| StorageAccess::Write {
+            key: key.clone(),
+            value_len: value.len() as u64,
+            evicted_len: evicted.as_ref().map(|evicted| evicted.len() as u64),
+        });
         if let Some(evicted) = evicted {
             self.storage_counter +=
                 value_len as StorageUsageChange - evicted.len() as StorageUsageChange;
@@ -220,8 +316,15 @@ impl<'a> Runtime<'a> {
 
     /// Remove key from storage
     fn storage_remove(&mut self, key_len: u32, key_ptr: u32) -> Result<()> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.storage_remove_base, costs.read_byte);
+        self.charge_ext(base, per_byte, key_len as usize)?;
         let key = self.memory_get(key_ptr as usize, key_len as usize)?;
         let removed = self.ext.storage_remove(&key).map_err(|_| Error::StorageRemoveError)?;
+        self.trace_storage(|| StorageAccess::Remove {
+            key: key.clone(),
+            removed_len: removed.as_ref().map(|removed| removed.len() as u64),
+        });
         if let Some(removed) = removed {
             self.storage_counter -=
                 key_len as StorageUsageChange + removed.len() as StorageUsageChange;
@@ -232,9 +335,16 @@ impl<'a> Runtime<'a> {
 
     /// Returns whether the key is present in the storage
     fn storage_has_key(&mut self, key_len: u32, key_ptr: u32) -> Result<u32> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.storage_read_base, costs.read_byte);
+        self.charge_ext(base, per_byte, key_len as usize)?;
         let key = self.memory_get(key_ptr as usize, key_len as usize)?;
         // TODO(#743): Improve performance of has_key. Don't need to retrive the value.
         let val = self.ext.storage_get(&key).map_err(|_| Error::StorageReadError)?;
+        self.trace_storage(|| StorageAccess::Read {
+            key: key.clone(),
+            value_len: val.as_ref().map(|val| val.len() as u64),
+        });
         let res = val.is_some();
         debug!(target: "wasm", "storage_has_key('{}') -> {}", pretty_utf8(&key), res);
         Ok(res as u32)
@@ -242,6 +352,9 @@ impl<'a> Runtime<'a> {
 
     /// Gets iterator for keys with given prefix
     fn storage_iter(&mut self, prefix_len: u32, prefix_ptr: u32) -> Result<u32> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.storage_iter_base, costs.read_byte);
+        self.charge_ext(base, per_byte, prefix_len as usize)?;
         let prefix = self.memory_get(prefix_ptr as usize, prefix_len as usize)?;
         let storage_id = self.ext.storage_iter(&prefix).map_err(|_| Error::StorageReadError)?;
         debug!(target: "wasm", "storage_iter('{}') -> {}", pretty_utf8(&prefix), storage_id);
@@ -256,6 +369,9 @@ impl<'a> Runtime<'a> {
         end_len: u32,
         end_ptr: u32,
     ) -> Result<u32> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.storage_iter_base, costs.read_byte);
+        self.charge_ext(base, per_byte, start_len as usize + end_len as usize)?;
         let start_key = self.memory_get(start_ptr as usize, start_len as usize)?;
         let end_key = self.memory_get(end_ptr as usize, end_len as usize)?;
         let storage_id =
@@ -269,7 +385,11 @@ impl<'a> Runtime<'a> {
 
     /// Advances iterator. Returns true if iteration isn't finished yet.
     fn storage_iter_next(&mut self, storage_id: u32) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.storage_iter_next_base, 0, 0)?;
         let key = self.ext.storage_iter_next(storage_id).map_err(|_| Error::StorageUpdateError)?;
+        if let Some(ref key) = key {
+            self.charge_ext(0, self.config.ext_costs.read_byte, key.len())?;
+        }
         debug!(target: "wasm", "storage_iter_next({}) -> '{}'", storage_id, pretty_utf8(&key.clone().unwrap_or_default()));
         Ok(key.is_some() as u32)
     }
@@ -284,6 +404,8 @@ impl<'a> Runtime<'a> {
         arguments_ptr: u32,
         amount_ptr: u32,
     ) -> Result<u32> {
+        let num_bytes = account_id_len as usize + method_name_len as usize + arguments_len as usize;
+        self.charge_ext(self.config.ext_costs.base, self.config.ext_costs.read_byte, num_bytes)?;
         let amount = self.memory_get_u128(amount_ptr as usize)?;
         let account_id = self.read_and_parse_account_id(account_id_ptr, account_id_len)?;
         let method_name = self.memory_get(method_name_ptr as usize, method_name_len as usize)?;
@@ -295,9 +417,20 @@ impl<'a> Runtime<'a> {
         let arguments = self.memory_get(arguments_ptr as usize, arguments_len as usize)?;
         self.charge_balance(self.config.contract_call_cost + amount)?;
 
+        // The promise gets half of the remaining gas, the other half is kept for the rest of the
+        // execution and the promises created later.
+        let prepaid_gas = self.remaining_gas() / 2;
+        self.promised_gas += Balance::from(prepaid_gas);
+        self.trace_promise(|| PromiseTrace::Create {
+            receiver_id: account_id.clone(),
+            method_name: String::from_utf8_lossy(&method_name).into_owned(),
+            args_len: arguments.len() as u64,
+            amount,
+            prepaid_gas,
+        });
         let promise_id = self
             .ext
-            .promise_create(account_id, method_name, arguments, amount)
+            .promise_create(account_id, method_name, arguments, amount, prepaid_gas)
             .map_err(|_| Error::PromiseError)?;
 
         let promise_index = self.promise_ids.len();
@@ -315,6 +448,8 @@ impl<'a> Runtime<'a> {
         arguments_ptr: u32,
         amount_ptr: u32,
     ) -> Result<u32> {
+        let num_bytes = method_name_len as usize + arguments_len as usize;
+        self.charge_ext(self.config.ext_costs.base, self.config.ext_costs.read_byte, num_bytes)?;
         let amount = self.memory_get_u128(amount_ptr as usize)?;
         let promise_id = self.promise_index_to_id(promise_index)?;
         let method_name = self.memory_get(method_name_ptr as usize, method_name_len as usize)?;
@@ -330,6 +465,13 @@ impl<'a> Runtime<'a> {
         } as u128;
         self.charge_balance((num_promises * self.config.contract_call_cost + amount).into())?;
 
+        self.trace_promise(|| PromiseTrace::Then {
+            promise_index,
+            method_name: String::from_utf8_lossy(&method_name).into_owned(),
+            args_len: arguments.len() as u64,
+            amount,
+        });
+
         let promise_id = self
             .ext
             .promise_then(promise_id, method_name, arguments, amount.into())
@@ -342,6 +484,7 @@ impl<'a> Runtime<'a> {
     }
 
     fn promise_and(&mut self, promise_index1: u32, promise_index2: u32) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let promise_ids =
             [self.promise_index_to_id(promise_index1)?, self.promise_index_to_id(promise_index2)?];
 
@@ -369,6 +512,7 @@ impl<'a> Runtime<'a> {
             }
         }
 
+        self.trace_promise(|| PromiseTrace::And { promise_index1, promise_index2 });
         let promise_id = PromiseId::Joiner(receipt_ids);
         let promise_index = self.promise_ids.len();
         self.promise_ids.push(promise_id);
@@ -386,6 +530,7 @@ impl<'a> Runtime<'a> {
         mix_hash_len: u32,
         difficulty: u64,
     ) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.check_ethash_base, 0, 0)?;
         let header_hash = self.memory_get(header_hash_ptr as usize, header_hash_len as usize)?;
         let mix_hash = self.memory_get(mix_hash_ptr as usize, mix_hash_len as usize)?;
         Ok(self.ext.check_ethash(block_number, &header_hash, nonce, &mix_hash, difficulty) as u32)
@@ -393,11 +538,13 @@ impl<'a> Runtime<'a> {
 
     /// Returns the number of results.
     /// Results are available as part of the callback from a promise.
-    fn result_count(&self) -> Result<u32> {
+    fn result_count(&mut self) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         Ok(self.result_data.len() as u32)
     }
 
-    fn result_is_ok(&self, result_index: u32) -> Result<u32> {
+    fn result_is_ok(&mut self, result_index: u32) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let result =
             self.result_data.get(result_index as usize).ok_or(Error::InvalidResultIndex)?;
 
@@ -405,6 +552,8 @@ impl<'a> Runtime<'a> {
     }
 
     fn return_value(&mut self, value_len: u32, value_ptr: u32) -> Result<()> {
+        let per_byte = Gas::from(self.config.return_data_per_byte_cost);
+        self.charge_ext(self.config.ext_costs.base, per_byte, value_len as usize)?;
         let return_val = self.memory_get(value_ptr as usize, value_len as usize)?;
 
         self.return_data = ReturnData::Value(return_val);
@@ -413,6 +562,7 @@ impl<'a> Runtime<'a> {
     }
 
     fn return_promise(&mut self, promise_index: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let promise_id = self.promise_index_to_id(promise_index)?;
 
         self.return_data = ReturnData::Promise(promise_id);
@@ -421,10 +571,12 @@ impl<'a> Runtime<'a> {
     }
 
     fn get_frozen_balance(&mut self, balance_ptr: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         self.memory_set(balance_ptr as usize, to_uint128(self.frozen_balance))
     }
 
     fn get_liquid_balance(&mut self, balance_ptr: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         self.memory_set(balance_ptr as usize, to_uint128(self.liquid_balance))
     }
 
@@ -461,6 +613,7 @@ impl<'a> Runtime<'a> {
         max_amount_ptr: u32,
         balance_ptr: u32,
     ) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let min_amount = self.memory_get_u128(min_amount_ptr as usize)?;
         let max_amount = self.memory_get_u128(max_amount_ptr as usize)?;
         Self::transfer_helper(
@@ -482,6 +635,7 @@ impl<'a> Runtime<'a> {
         max_amount_ptr: u32,
         balance_ptr: u32,
     ) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let min_amount = self.memory_get_u128(min_amount_ptr as usize)?;
         let max_amount = self.memory_get_u128(max_amount_ptr as usize)?;
         Self::transfer_helper(
@@ -494,16 +648,19 @@ impl<'a> Runtime<'a> {
         .and_then(|val| self.memory_set(balance_ptr as usize, val))
     }
 
-    fn storage_usage(&self) -> Result<StorageUsage> {
+    fn storage_usage(&mut self) -> Result<StorageUsage> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let storage_usage = self.context.storage_usage as StorageUsageChange + self.storage_counter;
         Ok(storage_usage as StorageUsage)
     }
 
     fn received_amount(&mut self, balance_ptr: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         self.memory_set(balance_ptr as usize, to_uint128(self.context.received_amount))
     }
 
-    fn assert(&self, expression: u32) -> Result<()> {
+    fn assert(&mut self, expression: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         if expression != 0 {
             Ok(())
         } else {
@@ -512,6 +669,7 @@ impl<'a> Runtime<'a> {
     }
 
     fn abort(&mut self, msg_ptr: u32, filename_ptr: u32, line: u32, col: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let msg = self.read_string(msg_ptr as usize)?;
         let filename = self.read_string(filename_ptr as usize)?;
 
@@ -524,6 +682,8 @@ impl<'a> Runtime<'a> {
     }
 
     fn debug(&mut self, msg_len: u32, msg_ptr: u32) -> Result<()> {
+        let (base, per_byte) = (self.config.ext_costs.base, self.config.ext_costs.write_byte);
+        self.charge_ext(base, per_byte, msg_len as usize)?;
         let val = self.memory_get(msg_ptr as usize, msg_len as usize)?;
         let message = format!(
             "LOG: {}",
@@ -536,17 +696,40 @@ impl<'a> Runtime<'a> {
     }
 
     fn log(&mut self, msg_ptr: u32) -> Result<()> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let message = format!(
             "LOG: {}",
             self.read_string(msg_ptr as usize)
                 .unwrap_or_else(|_| "log(): read_string failed".to_string())
         );
+        self.charge_ext(0, self.config.ext_costs.write_byte, message.len())?;
         debug!(target: "wasm", "{}", &message);
         self.logs.push(message);
 
         Ok(())
     }
 
+    /// Emits an event with the given utf-8 topic and binary data.
+    fn emit_event(
+        &mut self,
+        topic_len: u32,
+        topic_ptr: u32,
+        data_len: u32,
+        data_ptr: u32,
+    ) -> Result<()> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.emit_event_base, costs.write_byte);
+        self.charge_ext(base, per_byte, topic_len as usize + data_len as usize)?;
+        let topic = self.memory_get(topic_ptr as usize, topic_len as usize)?;
+        let topic = String::from_utf8(topic).map_err(|_| Error::BadUtf8)?;
+        let data = self.memory_get(data_ptr as usize, data_len as usize)?;
+        debug!(target: "wasm", "emit_event('{}', '{}')", topic, pretty_utf8(&data));
+        let account_id = self.context.account_id.clone();
+        self.events.push(ContractEvent { account_id, topic, data });
+
+        Ok(())
+    }
+
     /// Generic data read. Tries to write data into the given buffer, only if the buffer has available capacity.
     /// Returns length of the data in bytes for the given buffer type and the given key.
     /// NOTE: Majority of reads would be small enough in size to fit into the given preallocated buffer.
@@ -564,13 +747,20 @@ impl<'a> Runtime<'a> {
         max_buf_len: u32,
         buf_ptr: u32,
     ) -> Result<u32> {
+        let (base, read_byte) = (self.config.ext_costs.base, self.config.ext_costs.read_byte);
         let tmp_vec;
         let buf = match data_type_index {
             DATA_TYPE_ORIGINATOR_ACCOUNT_ID => self.context.originator_id.as_bytes(),
             DATA_TYPE_CURRENT_ACCOUNT_ID => self.context.account_id.as_bytes(),
             DATA_TYPE_STORAGE => {
+                let storage_read_base = self.config.ext_costs.storage_read_base;
+                self.charge_ext(storage_read_base, read_byte, key_len as usize)?;
                 let key = self.memory_get(key as usize, key_len as usize)?;
                 let val = self.ext.storage_get(&key).map_err(|_| Error::StorageUpdateError)?;
+                self.trace_storage(|| StorageAccess::Read {
+                    key: key.clone(),
+                    value_len: val.as_ref().map(|val| val.len() as u64),
+                });
                 match val {
                     Some(v) => {
                         tmp_vec = v;
@@ -604,6 +794,7 @@ impl<'a> Runtime<'a> {
             }
             _ => return Err(Error::UnknownDataTypeIndex),
         };
+        self.charge_ext(base, read_byte, buf.len())?;
         if buf.len() <= max_buf_len as usize {
             self.memory_set(buf_ptr as usize, &buf)?;
         }
@@ -611,13 +802,89 @@ impl<'a> Runtime<'a> {
     }
 
     fn hash(&mut self, value_len: u32, value_ptr: u32, buf_ptr: u32) -> Result<()> {
+        let (base, per_byte) = (self.config.ext_costs.hash_base, self.config.ext_costs.hash_byte);
+        self.charge_ext(base, per_byte, value_len as usize)?;
         let buf = self.memory_get(value_ptr as usize, value_len as usize)?;
         let buf_hash = hash(&buf);
 
         self.memory_set(buf_ptr as usize, buf_hash.as_ref())
     }
 
-    fn hash32(&self, value_len: u32, value_ptr: u32) -> Result<u32> {
+    /// Hashes the value with keccak256 and writes 32 bytes of the result into `buf_ptr`.
+    fn keccak256(&mut self, value_len: u32, value_ptr: u32, buf_ptr: u32) -> Result<()> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.keccak256_base, costs.keccak256_byte);
+        self.charge_ext(base, per_byte, value_len as usize)?;
+        let buf = self.memory_get(value_ptr as usize, value_len as usize)?;
+        let buf_hash = Keccak256::digest(&buf);
+
+        self.memory_set(buf_ptr as usize, buf_hash.as_slice())
+    }
+
+    /// Verifies the ed25519 signature of the message with the public key.
+    /// Returns 1 if the signature is valid, 0 otherwise.
+    fn ed25519_verify(
+        &mut self,
+        message_len: u32,
+        message_ptr: u32,
+        signature_ptr: u32,
+        public_key_ptr: u32,
+    ) -> Result<u32> {
+        let costs = &self.config.ext_costs;
+        let (base, per_byte) = (costs.ed25519_verify_base, costs.ed25519_verify_byte);
+        self.charge_ext(base, per_byte, message_len as usize)?;
+        let message = self.memory_get(message_ptr as usize, message_len as usize)?;
+        let signature = self.memory_get(signature_ptr as usize, ED25519_SIGNATURE_LEN)?;
+        let public_key = self.memory_get(public_key_ptr as usize, ED25519_PUBLIC_KEY_LEN)?;
+        let signature = Signature::try_from(&signature[..]);
+        let public_key = PublicKey::try_from(&public_key[..]);
+        let valid = match (signature, public_key) {
+            (Ok(signature), Ok(public_key)) => verify(&message, &signature, &public_key),
+            _ => false,
+        };
+        Ok(valid as u32)
+    }
+
+    /// Recovers the secp256k1 public key that signed the 32 bytes hash, given the signature as
+    /// 64 bytes of `r` and `s` and the recovery id `v` (0 or 1, Ethereum's `v` minus 27).
+    /// Writes 64 bytes of the uncompressed public key without the prefix into `buf_ptr` and
+    /// returns 1, or returns 0 if the key can't be recovered.
+    fn ecrecover(
+        &mut self,
+        hash_ptr: u32,
+        signature_ptr: u32,
+        v: u32,
+        buf_ptr: u32,
+    ) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.ecrecover_base, 0, 0)?;
+        let hash = self.memory_get(hash_ptr as usize, 32)?;
+        let signature = self.memory_get(signature_ptr as usize, 64)?;
+        if !self.memory_can_fit(buf_ptr as usize, 64) {
+            return Err(Error::MemoryAccessViolation);
+        }
+        let recovery_id = match secp256k1::RecoveryId::parse(v as u8) {
+            Ok(recovery_id) if v <= 1 => recovery_id,
+            _ => return Ok(0),
+        };
+        let mut hash_bytes = [0u8; 32];
+        hash_bytes.copy_from_slice(&hash);
+        let mut signature_bytes = [0u8; 64];
+        signature_bytes.copy_from_slice(&signature);
+        let message = secp256k1::Message::parse(&hash_bytes);
+        let signature = secp256k1::Signature::parse(&signature_bytes);
+        match secp256k1::recover(&message, &signature, &recovery_id) {
+            Ok(public_key) => {
+                // Skips the prefix 0x04 of the uncompressed key.
+                self.memory_set(buf_ptr as usize, &public_key.serialize()[1..])?;
+                Ok(1)
+            }
+            Err(_) => Ok(0),
+        }
+    }
+
+    fn hash32(&mut self, value_len: u32, value_ptr: u32) -> Result<u32> {
+        let (base, per_byte) = (self.config.ext_costs.hash_base, self.config.ext_costs.hash_byte);
+        self.charge_ext(base, per_byte, value_len as usize)?;
         let buf = self.memory_get(value_ptr as usize, value_len as usize)?;
         let buf_hash = hash(&buf);
         let buf_hash_ref = buf_hash.as_ref();
@@ -632,6 +899,8 @@ impl<'a> Runtime<'a> {
     }
 
     fn random_buf(&mut self, len: u32, out_ptr: u32) -> Result<()> {
+        let (base, per_byte) = (self.config.ext_costs.base, self.config.ext_costs.write_byte);
+        self.charge_ext(base, per_byte, len as usize)?;
         if !self.memory_can_fit(out_ptr as usize, len as usize) {
             return Err(Error::MemoryAccessViolation);
         }
@@ -645,6 +914,7 @@ impl<'a> Runtime<'a> {
     }
 
     fn random32(&mut self) -> Result<u32> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         let mut random_val: u32 = 0;
         for _ in 0..4 {
             random_val <<= 8;
@@ -654,7 +924,8 @@ impl<'a> Runtime<'a> {
         Ok(random_val)
     }
 
-    fn block_index(&self) -> Result<u64> {
+    fn block_index(&mut self) -> Result<u64> {
+        self.charge_ext(self.config.ext_costs.base, 0, 0)?;
         Ok(self.context.block_index as u64)
     }
 }
@@ -662,19 +933,82 @@ impl<'a> Runtime<'a> {
 pub mod imports {
     use std::ffi::c_void;
 
-    use wasmer_runtime::{func, imports, Ctx, ImportObject};
+    use parity_wasm::elements::ValueType;
+    use wasmer_runtime::{func, imports, memory::Memory, Ctx, ImportObject};
+    #[cfg(feature = "interpreter")]
+    use wasmi::{RuntimeValue, Trap, TrapKind};
+
+    use super::{Result, Runtime};
+
+    macro_rules! value_type {
+        (u32) => {
+            ValueType::I32
+        };
+        (u64) => {
+            ValueType::I64
+        };
+    }
+
+    #[cfg(feature = "interpreter")]
+    macro_rules! into_runtime_value {
+        ($value:expr, ) => {{
+            let () = $value;
+            None
+        }};
+        ($value:expr, $returns:ident) => {
+            Some(RuntimeValue::from($value))
+        };
+    }
 
-    use super::{Memory, Result, Runtime};
+    /// Takes the next argument of the interpreted call, which must have the expected type.
+    #[cfg(feature = "interpreter")]
+    fn next_arg<T: wasmi::FromRuntimeValue>(
+        values: &mut std::slice::Iter<RuntimeValue>,
+    ) -> std::result::Result<T, Trap> {
+        values
+            .next()
+            .and_then(|value| value.try_into())
+            .ok_or_else(|| Trap::new(TrapKind::UnexpectedSignature))
+    }
 
     macro_rules! wrapped_imports {
         ( $( $import_name:expr => $func:ident < [ $( $arg_name:ident : $arg_type:ident ),* ] -> [ $( $returns:ident ),* ] >, )* ) => {
             $(
                 fn $func( ctx: &mut Ctx, $( $arg_name: $arg_type ),* ) -> Result<($( $returns ),*)> {
                     let runtime: &mut Runtime = unsafe { &mut *(ctx.data as *mut Runtime) };
-                    runtime.$func( $( $arg_name, )* )
+                    let args: &[(&str, u64)] =
+                        &[$( (stringify!($arg_name), u64::from($arg_name)) ),*];
+                    runtime.call_host($import_name, args, |runtime| runtime.$func( $( $arg_name, )* ))
                 }
             )*
 
+            /// Names of the host functions, interpreted calls refer to them by the index.
+            #[cfg(feature = "interpreter")]
+            pub(crate) const NAMES: &[&str] = &[$( $import_name ),*];
+
+            /// Calls the host function with the given name from the interpreter.
+            #[cfg(feature = "interpreter")]
+            pub(crate) fn invoke(
+                runtime: &mut Runtime,
+                name: &str,
+                values: &[RuntimeValue],
+            ) -> std::result::Result<Option<RuntimeValue>, Trap> {
+                $(
+                    if name == $import_name {
+                        #[allow(unused_mut, unused_variables)]
+                        let mut values = values.iter();
+                        $( let $arg_name: $arg_type = next_arg(&mut values)?; )*
+                        let args: &[(&str, u64)] =
+                            &[$( (stringify!($arg_name), u64::from($arg_name)) ),*];
+                        return runtime
+                            .call_host($import_name, args, |runtime| runtime.$func( $( $arg_name, )* ))
+                            .map(|value| into_runtime_value!(value, $( $returns )*))
+                            .map_err(|err| Trap::new(TrapKind::Host(Box::new(err))));
+                    }
+                )*
+                Err(Trap::new(TrapKind::UnexpectedSignature))
+            }
+
             pub(crate) fn build(memory: Memory, raw_ptr: *mut c_void) -> ImportObject {
                 let dtor = (|_: *mut c_void| {}) as fn(*mut c_void);
                 imports! {
@@ -687,6 +1021,17 @@ pub mod imports {
                     },
                 }
             }
+
+            /// Returns parameter and return types of the host function with the given name.
+            pub(crate) fn signature(name: &str) -> Option<(Vec<ValueType>, Option<ValueType>)> {
+                $(
+                    if name == $import_name {
+                        let returns: &[ValueType] = &[$( value_type!($returns) ),*];
+                        return Some((vec![$( value_type!($arg_type) ),*], returns.first().cloned()));
+                    }
+                )*
+                None
+            }
         }
     }
 
@@ -763,6 +1108,12 @@ pub mod imports {
         "hash" => hash<[value_len: u32, value_ptr: u32, buf_ptr: u32] -> []>,
         // Hashes given value and returns first 32 bits as u32.
         "hash32" => hash32<[value_len: u32, value_ptr: u32] -> [u32]>,
+        // Hashes given value with keccak256 and writes 32 bytes of result in the given pointer.
+        "keccak256" => keccak256<[value_len: u32, value_ptr: u32, buf_ptr: u32] -> []>,
+        // Verifies ed25519 signature of 64 bytes of the given message with public key of 32 bytes.
+        "ed25519_verify" => ed25519_verify<[message_len: u32, message_ptr: u32, signature_ptr: u32, public_key_ptr: u32] -> [u32]>,
+        // Recovers secp256k1 public key of 64 bytes from the 32 bytes hash and signature of 64 bytes.
+        "ecrecover" => ecrecover<[hash_ptr: u32, signature_ptr: u32, v: u32, buf_ptr: u32] -> [u32]>,
         // Fills given buffer of given length with random values.
         "random_buf" => random_buf<[buf_len: u32, buf_ptr: u32] -> []>,
         // Returns random u32.
@@ -771,6 +1122,8 @@ pub mod imports {
         "debug" => debug<[msg_len: u32, msg_ptr: u32] -> []>,
         // Prints to logs given AssemblyScript string in utf-16 format
         "log" => log<[msg_ptr: u32] -> []>,
+        // Emits an event with the given utf-8 topic and binary data, indexed by the node.
+        "emit_event" => emit_event<[topic_len: u32, topic_ptr: u32, data_len: u32, data_ptr: u32] -> []>,
 
         // Function for the injected gas counter. Automatically called by the gas meter.
         "gas" => gas<[gas_amount: u32] -> []>,
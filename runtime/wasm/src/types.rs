@@ -2,49 +2,14 @@ use std::fmt;
 
 use wasmer_runtime::error as WasmerError;
 
+pub use near_primitives::contract::PrepareError;
 use near_primitives::crypto::signature::PublicKey;
 use near_primitives::logging;
-use near_primitives::types::{AccountId, Balance, BlockIndex, PromiseId, StorageUsage};
+use near_primitives::serialize::u128_dec_format;
+use near_primitives::types::{AccountId, Balance, BlockIndex, Gas, PromiseId, StorageUsage};
 
 use crate::types::Error::Runtime;
 
-#[derive(Debug, Clone)]
-/// Error that can occur while preparing or executing wasm smart-contract.
-pub enum PrepareError {
-    /// Error happened while serializing the module.
-    Serialization,
-
-    /// Error happened while deserializing the module.
-    Deserialization,
-
-    /// Internal memory declaration has been found in the module.
-    InternalMemoryDeclared,
-
-    /// Gas instrumentation failed.
-    ///
-    /// This most likely indicates the module isn't valid.
-    GasInstrumentation,
-
-    /// Stack instrumentation failed.
-    ///
-    /// This  most likely indicates the module isn't valid.
-    StackHeightInstrumentation,
-
-    /// Error happened during invocation of the contract's entrypoint.
-    ///
-    /// Most likely because of trap.
-    Invoke,
-
-    /// Error happened during instantiation.
-    ///
-    /// This might indicate that `start` function trapped, or module isn't
-    /// instantiable and/or unlinkable.
-    Instantiate,
-
-    /// Memory error.
-    Memory,
-}
-
 /// User trap in native code
 #[allow(unused)]
 #[derive(Debug, Clone, PartialEq)]
@@ -77,6 +42,8 @@ pub enum RuntimeError {
     AssertFailed,
     /// Gas limit reached
     UsageLimit,
+    /// Gas prepaid by the caller is exhausted
+    PrepaidGasExceeded,
     /// Unknown runtime function
     Unknown,
     /// Passed string had invalid utf-8 encoding
@@ -137,6 +104,9 @@ impl ::std::fmt::Display for RuntimeError {
             RuntimeError::BadUtf8 => write!(f, "String encoding is bad utf-8 sequence"),
             RuntimeError::BadUtf16 => write!(f, "String encoding is bad utf-16 sequence"),
             RuntimeError::UsageLimit => write!(f, "Invocation resulted in usage limit violated"),
+            RuntimeError::PrepaidGasExceeded => {
+                write!(f, "Invocation burnt more gas than was prepaid for it")
+            }
             RuntimeError::Log => write!(f, "Error occured while logging an event"),
             RuntimeError::InvalidSyscall => {
                 write!(f, "Invalid syscall signature encountered at runtime")
@@ -174,6 +144,9 @@ pub enum Error {
 
     Wasmer(String), // TODO: WasmerError::Error is not shareable between threads
 
+    /// Error of the interpreter backend, other than the error of the host function.
+    Interpreter(String),
+
     Runtime(RuntimeError),
 
     Prepare(PrepareError),
@@ -260,13 +233,21 @@ pub struct Config {
     /// Gas cost of a growing memory by single page.
     pub grow_mem_cost: u32,
 
-    /// Gas cost of a regular operation.
+    /// Gas cost of a regular operation, i.e. an operation that doesn't belong to any of the
+    /// classes in `op_costs`.
     pub regular_op_cost: u32,
 
+    /// Gas costs of the operations by their class.
+    pub op_costs: OpCosts,
+
+    /// Gas costs of the host functions.
+    pub ext_costs: ExtCosts,
+
     /// Gas cost per one byte returned.
     pub return_data_per_byte_cost: u32,
 
     /// Gas cost of the contract call.
+    #[serde(with = "u128_dec_format")]
     pub contract_call_cost: Balance,
 
     /// How tall the stack is allowed to grow?
@@ -284,6 +265,9 @@ pub struct Config {
 
     /// Gas limit of the one contract call
     pub usage_limit: u64,
+
+    /// Maximal size of the contract code in bytes.
+    pub max_code_size: u32,
 }
 
 impl Default for Config {
@@ -291,12 +275,130 @@ impl Default for Config {
         Config {
             grow_mem_cost: 1,
             regular_op_cost: 1,
+            op_costs: OpCosts::default(),
+            ext_costs: ExtCosts::default(),
             return_data_per_byte_cost: 1,
             contract_call_cost: 0,
             max_stack_height: 64 * 1024,
             initial_memory_pages: 17,
             max_memory_pages: 32,
             usage_limit: 1024 * 1024 * 1024,
+            max_code_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+/// Gas costs of the wasm operations by their class.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpCosts {
+    /// Bitwise operations, e.g. `i32.and` or `i64.rotl`.
+    pub bit: u32,
+    /// Additions and subtractions.
+    pub add: u32,
+    /// Multiplications.
+    pub mul: u32,
+    /// Divisions and remainders.
+    pub div: u32,
+    /// Memory loads.
+    pub load: u32,
+    /// Memory stores.
+    pub store: u32,
+    /// Integer constants.
+    pub constant: u32,
+    /// Reads and writes of the locals.
+    pub local: u32,
+    /// Reads and writes of the globals.
+    pub global: u32,
+    /// Blocks, branches and calls.
+    pub control_flow: u32,
+    /// Integer comparisons.
+    pub integer_comparison: u32,
+    /// Conversions between integer types.
+    pub conversion: u32,
+    /// `current_memory` operation.
+    pub current_memory: u32,
+}
+
+impl Default for OpCosts {
+    fn default() -> OpCosts {
+        OpCosts {
+            bit: 1,
+            add: 1,
+            mul: 1,
+            div: 1,
+            load: 1,
+            store: 1,
+            constant: 1,
+            local: 1,
+            global: 1,
+            control_flow: 1,
+            integer_comparison: 1,
+            conversion: 1,
+            current_memory: 1,
+        }
+    }
+}
+
+/// Gas costs of the host functions. Every call is charged the base cost of the function,
+/// functions that move or process data are also charged for every byte of it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ExtCosts {
+    /// Base cost of the host functions that don't have a dedicated cost below.
+    pub base: Gas,
+    /// Base cost of reading a value from the storage, also charged for `storage_has_key`.
+    pub storage_read_base: Gas,
+    /// Base cost of writing a value into the storage.
+    pub storage_write_base: Gas,
+    /// Base cost of removing a value from the storage.
+    pub storage_remove_base: Gas,
+    /// Base cost of creating a storage iterator.
+    pub storage_iter_base: Gas,
+    /// Base cost of advancing a storage iterator.
+    pub storage_iter_next_base: Gas,
+    /// Base cost of hashing.
+    pub hash_base: Gas,
+    /// Base cost of the ethash verification.
+    pub check_ethash_base: Gas,
+    /// Base cost of emitting an event, which is indexed by the node.
+    pub emit_event_base: Gas,
+    /// Base cost of the keccak256 hashing.
+    pub keccak256_base: Gas,
+    /// Cost per byte hashed with keccak256.
+    pub keccak256_byte: Gas,
+    /// Base cost of the ed25519 signature verification.
+    pub ed25519_verify_base: Gas,
+    /// Cost per byte of the message verified with ed25519.
+    pub ed25519_verify_byte: Gas,
+    /// Cost of the secp256k1 public key recovery.
+    pub ecrecover_base: Gas,
+    /// Cost per byte read by the contract, e.g. from the storage or from the input.
+    pub read_byte: Gas,
+    /// Cost per byte written by the contract, e.g. into the storage or into the logs.
+    pub write_byte: Gas,
+    /// Cost per byte hashed.
+    pub hash_byte: Gas,
+}
+
+impl Default for ExtCosts {
+    fn default() -> ExtCosts {
+        ExtCosts {
+            base: 1,
+            storage_read_base: 50,
+            storage_write_base: 50,
+            storage_remove_base: 50,
+            storage_iter_base: 50,
+            storage_iter_next_base: 50,
+            hash_base: 50,
+            check_ethash_base: 20,
+            emit_event_base: 50,
+            keccak256_base: 50,
+            keccak256_byte: 1,
+            ed25519_verify_base: 300,
+            ed25519_verify_byte: 1,
+            ecrecover_base: 1000,
+            read_byte: 1,
+            write_byte: 1,
+            hash_byte: 1,
         }
     }
 }
@@ -309,6 +411,8 @@ pub struct RuntimeContext<'a> {
     pub initial_balance: Balance,
     /// The amount sent by the Sender.
     pub received_amount: Balance,
+    /// Gas the execution is allowed to burn, on top of the `Config::usage_limit`.
+    pub prepaid_gas: Gas,
     /// Originator's Account ID.
     pub originator_id: &'a AccountId,
     /// Current Account ID.
@@ -321,6 +425,8 @@ pub struct RuntimeContext<'a> {
     pub random_seed: Vec<u8>,
     /// Whether the execution should not charge any costs.
     pub free_of_charge: bool,
+    /// Whether the execution should record the `ExecutionTrace`.
+    pub trace: bool,
     /// TODO(#1017): Rename to originator_id
     /// Account ID of the account who signed the initial transaction.
     pub tx_originator_id: AccountId,
@@ -332,24 +438,28 @@ impl<'a> RuntimeContext<'a> {
     pub fn new(
         initial_balance: Balance,
         received_amount: Balance,
+        prepaid_gas: Gas,
         sender_id: &'a AccountId,
         account_id: &'a AccountId,
         storage_usage: StorageUsage,
         block_index: BlockIndex,
         random_seed: Vec<u8>,
         free_of_charge: bool,
+        trace: bool,
         originator_id: &AccountId,
         public_key: &PublicKey,
     ) -> RuntimeContext<'a> {
         RuntimeContext {
             initial_balance,
             received_amount,
+            prepaid_gas,
             originator_id: sender_id,
             account_id,
             storage_usage,
             block_index,
             random_seed,
             free_of_charge,
+            trace,
             tx_originator_id: originator_id.clone(),
             public_key: public_key.clone(),
         }
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 use std::thread;
@@ -9,6 +10,7 @@ use reqwest::r#async::Client as AsyncClient;
 use reqwest::Client as SyncClient;
 
 use near_primitives::crypto::signer::InMemorySigner;
+use near_primitives::hash::CryptoHash;
 use near_primitives::rpc::AccountViewCallResult;
 use near_primitives::serialize::from_base;
 use near_primitives::transaction::SignedTransaction;
@@ -32,6 +34,9 @@ pub struct RemoteNode {
     pub addr: SocketAddr,
     pub signers: Vec<Arc<InMemorySigner>>,
     pub nonces: Vec<Nonce>,
+    /// Chain id and hash of the block transactions are signed for, fetched when connecting.
+    pub chain_id: String,
+    pub block_hash: CryptoHash,
     pub url: String,
     async_client: Arc<AsyncClient>,
     sync_client: SyncClient,
@@ -115,11 +120,24 @@ impl RemoteNode {
             .connect_timeout(CONNECT_TIMEOUT)
             .build()
             .unwrap();
-        let mut result = Self { addr, signers, nonces, url, async_client, sync_client };
+        let mut result = Self {
+            addr,
+            signers,
+            nonces,
+            chain_id: String::new(),
+            block_hash: CryptoHash::default(),
+            url,
+            async_client,
+            sync_client,
+        };
 
         // Wait for the node to be up.
         wait(|| result.health_ok());
 
+        let (chain_id, block_hash) = get_result(|| result.get_latest_block());
+        result.chain_id = chain_id;
+        result.block_hash = block_hash;
+
         // Collect nonces.
         result.get_nonces(signers_accs);
         Arc::new(RwLock::new(result))
@@ -202,6 +220,17 @@ impl RemoteNode {
         Ok(response["result"]["height"].as_str().ok_or(VALUE_NOT_STR_ERR)?.parse()?)
     }
 
+    /// Returns chain id and hash of the latest block.
+    fn get_latest_block(&self) -> Result<(String, CryptoHash), Box<dyn std::error::Error>> {
+        let url = format!("{}{}", self.url, "/status");
+        let response: serde_json::Value = self.sync_client.post(url.as_str()).send()?.json()?;
+        let chain_id = response["result"]["chain_id"].as_str().ok_or(VALUE_NOT_STR_ERR)?;
+        let block_hash = response["result"]["sync_info"]["latest_block_hash"]
+            .as_str()
+            .ok_or(VALUE_NOT_STR_ERR)?;
+        Ok((chain_id.to_string(), CryptoHash::try_from(block_hash.to_string())?))
+    }
+
     pub fn get_current_height(&self) -> Result<u64, Box<dyn std::error::Error>> {
         let url = format!("{}{}", self.url, "/status");
         let response: serde_json::Value = self.sync_client.post(url.as_str()).send()?.json()?;
@@ -5,9 +5,13 @@ use std::sync::{Arc, RwLock};
 use near_primitives::transaction::{
     DeployContractTransaction, FunctionCallTransaction, SignedTransaction, TransactionBody,
 };
+use near_primitives::types::Gas;
 
 use crate::remote_node::RemoteNode;
 
+/// Gas attached to the generated function calls.
+const PREPAID_GAS: Gas = 1_000_000;
+
 #[derive(Clone, Copy)]
 pub enum TransactionType {
     SendMoney,
@@ -24,10 +28,15 @@ impl Generator {
         signer_ind: usize,
         all_accounts: &Vec<String>,
     ) -> SignedTransaction {
-        let (signer_from, nonce) = {
+        let (signer_from, nonce, block_hash, chain_id) = {
             let mut node = node.write().unwrap();
             node.nonces[signer_ind] += 1;
-            (node.signers[signer_ind].clone(), node.nonces[signer_ind])
+            (
+                node.signers[signer_ind].clone(),
+                node.nonces[signer_ind],
+                node.block_hash,
+                node.chain_id.clone(),
+            )
         };
 
         let acc_from = signer_from.account_id.clone();
@@ -39,7 +48,7 @@ impl Generator {
         };
 
         TransactionBody::send_money(nonce, acc_from.as_str(), acc_to.as_str(), 1)
-            .sign(&*signer_from)
+            .sign_for_block(&*signer_from, block_hash, &chain_id)
     }
 
     /// Returns transactions that deploy test contract to an every account used by the node.
@@ -56,18 +65,28 @@ impl Generator {
                 nonce,
                 contract_id,
                 wasm_byte_array: wasm_binary.to_vec(),
+                migration: None,
             };
-            res.push(TransactionBody::DeployContract(t).sign(&*signer));
+            res.push(TransactionBody::DeployContract(t).sign_for_block(
+                &*signer,
+                node.block_hash,
+                &node.chain_id,
+            ));
         }
         res
     }
 
     /// Create set key/value transaction.
     pub fn call_set(node: &Arc<RwLock<RemoteNode>>, signer_ind: usize) -> SignedTransaction {
-        let (signer_from, nonce) = {
+        let (signer_from, nonce, block_hash, chain_id) = {
             let mut node = node.write().unwrap();
             node.nonces[signer_ind] += 1;
-            (node.signers[signer_ind].clone(), node.nonces[signer_ind])
+            (
+                node.signers[signer_ind].clone(),
+                node.nonces[signer_ind],
+                node.block_hash,
+                node.chain_id.clone(),
+            )
         };
         let acc_from = signer_from.account_id.clone();
 
@@ -80,8 +99,9 @@ impl Generator {
             method_name: b"setKeyValue".to_vec(),
             args: format!("{{\"key\":\"{}\", \"value\":\"{}\"}}", key, value).as_bytes().to_vec(),
             amount: 1,
+            prepaid_gas: PREPAID_GAS,
         };
-        TransactionBody::FunctionCall(t).sign(&*signer_from)
+        TransactionBody::FunctionCall(t).sign_for_block(&*signer_from, block_hash, &chain_id)
     }
 
     /// Returns a transaction that calls `heavy_storage_blocks` on a contract.
@@ -89,10 +109,15 @@ impl Generator {
         node: &Arc<RwLock<RemoteNode>>,
         signer_ind: usize,
     ) -> SignedTransaction {
-        let (signer_from, nonce) = {
+        let (signer_from, nonce, block_hash, chain_id) = {
             let mut node = node.write().unwrap();
             node.nonces[signer_ind] += 1;
-            (node.signers[signer_ind].clone(), node.nonces[signer_ind])
+            (
+                node.signers[signer_ind].clone(),
+                node.nonces[signer_ind],
+                node.block_hash,
+                node.chain_id.clone(),
+            )
         };
         let acc_from = signer_from.account_id.clone();
 
@@ -103,7 +128,8 @@ impl Generator {
             method_name: b"heavy_storage_blocks".to_vec(),
             args: "{\"n\":1000}".as_bytes().to_vec(),
             amount: 1,
+            prepaid_gas: PREPAID_GAS,
         };
-        TransactionBody::FunctionCall(t).sign(&*signer_from)
+        TransactionBody::FunctionCall(t).sign_for_block(&*signer_from, block_hash, &chain_id)
     }
 }
@@ -7,7 +7,7 @@ use clap::{App, Arg, SubCommand};
 use protobuf::parse_from_bytes;
 
 use near::{get_default_home, get_store_path, load_config, NearConfig, NightshadeRuntime};
-use near_chain::{ChainStore, ChainStoreAccess};
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
 use near_network::peer_store::PeerStore;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::crypto::signature::PublicKey;
@@ -101,14 +101,34 @@ fn load_trie(
     store: Arc<Store>,
     home_dir: &Path,
     near_config: &NearConfig,
-) -> (NightshadeRuntime, CryptoHash, BlockIndex) {
+) -> (NightshadeRuntime, Vec<CryptoHash>, BlockIndex) {
     let mut chain_store = ChainStore::new(store.clone());
 
     let runtime = NightshadeRuntime::new(&home_dir, store, near_config.genesis_config.clone());
     let head = chain_store.head().unwrap();
     let last_header = chain_store.get_block_header(&head.last_block_hash).unwrap().clone();
-    let state_root = chain_store.get_post_state_root(&head.last_block_hash).unwrap();
-    (runtime, *state_root, last_header.height)
+    let state_roots = chain_store.get_post_state_roots(&head.last_block_hash).unwrap().clone();
+    (runtime, state_roots, last_header.height)
+}
+
+/// Finds the block, which applied the receipt with the given hash. Returns the height of the block,
+/// hash of its previous block and the index of the receipt among the receipts of that block.
+fn find_receipt(
+    chain_store: &mut ChainStore,
+    receipt_hash: &CryptoHash,
+) -> Option<(BlockIndex, CryptoHash, usize)> {
+    let mut header = chain_store.head_header().ok()?.clone();
+    // Receipts generated by the block are applied in the next one.
+    while header.height > 0 {
+        let prev_hash = header.prev_hash;
+        let receipts = chain_store.get_receipts(&prev_hash).ok()?;
+        if let Some(index) = receipts.iter().position(|receipt| &receipt.get_hash() == receipt_hash)
+        {
+            return Some((header.height, prev_hash, index));
+        }
+        header = chain_store.get_block_header(&prev_hash).ok()?.clone();
+    }
+    None
 }
 
 fn main() {
@@ -125,6 +145,15 @@ fn main() {
         )
         .subcommand(SubCommand::with_name("peers"))
         .subcommand(SubCommand::with_name("state"))
+        .subcommand(
+            SubCommand::with_name("replay_receipt").arg(
+                Arg::with_name("hash")
+                    .long("hash")
+                    .required(true)
+                    .help("Hash of the receipt to apply again with the contract call traced")
+                    .takes_value(true),
+            ),
+        )
         .subcommand(
             SubCommand::with_name("dump_state").arg(
                 Arg::with_name("output")
@@ -149,23 +178,62 @@ fn main() {
             }
         }
         ("state", Some(_args)) => {
-            let (runtime, state_root, height) = load_trie(store, &home_dir, &near_config);
-            println!("Storage root is {}, block height is {}", state_root, height);
-            let trie = TrieIterator::new(&runtime.trie, &state_root).unwrap();
-            for item in trie {
-                let (key, value) = item.unwrap();
-                print_state_entry(key, value);
+            let (runtime, state_roots, height) = load_trie(store, &home_dir, &near_config);
+            for (shard_id, state_root) in state_roots.iter().enumerate() {
+                println!(
+                    "Storage root of shard #{} is {}, block height is {}",
+                    shard_id, state_root, height
+                );
+                let trie = TrieIterator::new(&runtime.trie, state_root).unwrap();
+                for item in trie {
+                    let (key, value) = item.unwrap();
+                    print_state_entry(key, value);
+                }
+            }
+        }
+        ("replay_receipt", Some(args)) => {
+            let receipt_hash = CryptoHash::try_from(args.value_of("hash").unwrap().to_string())
+                .expect("Failed to parse the receipt hash");
+            let mut chain_store = ChainStore::new(store.clone());
+            let (height, prev_hash, index) = find_receipt(&mut chain_store, &receipt_hash)
+                .expect("Receipt is not found in the stored blocks");
+            let state_roots = chain_store.get_post_state_roots(&prev_hash).unwrap().clone();
+            // Receipts before it in the same block may have changed the state it was applied to.
+            let receipts = chain_store.get_receipts(&prev_hash).unwrap()[..=index].to_vec();
+            let runtime = NightshadeRuntime::new(&home_dir, store, near_config.genesis_config);
+            let result = runtime.replay_receipts(&state_roots, height, &receipts).unwrap().pop();
+            let result = result.expect("Receipt was not applied");
+            let shard_id = runtime.account_id_to_shard_id(&receipts[index].receiver);
+            println!(
+                "Receipt {} applied at height {} on top of {} in shard #{}",
+                receipt_hash, height, state_roots[shard_id as usize], shard_id
+            );
+            println!("{:#?}", receipts[index]);
+            println!("{:#?}", result);
+            if result.traces.is_empty() {
+                println!("Receipt didn't call a contract");
+            }
+            for trace in result.traces.iter() {
+                println!("{:#?}", trace);
             }
         }
         ("dump_state", Some(args)) => {
-            let (runtime, state_root, height) = load_trie(store, home_dir, &near_config);
+            let (runtime, state_roots, height) = load_trie(store, home_dir, &near_config);
             let output_path = args.value_of("output").map(|path| Path::new(path)).unwrap();
-            println!("Saving state at {} @ {} into {}", state_root, height, output_path.display());
-            near_config.genesis_config.records = vec![vec![]];
-            let trie = TrieIterator::new(&runtime.trie, &state_root).unwrap();
-            for item in trie {
-                let (key, value) = item.unwrap();
-                near_config.genesis_config.records[0].push(kv_to_state_record(key, value));
+            println!(
+                "Saving state at {:?} @ {} into {}",
+                state_roots,
+                height,
+                output_path.display()
+            );
+            near_config.genesis_config.records = vec![vec![]; state_roots.len()];
+            for (shard_id, state_root) in state_roots.iter().enumerate() {
+                let trie = TrieIterator::new(&runtime.trie, state_root).unwrap();
+                for item in trie {
+                    let (key, value) = item.unwrap();
+                    near_config.genesis_config.records[shard_id]
+                        .push(kv_to_state_record(key, value));
+                }
             }
             near_config.genesis_config.write_to_file(&output_path);
         }
@@ -10,7 +10,8 @@ use near_primitives::crypto::signer::{EDSigner, InMemorySigner};
 use near_primitives::rpc::AccountViewCallResult;
 use near_primitives::serialize::to_base;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, Balance};
+use near_primitives::types::{AccountId, Balance, ShardId};
+use near_primitives::utils::account_to_shard_id;
 use node_runtime::StateRecord;
 
 pub use crate::node::process_node::ProcessNode;
@@ -142,8 +143,10 @@ pub fn create_nodes_from_seeds(seeds: Vec<String>) -> Vec<NodeConfig> {
         to_base(include_bytes!("../../../../runtime/wasm/runtest/res/wasm_with_mem.wasm").as_ref());
     let (configs, signers, network_signers, mut genesis_config) =
         create_testnet_configs_from_seeds(seeds.clone(), 0, true);
+    let num_shards = genesis_config.records.len() as ShardId;
     for seed in seeds {
-        genesis_config.records[0]
+        let shard_id = account_to_shard_id(&seed, num_shards) as usize;
+        genesis_config.records[shard_id]
             .push(StateRecord::Contract { account_id: seed, code: code.clone() });
     }
     near_configs_to_node_configs(configs, signers, network_signers, genesis_config)
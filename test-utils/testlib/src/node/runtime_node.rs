@@ -1,13 +1,19 @@
 use std::sync::{Arc, RwLock};
 
+use near::GenesisConfig;
+use near_primitives::contract::ContractCode;
 use near_primitives::crypto::signer::{EDSigner, InMemorySigner};
 use near_primitives::transaction::{FunctionCallTransaction, TransactionBody};
-use near_primitives::types::{AccountId, Balance};
+use near_primitives::types::{AccountId, Balance, MerkleHash};
+use near_store::{get_account, set_account, set_code, Trie};
+use node_runtime::Runtime;
 
 use crate::node::Node;
-use crate::runtime_utils::get_runtime_and_trie;
+use crate::runtime_utils::{
+    get_runtime_and_trie, get_runtime_and_trie_from_genesis, FUNCTION_CALL_GAS,
+};
 use crate::user::runtime_user::MockClient;
-use crate::user::{RuntimeUser, User};
+use crate::user::{RuntimeUser, SignForUser, User};
 
 pub struct RuntimeNode {
     pub client: Arc<RwLock<MockClient>>,
@@ -16,17 +22,49 @@ pub struct RuntimeNode {
 
 impl RuntimeNode {
     pub fn new(account_id: &AccountId) -> Self {
-        let signer = Arc::new(InMemorySigner::from_seed(account_id, account_id));
         let (runtime, trie, root) = get_runtime_and_trie();
-        let client = Arc::new(RwLock::new(MockClient { runtime, trie, state_root: root }));
+        Self::from_runtime(account_id, runtime, trie, root)
+    }
+
+    /// Node with the state and the economics of the given genesis.
+    pub fn new_from_genesis(account_id: &AccountId, genesis_config: &GenesisConfig) -> Self {
+        let (runtime, trie, root) = get_runtime_and_trie_from_genesis(genesis_config);
+        Self::from_runtime(account_id, runtime, trie, root)
+    }
+
+    fn from_runtime(
+        account_id: &AccountId,
+        runtime: Runtime,
+        trie: Arc<Trie>,
+        state_root: MerkleHash,
+    ) -> Self {
+        let signer = Arc::new(InMemorySigner::from_seed(account_id, account_id));
+        let client = Arc::new(RwLock::new(MockClient { runtime, trie, state_root }));
         RuntimeNode { signer, client }
     }
 
+    /// Writes contract code of the existing account directly into the state, bypassing the checks
+    /// done by `DeployContract`. TODO(#194): Needed for the contracts compiled by AssemblyScript,
+    /// since it emits floating point operations.
+    pub fn set_code_unchecked(&self, account_id: &AccountId, code: &[u8]) {
+        let mut client = self.client.write().unwrap();
+        let mut state_update = client.get_state_update();
+        let code = ContractCode::new(code.to_vec());
+        let mut account = get_account(&state_update, account_id).expect("Account should exist");
+        account.code_hash = code.get_hash();
+        set_code(&mut state_update, account_id, &code);
+        set_account(&mut state_update, account_id, &account);
+        let (store_update, root) =
+            state_update.finalize().unwrap().into(client.trie.clone()).unwrap();
+        store_update.commit().unwrap();
+        client.state_root = root;
+    }
+
     pub fn send_money(&self, account_id: &AccountId, amount: Balance) {
         let nonce = self.get_account_nonce(&self.account_id().unwrap()).unwrap_or_default() + 1;
         let transaction =
             TransactionBody::send_money(nonce, &self.account_id().unwrap(), account_id, amount)
-                .sign(&*self.signer());
+                .sign_for(&*self.user(), &*self.signer());
         self.user().add_transaction(transaction).unwrap();
     }
 
@@ -46,8 +84,9 @@ impl RuntimeNode {
             method_name: method_name.as_bytes().to_vec(),
             args,
             amount,
+            prepaid_gas: FUNCTION_CALL_GAS,
         })
-        .sign(&*self.signer());
+        .sign_for(&*self.user(), &*self.signer());
         self.user().add_transaction(transaction).unwrap();
     }
 }
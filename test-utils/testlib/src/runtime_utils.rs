@@ -5,12 +5,15 @@ use tempdir::TempDir;
 
 use near::GenesisConfig;
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::types::{AccountId, MerkleHash};
+use near_primitives::types::{AccountId, Gas, MerkleHash};
 use near_store::test_utils::create_trie;
 use near_store::{Trie, TrieUpdate};
 use node_runtime::ethereum::EthashProvider;
 use node_runtime::{state_viewer::TrieViewer, Runtime};
 
+/// Gas attached to the function calls made in tests.
+pub const FUNCTION_CALL_GAS: Gas = 1_000_000_000;
+
 pub fn alice_account() -> AccountId {
     "alice.near".to_string()
 }
@@ -32,7 +35,7 @@ pub fn get_runtime_and_trie_from_genesis(
     let trie = create_trie();
     let dir = TempDir::new("ethash_test").unwrap();
     let ethash_provider = Arc::new(Mutex::new(EthashProvider::new(dir.path())));
-    let runtime = Runtime::new(ethash_provider);
+    let runtime = Runtime::new(ethash_provider, genesis_config.economics_config.clone());
     let trie_update = TrieUpdate::new(trie.clone(), MerkleHash::default());
     let (store_update, genesis_root) = runtime.apply_genesis_state(
         trie_update,
@@ -1,11 +1,13 @@
 use near::config::{TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
 use near_primitives::account::AccessKey;
+use near_primitives::contract::PrepareError;
 use near_primitives::crypto::signer::InMemorySigner;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::rpc::AccountViewCallResult;
 use near_primitives::serialize::{BaseEncode, Decode};
 use near_primitives::transaction::{
-    AddKeyTransaction, AsyncCall, Callback, CallbackInfo, CallbackResult, CreateAccountTransaction,
+    Action, AddKeyTransaction, AsyncCall, BatchTransaction, Callback, CallbackInfo,
+    CallbackResult, ContractMigration, CreateAccountTransaction, DeleteAccountTransaction,
     DeleteKeyTransaction, DeployContractTransaction, FinalTransactionStatus,
     FunctionCallTransaction, ReceiptBody, ReceiptTransaction, StakeTransaction, SwapKeyTransaction,
     TransactionBody, TransactionStatus,
@@ -15,9 +17,11 @@ use near_primitives::utils::key_for_callback;
 use near_store::set_callback;
 
 use crate::node::{Node, RuntimeNode};
-use crate::runtime_utils::{bob_account, default_code_hash, encode_int, eve_account};
+use crate::runtime_utils::{
+    bob_account, default_code_hash, encode_int, eve_account, FUNCTION_CALL_GAS,
+};
 use crate::test_helpers::wait;
-use crate::user::User;
+use crate::user::{SignForUser, User};
 
 /// The amount to send with function call.
 const FUNCTION_CALL_AMOUNT: Balance = 1_000_000_000_000;
@@ -63,8 +67,9 @@ fn add_access_key(
         originator: account_id.clone(),
         new_key: signer2.public_key.0[..].to_vec(),
         access_key: Some(access_key.clone()),
+        top_up: false,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -97,8 +102,9 @@ pub fn test_smart_contract_simple(node: impl Node) {
         method_name: b"run_test".to_vec(),
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -108,6 +114,33 @@ pub fn test_smart_contract_simple(node: impl Node) {
     validate_tx_result(node_user, root, &hash, 2);
 }
 
+pub fn test_smart_contract_prepaid_gas_exceeded(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let prepaid_gas = 1;
+    let transaction = TransactionBody::FunctionCall(FunctionCallTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        contract_id: bob_account(),
+        method_name: b"run_test".to_vec(),
+        args: vec![],
+        amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas,
+    })
+    .sign_for(&*node.user(), &*node.signer());
+
+    let node_user = node.user();
+    let hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &hash);
+
+    let transaction_result = node_user.get_transaction_result(&hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.receipts.len(), 1);
+    let receipt_result = node_user.get_transaction_result(&transaction_result.receipts[0]);
+    assert_eq!(receipt_result.status, TransactionStatus::Failed);
+    assert!(receipt_result.gas_used <= prepaid_gas);
+}
+
 pub fn test_smart_contract_self_call(node: impl Node) {
     let account_id = &node.account_id().unwrap();
     let transaction = TransactionBody::FunctionCall(FunctionCallTransaction {
@@ -117,8 +150,9 @@ pub fn test_smart_contract_self_call(node: impl Node) {
         method_name: b"run_test".to_vec(),
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -137,8 +171,9 @@ pub fn test_smart_contract_bad_method_name(node: impl Node) {
         method_name: b"_run_test".to_vec(),
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -162,8 +197,9 @@ pub fn test_smart_contract_empty_method_name_with_no_tokens(node: impl Node) {
         method_name: vec![],
         args: vec![],
         amount: 0,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -187,8 +223,9 @@ pub fn test_smart_contract_empty_method_name_with_tokens(node: impl Node) {
         method_name: vec![],
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -207,8 +244,9 @@ pub fn test_smart_contract_with_args(node: impl Node) {
         method_name: b"run_test".to_vec(),
         args: (2..4).flat_map(|x| encode_int(x).to_vec()).collect(),
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -232,6 +270,7 @@ pub fn test_async_call_with_no_callback(node: impl Node) {
             account_id.clone(),
             account_id.clone(),
             node.signer().public_key().clone(),
+            FUNCTION_CALL_GAS,
         )),
     };
 
@@ -272,6 +311,7 @@ pub fn test_async_call_with_callback(node: impl Node) {
         refund_account.clone(),
         account_id.clone(),
         node.signer().public_key().clone(),
+        FUNCTION_CALL_GAS,
     );
     let callback_info = CallbackInfo::new(callback_id.clone(), 0, account_id.clone());
     async_call.callback = Some(callback_info.clone());
@@ -321,6 +361,7 @@ pub fn test_async_call_with_logs(node: impl Node) {
             account_id.clone(),
             account_id.clone(),
             node.signer().public_key().clone(),
+            FUNCTION_CALL_GAS,
         )),
     };
 
@@ -362,6 +403,7 @@ pub fn test_deposit_with_callback(node: impl Node) {
         refund_account.clone(),
         account_id.clone(),
         node.signer().public_key().clone(),
+        0,
     );
     let callback_info = CallbackInfo::new(callback_id.clone(), 0, account_id.clone());
     async_call.callback = Some(callback_info.clone());
@@ -494,8 +536,9 @@ pub fn test_nonce_update_when_deploying_contract(node: impl Node) {
         nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
         contract_id: account_id.clone(),
         wasm_byte_array: wasm_binary.to_vec(),
+        migration: None,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -518,7 +561,7 @@ pub fn test_nonce_updated_when_tx_failed(node: impl Node) {
         &bob_account(),
         TESTING_INIT_BALANCE + 1,
     )
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let node_user = node.user();
     let hash = transaction.get_hash();
@@ -544,7 +587,7 @@ pub fn test_upload_contract(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: 10,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -556,8 +599,9 @@ pub fn test_upload_contract(node: impl Node) {
         nonce: 1,
         contract_id: eve_account(),
         wasm_byte_array: wasm_binary.to_vec(),
+        migration: None,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let tx_hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -576,13 +620,15 @@ pub fn test_upload_contract(node: impl Node) {
 pub fn test_redeploy_contract(node: impl Node) {
     let account_id = &node.account_id().unwrap();
     let node_user = node.user();
-    let test_binary = b"test_binary";
+    // Empty wasm module.
+    let test_binary = b"\0asm\x01\0\0\0";
     let transaction = TransactionBody::DeployContract(DeployContractTransaction {
         nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
         contract_id: account_id.clone(),
         wasm_byte_array: test_binary.to_vec(),
+        migration: None,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     let root = node_user.get_state_root();
     node_user.add_transaction(transaction).unwrap();
@@ -597,6 +643,84 @@ pub fn test_redeploy_contract(node: impl Node) {
     assert_eq!(account.code_hash, hash(test_binary));
 }
 
+pub fn test_deploy_contract_with_migration(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let wasm_binary = include_bytes!("../../../runtime/wasm/runtest/res/wasm_with_mem.wasm");
+    let transaction = TransactionBody::DeployContract(DeployContractTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        contract_id: account_id.clone(),
+        wasm_byte_array: wasm_binary.to_vec(),
+        migration: Some(ContractMigration {
+            method_name: b"run_test_with_storage_change".to_vec(),
+            args: vec![],
+            amount: FUNCTION_CALL_AMOUNT,
+            prepaid_gas: FUNCTION_CALL_GAS,
+        }),
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.result, Some(encode_int(20).to_vec()));
+    assert_eq!(transaction_result.previous_code_hash, Some(default_code_hash()));
+    assert!(!node_user.view_state(account_id).unwrap().values.is_empty());
+}
+
+pub fn test_deploy_contract_with_failed_migration(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    // Empty wasm module, doesn't export the migration method.
+    let test_binary = b"\0asm\x01\0\0\0";
+    let transaction = TransactionBody::DeployContract(DeployContractTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        contract_id: account_id.clone(),
+        wasm_byte_array: test_binary.to_vec(),
+        migration: Some(ContractMigration {
+            method_name: b"migrate".to_vec(),
+            args: vec![],
+            amount: FUNCTION_CALL_AMOUNT,
+            prepaid_gas: FUNCTION_CALL_GAS,
+        }),
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.previous_code_hash, None);
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.code_hash, default_code_hash());
+    assert_eq!(node_user.get_account_nonce(account_id).unwrap(), 1);
+}
+
+pub fn test_deploy_invalid_contract(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let code_hash = node_user.view_account(account_id).unwrap().code_hash;
+    let transaction = TransactionBody::DeployContract(DeployContractTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        contract_id: account_id.clone(),
+        wasm_byte_array: b"test_binary".to_vec(),
+        migration: None,
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.prepare_error, Some(PrepareError::Deserialization));
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.code_hash, code_hash);
+}
+
 pub fn test_send_money(node: impl Node) {
     let account_id = &node.account_id().unwrap();
     let node_user = node.user();
@@ -607,7 +731,7 @@ pub fn test_send_money(node: impl Node) {
         &bob_account(),
         money_used,
     )
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let tx_hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -655,7 +779,7 @@ pub fn test_send_money_over_balance(node: impl Node) {
         &bob_account(),
         money_used,
     )
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let tx_hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -704,7 +828,7 @@ pub fn test_refund_on_send_money_to_non_existent_account(node: impl Node) {
         &eve_account(),
         money_used,
     )
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let tx_hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -751,7 +875,7 @@ pub fn test_create_account(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: money_used,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -804,7 +928,7 @@ pub fn test_create_account_again(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: money_used,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -843,7 +967,7 @@ pub fn test_create_account_again(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: money_used,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     let root = node_user.get_state_root();
     node_user.add_transaction(transaction).unwrap();
@@ -898,7 +1022,7 @@ pub fn test_create_account_failure_invalid_name(node: impl Node) {
             public_key: node.signer().public_key().0[..].to_vec(),
             amount: money_used,
         })
-        .sign(&*node.signer());
+        .sign_for(&*node.user(), &*node.signer());
         let tx_hash = transaction.get_hash();
         node_user.add_transaction(transaction).unwrap();
         wait_for_transaction(&node_user, &tx_hash);
@@ -935,7 +1059,7 @@ pub fn test_create_account_failure_already_exists(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: money_used,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -994,7 +1118,7 @@ pub fn test_swap_key(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: money_used,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1007,7 +1131,7 @@ pub fn test_swap_key(node: impl Node) {
         cur_key: node.signer().public_key().0[..].to_vec(),
         new_key: signer2.public_key.0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1032,8 +1156,9 @@ pub fn test_add_key(node: impl Node) {
         originator: account_id.clone(),
         new_key: signer2.public_key.0[..].to_vec(),
         access_key: None,
+        top_up: false,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1058,8 +1183,9 @@ pub fn test_add_existing_key(node: impl Node) {
         originator: account_id.clone(),
         new_key: node.signer().public_key().0[..].to_vec(),
         access_key: None,
+        top_up: false,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1074,6 +1200,185 @@ pub fn test_add_existing_key(node: impl Node) {
     assert_eq!(account.public_keys.len(), 1);
 }
 
+pub fn test_batch_transaction(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let signer2 = InMemorySigner::from_random();
+    let signer3 = InMemorySigner::from_random();
+    let node_user = node.user();
+    let root = node_user.get_state_root();
+    let transaction = TransactionBody::Batch(BatchTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        receiver: account_id.clone(),
+        actions: vec![
+            Action::AddKey { new_key: signer2.public_key.0[..].to_vec(), access_key: None },
+            Action::AddKey { new_key: signer3.public_key.0[..].to_vec(), access_key: None },
+        ],
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+    let new_root = node_user.get_state_root();
+    assert_ne!(root, new_root);
+
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(
+        account.public_keys,
+        vec![node.signer().public_key(), signer2.public_key, signer3.public_key]
+    );
+}
+
+pub fn test_batch_transaction_atomic(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let signer2 = InMemorySigner::from_random();
+    let node_user = node.user();
+    let root = node_user.get_state_root();
+    let transaction = TransactionBody::Batch(BatchTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        receiver: account_id.clone(),
+        actions: vec![
+            Action::AddKey { new_key: signer2.public_key.0[..].to_vec(), access_key: None },
+            Action::AddKey { new_key: node.signer().public_key().0[..].to_vec(), access_key: None },
+        ],
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+    let new_root = node_user.get_state_root();
+    assert_ne!(root, new_root);
+
+    // Only the nonce is updated, the key added by the first action is rolled back.
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.nonce, 1);
+    assert_eq!(account.public_keys, vec![node.signer().public_key()]);
+}
+
+pub fn test_batch_transaction_not_owned(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let signer2 = InMemorySigner::from_random();
+    let node_user = node.user();
+    let transaction = TransactionBody::Batch(BatchTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        receiver: bob_account(),
+        actions: vec![
+            Action::Transfer { amount: 10 },
+            Action::AddKey { new_key: signer2.public_key.0[..].to_vec(), access_key: None },
+        ],
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.amount, TESTING_INIT_BALANCE - TESTING_INIT_STAKE);
+    let bob = node_user.view_account(&bob_account()).unwrap();
+    assert_eq!(bob.public_keys.len(), 1);
+}
+
+pub fn test_batch_transaction_empty(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let transaction = TransactionBody::Batch(BatchTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        receiver: account_id.clone(),
+        actions: vec![],
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    let root = node_user.get_state_root();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+    assert_eq!(node_user.get_state_root(), root);
+}
+
+pub fn test_batch_transaction_create_account(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let signer2 = InMemorySigner::from_random();
+    let signer3 = InMemorySigner::from_random();
+    let node_user = node.user();
+    let transaction = TransactionBody::Batch(BatchTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        receiver: eve_account(),
+        actions: vec![
+            Action::CreateAccount { amount: 10, public_key: signer2.public_key.0[..].to_vec() },
+            Action::AddKey { new_key: signer3.public_key.0[..].to_vec(), access_key: None },
+            Action::Transfer { amount: 5 },
+        ],
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.receipts.len(), 1);
+    let transaction_result = node_user.get_transaction_result(&transaction_result.receipts[0]);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert!(transaction_result.receipts.is_empty());
+
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.amount, TESTING_INIT_BALANCE - TESTING_INIT_STAKE - 15);
+    let eve = node_user.view_account(&eve_account()).unwrap();
+    assert_eq!(eve.amount, 15);
+    assert_eq!(eve.public_keys, vec![signer2.public_key, signer3.public_key]);
+}
+
+pub fn test_batch_transaction_create_account_atomic(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let signer2 = InMemorySigner::from_random();
+    let node_user = node.user();
+    let transaction = TransactionBody::Batch(BatchTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        receiver: eve_account(),
+        actions: vec![
+            Action::CreateAccount { amount: 10, public_key: signer2.public_key.0[..].to_vec() },
+            Action::AddKey { new_key: signer2.public_key.0[..].to_vec(), access_key: None },
+        ],
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.receipts.len(), 1);
+    let transaction_result = node_user.get_transaction_result(&transaction_result.receipts[0]);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.receipts.len(), 1);
+    wait_for_transaction(&node_user, &transaction_result.receipts[0]);
+
+    // The account created by the first action is rolled back and the deposit is refunded.
+    assert!(node_user.view_account(&eve_account()).is_err());
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.amount, TESTING_INIT_BALANCE - TESTING_INIT_STAKE);
+}
+
 pub fn test_delete_key(node: impl Node) {
     let account_id = &node.account_id().unwrap();
     let signer2 = InMemorySigner::from_random();
@@ -1084,8 +1389,9 @@ pub fn test_delete_key(node: impl Node) {
         originator: account_id.clone(),
         new_key: signer2.public_key.0[..].to_vec(),
         access_key: None,
+        top_up: false,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1101,7 +1407,7 @@ pub fn test_delete_key(node: impl Node) {
         originator: account_id.clone(),
         cur_key: node.signer().public_key().0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1128,7 +1434,7 @@ pub fn test_delete_key_not_owned(node: impl Node) {
         originator: account_id.clone(),
         cur_key: signer2.public_key.0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1153,7 +1459,7 @@ pub fn test_delete_key_last(node: impl Node) {
         originator: account_id.clone(),
         cur_key: node.signer().public_key().0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1197,7 +1503,7 @@ pub fn test_delete_access_key(node: impl Node) {
         originator: account_id.clone(),
         cur_key: signer2.public_key.0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1234,6 +1540,42 @@ pub fn test_add_access_key_with_funding(node: impl Node) {
     assert_eq!(view_access_key, Some(access_key));
 }
 
+pub fn test_top_up_access_key(node: impl Node) {
+    let access_key =
+        AccessKey { amount: 10, balance_owner: None, contract_id: None, method_name: None };
+    let node_user = node.user();
+    let account_id = &node.account_id().unwrap();
+    let signer2 = InMemorySigner::from_random();
+    let account = node_user.view_account(account_id).unwrap();
+    let initial_balance = account.amount;
+    add_access_key(&node, &node_user, &access_key, &signer2);
+
+    let root = node_user.get_state_root();
+    let transaction = TransactionBody::AddKey(AddKeyTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        new_key: signer2.public_key.0[..].to_vec(),
+        access_key: Some(AccessKey { amount: 5, ..access_key.clone() }),
+        top_up: true,
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+    let new_root = node_user.get_state_root();
+    assert_ne!(new_root, root);
+
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.amount, initial_balance - 15);
+
+    let view_access_key = node_user.get_access_key(account_id, &signer2.public_key).unwrap();
+    assert_eq!(view_access_key, Some(AccessKey { amount: 15, ..access_key }));
+}
+
 pub fn test_delete_access_key_with_owner_refund(node: impl Node) {
     let access_key =
         AccessKey { amount: 10, balance_owner: None, contract_id: None, method_name: None };
@@ -1250,7 +1592,7 @@ pub fn test_delete_access_key_with_owner_refund(node: impl Node) {
         originator: account_id.clone(),
         cur_key: signer2.public_key.0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1292,7 +1634,7 @@ pub fn test_delete_access_key_with_bob_refund(node: impl Node) {
         originator: account_id.clone(),
         cur_key: signer2.public_key.0[..].to_vec(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1339,8 +1681,9 @@ pub fn test_access_key_smart_contract(node: impl Node) {
         method_name: b"run_test".to_vec(),
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&signer2);
+    .sign_for(&*node.user(), &signer2);
 
     let hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -1368,8 +1711,9 @@ pub fn test_access_key_smart_contract_reject_method_name(node: impl Node) {
         method_name: b"run_test".to_vec(),
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&signer2);
+    .sign_for(&*node.user(), &signer2);
 
     let hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -1401,8 +1745,9 @@ pub fn test_access_key_smart_contract_reject_contract_id(node: impl Node) {
         method_name: b"run_test".to_vec(),
         args: vec![],
         amount: FUNCTION_CALL_AMOUNT,
+        prepaid_gas: FUNCTION_CALL_GAS,
     })
-    .sign(&signer2);
+    .sign_for(&*node.user(), &signer2);
 
     let hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -1415,6 +1760,48 @@ pub fn test_access_key_smart_contract_reject_contract_id(node: impl Node) {
     assert_eq!(root, new_root);
 }
 
+/// Expects the node to charge for the function calls, so that the allowance covering the attached
+/// amount isn't enough to pay for the transaction too.
+pub fn test_access_key_smart_contract_reject_allowance(node: impl Node) {
+    let node_user = node.user();
+    let account_id = &node.account_id().unwrap();
+    // The allowance doesn't cover the amount, or covers it but not the cost of the transaction.
+    for allowance in vec![FUNCTION_CALL_AMOUNT - 1, FUNCTION_CALL_AMOUNT] {
+        let access_key = AccessKey {
+            amount: allowance,
+            balance_owner: None,
+            contract_id: Some(bob_account()),
+            method_name: None,
+        };
+        let signer2 = InMemorySigner::from_random();
+        add_access_key(&node, &node_user, &access_key, &signer2);
+
+        let transaction = TransactionBody::FunctionCall(FunctionCallTransaction {
+            nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+            originator: account_id.clone(),
+            contract_id: bob_account(),
+            method_name: b"run_test".to_vec(),
+            args: vec![],
+            amount: FUNCTION_CALL_AMOUNT,
+            prepaid_gas: FUNCTION_CALL_GAS,
+        })
+        .sign_for(&*node.user(), &signer2);
+
+        let hash = transaction.get_hash();
+        let root = node_user.get_state_root();
+        node_user.add_transaction(transaction).unwrap();
+        wait_for_transaction(&node_user, &hash);
+        let transaction_result = node_user.get_transaction_result(&hash);
+        assert_eq!(transaction_result.status, TransactionStatus::Failed);
+        assert_eq!(transaction_result.receipts.len(), 0);
+        let new_root = node_user.get_state_root();
+        assert_eq!(root, new_root);
+
+        let view_access_key = node_user.get_access_key(account_id, &signer2.public_key).unwrap();
+        assert_eq!(view_access_key, Some(access_key));
+    }
+}
+
 pub fn test_access_key_reject_non_function_call(node: impl Node) {
     let access_key =
         AccessKey { amount: 0, balance_owner: None, contract_id: None, method_name: None };
@@ -1428,7 +1815,7 @@ pub fn test_access_key_reject_non_function_call(node: impl Node) {
         originator: account_id.clone(),
         cur_key: node.signer().public_key().0[..].to_vec(),
     })
-    .sign(&signer2);
+    .sign_for(&*node.user(), &signer2);
 
     let hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -1451,7 +1838,7 @@ pub fn test_increase_stake(node: impl Node) {
         amount: amount_staked,
         public_key: node.signer().public_key().to_base(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -1478,7 +1865,7 @@ pub fn test_decrease_stake(node: impl Node) {
         amount: amount_staked,
         public_key: node.signer().public_key().to_base(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let hash = transaction.get_hash();
     let root = node_user.get_state_root();
@@ -1505,7 +1892,7 @@ pub fn test_unstake_while_not_staked(node: impl Node) {
         public_key: node.signer().public_key().0[..].to_vec(),
         amount: 10,
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
     let tx_hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
     wait_for_transaction(&node_user, &tx_hash);
@@ -1517,7 +1904,7 @@ pub fn test_unstake_while_not_staked(node: impl Node) {
         amount: amount_staked,
         public_key: node.signer().public_key().to_base(),
     })
-    .sign(&*node.signer());
+    .sign_for(&*node.user(), &*node.signer());
 
     let hash = transaction.get_hash();
     node_user.add_transaction(transaction).unwrap();
@@ -1526,3 +1913,95 @@ pub fn test_unstake_while_not_staked(node: impl Node) {
     assert_eq!(transaction_result.status, TransactionStatus::Failed);
     assert_eq!(transaction_result.receipts.len(), 0);
 }
+
+pub fn test_delete_account(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let money_used = 10;
+    let transaction = TransactionBody::CreateAccount(CreateAccountTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        new_account_id: eve_account(),
+        public_key: node.signer().public_key().0[..].to_vec(),
+        amount: money_used,
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+    assert!(node_user.view_account(&eve_account()).is_ok());
+
+    let bob_amount = node_user.view_balance(&bob_account()).unwrap();
+    let transaction = TransactionBody::DeleteAccount(DeleteAccountTransaction {
+        nonce: node.get_account_nonce(&eve_account()).unwrap_or_default() + 1,
+        originator: eve_account(),
+        beneficiary_id: bob_account(),
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+    assert_eq!(transaction_result.receipts.len(), 1);
+    let transaction_result = node_user.get_transaction_result(&transaction_result.receipts[0]);
+    assert_eq!(transaction_result.status, TransactionStatus::Completed);
+
+    assert!(node_user.view_account(&eve_account()).is_err());
+    assert!(node_user.view_balance(&bob_account()).unwrap() > bob_amount);
+}
+
+pub fn test_delete_account_missing_beneficiary(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let transaction = TransactionBody::CreateAccount(CreateAccountTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        new_account_id: eve_account(),
+        public_key: node.signer().public_key().0[..].to_vec(),
+        amount: 10,
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction = TransactionBody::DeleteAccount(DeleteAccountTransaction {
+        nonce: node.get_account_nonce(&eve_account()).unwrap_or_default() + 1,
+        originator: eve_account(),
+        beneficiary_id: "nonexistent.near".to_string(),
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+    // The account with its balance is kept.
+    assert!(node_user.view_balance(&eve_account()).unwrap() > 0);
+}
+
+pub fn test_delete_account_while_staked(node: impl Node) {
+    let account_id = &node.account_id().unwrap();
+    let node_user = node.user();
+    let transaction = TransactionBody::DeleteAccount(DeleteAccountTransaction {
+        nonce: node.get_account_nonce(account_id).unwrap_or_default() + 1,
+        originator: account_id.clone(),
+        beneficiary_id: bob_account(),
+    })
+    .sign_for(&*node.user(), &*node.signer());
+    let tx_hash = transaction.get_hash();
+    node_user.add_transaction(transaction).unwrap();
+    wait_for_transaction(&node_user, &tx_hash);
+
+    let transaction_result = node_user.get_transaction_result(&tx_hash);
+    assert_eq!(transaction_result.status, TransactionStatus::Failed);
+    assert_eq!(transaction_result.receipts.len(), 0);
+
+    let account = node_user.view_account(account_id).unwrap();
+    assert_eq!(account.stake, TESTING_INIT_STAKE);
+    assert_eq!(account.amount, TESTING_INIT_BALANCE - TESTING_INIT_STAKE);
+}
@@ -3,11 +3,13 @@ use futures::Future;
 use near_chain::Block;
 use near_primitives::account::AccessKey;
 use near_primitives::crypto::signature::PublicKey;
+use near_primitives::crypto::signer::EDSigner;
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::ReceiptInfo;
 use near_primitives::rpc::{AccountViewCallResult, ViewStateResult};
 use near_primitives::transaction::{
-    FinalTransactionResult, ReceiptTransaction, SignedTransaction, TransactionResult,
+    FinalTransactionResult, ReceiptTransaction, SignedTransaction, TransactionBody,
+    TransactionResult,
 };
 use near_primitives::types::{AccountId, Balance, MerkleHash};
 
@@ -29,6 +31,9 @@ pub trait User {
 
     fn add_transaction(&self, transaction: SignedTransaction) -> Result<(), String>;
 
+    /// Signs transaction for the latest block of the chain user is connected to.
+    fn sign_transaction(&self, body: TransactionBody, signer: &dyn EDSigner) -> SignedTransaction;
+
     fn commit_transaction(
         &self,
         transaction: SignedTransaction,
@@ -57,6 +62,17 @@ pub trait User {
     ) -> Result<Option<AccessKey>, String>;
 }
 
+/// Allows to sign transactions in place, while building them.
+pub trait SignForUser {
+    fn sign_for(self, user: &dyn User, signer: &dyn EDSigner) -> SignedTransaction;
+}
+
+impl SignForUser for TransactionBody {
+    fn sign_for(self, user: &dyn User, signer: &dyn EDSigner) -> SignedTransaction {
+        user.sign_transaction(self, signer)
+    }
+}
+
 /// Same as `User` by provides async API that can be used inside tokio.
 pub trait AsyncUser: Send + Sync {
     fn view_account(
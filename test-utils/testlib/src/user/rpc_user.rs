@@ -9,12 +9,15 @@ use near_client::StatusResponse;
 use near_jsonrpc::client::{new_client, JsonRpcClient};
 use near_primitives::account::AccessKey;
 use near_primitives::crypto::signature::PublicKey;
+use near_primitives::crypto::signer::EDSigner;
 use near_primitives::hash::CryptoHash;
+use near_primitives::merkle::merklize;
 use near_primitives::receipt::ReceiptInfo;
-use near_primitives::rpc::{AccountViewCallResult, QueryResponse, ViewStateResult};
+use near_primitives::rpc::{AccountViewCallResult, BlockReference, QueryResponse, ViewStateResult};
 use near_primitives::serialize::{to_base, to_base64, BaseEncode};
 use near_primitives::transaction::{
-    FinalTransactionResult, ReceiptTransaction, SignedTransaction, TransactionResult,
+    FinalTransactionResult, ReceiptTransaction, SignedTransaction, TransactionBody,
+    TransactionResult,
 };
 use near_primitives::types::{AccountId, MerkleHash};
 use near_protos::signed_transaction as transaction_proto;
@@ -35,7 +38,11 @@ impl RpcUser {
     }
 
     pub fn query(&self, path: String, data: Vec<u8>) -> Result<QueryResponse, String> {
-        System::new("actix").block_on(self.client.write().unwrap().query(path, to_base(&data)))
+        System::new("actix").block_on(self.client.write().unwrap().query(
+            path,
+            to_base(&data),
+            BlockReference::Best,
+        ))
     }
 }
 
@@ -56,6 +63,11 @@ impl User for RpcUser {
         Ok(())
     }
 
+    fn sign_transaction(&self, body: TransactionBody, signer: &dyn EDSigner) -> SignedTransaction {
+        let status = self.get_status().expect("Failed to get status of the node");
+        body.sign_for_block(signer, status.sync_info.latest_block_hash, &status.chain_id)
+    }
+
     fn commit_transaction(
         &self,
         transaction: SignedTransaction,
@@ -90,8 +102,9 @@ impl User for RpcUser {
         System::new("actix").block_on(self.client.write().unwrap().tx(hash.into())).unwrap()
     }
 
+    /// Single root over the states of all the shards.
     fn get_state_root(&self) -> MerkleHash {
-        self.get_status().map(|status| status.sync_info.latest_state_root).unwrap()
+        self.get_status().map(|status| merklize(&status.sync_info.latest_state_roots).0).unwrap()
     }
 
     fn get_receipt_info(&self, _hash: &CryptoHash) -> Option<ReceiptInfo> {
@@ -8,12 +8,13 @@ use lazy_static::lazy_static;
 use near_chain::Block;
 use near_primitives::account::AccessKey;
 use near_primitives::crypto::signature::PublicKey;
+use near_primitives::crypto::signer::EDSigner;
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::ReceiptInfo;
 use near_primitives::rpc::{AccountViewCallResult, ViewStateResult};
 use near_primitives::transaction::{
     FinalTransactionResult, FinalTransactionStatus, ReceiptTransaction, SignedTransaction,
-    TransactionLogs, TransactionResult, TransactionStatus,
+    TransactionBody, TransactionLogs, TransactionResult, TransactionStatus,
 };
 use near_primitives::types::{AccountId, MerkleHash};
 use near_store::{Trie, TrieUpdate};
@@ -100,6 +101,7 @@ impl RuntimeUser {
                 shard_id: cur_apply_state.shard_id,
                 block_index: cur_apply_state.block_index,
                 parent_block_hash: cur_apply_state.parent_block_hash,
+                chain_context: cur_apply_state.chain_context,
             };
             let new_receipts: Vec<_> =
                 apply_result.new_receipts.drain().flat_map(|(_, v)| v).collect();
@@ -157,11 +159,17 @@ impl User for RuntimeUser {
             shard_id: 0,
             parent_block_hash: CryptoHash::default(),
             block_index: 0,
+            chain_context: None,
         };
         self.apply_all(apply_state, vec![], vec![transaction]);
         Ok(())
     }
 
+    fn sign_transaction(&self, body: TransactionBody, signer: &dyn EDSigner) -> SignedTransaction {
+        // There is no chain, runtime doesn't check the reference block of transactions.
+        body.sign(signer)
+    }
+
     fn commit_transaction(
         &self,
         transaction: SignedTransaction,
@@ -171,6 +179,7 @@ impl User for RuntimeUser {
             shard_id: 0,
             parent_block_hash: CryptoHash::default(),
             block_index: 0,
+            chain_context: None,
         };
         self.apply_all(apply_state, vec![], vec![transaction.clone()]);
         Ok(self.get_transaction_final_result(&transaction.get_hash()))
@@ -182,6 +191,7 @@ impl User for RuntimeUser {
             shard_id: 0,
             parent_block_hash: CryptoHash::default(),
             block_index: 0,
+            chain_context: None,
         };
         self.apply_all(apply_state, vec![vec![receipt]], vec![]);
         Ok(())
@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod test {
+    use near::GenesisConfig;
     use testlib::node::RuntimeNode;
-    use testlib::runtime_utils::alice_account;
+    use testlib::runtime_utils::{alice_account, bob_account};
     use testlib::standard_test_cases::*;
 
     fn create_runtime_node() -> RuntimeNode {
@@ -14,6 +15,12 @@ mod test {
         test_smart_contract_simple(node);
     }
 
+    #[test]
+    fn test_smart_contract_prepaid_gas_exceeded_runtime() {
+        let node = create_runtime_node();
+        test_smart_contract_prepaid_gas_exceeded(node);
+    }
+
     #[test]
     fn test_smart_contract_self_call_runtime() {
         let node = create_runtime_node();
@@ -104,6 +111,24 @@ mod test {
         test_redeploy_contract(node);
     }
 
+    #[test]
+    fn test_deploy_contract_with_migration_runtime() {
+        let node = create_runtime_node();
+        test_deploy_contract_with_migration(node);
+    }
+
+    #[test]
+    fn test_deploy_contract_with_failed_migration_runtime() {
+        let node = create_runtime_node();
+        test_deploy_contract_with_failed_migration(node);
+    }
+
+    #[test]
+    fn test_deploy_invalid_contract_runtime() {
+        let node = create_runtime_node();
+        test_deploy_invalid_contract(node);
+    }
+
     #[test]
     fn test_send_money_runtime() {
         let node = create_runtime_node();
@@ -164,6 +189,42 @@ mod test {
         test_add_existing_key(node);
     }
 
+    #[test]
+    fn test_batch_transaction_runtime() {
+        let node = create_runtime_node();
+        test_batch_transaction(node);
+    }
+
+    #[test]
+    fn test_batch_transaction_atomic_runtime() {
+        let node = create_runtime_node();
+        test_batch_transaction_atomic(node);
+    }
+
+    #[test]
+    fn test_batch_transaction_not_owned_runtime() {
+        let node = create_runtime_node();
+        test_batch_transaction_not_owned(node);
+    }
+
+    #[test]
+    fn test_batch_transaction_empty_runtime() {
+        let node = create_runtime_node();
+        test_batch_transaction_empty(node);
+    }
+
+    #[test]
+    fn test_batch_transaction_create_account_runtime() {
+        let node = create_runtime_node();
+        test_batch_transaction_create_account(node);
+    }
+
+    #[test]
+    fn test_batch_transaction_create_account_atomic_runtime() {
+        let node = create_runtime_node();
+        test_batch_transaction_create_account_atomic(node);
+    }
+
     #[test]
     fn test_delete_key_runtime() {
         let node = create_runtime_node();
@@ -200,6 +261,12 @@ mod test {
         test_add_access_key_with_funding(node);
     }
 
+    #[test]
+    fn test_top_up_access_key_runtime() {
+        let node = create_runtime_node();
+        test_top_up_access_key(node);
+    }
+
     #[test]
     fn test_delete_access_key_with_owner_refund_runtime() {
         let node = create_runtime_node();
@@ -230,6 +297,15 @@ mod test {
         test_access_key_smart_contract_reject_contract_id(node);
     }
 
+    #[test]
+    fn test_access_key_smart_contract_reject_allowance_runtime() {
+        let mut genesis_config =
+            GenesisConfig::test(vec![&alice_account(), &bob_account(), "carol.near"]);
+        genesis_config.economics_config.transactions_costs.function_call = 1;
+        let node = RuntimeNode::new_from_genesis(&alice_account(), &genesis_config);
+        test_access_key_smart_contract_reject_allowance(node);
+    }
+
     #[test]
     fn test_access_key_reject_non_function_call_runtime() {
         let node = create_runtime_node();
@@ -253,4 +329,22 @@ mod test {
         let node = create_runtime_node();
         test_unstake_while_not_staked(node);
     }
+
+    #[test]
+    fn test_delete_account_runtime() {
+        let node = create_runtime_node();
+        test_delete_account(node);
+    }
+
+    #[test]
+    fn test_delete_account_missing_beneficiary_runtime() {
+        let node = create_runtime_node();
+        test_delete_account_missing_beneficiary(node);
+    }
+
+    #[test]
+    fn test_delete_account_while_staked_runtime() {
+        let node = create_runtime_node();
+        test_delete_account_while_staked(node);
+    }
 }
@@ -62,6 +62,11 @@ mod test {
         run_testnet_test!(test_smart_contract_simple);
     }
 
+    #[test]
+    fn test_smart_contract_prepaid_gas_exceeded_testnet() {
+        run_testnet_test!(test_smart_contract_prepaid_gas_exceeded);
+    }
+
     #[test]
     fn test_smart_contract_self_call_testnet() {
         run_testnet_test!(test_smart_contract_self_call);
@@ -107,6 +112,21 @@ mod test {
         run_testnet_test!(test_redeploy_contract);
     }
 
+    #[test]
+    fn test_deploy_contract_with_migration_testnet() {
+        run_testnet_test!(test_deploy_contract_with_migration);
+    }
+
+    #[test]
+    fn test_deploy_contract_with_failed_migration_testnet() {
+        run_testnet_test!(test_deploy_contract_with_failed_migration);
+    }
+
+    #[test]
+    fn test_deploy_invalid_contract_testnet() {
+        run_testnet_test!(test_deploy_invalid_contract);
+    }
+
     #[test]
     fn test_send_money_testnet() {
         run_testnet_test!(test_send_money);
@@ -157,6 +177,31 @@ mod test {
         run_testnet_test!(test_add_existing_key);
     }
 
+    #[test]
+    fn test_batch_transaction_testnet() {
+        run_testnet_test!(test_batch_transaction);
+    }
+
+    #[test]
+    fn test_batch_transaction_atomic_testnet() {
+        run_testnet_test!(test_batch_transaction_atomic);
+    }
+
+    #[test]
+    fn test_batch_transaction_not_owned_testnet() {
+        run_testnet_test!(test_batch_transaction_not_owned);
+    }
+
+    #[test]
+    fn test_batch_transaction_create_account_testnet() {
+        run_testnet_test!(test_batch_transaction_create_account);
+    }
+
+    #[test]
+    fn test_batch_transaction_create_account_atomic_testnet() {
+        run_testnet_test!(test_batch_transaction_create_account_atomic);
+    }
+
     #[test]
     fn test_delete_key_testnet() {
         run_testnet_test!(test_delete_key);
@@ -187,6 +232,11 @@ mod test {
         run_testnet_test!(test_add_access_key_with_funding);
     }
 
+    #[test]
+    fn test_top_up_access_key_testnet() {
+        run_testnet_test!(test_top_up_access_key);
+    }
+
     #[test]
     fn test_delete_access_key_with_owner_refund_testnet() {
         run_testnet_test!(test_delete_access_key_with_owner_refund);
@@ -201,4 +251,19 @@ mod test {
     fn test_access_key_smart_contract_testnet() {
         run_testnet_test!(test_access_key_smart_contract);
     }
+
+    #[test]
+    fn test_delete_account_testnet() {
+        run_testnet_test!(test_delete_account);
+    }
+
+    #[test]
+    fn test_delete_account_missing_beneficiary_testnet() {
+        run_testnet_test!(test_delete_account_missing_beneficiary);
+    }
+
+    #[test]
+    fn test_delete_account_while_staked_testnet() {
+        run_testnet_test!(test_delete_account_while_staked);
+    }
 }
@@ -5,6 +5,7 @@ use near_primitives::crypto::signer::InMemorySigner;
 use near_primitives::test_utils::init_integration_logger;
 use near_primitives::transaction::{CreateAccountTransaction, TransactionBody};
 use testlib::node::{Node, ThreadNode};
+use testlib::user::SignForUser;
 
 fn start_node() -> ThreadNode {
     init_integration_logger();
@@ -28,7 +29,7 @@ fn test_check_tx_error_log() {
         amount: 1_000,
         public_key: signer.public_key.0[..].to_vec(),
     })
-    .sign(&signer);
+    .sign_for(&*node.user(), &signer);
 
     let tx_result = node.user().commit_transaction(tx);
     assert_eq!(
@@ -48,7 +49,7 @@ fn test_deliver_tx_error_log() {
         amount: TESTING_INIT_BALANCE + 1,
         public_key: signer.public_key.0[..].to_vec(),
     })
-    .sign(&signer);
+    .sign_for(&*node.user(), &signer);
 
     let tx_result = node.user().commit_transaction(tx).unwrap();
     assert_eq!(
@@ -11,6 +11,7 @@ mod test {
     use near_primitives::types::AccountId;
     use testlib::node::{create_nodes, sample_queryable_node, sample_two_nodes, Node, NodeConfig};
     use testlib::test_helpers::{heavy_test, wait, wait_for_catchup};
+    use testlib::user::SignForUser;
 
     fn warmup() {
         Command::new("cargo").args(&["build", "-p", "near"]).spawn().expect("warmup failed").wait().unwrap();
@@ -38,7 +39,10 @@ mod test {
                     account_names[to].as_str(),
                     1000,
                 )
-                .sign(&*nodes[from].read().unwrap().signer()),
+                .sign_for(
+                    &*nodes[k].read().unwrap().user(),
+                    &*nodes[from].read().unwrap().signer(),
+                ),
             )
             .unwrap();
     }
@@ -0,0 +1,102 @@
+//! Runs a node with the state split into two shards and checks that the transactions between
+//! the accounts of different shards are executed through the cross-shard receipts.
+use near::config::{TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
+use near::{load_test_config, GenesisConfig};
+use near_network::test_utils::open_port;
+use near_primitives::crypto::signer::InMemorySigner;
+use near_primitives::test_utils::init_integration_logger;
+use near_primitives::transaction::{
+    CreateAccountTransaction, FinalTransactionStatus, FunctionCallTransaction, TransactionBody,
+    TransactionStatus,
+};
+use near_primitives::utils::account_to_shard_id;
+use testlib::node::{Node, ThreadNode};
+use testlib::runtime_utils::{encode_int, FUNCTION_CALL_GAS};
+use testlib::user::SignForUser;
+
+const NUM_SHARDS: u64 = 2;
+
+fn start_node() -> ThreadNode {
+    init_integration_logger();
+    let genesis_config = GenesisConfig::test_sharded(
+        vec!["alice.near", "bob.near"],
+        1,
+        vec![1; NUM_SHARDS as usize],
+    );
+    let mut near_config = load_test_config("alice.near", open_port(), &genesis_config);
+    near_config.client_config.skip_sync_wait = true;
+
+    let mut node = ThreadNode::new(near_config);
+    node.start();
+    node
+}
+
+fn assert_different_shards(account_id1: &str, account_id2: &str) {
+    assert_ne!(
+        account_to_shard_id(&account_id1.to_string(), NUM_SHARDS),
+        account_to_shard_id(&account_id2.to_string(), NUM_SHARDS)
+    );
+}
+
+#[test]
+fn test_cross_shard_send_money() {
+    assert_different_shards("alice.near", "bob.near");
+    let node = start_node();
+    let amount = 1_000;
+    let tx = TransactionBody::send_money(1, "alice.near", "bob.near", amount)
+        .sign_for(&*node.user(), &*node.signer());
+
+    let tx_result = node.user().commit_transaction(tx).unwrap();
+    assert_eq!(tx_result.status, FinalTransactionStatus::Completed);
+    assert_eq!(
+        node.view_balance(&"alice.near".to_string()).unwrap(),
+        TESTING_INIT_BALANCE - TESTING_INIT_STAKE - amount
+    );
+    assert_eq!(node.view_balance(&"bob.near".to_string()).unwrap(), TESTING_INIT_BALANCE + amount);
+}
+
+#[test]
+fn test_cross_shard_create_account() {
+    assert_different_shards("alice.near", "test.near");
+    let node = start_node();
+    let signer = InMemorySigner::from_seed("test.near", "test.near");
+    let amount = 1_000;
+    let tx = TransactionBody::CreateAccount(CreateAccountTransaction {
+        nonce: 1,
+        originator: "alice.near".to_string(),
+        new_account_id: "test.near".to_string(),
+        amount,
+        public_key: signer.public_key.0[..].to_vec(),
+    })
+    .sign_for(&*node.user(), &*node.signer());
+
+    let tx_result = node.user().commit_transaction(tx).unwrap();
+    assert_eq!(tx_result.status, FinalTransactionStatus::Completed);
+    let account = node.view_account(&"test.near".to_string()).unwrap();
+    assert_eq!(account.amount, amount);
+    assert_eq!(account.public_keys, vec![signer.public_key]);
+}
+
+#[test]
+fn test_cross_shard_function_call() {
+    assert_different_shards("alice.near", "bob.near");
+    let node = start_node();
+    let tx = TransactionBody::FunctionCall(FunctionCallTransaction {
+        nonce: 1,
+        originator: "alice.near".to_string(),
+        contract_id: "bob.near".to_string(),
+        method_name: b"run_test".to_vec(),
+        args: vec![],
+        amount: 0,
+        prepaid_gas: FUNCTION_CALL_GAS,
+    })
+    .sign_for(&*node.user(), &*node.signer());
+
+    let tx_result = node.user().commit_transaction(tx).unwrap();
+    assert_eq!(tx_result.status, FinalTransactionStatus::Completed);
+    // The call itself is executed by the receipt on the shard of bob.
+    assert_eq!(tx_result.logs[0].receipts.len(), 1);
+    let receipt_result = node.user().get_transaction_result(&tx_result.logs[0].receipts[0]);
+    assert_eq!(receipt_result.status, TransactionStatus::Completed);
+    assert_eq!(receipt_result.result, Some(encode_int(10).to_vec()));
+}
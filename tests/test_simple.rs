@@ -6,6 +6,7 @@ mod test {
     use near_primitives::transaction::TransactionBody;
     use testlib::node::{create_nodes, sample_two_nodes, Node};
     use testlib::test_helpers::{heavy_test, wait};
+    use testlib::user::SignForUser;
 
     fn run_multiple_nodes(num_nodes: usize, num_trials: usize, test_prefix: &str) {
         init_integration_logger();
@@ -36,7 +37,7 @@ mod test {
                 account_names[j].as_str(),
                 amount_to_send,
             )
-            .sign(&*nodes[i].read().unwrap().signer());
+            .sign_for(&*nodes[k].read().unwrap().user(), &*nodes[i].read().unwrap().signer());
             nodes[k].read().unwrap().add_transaction(transaction).unwrap();
 
             wait(
@@ -14,6 +14,7 @@ mod test {
     use near_primitives::transaction::TransactionBody;
     use testlib::node::{create_nodes, sample_queryable_node, sample_two_nodes, Node};
     use testlib::test_helpers::heavy_test;
+    use testlib::user::SignForUser;
 
     /// Creates and sends a random transaction.
     /// Args:
@@ -36,7 +37,10 @@ mod test {
         let receiver_acc = nodes[money_receiver].read().unwrap().account_id().unwrap();
         let transaction =
             TransactionBody::send_money(nonce, sender_acc.as_str(), receiver_acc.as_str(), 1)
-                .sign(&*nodes[money_sender].read().unwrap().signer());
+                .sign_for(
+                    &*nodes[tx_receiver].read().unwrap().user(),
+                    &*nodes[money_sender].read().unwrap().signer(),
+                );
         nodes[tx_receiver].read().unwrap().add_transaction(transaction).unwrap();
         submitted_transactions.write().unwrap().push((1, Instant::now()));
     }